@@ -0,0 +1,22 @@
+-- 002_external_import.sql
+
+CREATE TABLE comments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    author_id INTEGER NOT NULL REFERENCES users(id),
+    body TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_comments_task ON comments(task_id);
+
+-- Соответствие объектов Jira/Trello нашим записям, чтобы повторный импорт
+-- обновлял уже созданные задачи, а не плодил дубликаты
+CREATE TABLE import_mappings (
+    source TEXT NOT NULL,
+    entity_type TEXT NOT NULL,
+    external_id TEXT NOT NULL,
+    local_id INTEGER NOT NULL,
+    imported_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (source, entity_type, external_id)
+);
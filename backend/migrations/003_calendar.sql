@@ -0,0 +1,11 @@
+-- 003_calendar.sql
+
+ALTER TABLE tasks ADD COLUMN due_date DATE;
+ALTER TABLE tasks ADD COLUMN planned_date DATE;
+
+-- Секретный токен ленты iCalendar. Храним только хэш, сам токен показывается один раз
+CREATE TABLE calendar_tokens (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT UNIQUE NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,11 @@
+-- 005_task_events.sql
+
+-- Журнал изменений задач для realtime-канала. Клиент после переподключения
+-- получает все события с id больше последнего увиденного
+CREATE TABLE task_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    event TEXT NOT NULL,
+    task_id INTEGER NOT NULL,
+    task TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
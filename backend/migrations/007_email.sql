@@ -0,0 +1,29 @@
+-- 007_email.sql
+
+-- Как доставлять уведомления на почту: immediate, digest или off.
+-- Нет строки - immediate
+CREATE TABLE email_preferences (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    mode TEXT NOT NULL DEFAULT 'immediate',
+    -- id последнего уведомления, уже попавшего в дайджест
+    digest_cursor INTEGER NOT NULL DEFAULT 0,
+    last_digest_at DATETIME,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Очередь писем. В payload - снимок уведомлений, письмо собирается при отправке
+CREATE TABLE email_outbox (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    to_address TEXT NOT NULL,
+    template TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    last_error TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    sent_at DATETIME
+);
+
+CREATE INDEX idx_email_outbox_due ON email_outbox(status, next_attempt_at);
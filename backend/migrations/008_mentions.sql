@@ -0,0 +1,14 @@
+-- 008_mentions.sql
+
+-- Упоминания @user в описании задачи (comment_id IS NULL) или в комментарии
+CREATE TABLE mentions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    comment_id INTEGER REFERENCES comments(id) ON DELETE CASCADE,
+    author_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_mentions_task ON mentions(task_id);
+CREATE INDEX idx_mentions_user ON mentions(user_id);
@@ -0,0 +1,20 @@
+-- 009_watchers.sql
+
+CREATE TABLE task_watchers (
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (task_id, user_id)
+);
+
+CREATE INDEX idx_task_watchers_user ON task_watchers(user_id);
+
+-- Авторы, исполнители и комментаторы существующих задач следят за ними автоматически
+INSERT OR IGNORE INTO task_watchers (task_id, user_id)
+SELECT id, created_by FROM tasks WHERE created_by IN (SELECT id FROM users);
+
+INSERT OR IGNORE INTO task_watchers (task_id, user_id)
+SELECT id, assignee_id FROM tasks WHERE assignee_id IN (SELECT id FROM users);
+
+INSERT OR IGNORE INTO task_watchers (task_id, user_id)
+SELECT DISTINCT task_id, author_id FROM comments WHERE author_id IN (SELECT id FROM users);
@@ -0,0 +1,19 @@
+-- 010_saved_views.sql
+
+-- Сохранённые представления доски. Проектов в трекере нет, поэтому
+-- shared-представление видно всем пользователям
+CREATE TABLE saved_views (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    shared BOOLEAN NOT NULL DEFAULT 0,
+    -- JSON с условиями отбора, см. ViewFilters
+    filters TEXT NOT NULL DEFAULT '{}',
+    sort_by TEXT NOT NULL DEFAULT 'created_at',
+    sort_desc BOOLEAN NOT NULL DEFAULT 1,
+    display TEXT NOT NULL DEFAULT 'kanban',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_saved_views_owner ON saved_views(owner_id);
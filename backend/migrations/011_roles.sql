@@ -0,0 +1,30 @@
+-- 011_roles.sql
+
+-- Роли - именованные наборы прав. users.role ссылается на roles.name.
+-- Встроенные роли нельзя удалить, а admin ещё и изменить
+CREATE TABLE roles (
+    name TEXT PRIMARY KEY,
+    description TEXT NOT NULL DEFAULT '',
+    -- JSON-массив прав, см. policy::PERMISSIONS
+    permissions TEXT NOT NULL DEFAULT '[]',
+    builtin BOOLEAN NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO roles (name, description, permissions, builtin) VALUES
+(
+    'admin',
+    'Full access',
+    '["user.view","user.email.view","user.create","user.delete","role.manage","task.create","task.view","task.update.own","task.update.any","task.delete.own","task.delete.any","task.comment","task.watch","task.import","task.export","view.manage.any","webhook.manage","import.external"]',
+    1
+),
+(
+    'member',
+    'Works on own and assigned tasks',
+    '["user.view","task.create","task.view","task.update.own","task.delete.own","task.comment","task.watch","task.import","task.export"]',
+    1
+);
+
+-- Раньше роль была произвольной строкой: всё, кроме admin, означало обычного участника
+UPDATE users SET role = 'member' WHERE role NOT IN (SELECT name FROM roles);
@@ -0,0 +1,25 @@
+-- 012_sessions.sql
+
+-- Сессия - один вход пользователя. Отзыв сессии гасит все её refresh-токены
+CREATE TABLE sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    revoked_at DATETIME
+);
+
+CREATE INDEX idx_sessions_user ON sessions(user_id);
+
+-- Refresh-токены одной сессии образуют семейство: каждый обменивается на новый
+-- ровно один раз. Повторное предъявление использованного токена значит, что он
+-- утёк, и сессия отзывается целиком. Храним только хэш
+CREATE TABLE refresh_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    token_hash TEXT UNIQUE NOT NULL,
+    expires_at DATETIME NOT NULL,
+    used_at DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_refresh_tokens_session ON refresh_tokens(session_id);
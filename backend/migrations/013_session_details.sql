@@ -0,0 +1,13 @@
+-- 013_session_details.sql
+
+-- Откуда выполнен вход - чтобы пользователь узнал свои сессии в списке
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
+ALTER TABLE sessions ADD COLUMN ip TEXT;
+ALTER TABLE sessions ADD COLUMN last_used_at DATETIME;
+
+UPDATE sessions SET last_used_at = created_at;
+
+UPDATE roles
+SET permissions = json_insert(permissions, '$[#]', 'session.manage'),
+    updated_at = CURRENT_TIMESTAMP
+WHERE name = 'admin';
@@ -0,0 +1,30 @@
+-- 014_login_protection.sql
+
+-- Неудачные попытки входа: scope = 'account' (введённый email, даже несуществующий)
+-- или 'ip'. Счётчик сбрасывается после паузы без ошибок или по истечении блокировки
+CREATE TABLE login_failures (
+    scope TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    locked_until DATETIME,
+    PRIMARY KEY (scope, subject)
+);
+
+-- Журнал событий безопасности
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    event TEXT NOT NULL,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    email TEXT,
+    ip TEXT,
+    details TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_audit_log_created ON audit_log(created_at);
+
+UPDATE roles
+SET permissions = json_insert(permissions, '$[#]', 'user.unlock', '$[#]', 'audit.view'),
+    updated_at = CURRENT_TIMESTAMP
+WHERE name = 'admin';
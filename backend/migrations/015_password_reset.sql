@@ -0,0 +1,21 @@
+-- 015_password_reset.sql
+
+-- Администратор может потребовать сменить пароль: до сброса по ссылке вход закрыт
+ALTER TABLE users ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT 0;
+
+-- Одноразовые токены сброса пароля. Храним только хэш, сам токен уходит в ссылке
+CREATE TABLE password_reset_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT UNIQUE NOT NULL,
+    expires_at DATETIME NOT NULL,
+    used_at DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_password_reset_tokens_user ON password_reset_tokens(user_id);
+
+UPDATE roles
+SET permissions = json_insert(permissions, '$[#]', 'user.password.reset'),
+    updated_at = CURRENT_TIMESTAMP
+WHERE name = 'admin';
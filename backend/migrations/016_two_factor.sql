@@ -0,0 +1,38 @@
+-- 016_two_factor.sql
+
+-- Секрет TOTP пользователя. До подтверждения кодом из приложения (enabled = 0)
+-- вход он не меняет. last_used_step - шаг последнего принятого кода: повторно
+-- тот же код не пройдёт
+CREATE TABLE user_totp (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT 0,
+    last_used_step INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    enabled_at DATETIME
+);
+
+-- Резервные коды на случай потери телефона, каждый одноразовый. Храним только хэш
+CREATE TABLE recovery_codes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at DATETIME
+);
+
+CREATE INDEX idx_recovery_codes_user ON recovery_codes(user_id);
+
+-- Пароль принят, ждём второй фактор. Токен вызова выдаётся клиенту вместо пары
+-- токенов и обменивается на неё вместе с кодом
+CREATE TABLE login_challenges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT UNIQUE NOT NULL,
+    expires_at DATETIME NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Пользователи роли без настроенного второго фактора получают токен,
+-- пригодный только для его настройки
+ALTER TABLE roles ADD COLUMN require_two_factor BOOLEAN NOT NULL DEFAULT 0;
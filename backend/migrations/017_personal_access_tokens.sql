@@ -0,0 +1,18 @@
+-- 017_personal_access_tokens.sql
+
+-- Токены для скриптов и интеграций вместо входа по паролю. Храним только хэш,
+-- сам токен показывается один раз при создании. scope: read - только чтение,
+-- write - всё, что позволяет роль владельца. expires_at пуст у бессрочных
+CREATE TABLE personal_access_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT UNIQUE NOT NULL,
+    scope TEXT NOT NULL,
+    expires_at DATETIME,
+    last_used_at DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    revoked_at DATETIME
+);
+
+CREATE INDEX idx_personal_access_tokens_user ON personal_access_tokens(user_id);
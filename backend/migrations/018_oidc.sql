@@ -0,0 +1,27 @@
+-- 018_oidc.sql
+
+-- Вход через внешний провайдер (OpenID Connect) начат, ждём возврата с кодом.
+-- state одноразовый, храним его хэш. nonce сверяется с ID-токеном, code_verifier
+-- (PKCE) отправляется провайдеру при обмене кода
+CREATE TABLE oidc_login_states (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    state_hash TEXT UNIQUE NOT NULL,
+    nonce TEXT NOT NULL,
+    code_verifier TEXT NOT NULL,
+    expires_at DATETIME NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Учётная запись у провайдера, связанная с пользователем. Пара (issuer, subject)
+-- не меняется, даже если у провайдера сменили email
+CREATE TABLE user_identities (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    last_login_at DATETIME,
+    UNIQUE (issuer, subject)
+);
+
+CREATE INDEX idx_user_identities_user ON user_identities(user_id);
@@ -0,0 +1,42 @@
+-- 019_jobs.sql
+
+-- Очередь фоновой работы. kind выбирает обработчик, payload - его параметры.
+-- status: pending, running, done или dead - попытки кончились, задача ждёт
+-- ручного перезапуска. Занятая воркером задача держит аренду до locked_until:
+-- если процесс упал, по истечении аренды её подхватит другой воркер
+CREATE TABLE jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    run_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    locked_until DATETIME,
+    last_error TEXT,
+    -- X-Request-Id запроса, поставившего задачу: по нему связываются логи
+    request_id TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    finished_at DATETIME
+);
+
+CREATE INDEX idx_jobs_due ON jobs(status, run_at);
+CREATE INDEX idx_jobs_kind ON jobs(kind, status);
+
+-- Доставки и письма, которые ждали прежних диспетчеров, переходят в очередь
+INSERT INTO jobs (kind, payload, max_attempts, run_at)
+SELECT 'deliver_webhook', json_object('kind', 'deliver_webhook', 'delivery_id', id), 8,
+       COALESCE(next_attempt_at, CURRENT_TIMESTAMP)
+FROM webhook_deliveries
+WHERE status = 'pending';
+
+INSERT INTO jobs (kind, payload, max_attempts, run_at)
+SELECT 'send_email', json_object('kind', 'send_email', 'email_id', id), 6,
+       COALESCE(next_attempt_at, CURRENT_TIMESTAMP)
+FROM email_outbox
+WHERE status = 'pending';
+
+UPDATE roles
+SET permissions = json_insert(permissions, '$[#]', 'job.manage'),
+    updated_at = CURRENT_TIMESTAMP
+WHERE name = 'admin';
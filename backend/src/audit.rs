@@ -0,0 +1,19 @@
+//! Журнал событий безопасности: входы, блокировки, сбросы паролей.
+
+use crate::repository;
+use sqlx::SqlitePool;
+
+/// Журнал не должен ломать основное действие: ошибку записи только логируем
+pub async fn record(
+    pool: &SqlitePool,
+    event: &str,
+    user_id: Option<i64>,
+    email: Option<&str>,
+    ip: Option<&str>,
+    details: Option<&str>,
+) {
+    tracing::info!("Audit: {} user={:?} email={:?} ip={:?}", event, user_id, email, ip);
+    if let Err(e) = repository::insert_audit_event(pool, event, user_id, email, ip, details).await {
+        tracing::error!("Failed to write audit event {}: {}", event, e);
+    }
+}
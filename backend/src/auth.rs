@@ -5,13 +5,31 @@ use argon2::{
 };
 use chrono::{Duration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use rand::distr::{Alphanumeric, SampleString};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Access-токен живёт недолго: отозванная сессия перестаёт работать самое позднее
+/// через это время. Дальше клиент обменивает refresh-токен на новую пару
+pub const ACCESS_TOKEN_MINUTES: i64 = 15;
+pub const REFRESH_TOKEN_DAYS: i64 = 30;
+/// По префиксу личный токен доступа отличается от JWT, а попавший в код или
+/// логи - легко найти
+pub const PERSONAL_TOKEN_PREFIX: &str = "ttp_";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: i64,     // user id
+    pub sid: i64,     // session id
     pub role: String, // user role
-    pub exp: i64,     // expiration time
+    // права роли: проверка доступа обходится без запроса к базе,
+    // изменения роли вступают в силу со следующим токеном
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    // роль требует 2FA, а она не настроена: токен годится только для настройки
+    #[serde(default)]
+    pub two_factor_setup: bool,
+    pub exp: i64, // expiration time
 }
 
 pub fn hash_password(password: &str) -> Result<String, AppError> {
@@ -32,15 +50,25 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool, AppError> {
         .is_ok())
 }
 
-pub fn create_token(user_id: i64, role: &str, secret: &str) -> Result<String, AppError> {
+pub fn create_token(
+    user_id: i64,
+    role: &str,
+    permissions: &[String],
+    session_id: i64,
+    two_factor_setup: bool,
+    secret: &str,
+) -> Result<String, AppError> {
     let expiration = Utc::now()
-        .checked_add_signed(Duration::hours(24))
+        .checked_add_signed(Duration::minutes(ACCESS_TOKEN_MINUTES))
         .expect("valid timestamp")
         .timestamp();
 
     let claims = Claims {
         sub: user_id,
+        sid: session_id,
         role: role.to_string(),
+        permissions: permissions.to_vec(),
+        two_factor_setup,
         exp: expiration,
     };
 
@@ -61,3 +89,14 @@ pub fn verify_token(token: &str, secret: &str) -> Result<Claims, AppError> {
     .map(|data| data.claims)
     .map_err(|_| AppError::Unauthorized)
 }
+
+/// Случайный секрет для ссылок и API-токенов
+pub fn generate_token() -> String {
+    Alphanumeric.sample_string(&mut rand::rng(), 40)
+}
+
+/// Токены высокой энтропии хэшируем быстрым SHA-256: Argon2 для них не нужен,
+/// а поиск по хэшу остаётся возможным
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
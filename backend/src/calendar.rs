@@ -0,0 +1,182 @@
+use crate::models::Task;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+const PRODID: &str = "-//Diploma//Task Tracker//EN";
+
+/// Строит ленту iCalendar (RFC 5545): срок задачи - VTODO, запланированная дата - VEVENT на весь день
+pub fn render(tasks: &[Task], app_url: &str) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Task Tracker".to_string(),
+    ];
+
+    for task in tasks {
+        let url = format!("{}/?task={}", app_url.trim_end_matches('/'), task.id);
+        let description = match &task.description {
+            Some(d) => format!("Status: {}\n\n{}", task.status, d),
+            None => format!("Status: {}", task.status),
+        };
+
+        if let Some(due) = task.due_date {
+            lines.push("BEGIN:VTODO".to_string());
+            lines.push(format!("UID:task-{}-due@task-tracker", task.id));
+            lines.push(format!("DTSTAMP:{}", format_timestamp(task.updated_at)));
+            lines.push(format!("LAST-MODIFIED:{}", format_timestamp(task.updated_at)));
+            lines.push(format!("SUMMARY:{}", escape(&task.title)));
+            lines.push(format!("DESCRIPTION:{}", escape(&description)));
+            lines.push(format!("DUE;VALUE=DATE:{}", format_date(due)));
+            lines.push(format!("STATUS:{}", todo_status(&task.status)));
+            lines.push(format!("CATEGORIES:{}", escape(&task.status)));
+            lines.push(format!("URL:{}", url));
+            lines.push("END:VTODO".to_string());
+        }
+
+        if let Some(planned) = task.planned_date {
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!("UID:task-{}-planned@task-tracker", task.id));
+            lines.push(format!("DTSTAMP:{}", format_timestamp(task.updated_at)));
+            lines.push(format!("LAST-MODIFIED:{}", format_timestamp(task.updated_at)));
+            lines.push(format!("SUMMARY:{}", escape(&task.title)));
+            lines.push(format!("DESCRIPTION:{}", escape(&description)));
+            lines.push(format!("DTSTART;VALUE=DATE:{}", format_date(planned)));
+            lines.push(format!(
+                "DTEND;VALUE=DATE:{}",
+                format_date(planned + Duration::days(1))
+            ));
+            lines.push("TRANSP:TRANSPARENT".to_string());
+            lines.push(format!("CATEGORIES:{}", escape(&task.status)));
+            lines.push(format!("URL:{}", url));
+            lines.push("END:VEVENT".to_string());
+        }
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in &lines {
+        fold_line(line, &mut out);
+    }
+    out
+}
+
+fn todo_status(status: &str) -> &'static str {
+    match status {
+        "in_progress" => "IN-PROCESS",
+        "done" => "COMPLETED",
+        _ => "NEEDS-ACTION",
+    }
+}
+
+fn format_timestamp(ts: DateTime<Utc>) -> String {
+    ts.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Строки длиннее 75 октетов переносятся: CRLF и пробел в начале продолжения
+fn fold_line(line: &str, out: &mut String) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task() -> Task {
+        let updated_at = "2026-02-01T10:30:00Z".parse().unwrap();
+        Task {
+            id: 42,
+            title: "Отчёт; часть 1, черновик".to_string(),
+            description: Some("Строка\r\nвторая \\ конец".to_string()),
+            status: "in_progress".to_string(),
+            predicted_hours: None,
+            actual_hours: None,
+            assignee_id: None,
+            created_by: 1,
+            created_at: updated_at,
+            updated_at,
+            due_date: "2026-03-01".parse().ok(),
+            planned_date: "2026-02-27".parse().ok(),
+        }
+    }
+
+    fn unfold(ics: &str) -> Vec<String> {
+        ics.replace("\r\n ", "").split("\r\n").map(str::to_string).collect()
+    }
+
+    #[test]
+    fn task_becomes_todo_and_all_day_event() {
+        let lines = unfold(&render(&[task()], "https://tracker.example/"));
+        let has = |line: &str| lines.iter().any(|l| l == line);
+
+        assert_eq!(lines.first().map(String::as_str), Some("BEGIN:VCALENDAR"));
+        assert_eq!(lines.iter().rev().nth(1).map(String::as_str), Some("END:VCALENDAR"));
+        assert!(has("UID:task-42-due@task-tracker"));
+        assert!(has("DTSTAMP:20260201T103000Z"));
+        assert!(has("DUE;VALUE=DATE:20260301"));
+        assert!(has("STATUS:IN-PROCESS"));
+        assert!(has("UID:task-42-planned@task-tracker"));
+        assert!(has("DTSTART;VALUE=DATE:20260227"));
+        assert!(has("DTEND;VALUE=DATE:20260228"));
+        assert!(has("URL:https://tracker.example/?task=42"));
+        assert!(has("SUMMARY:Отчёт\\; часть 1\\, черновик"));
+        assert!(has("DESCRIPTION:Status: in_progress\\n\\nСтрока\\nвторая \\\\ конец"));
+    }
+
+    #[test]
+    fn dates_decide_which_components_appear() {
+        let mut undated = task();
+        undated.due_date = None;
+        undated.planned_date = None;
+        let ics = render(&[undated], "https://tracker.example");
+        assert!(!ics.contains("BEGIN:VTODO"));
+        assert!(!ics.contains("BEGIN:VEVENT"));
+    }
+
+    #[test]
+    fn todo_status_follows_task_status() {
+        assert_eq!(todo_status("done"), "COMPLETED");
+        assert_eq!(todo_status("todo"), "NEEDS-ACTION");
+    }
+
+    #[test]
+    fn long_lines_fold_at_75_octets_without_splitting_chars() {
+        let mut out = String::new();
+        let line = format!("SUMMARY:{}", "ж".repeat(100));
+        fold_line(&line, &mut out);
+
+        let parts: Vec<&str> = out.trim_end_matches("\r\n").split("\r\n").collect();
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(part.len() <= 75, "{} octets", part.len());
+        }
+        assert!(parts[1..].iter().all(|p| p.starts_with(' ')));
+        assert_eq!(out.replace("\r\n ", "").trim_end(), line);
+
+        let mut short = String::new();
+        fold_line("VERSION:2.0", &mut short);
+        assert_eq!(short, "VERSION:2.0\r\n");
+    }
+}
@@ -0,0 +1,68 @@
+use crate::config::Config;
+use crate::models::{ExternalImportRequest, ImportSource};
+use crate::{repository, services};
+use sqlx::SqlitePool;
+
+pub const USAGE: &str = "\
+Usage: backend [options] [import <jira|trello> <file> | mock-idp [port]]
+
+Options:
+  --config <file>    TOML settings file (default: config.toml if present)
+  --print-config     Print the effective settings with secrets hidden and exit
+  --<setting> <value>  Override a setting, e.g. --port 8081 or --app-env=production
+
+mock-idp is available only in builds with --features mock-idp";
+/// Порт учебного провайдера SSO по умолчанию: OIDC_ISSUER=http://127.0.0.1:8090
+#[cfg(feature = "mock-idp")]
+const MOCK_IDP_PORT: u16 = 8090;
+
+/// Разовые команды администратора. Без аргументов backend запускает HTTP-сервер
+pub async fn run(args: &[String], pool: &SqlitePool, config: &Config) -> std::io::Result<()> {
+    match args {
+        [command, source, path] if command == "import" => {
+            let source: ImportSource = source.parse().map_err(std::io::Error::other)?;
+            let data = std::fs::read_to_string(path)?;
+
+            // Импортированные задачи без автора записываются на администратора из конфигурации
+            let admin = repository::get_user_by_email(pool, &config.admin_email)
+                .await
+                .map_err(std::io::Error::other)?
+                .ok_or_else(|| std::io::Error::other("Admin user not found"))?;
+
+            let req = ExternalImportRequest {
+                data,
+                ..Default::default()
+            };
+            let report = services::import_external(pool, source, req, admin.id)
+                .await
+                .map_err(std::io::Error::other)?;
+
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).map_err(std::io::Error::other)?
+            );
+            Ok(())
+        }
+        _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, USAGE)),
+    }
+}
+
+/// Учебный провайдер SSO. Запускается до подключения к базе
+#[cfg(feature = "mock-idp")]
+pub async fn run_mock_idp(args: &[String]) -> std::io::Result<()> {
+    let port = match args {
+        [] => MOCK_IDP_PORT,
+        [port] => port.parse().map_err(std::io::Error::other)?,
+        _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, USAGE)),
+    };
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+    crate::mock_idp::server(listener)?.await
+}
+
+#[cfg(not(feature = "mock-idp"))]
+pub async fn run_mock_idp(_args: &[String]) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "mock-idp is not built in: run it with cargo run --features mock-idp -- mock-idp",
+    ))
+}
@@ -1,5 +1,96 @@
+//! Настройки собираются слоями: значения по умолчанию, файл TOML, переменные
+//! окружения (и .env), флаги командной строки - каждый следующий слой главнее.
+//! Проверяются все сразу, и ошибка перечисляет каждую неверную настройку.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use crate::metrics;
+
+/// Файл настроек, если он не указан через --config или CONFIG_FILE
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+/// Значения из примеров и по умолчанию: с ними в production не запускаемся
+const INSECURE_JWT_SECRETS: [&str; 2] = ["super-secret-change-me", "change-me-in-production"];
+const INSECURE_ADMIN_PASSWORD: &str = "adminpass123";
+const MIN_PRODUCTION_SECRET_LENGTH: usize = 32;
+const REDACTED: &str = "<redacted>";
+
+struct Setting {
+    /// Имя в TOML; переменная окружения - оно же заглавными, флаг - через дефис
+    key: &'static str,
+    default: &'static str,
+    /// Не показывается в --print-config и в сообщениях об ошибках
+    secret: bool,
+}
+
+const fn setting(key: &'static str, default: &'static str) -> Setting {
+    Setting { key, default, secret: false }
+}
+
+const fn secret(key: &'static str, default: &'static str) -> Setting {
+    Setting { key, default, secret: true }
+}
+
+const SETTINGS: &[Setting] = &[
+    setting("app_env", "development"),
+    setting("database_url", "sqlite:data.db?mode=rwc"),
+    secret("jwt_secret", "super-secret-change-me"),
+    setting("host", "127.0.0.1"),
+    setting("port", "8080"),
+    setting("ml_service_url", "http://localhost:8000"),
+    setting("admin_email", "admin@example.com"),
+    secret("admin_password", INSECURE_ADMIN_PASSWORD),
+    setting("app_url", "http://localhost:3000"),
+    setting("log_filter", "info"),
+    setting("log_format", "text"),
+    setting("metrics_allow", "127.0.0.1,::1"),
+    secret("metrics_token", ""),
+    setting("smtp_host", ""),
+    setting("smtp_port", "587"),
+    setting("smtp_tls", "starttls"),
+    setting("smtp_username", ""),
+    secret("smtp_password", ""),
+    setting("mail_from", "Task Tracker <noreply@localhost>"),
+    setting("digest_hour", "8"),
+    setting("job_workers", "4"),
+    setting("job_drain_secs", "30"),
+    setting("password_min_length", "10"),
+    setting("breached_passwords_file", "breached_passwords.txt"),
+    setting("reset_notifier", "email"),
+    setting("oidc_issuer", ""),
+    setting("oidc_client_id", "task-tracker"),
+    secret("oidc_client_secret", ""),
+    setting("oidc_redirect_url", "http://localhost:3000/"),
+    setting("oidc_scopes", "openid email profile"),
+    setting("oidc_auto_provision", "true"),
+    setting("oidc_default_role", "member"),
+    setting("oidc_groups_claim", "groups"),
+    setting("oidc_group_roles", ""),
+];
+
+fn find_setting(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|s| s.key == key)
+}
+
+fn env_name(key: &str) -> String {
+    key.to_uppercase()
+}
+
+fn flag_name(key: &str) -> String {
+    format!("--{}", key.replace('_', "-"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Environment {
+    Development,
+    Production,
+}
+
 #[derive(Clone)]
 pub struct Config {
+    /// production запрещает значения по умолчанию для секретов
+    pub environment: Environment,
     pub database_url: String,
     pub jwt_secret: String,
     pub host: String,
@@ -7,29 +98,565 @@ pub struct Config {
     pub ml_service_url: String,
     pub admin_email: String,
     pub admin_password: String,
+    /// Адрес фронтенда - для ссылок на задачи во внешних клиентах
+    pub app_url: String,
+    /// Уровни логов в синтаксисе RUST_LOG: `info,backend=debug,sqlx=warn`
+    pub log_filter: String,
+    /// text или json - по строке JSON на событие, с полями запроса
+    pub log_format: String,
+    /// Адреса и сети, которым /metrics открыт без токена
+    pub metrics_allow: Vec<(IpAddr, u8)>,
+    /// Bearer-токен для /metrics - для Prometheus из другой сети
+    pub metrics_token: Option<String>,
+    /// SMTP-сервер. Без него письма только пишутся в лог
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    /// starttls, tls или none (локальный SMTP-приёмник)
+    pub smtp_tls: String,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub mail_from: String,
+    /// Час (UTC), в который рассылается ежедневный дайджест
+    pub digest_hour: u32,
+    /// Сколько фоновых задач выполняется одновременно
+    pub job_workers: usize,
+    /// Сколько ждать начатые задачи при остановке сервера
+    pub job_drain_secs: u64,
+    pub password_min_length: usize,
+    /// Пароли из утечек, по одному на строку
+    pub breached_passwords_file: String,
+    /// Как доставлять ссылку сброса пароля: email или log
+    pub reset_notifier: String,
+    /// Провайдер OpenID Connect для входа через SSO. Без него вход только по паролю
+    pub oidc_issuer: Option<String>,
+    pub oidc_client_id: String,
+    pub oidc_client_secret: Option<String>,
+    /// Куда провайдер возвращает браузер с кодом - страница фронтенда
+    pub oidc_redirect_url: String,
+    pub oidc_scopes: String,
+    /// Создавать ли пользователя при первом входе через SSO
+    pub oidc_auto_provision: bool,
+    /// Роль новых пользователей и тех, чьи группы не указаны в oidc_group_roles
+    pub oidc_default_role: String,
+    /// Claim ID-токена со списком групп пользователя
+    pub oidc_groups_claim: String,
+    /// Группа провайдера -> роль, в порядке приоритета
+    pub oidc_group_roles: Vec<(String, String)>,
 }
 
 impl Config {
-    pub fn from_env() -> Self {
+    /// Секреты из примеров: в разработке о них только предупреждаем
+    pub fn uses_default_secrets(&self) -> bool {
+        INSECURE_JWT_SECRETS.contains(&self.jwt_secret.as_str())
+            || self.admin_password == INSECURE_ADMIN_PASSWORD
+    }
+
+    /// Только значения по умолчанию и JWT_SECRET: локальные .env, config.toml
+    /// и окружение оболочки на тесты не влияют
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        let env = |name: &str| match name {
+            "JWT_SECRET" => Some("module-tests-jwt-secret".to_string()),
+            _ => None,
+        };
+        Layers::from_sources(None, env, &[]).build().expect("test config")
+    }
+}
+
+/// Все найденные проблемы настроек, по одной на строку
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for problem in &self.0 {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Аргументы командной строки: флаги настроек и команда после них
+#[derive(Debug, Default)]
+pub struct Cli {
+    pub config_file: Option<String>,
+    pub print_config: bool,
+    /// Значения настроек из флагов `--port 8081` или `--port=8081`
+    pub overrides: Vec<(String, String)>,
+    /// Разовая команда (import, mock-idp); пусто - запуск сервера
+    pub command: Vec<String>,
+}
+
+impl Cli {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut cli = Cli::default();
+        let mut errors = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                // Флаги идут до команды, всё остальное - её аргументы
+                cli.command.push(arg);
+                cli.command.extend(args.by_ref());
+                break;
+            };
+            if flag == "print-config" {
+                cli.print_config = true;
+                continue;
+            }
+
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let key = name.replace('-', "_");
+            if key != "config" && find_setting(&key).is_none() {
+                errors.push(format!("Unknown option --{}", name));
+                continue;
+            }
+            let Some(value) = inline_value.or_else(|| args.next()) else {
+                errors.push(format!("Option --{} needs a value", name));
+                continue;
+            };
+            if key == "config" {
+                cli.config_file = Some(value);
+            } else {
+                cli.overrides.push((key, value));
+            }
+        }
+
+        if errors.is_empty() { Ok(cli) } else { Err(ConfigError(errors)) }
+    }
+}
+
+/// Откуда взялось значение настройки
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    Default,
+    File(String),
+    Env,
+    Cli,
+}
+
+/// Значения настроек после наложения слоёв, ещё не проверенные
+pub struct Layers {
+    values: HashMap<&'static str, (String, Source)>,
+    /// Ошибки чтения слоёв: нет файла, неизвестный ключ в нём
+    errors: Vec<String>,
+}
+
+impl Layers {
+    /// .env подхватывается как часть окружения
+    pub fn collect(cli: &Cli) -> Self {
         dotenvy::dotenv().ok();
-        
-        Self {
-            database_url: std::env::var("DATABASE_URL")
-                .unwrap_or_else(|_| "sqlite:data.db?mode=rwc".to_string()),
-            jwt_secret: std::env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "super-secret-change-me".to_string()),
-            host: std::env::var("HOST")
-                .unwrap_or_else(|_| "127.0.0.1".to_string()),
-            port: std::env::var("PORT")
-                .unwrap_or_else(|_| "8080".to_string())
-                .parse()
-                .expect("PORT must be a number"),
-            ml_service_url: std::env::var("ML_SERVICE_URL")
-                .unwrap_or_else(|_| "http://localhost:8000".to_string()),
-            admin_email: std::env::var("ADMIN_EMAIL")
-                .unwrap_or_else(|_| "admin@example.com".to_string()),
-            admin_password: std::env::var("ADMIN_PASSWORD")
-                .unwrap_or_else(|_| "adminpass123".to_string()),
-        }
-    }
-}
\ No newline at end of file
+        let env = |name: &str| std::env::var(name).ok();
+
+        let explicit_file = cli.config_file.clone().or_else(|| env("CONFIG_FILE"));
+        let file = match &explicit_file {
+            Some(path) => Some(
+                std::fs::read_to_string(path)
+                    .map(|text| (path.clone(), text))
+                    .map_err(|e| format!("Cannot read config file {}: {}", path, e)),
+            ),
+            None => std::fs::read_to_string(DEFAULT_CONFIG_FILE)
+                .ok()
+                .map(|text| Ok((DEFAULT_CONFIG_FILE.to_string(), text))),
+        };
+
+        Self::from_sources(file, env, &cli.overrides)
+    }
+
+    fn from_sources(
+        file: Option<Result<(String, String), String>>,
+        env: impl Fn(&str) -> Option<String>,
+        overrides: &[(String, String)],
+    ) -> Self {
+        let mut layers = Layers {
+            values: SETTINGS
+                .iter()
+                .map(|s| (s.key, (s.default.to_string(), Source::Default)))
+                .collect(),
+            errors: Vec::new(),
+        };
+
+        match file {
+            Some(Ok((path, text))) => layers.apply_file(&path, &text),
+            Some(Err(e)) => layers.errors.push(e),
+            None => {}
+        }
+        for setting in SETTINGS {
+            if let Some(value) = env(&env_name(setting.key)) {
+                layers.values.insert(setting.key, (value, Source::Env));
+            }
+        }
+        for (key, value) in overrides {
+            if let Some(setting) = find_setting(key) {
+                layers.values.insert(setting.key, (value.clone(), Source::Cli));
+            }
+        }
+        layers
+    }
+
+    fn apply_file(&mut self, path: &str, text: &str) {
+        let table = match text.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                self.errors.push(format!("Cannot parse config file {}: {}", path, e));
+                return;
+            }
+        };
+        let mut entries = Vec::new();
+        flatten_table("", table, &mut entries);
+
+        for (key, value) in entries {
+            let Some(setting) = find_setting(&key) else {
+                self.errors.push(format!("Unknown setting {} in {}", key, path));
+                continue;
+            };
+            match value {
+                Some(value) => {
+                    self.values.insert(setting.key, (value, Source::File(path.to_string())));
+                }
+                None => self.errors.push(format!(
+                    "{} in {} must be a string, number, boolean or list of strings",
+                    key, path
+                )),
+            }
+        }
+    }
+
+    /// Итоговые настройки в формате TOML, секреты скрыты. Источник - в комментарии
+    pub fn render(&self) -> String {
+        SETTINGS
+            .iter()
+            .map(|setting| {
+                let (value, source) = &self.values[setting.key];
+                let shown = if setting.secret && !value.is_empty() { REDACTED } else { value };
+                let origin = match source {
+                    Source::Default => "default".to_string(),
+                    Source::File(path) => path.clone(),
+                    Source::Env => format!("env {}", env_name(setting.key)),
+                    Source::Cli => flag_name(setting.key),
+                };
+                format!("{} = {:?}  # {}\n", setting.key, shown, origin)
+            })
+            .collect()
+    }
+
+    pub fn build(self) -> Result<Config, ConfigError> {
+        let mut v = Validator {
+            layers: &self,
+            errors: self.errors.clone(),
+        };
+
+        let environment = match v.text("app_env").as_str() {
+            "development" => Environment::Development,
+            "production" => Environment::Production,
+            _ => {
+                v.error("app_env", "must be development or production");
+                Environment::Development
+            }
+        };
+
+        let config = Config {
+            environment,
+            database_url: v.text("database_url"),
+            jwt_secret: v.text("jwt_secret"),
+            host: v.text("host"),
+            port: v.port("port"),
+            ml_service_url: v.url("ml_service_url"),
+            admin_email: v.email("admin_email"),
+            admin_password: v.text("admin_password"),
+            app_url: v.url("app_url"),
+            log_filter: v.log_filter("log_filter"),
+            log_format: v.one_of("log_format", &["text", "json"]),
+            metrics_allow: v.networks("metrics_allow"),
+            metrics_token: v.optional("metrics_token"),
+            smtp_host: v.optional("smtp_host"),
+            smtp_port: v.port("smtp_port"),
+            smtp_tls: v.one_of("smtp_tls", &["starttls", "tls", "none"]),
+            smtp_username: v.optional("smtp_username"),
+            smtp_password: v.optional("smtp_password"),
+            mail_from: v.mailbox("mail_from"),
+            digest_hour: v.in_range("digest_hour", 0, 23),
+            job_workers: v.in_range("job_workers", 1, 32),
+            job_drain_secs: v.in_range("job_drain_secs", 1, 600),
+            password_min_length: v.in_range("password_min_length", 8, 128),
+            breached_passwords_file: v.text("breached_passwords_file"),
+            reset_notifier: v.one_of("reset_notifier", &["email", "log"]),
+            oidc_issuer: v.optional("oidc_issuer").map(|_| v.url("oidc_issuer")),
+            oidc_client_id: v.text("oidc_client_id"),
+            oidc_client_secret: v.optional("oidc_client_secret"),
+            oidc_redirect_url: v.url("oidc_redirect_url"),
+            oidc_scopes: v.text("oidc_scopes"),
+            oidc_auto_provision: v.parse("oidc_auto_provision", "must be true or false"),
+            oidc_default_role: v.text("oidc_default_role"),
+            oidc_groups_claim: v.text("oidc_groups_claim"),
+            oidc_group_roles: v.group_roles("oidc_group_roles"),
+        };
+        v.check_secrets(&config);
+
+        if v.errors.is_empty() { Ok(config) } else { Err(ConfigError(v.errors)) }
+    }
+}
+
+/// TOML-таблицы разворачиваются в ключи через `_`: `[smtp] host` - это `smtp_host`.
+/// `None` - значение, которое не свести к строке
+fn flatten_table(prefix: &str, table: toml::Table, out: &mut Vec<(String, Option<String>)>) {
+    for (name, value) in table {
+        let key = if prefix.is_empty() { name } else { format!("{}_{}", prefix, name) };
+        let text = match value {
+            toml::Value::Table(inner) => {
+                flatten_table(&key, inner, out);
+                continue;
+            }
+            toml::Value::String(s) => Some(s),
+            toml::Value::Integer(i) => Some(i.to_string()),
+            toml::Value::Float(f) => Some(f.to_string()),
+            toml::Value::Boolean(b) => Some(b.to_string()),
+            toml::Value::Array(items) => items
+                .into_iter()
+                .map(|item| item.as_str().map(String::from))
+                .collect::<Option<Vec<_>>>()
+                .map(|items| items.join(",")),
+            toml::Value::Datetime(_) => None,
+        };
+        out.push((key, text));
+    }
+}
+
+/// Проверяет значения по одному, копя ошибки вместо того, чтобы остановиться на первой
+struct Validator<'a> {
+    layers: &'a Layers,
+    errors: Vec<String>,
+}
+
+impl Validator<'_> {
+    fn text(&self, key: &str) -> String {
+        self.layers.values[key].0.clone()
+    }
+
+    /// Пустая строка - настройка не задана
+    fn optional(&self, key: &str) -> Option<String> {
+        Some(self.text(key)).filter(|value| !value.trim().is_empty())
+    }
+
+    fn error(&mut self, key: &str, problem: &str) {
+        let setting = find_setting(key).expect("validated settings are registered");
+        let (value, source) = &self.layers.values[key];
+        let origin = match source {
+            Source::Default => "default".to_string(),
+            Source::File(path) => path.clone(),
+            Source::Env => format!("environment variable {}", env_name(key)),
+            Source::Cli => format!("option {}", flag_name(key)),
+        };
+        let shown = if setting.secret { REDACTED.to_string() } else { format!("{:?}", value) };
+        self.errors.push(format!("{} = {} (from {}): {}", key, shown, origin, problem));
+    }
+
+    fn parse<T: FromStr + Default>(&mut self, key: &str, problem: &str) -> T {
+        match self.text(key).trim().parse() {
+            Ok(value) => value,
+            Err(_) => {
+                self.error(key, problem);
+                T::default()
+            }
+        }
+    }
+
+    fn port(&mut self, key: &str) -> u16 {
+        match self.text(key).trim().parse::<u16>() {
+            Ok(port) if port > 0 => port,
+            _ => {
+                self.error(key, "must be a port number from 1 to 65535");
+                0
+            }
+        }
+    }
+
+    fn in_range<T>(&mut self, key: &str, min: T, max: T) -> T
+    where
+        T: FromStr + Default + PartialOrd + fmt::Display + Copy,
+    {
+        let problem = format!("must be a number from {} to {}", min, max);
+        let value = self.parse(key, &problem);
+        if value < min || value > max {
+            self.error(key, &problem);
+        }
+        value
+    }
+
+    fn one_of(&mut self, key: &str, allowed: &[&str]) -> String {
+        let value = self.text(key);
+        if !allowed.contains(&value.as_str()) {
+            self.error(key, &format!("must be one of: {}", allowed.join(", ")));
+        }
+        value
+    }
+
+    fn url(&mut self, key: &str) -> String {
+        let value = self.text(key);
+        let valid = reqwest::Url::parse(&value)
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+        if !valid {
+            self.error(key, "must be an http or https URL");
+        }
+        value
+    }
+
+    fn email(&mut self, key: &str) -> String {
+        let value = self.text(key);
+        if value.parse::<lettre::Address>().is_err() {
+            self.error(key, "must be an email address");
+        }
+        value
+    }
+
+    fn mailbox(&mut self, key: &str) -> String {
+        let value = self.text(key);
+        if value.parse::<lettre::message::Mailbox>().is_err() {
+            self.error(key, "must be an address like Name <user@example.com>");
+        }
+        value
+    }
+
+    fn log_filter(&mut self, key: &str) -> String {
+        let value = self.text(key);
+        if let Err(e) = tracing_subscriber::EnvFilter::builder().parse(&value) {
+            self.error(key, &format!("must be a filter like info,backend=debug: {}", e));
+        }
+        value
+    }
+
+    /// `127.0.0.1,10.0.0.0/8`; пустой список - только по токену
+    fn networks(&mut self, key: &str) -> Vec<(IpAddr, u8)> {
+        let value = self.text(key);
+        let networks: Option<Vec<_>> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|network| !network.is_empty())
+            .map(metrics::parse_network)
+            .collect();
+        networks.unwrap_or_else(|| {
+            self.error(key, "must be a list of addresses or networks like 10.0.0.0/8");
+            Vec::new()
+        })
+    }
+
+    /// `developers=member,tracker-admins=admin`
+    fn group_roles(&mut self, key: &str) -> Vec<(String, String)> {
+        let value = self.text(key);
+        let pairs: Option<Vec<_>> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (group, role) = pair.split_once('=')?;
+                let (group, role) = (group.trim(), role.trim());
+                (!group.is_empty() && !role.is_empty())
+                    .then(|| (group.to_string(), role.to_string()))
+            })
+            .collect();
+        pairs.unwrap_or_else(|| {
+            self.error(key, "must look like group=role,group=role");
+            Vec::new()
+        })
+    }
+
+    /// В production секреты из примеров и значения по умолчанию запрещены
+    fn check_secrets(&mut self, config: &Config) {
+        if config.environment == Environment::Development {
+            return;
+        }
+        let weak_jwt = INSECURE_JWT_SECRETS.contains(&config.jwt_secret.as_str());
+        if weak_jwt || config.jwt_secret.len() < MIN_PRODUCTION_SECRET_LENGTH {
+            self.error(
+                "jwt_secret",
+                &format!(
+                    "production needs a random secret of at least {} characters, not the default",
+                    MIN_PRODUCTION_SECRET_LENGTH
+                ),
+            );
+        }
+        if config.admin_password == INSECURE_ADMIN_PASSWORD {
+            self.error("admin_password", "the default admin password cannot be used in production");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layers(file: Option<&str>, env: &[(&str, &str)], flags: &[&str]) -> Layers {
+        let env: HashMap<String, String> =
+            env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let cli = Cli::parse(flags.iter().map(|f| f.to_string())).expect("flags");
+        let file = file.map(|text| Ok(("test.toml".to_string(), text.to_string())));
+        Layers::from_sources(file, |name| env.get(name).cloned(), &cli.overrides)
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let file = "port = 8081\ndigest_hour = 6\n[smtp]\nhost = \"mail.test\"\n";
+        let env = [("PORT", "8082"), ("DIGEST_HOUR", "7")];
+        let config = layers(Some(file), &env, &["--port", "8083"]).build().expect("config");
+
+        assert_eq!(config.port, 8083);
+        assert_eq!(config.digest_hour, 7);
+        assert_eq!(config.smtp_host.as_deref(), Some("mail.test"));
+        assert_eq!(config.smtp_port, 587);
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let file = "prot = 1\n";
+        let env = [("PORT", "eighty"), ("SMTP_TLS", "ssl"), ("APP_URL", "localhost")];
+        let errors = layers(Some(file), &env, &["--digest-hour=25"]).build().err().expect("errors");
+
+        assert_eq!(errors.0.len(), 5, "{}", errors);
+        let text = errors.to_string();
+        for key in ["prot", "port", "smtp_tls", "app_url", "digest_hour"] {
+            assert!(text.contains(key), "{} not reported:\n{}", key, text);
+        }
+    }
+
+    #[test]
+    fn production_refuses_default_secrets() {
+        let env = [("APP_ENV", "production"), ("JWT_SECRET", "super-secret-change-me")];
+        let errors = layers(None, &env, &[]).build().err().expect("errors").to_string();
+        assert!(errors.contains("jwt_secret") && errors.contains("admin_password"), "{}", errors);
+        assert!(!errors.contains("super-secret-change-me"), "secret leaked: {}", errors);
+
+        let env = [
+            ("APP_ENV", "production"),
+            ("JWT_SECRET", "0123456789abcdef0123456789abcdef"),
+            ("ADMIN_PASSWORD", "a much better password"),
+        ];
+        assert!(layers(None, &env, &[]).build().is_ok());
+    }
+
+    #[test]
+    fn printed_config_hides_secrets() {
+        let env = [("SMTP_PASSWORD", "hunter2")];
+        let printed = layers(None, &env, &["--host=0.0.0.0"]).render();
+
+        assert!(printed.contains("host = \"0.0.0.0\"  # --host"));
+        assert!(printed.contains("smtp_password = \"<redacted>\"  # env SMTP_PASSWORD"));
+        assert!(!printed.contains("hunter2") && !printed.contains("adminpass123"));
+    }
+
+    #[test]
+    fn flags_come_before_the_command() {
+        let args = ["--config", "prod.toml", "import", "jira", "--port"];
+        let cli = Cli::parse(args.iter().map(|a| a.to_string())).expect("cli");
+        assert_eq!(cli.config_file.as_deref(), Some("prod.toml"));
+        assert_eq!(cli.command, ["import", "jira", "--port"]);
+
+        let unknown = Cli::parse(["--prot".to_string(), "1".to_string()]);
+        assert!(unknown.is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::jobs::{self, Attempt, Job};
+use crate::models::{Notification, User};
+use crate::passwords::{self, ResetNotifier};
+use crate::repository;
+use chrono::{Timelike, Utc};
+use futures_util::future::BoxFuture;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+pub const TEMPLATE_NOTIFICATION: &str = "notification";
+pub const TEMPLATE_DIGEST: &str = "digest";
+
+/// Как часто проверять, не пора ли рассылать дайджесты
+pub const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct Mailer {
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+    from: Mailbox,
+    app_url: String,
+}
+
+impl Mailer {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let from = config
+            .mail_from
+            .parse()
+            .map_err(|e| format!("Invalid MAIL_FROM: {}", e))?;
+
+        let transport = match &config.smtp_host {
+            None => None,
+            Some(host) => {
+                let builder = match config.smtp_tls.as_str() {
+                    "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                        .map_err(|e| e.to_string())?,
+                    "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                        .map_err(|e| e.to_string())?,
+                    "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+                    other => return Err(format!("Unknown SMTP_TLS mode: {}", other)),
+                };
+                let builder = builder.port(config.smtp_port);
+                let builder = match (&config.smtp_username, &config.smtp_password) {
+                    (Some(user), Some(password)) => {
+                        builder.credentials(Credentials::new(user.clone(), password.clone()))
+                    }
+                    _ => builder,
+                };
+                Some(builder.build())
+            }
+        };
+
+        Ok(Self {
+            transport,
+            from,
+            app_url: config.app_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    async fn send(&self, to: &str, content: EmailContent) -> Result<(), String> {
+        let Some(transport) = &self.transport else {
+            tracing::info!("Email to {}: {}\n{}", to, content.subject, content.text);
+            return Ok(());
+        };
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse().map_err(|e| format!("Invalid address {}: {}", to, e))?)
+            .subject(content.subject)
+            .multipart(MultiPart::alternative_plain_html(content.text, content.html))
+            .map_err(|e| e.to_string())?;
+
+        transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Ссылку сброса отправляем сразу, минуя очередь: в outbox она лежала бы открытым текстом
+impl ResetNotifier for Mailer {
+    fn send_reset_link<'a>(&'a self, user: &'a User, link: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(self.send(&user.email, render_password_reset(&user.name, link)))
+    }
+}
+
+// ============ Queue ============
+
+/// Отправляет письмо по уведомлению, если пользователь выбрал режим immediate.
+/// Ошибки только логируются: сбой почты не должен ломать изменение задачи
+pub async fn on_notification(pool: &SqlitePool, notification: &Notification) {
+    let mode = match repository::get_email_preferences(pool, notification.user_id).await {
+        Ok(prefs) => prefs.mode,
+        Err(e) => {
+            tracing::error!("Failed to load email preferences: {}", e);
+            return;
+        }
+    };
+    if mode != "immediate" {
+        return;
+    }
+
+    enqueue(
+        pool,
+        notification.user_id,
+        TEMPLATE_NOTIFICATION,
+        std::slice::from_ref(notification),
+    )
+    .await;
+}
+
+async fn enqueue(pool: &SqlitePool, user_id: i64, template: &str, notifications: &[Notification]) {
+    let user = match repository::get_user_by_id(pool, user_id).await {
+        Ok(user) => user,
+        Err(e) => {
+            tracing::error!("Failed to load user {} for email: {}", user_id, e);
+            return;
+        }
+    };
+    // Заглушки импортированных пользователей писем не получают
+    if user.email.ends_with(".invalid") {
+        return;
+    }
+
+    if let Err(e) = insert_email(pool, user_id, &user.email, template, notifications).await {
+        tracing::error!("Failed to enqueue email for user {}: {}", user_id, e);
+    }
+}
+
+/// Письмо в outbox и задача на его отправку появляются вместе
+async fn insert_email(
+    pool: &SqlitePool,
+    user_id: i64,
+    to_address: &str,
+    template: &str,
+    notifications: &[Notification],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    let email_id =
+        repository::enqueue_email(&mut *tx, user_id, to_address, template, notifications).await?;
+    jobs::enqueue(&mut *tx, &Job::SendEmail { email_id }).await?;
+    tx.commit().await?;
+    jobs::wake();
+    Ok(())
+}
+
+/// Одна попытка отправки из очереди. Ошибка - повод для повтора
+pub async fn deliver(
+    pool: &SqlitePool,
+    mailer: &Mailer,
+    email_id: i64,
+    attempt: &Attempt,
+) -> Result<(), String> {
+    let email = repository::get_unsent_email(pool, email_id).await.map_err(|e| e.to_string())?;
+    let Some(email) = email else {
+        return Ok(());
+    };
+
+    let name = repository::get_user_by_id(pool, email.user_id)
+        .await
+        .map(|u| u.name)
+        .unwrap_or_default();
+    let content = render(&email.template, &name, &email.payload.0, &mailer.app_url);
+
+    let error = match mailer.send(&email.to_address, content).await {
+        Ok(()) => {
+            return repository::mark_email_sent(pool, email.id).await.map_err(|e| e.to_string());
+        }
+        Err(e) => e,
+    };
+
+    tracing::warn!("Email {} failed (attempt {}): {}", email.id, attempt.number, error);
+    if let Err(e) =
+        repository::mark_email_attempt_failed(pool, email.id, &error, attempt.retry_in).await
+    {
+        tracing::error!("Failed to record email {}: {}", email.id, e);
+    }
+    Err(error)
+}
+
+/// Раз в день после `digest_hour` (UTC) собирает новые уведомления в одно письмо.
+/// Запускается из очереди каждые DIGEST_CHECK_INTERVAL
+pub async fn send_digests(pool: &SqlitePool, digest_hour: u32) -> Result<(), AppError> {
+    if Utc::now().hour() < digest_hour {
+        return Ok(());
+    }
+
+    let users = repository::get_users_due_for_digest(pool).await?;
+    for (user_id, cursor) in users {
+        let notifications = match repository::get_notifications_after(pool, user_id, cursor).await {
+            Ok(notifications) => notifications,
+            Err(e) => {
+                tracing::error!("Failed to load digest for user {}: {}", user_id, e);
+                continue;
+            }
+        };
+        if !notifications.is_empty() {
+            enqueue(pool, user_id, TEMPLATE_DIGEST, &notifications).await;
+        }
+
+        let cursor = notifications.last().map_or(cursor, |n| n.id);
+        if let Err(e) = repository::record_digest(pool, user_id, cursor).await {
+            tracing::error!("Failed to record digest for user {}: {}", user_id, e);
+        }
+    }
+    Ok(())
+}
+
+// ============ Templates ============
+
+struct EmailContent {
+    subject: String,
+    text: String,
+    html: String,
+}
+
+fn render(template: &str, name: &str, notifications: &[Notification], app_url: &str) -> EmailContent {
+    let subject = match (template, notifications) {
+        (TEMPLATE_NOTIFICATION, [notification]) => notification.message.clone(),
+        (_, [_]) => "Your daily digest: 1 update".to_string(),
+        _ => format!("Your daily digest: {} updates", notifications.len()),
+    };
+    let intro = if template == TEMPLATE_DIGEST {
+        "Here is what happened since your last digest:"
+    } else {
+        "You have a new notification:"
+    };
+    let settings_url = format!("{}/", app_url);
+
+    let mut text = format!("Hi {},\n\n{}\n\n", name, intro);
+    let mut html = format!(
+        "<p>Hi {},</p>\n<p>{}</p>\n<ul>\n",
+        escape_html(name),
+        escape_html(intro)
+    );
+
+    for notification in notifications {
+        match notification.task_id {
+            Some(task_id) => {
+                let url = format!("{}/?task={}", app_url, task_id);
+                text.push_str(&format!("- {}\n  {}\n", notification.message, url));
+                html.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    escape_html(&url),
+                    escape_html(&notification.message)
+                ));
+            }
+            None => {
+                text.push_str(&format!("- {}\n", notification.message));
+                html.push_str(&format!("<li>{}</li>\n", escape_html(&notification.message)));
+            }
+        }
+    }
+
+    text.push_str(&format!(
+        "\n--\nTask Tracker\nYou can change email preferences in your profile: {}\n",
+        settings_url
+    ));
+    html.push_str(&format!(
+        "</ul>\n<hr>\n<p style=\"color:#888;font-size:12px\">Task Tracker. \
+         You can change email preferences in your <a href=\"{}\">profile</a>.</p>\n",
+        escape_html(&settings_url)
+    ));
+
+    EmailContent {
+        subject,
+        text,
+        html,
+    }
+}
+
+fn render_password_reset(name: &str, link: &str) -> EmailContent {
+    let text = format!(
+        "Hi {},\n\nSomeone asked to reset your Task Tracker password. \
+         Open this link within {} minutes to choose a new one:\n\n{}\n\n\
+         If it wasn't you, ignore this email: your password stays the same.\n",
+        name,
+        passwords::RESET_TOKEN_MINUTES,
+        link
+    );
+    let html = format!(
+        "<p>Hi {},</p>\n<p>Someone asked to reset your Task Tracker password. \
+         Open this link within {} minutes to choose a new one:</p>\n\
+         <p><a href=\"{}\">Reset password</a></p>\n\
+         <p style=\"color:#888;font-size:12px\">If it wasn't you, ignore this email: \
+         your password stays the same.</p>\n",
+        escape_html(name),
+        passwords::RESET_TOKEN_MINUTES,
+        escape_html(link)
+    );
+
+    EmailContent {
+        subject: "Reset your password".to_string(),
+        text,
+        html,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(task_id: Option<i64>, message: &str) -> Notification {
+        Notification {
+            id: 1,
+            user_id: 1,
+            kind: "commented".to_string(),
+            task_id,
+            actor_id: None,
+            message: message.to_string(),
+            read_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn single_notification_uses_its_message_as_subject() {
+        let notifications = [notification(Some(7), "Bob commented on \"<b>Отчёт</b>\"")];
+        let content = render(TEMPLATE_NOTIFICATION, "Ann", &notifications, "https://t.example");
+
+        assert_eq!(content.subject, "Bob commented on \"<b>Отчёт</b>\"");
+        assert!(content.text.starts_with("Hi Ann,\n\nYou have a new notification:"));
+        assert!(content.text.contains("  https://t.example/?task=7\n"));
+        // В HTML сообщение экранируется, в тексте остаётся как есть
+        assert!(content.html.contains(
+            "<a href=\"https://t.example/?task=7\">\
+             Bob commented on &quot;&lt;b&gt;Отчёт&lt;/b&gt;&quot;</a>"
+        ));
+    }
+
+    #[test]
+    fn digest_subject_counts_updates() {
+        let one = [notification(None, "a")];
+        assert_eq!(render(TEMPLATE_DIGEST, "Ann", &one, "").subject, "Your daily digest: 1 update");
+
+        let many = [notification(None, "a"), notification(Some(2), "b")];
+        let content = render(TEMPLATE_DIGEST, "Ann", &many, "");
+        assert_eq!(content.subject, "Your daily digest: 2 updates");
+        assert!(content.text.contains("Here is what happened since your last digest:"));
+        assert!(content.html.contains("<li>a</li>"));
+    }
+
+    #[test]
+    fn reset_email_escapes_name_and_link() {
+        let content = render_password_reset("<Ann>", "https://t.example/reset?a=1&b=2");
+        assert!(content.text.contains("\n\nhttps://t.example/reset?a=1&b=2\n\n"));
+        assert!(content.html.contains("Hi &lt;Ann&gt;,"));
+        assert!(content.html.contains("href=\"https://t.example/reset?a=1&amp;b=2\""));
+    }
+
+    #[test]
+    fn html_escaping_covers_markup_and_quotes() {
+        assert_eq!(
+            escape_html(r#"<a href="x">&</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+}
@@ -7,6 +7,8 @@ pub enum AppError {
     BadRequest(String),
     Unauthorized,
     Forbidden,
+    /// Слишком много попыток; повторить можно через столько секунд
+    TooManyRequests(i64),
     Internal(String),
 }
 
@@ -17,11 +19,14 @@ impl fmt::Display for AppError {
             AppError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
             AppError::Unauthorized => write!(f, "Unauthorized"),
             AppError::Forbidden => write!(f, "Forbidden"),
+            AppError::TooManyRequests(secs) => write!(f, "Too many requests, retry in {}s", secs),
             AppError::Internal(msg) => write!(f, "Internal error: {}", msg),
         }
     }
 }
 
+impl std::error::Error for AppError {}
+
 impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
         match self {
@@ -37,6 +42,11 @@ impl ResponseError for AppError {
             AppError::Forbidden => {
                 HttpResponse::Forbidden().json(serde_json::json!({"error": "Forbidden"}))
             }
+            AppError::TooManyRequests(secs) => HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", secs.to_string()))
+                .json(serde_json::json!({
+                    "error": format!("Too many attempts, try again in {} seconds", secs)
+                })),
             AppError::Internal(msg) => {
                 tracing::error!("Internal error: {}", msg);
                 HttpResponse::InternalServerError()
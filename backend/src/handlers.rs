@@ -1,39 +1,110 @@
 use crate::auth;
 use crate::config::Config;
 use crate::errors::AppError;
+use crate::import_export::TaskEncoder;
+use crate::metrics::{HttpMetrics, MetricsAccess};
 use crate::ml_client::MlClient;
+use crate::oidc::OidcClient;
 use crate::models::{
-    AuthenticatedUser, ChangePasswordRequest, CreateTaskRequest, CreateUserRequest, LoginRequest,
-    UpdateTaskRequest,
+    AuditLogQuery, AuthenticatedUser, CalendarFeedResponse, ChangePasswordRequest, ClientInfo,
+    CreateAccessTokenRequest, CreateCommentRequest, CreateRoleRequest, CreateTaskRequest,
+    CreateUserRequest, CreateViewRequest, CreateWebhookRequest, DisableTwoFactorRequest,
+    EmailPreferences, EventsQuery, ExportQuery, ExternalImportRequest, ForgotPasswordRequest,
+    HealthStatus, ImportSource, ImportTasksRequest, JobsQuery, LoginRequest, NotificationsQuery,
+    OidcCallbackRequest, RefreshRequest, ResetPasswordRequest, TaskFilter, TaskListQuery,
+    TwoFactorCodeRequest, TwoFactorLoginRequest, UnreadCount, UpdateRoleRequest, UpdateTaskRequest,
+    UpdateViewRequest, UpdateWebhookRequest,
 };
+use crate::passwords::{PasswordPolicy, ResetNotifier};
+use crate::policy::{self, Action, Resource};
+use crate::realtime::Broadcaster;
 use crate::services;
+use crate::telemetry;
+use actix_web::http::Method;
 use actix_web::{HttpRequest, HttpResponse, web};
 use sqlx::SqlitePool;
 
-fn extract_user(req: &HttpRequest, config: &Config) -> Result<AuthenticatedUser, AppError> {
-    let header = req
-        .headers()
+/// Файлы импорта заметно больше обычных JSON-запросов
+const IMPORT_MAX_BYTES: usize = 10 * 1024 * 1024;
+
+async fn extract_user(
+    req: &HttpRequest,
+    config: &Config,
+    pool: &SqlitePool,
+) -> Result<AuthenticatedUser, AppError> {
+    user_for_token(req, bearer_token(req)?, config, pool, false).await
+}
+
+/// Пускает и с токеном, выданным до настройки обязательной 2FA: он годится
+/// для самой настройки, профиля и выхода
+async fn extract_user_during_setup(
+    req: &HttpRequest,
+    config: &Config,
+    pool: &SqlitePool,
+) -> Result<AuthenticatedUser, AppError> {
+    user_for_token(req, bearer_token(req)?, config, pool, true).await
+}
+
+fn bearer_token(req: &HttpRequest) -> Result<&str, AppError> {
+    req.headers()
         .get("Authorization")
         .and_then(|h| h.to_str().ok())
-        .ok_or(AppError::Unauthorized)?;
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(AppError::Unauthorized)
+}
+
+/// Проверки токена, общие для заголовка Authorization и параметра `?token=`
+async fn user_for_token(
+    req: &HttpRequest,
+    token: &str,
+    config: &Config,
+    pool: &SqlitePool,
+    during_setup: bool,
+) -> Result<AuthenticatedUser, AppError> {
+    let user = authenticate(token, config, pool).await?;
+    telemetry::record_user(user.id);
+    // Токен на чтение годится только для запросов, которые ничего не меняют
+    if user.read_only && !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return Err(AppError::Forbidden);
+    }
+    if user.two_factor_setup_required && !during_setup {
+        return Err(AppError::Forbidden);
+    }
+    Ok(user)
+}
 
-    let token = header
-        .strip_prefix("Bearer ")
-        .ok_or(AppError::Unauthorized)?;
+/// JWT из входа или личный токен доступа - их различает префикс
+async fn authenticate(
+    token: &str,
+    config: &Config,
+    pool: &SqlitePool,
+) -> Result<AuthenticatedUser, AppError> {
+    if token.starts_with(auth::PERSONAL_TOKEN_PREFIX) {
+        return services::authenticate_access_token(pool, token).await;
+    }
 
     let claims = auth::verify_token(token, &config.jwt_secret)?;
+    services::check_session(pool, claims.sid).await?;
 
     Ok(AuthenticatedUser {
         id: claims.sub,
-        role: claims.role,
+        session_id: Some(claims.sid),
+        permissions: claims.permissions,
+        two_factor_setup_required: claims.two_factor_setup,
+        read_only: false,
     })
 }
 
-fn require_admin(user: &AuthenticatedUser) -> Result<(), AppError> {
-    if user.role != "admin" {
-        return Err(AppError::Forbidden);
+/// Адрес берём у соединения, а не из X-Forwarded-For: заголовок подделывается
+fn client_info(req: &HttpRequest) -> ClientInfo {
+    ClientInfo {
+        user_agent: req
+            .headers()
+            .get("User-Agent")
+            .and_then(|h| h.to_str().ok())
+            .map(String::from),
+        ip: req.peer_addr().map(|addr| addr.ip().to_string()),
     }
-    Ok(())
 }
 
 // ============ Auth ============
@@ -41,35 +112,349 @@ fn require_admin(user: &AuthenticatedUser) -> Result<(), AppError> {
 pub async fn login(
     pool: web::Data<SqlitePool>,
     config: web::Data<Config>,
+    http_req: HttpRequest,
     req: web::Json<LoginRequest>,
 ) -> Result<HttpResponse, AppError> {
-    let response = services::login(pool.get_ref(), req.into_inner(), &config.jwt_secret).await?;
+    let client = client_info(&http_req);
+    let response =
+        services::login(pool.get_ref(), req.into_inner(), &client, &config.jwt_secret).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn login_two_factor(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: web::Json<TwoFactorLoginRequest>,
+) -> Result<HttpResponse, AppError> {
+    let client = client_info(&http_req);
+    let response =
+        services::login_two_factor(pool.get_ref(), req.into_inner(), &client, &config.jwt_secret)
+            .await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn get_oidc_status(oidc: web::Data<OidcClient>) -> HttpResponse {
+    HttpResponse::Ok().json(services::get_oidc_status(&oidc))
+}
+
+pub async fn start_oidc_login(
+    pool: web::Data<SqlitePool>,
+    oidc: web::Data<OidcClient>,
+) -> Result<HttpResponse, AppError> {
+    let response = services::start_oidc_login(pool.get_ref(), &oidc).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn finish_oidc_login(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    oidc: web::Data<OidcClient>,
+    http_req: HttpRequest,
+    req: web::Json<OidcCallbackRequest>,
+) -> Result<HttpResponse, AppError> {
+    let client = client_info(&http_req);
+    let response = services::finish_oidc_login(
+        pool.get_ref(),
+        &oidc,
+        req.into_inner(),
+        &client,
+        &config.jwt_secret,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn refresh(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    req: web::Json<RefreshRequest>,
+) -> Result<HttpResponse, AppError> {
+    let response =
+        services::refresh(pool.get_ref(), &req.refresh_token, &config.jwt_secret).await?;
     Ok(HttpResponse::Ok().json(response))
 }
 
+pub async fn logout(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user_during_setup(&http_req, &config, &pool).await?;
+    // Личный токен не выходит, а отзывается
+    let session_id = user.session_id.ok_or(AppError::Forbidden)?;
+    services::logout(pool.get_ref(), session_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Выход на всех устройствах, включая текущее
+pub async fn logout_everywhere(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user_during_setup(&http_req, &config, &pool).await?;
+    services::logout_everywhere(pool.get_ref(), user.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn unlock_user(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let user_id = path.into_inner();
+    policy::authorize(&user, Action::UnlockUser, Resource::User(user_id))?;
+
+    services::unlock_user(pool.get_ref(), user_id, user.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_audit_log(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    query: web::Query<AuditLogQuery>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ViewAuditLog, Resource::None)?;
+
+    let events = services::get_audit_log(pool.get_ref(), query.limit).await?;
+    Ok(HttpResponse::Ok().json(events))
+}
+
+// ============ Sessions ============
+
+pub async fn get_my_sessions(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+
+    let sessions = services::get_sessions(pool.get_ref(), user.id, user.session_id).await?;
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+pub async fn get_user_sessions(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let user_id = path.into_inner();
+    policy::authorize(&user, Action::ManageSessions, Resource::User(user_id))?;
+
+    let sessions = services::get_sessions(pool.get_ref(), user_id, user.session_id).await?;
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+pub async fn revoke_session(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let session = services::get_session(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::ManageSessions, Resource::User(session.user_id))?;
+
+    services::revoke_session(pool.get_ref(), session.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// ============ Personal access tokens ============
+
+pub async fn get_my_access_tokens(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+
+    let tokens = services::get_access_tokens(pool.get_ref(), user.id).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+/// Выпустить токен можно только из сессии входа: иначе утёкший токен
+/// продлевал бы сам себя
+pub async fn create_access_token(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: web::Json<CreateAccessTokenRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    if user.session_id.is_none() {
+        return Err(AppError::Forbidden);
+    }
+
+    let created = services::create_access_token(pool.get_ref(), user.id, req.into_inner()).await?;
+    Ok(HttpResponse::Created().json(created))
+}
+
+pub async fn get_user_access_tokens(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let user_id = path.into_inner();
+    policy::authorize(&user, Action::ManageSessions, Resource::User(user_id))?;
+
+    let tokens = services::get_access_tokens(pool.get_ref(), user_id).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+pub async fn revoke_access_token(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let token = services::get_access_token(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::ManageSessions, Resource::User(token.user_id))?;
+
+    services::revoke_access_token(pool.get_ref(), &token, user.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
 pub async fn change_password(
     pool: web::Data<SqlitePool>,
     config: web::Data<Config>,
+    passwords: web::Data<PasswordPolicy>,
     http_req: HttpRequest,
     req: web::Json<ChangePasswordRequest>,
 ) -> Result<HttpResponse, AppError> {
-    let user = extract_user(&http_req, &config)?;
-    services::change_password(pool.get_ref(), user.id, req.into_inner()).await?;
+    let user = extract_user(&http_req, &config, &pool).await?;
+    services::change_password(pool.get_ref(), &passwords, user.id, req.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"message": "Password changed"})))
+}
+
+// ============ Two-factor ============
+
+pub async fn get_two_factor(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user_during_setup(&http_req, &config, &pool).await?;
+    let status = services::get_two_factor_status(pool.get_ref(), user.id).await?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+pub async fn setup_two_factor(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user_during_setup(&http_req, &config, &pool).await?;
+    let setup = services::setup_two_factor(pool.get_ref(), user.id).await?;
+    Ok(HttpResponse::Ok().json(setup))
+}
+
+pub async fn confirm_two_factor(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: web::Json<TwoFactorCodeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user_during_setup(&http_req, &config, &pool).await?;
+    let codes = services::confirm_two_factor(pool.get_ref(), user.id, &req.code).await?;
+    Ok(HttpResponse::Ok().json(codes))
+}
+
+pub async fn regenerate_recovery_codes(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: web::Json<TwoFactorCodeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let codes = services::regenerate_recovery_codes(pool.get_ref(), user.id, &req.code).await?;
+    Ok(HttpResponse::Ok().json(codes))
+}
+
+pub async fn disable_two_factor(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: web::Json<DisableTwoFactorRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let client = client_info(&http_req);
+    services::disable_two_factor(pool.get_ref(), user.id, &req, &client).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// ============ Password reset ============
+
+pub async fn forgot_password(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    notifier: web::Data<dyn ResetNotifier>,
+    http_req: HttpRequest,
+    req: web::Json<ForgotPasswordRequest>,
+) -> Result<HttpResponse, AppError> {
+    let client = client_info(&http_req);
+    let notifier = notifier.into_inner();
+    services::forgot_password(pool.get_ref(), notifier, &req.email, &client, &config.app_url)
+        .await?;
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "message": "If the account exists, a reset link has been sent"
+    })))
+}
+
+pub async fn reset_password(
+    pool: web::Data<SqlitePool>,
+    passwords: web::Data<PasswordPolicy>,
+    req: web::Json<ResetPasswordRequest>,
+) -> Result<HttpResponse, AppError> {
+    services::reset_password(pool.get_ref(), &passwords, req.into_inner()).await?;
     Ok(HttpResponse::Ok().json(serde_json::json!({"message": "Password changed"})))
 }
 
+pub async fn force_password_reset(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    notifier: web::Data<dyn ResetNotifier>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let user_id = path.into_inner();
+    policy::authorize(&user, Action::ResetUserPassword, Resource::User(user_id))?;
+
+    services::force_password_reset(
+        pool.get_ref(),
+        notifier.into_inner(),
+        user_id,
+        user.id,
+        &config.app_url,
+    )
+    .await?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
 // ============ Users ============
 
 pub async fn create_user(
     pool: web::Data<SqlitePool>,
     config: web::Data<Config>,
+    passwords: web::Data<PasswordPolicy>,
     http_req: HttpRequest,
     req: web::Json<CreateUserRequest>,
 ) -> Result<HttpResponse, AppError> {
-    let user = extract_user(&http_req, &config)?;
-    require_admin(&user)?;
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::CreateUser, Resource::None)?;
 
-    let new_user = services::create_user(pool.get_ref(), req.into_inner()).await?;
+    let new_user =
+        services::create_user(pool.get_ref(), &passwords, &user, req.into_inner()).await?;
     Ok(HttpResponse::Created().json(new_user))
 }
 
@@ -78,9 +463,11 @@ pub async fn get_all_users(
     config: web::Data<Config>,
     http_req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
-    let _ = extract_user(&http_req, &config)?;
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ViewUsers, Resource::None)?;
+
     let users = services::get_all_users(pool.get_ref()).await?;
-    Ok(HttpResponse::Ok().json(users))
+    Ok(HttpResponse::Ok().json(policy::present_users(&user, users)))
 }
 
 pub async fn get_user(
@@ -89,9 +476,11 @@ pub async fn get_user(
     http_req: HttpRequest,
     path: web::Path<i64>,
 ) -> Result<HttpResponse, AppError> {
-    let _ = extract_user(&http_req, &config)?;
-    let user = services::get_user_by_id(pool.get_ref(), path.into_inner()).await?;
-    Ok(HttpResponse::Ok().json(user))
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ViewUsers, Resource::None)?;
+
+    let found = services::get_user_by_id(pool.get_ref(), path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(policy::present_user(&user, found)))
 }
 
 pub async fn delete_user(
@@ -100,8 +489,8 @@ pub async fn delete_user(
     http_req: HttpRequest,
     path: web::Path<i64>,
 ) -> Result<HttpResponse, AppError> {
-    let user = extract_user(&http_req, &config)?;
-    require_admin(&user)?;
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::DeleteUser, Resource::None)?;
 
     let user_id = path.into_inner();
 
@@ -118,94 +507,1696 @@ pub async fn get_me(
     config: web::Data<Config>,
     http_req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
-    let auth_user = extract_user(&http_req, &config)?;
+    let auth_user = extract_user_during_setup(&http_req, &config, &pool).await?;
     let user = services::get_user_by_id(pool.get_ref(), auth_user.id).await?;
     Ok(HttpResponse::Ok().json(user))
 }
 
-// ============ Tasks ============
+// ============ Roles ============
 
-pub async fn create_task(
+pub async fn get_roles(
     pool: web::Data<SqlitePool>,
     config: web::Data<Config>,
-    ml_client: web::Data<MlClient>,
     http_req: HttpRequest,
-    req: web::Json<CreateTaskRequest>,
 ) -> Result<HttpResponse, AppError> {
-    let user = extract_user(&http_req, &config)?;
-    let task = services::create_task(
-        pool.get_ref(),
-        ml_client.get_ref(),
-        req.into_inner(),
-        user.id,
-    )
-    .await?;
-    Ok(HttpResponse::Created().json(task))
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageRoles, Resource::None)?;
+
+    let roles = services::get_all_roles(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(roles))
 }
 
-pub async fn get_all_tasks(
+pub async fn create_role(
     pool: web::Data<SqlitePool>,
     config: web::Data<Config>,
     http_req: HttpRequest,
+    req: web::Json<CreateRoleRequest>,
 ) -> Result<HttpResponse, AppError> {
-    let _ = extract_user(&http_req, &config)?;
-    let tasks = services::get_all_tasks(pool.get_ref()).await?;
-    Ok(HttpResponse::Ok().json(tasks))
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageRoles, Resource::None)?;
+
+    let role = services::create_role(pool.get_ref(), req.into_inner()).await?;
+    Ok(HttpResponse::Created().json(role))
 }
 
-pub async fn get_task(
+pub async fn update_role(
     pool: web::Data<SqlitePool>,
     config: web::Data<Config>,
     http_req: HttpRequest,
-    path: web::Path<i64>,
+    path: web::Path<String>,
+    req: web::Json<UpdateRoleRequest>,
 ) -> Result<HttpResponse, AppError> {
-    let _ = extract_user(&http_req, &config)?;
-    let task = services::get_task_by_id(pool.get_ref(), path.into_inner()).await?;
-    Ok(HttpResponse::Ok().json(task))
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageRoles, Resource::None)?;
+
+    let role = services::update_role(pool.get_ref(), &path, req.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(role))
 }
 
-pub async fn update_task(
+pub async fn delete_role(
     pool: web::Data<SqlitePool>,
     config: web::Data<Config>,
     http_req: HttpRequest,
-    path: web::Path<i64>,
-    req: web::Json<UpdateTaskRequest>,
+    path: web::Path<String>,
 ) -> Result<HttpResponse, AppError> {
-    let _ = extract_user(&http_req, &config)?;
-    let task = services::update_task(pool.get_ref(), path.into_inner(), req.into_inner()).await?;
-    Ok(HttpResponse::Ok().json(task))
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageRoles, Resource::None)?;
+
+    services::delete_role(pool.get_ref(), &path).await?;
+    Ok(HttpResponse::NoContent().finish())
 }
 
-pub async fn delete_task(
+pub async fn get_permissions(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageRoles, Resource::None)?;
+
+    Ok(HttpResponse::Ok().json(services::get_permissions()))
+}
+
+// ============ Calendar ============
+
+pub async fn get_calendar_feed(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let status = services::get_calendar_feed_status(pool.get_ref(), user.id).await?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+pub async fn create_calendar_feed(
     pool: web::Data<SqlitePool>,
     config: web::Data<Config>,
     http_req: HttpRequest,
-    path: web::Path<i64>,
 ) -> Result<HttpResponse, AppError> {
-    let _ = extract_user(&http_req, &config)?;
-    services::delete_task(pool.get_ref(), path.into_inner()).await?;
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let token = services::create_calendar_token(pool.get_ref(), user.id).await?;
+
+    let conn = http_req.connection_info();
+    let url = format!("{}://{}/api/calendar/{}.ics", conn.scheme(), conn.host(), token);
+    Ok(HttpResponse::Created().json(CalendarFeedResponse { url }))
+}
+
+pub async fn revoke_calendar_feed(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    services::revoke_calendar_token(pool.get_ref(), user.id).await?;
     Ok(HttpResponse::NoContent().finish())
 }
 
-// ============ Routes ============
+// ============ Email ============
 
-pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/api")
-            // Auth
-            .route("/login", web::post().to(login))
-            .route("/change-password", web::post().to(change_password))
-            .route("/me", web::get().to(get_me))
-            // Users
+pub async fn get_email_preferences(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let prefs = services::get_email_preferences(pool.get_ref(), user.id).await?;
+    Ok(HttpResponse::Ok().json(prefs))
+}
+
+pub async fn update_email_preferences(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: web::Json<EmailPreferences>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let prefs = services::update_email_preferences(pool.get_ref(), user.id, req.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(prefs))
+}
+
+/// Публичная лента: календарные клиенты не умеют в Bearer, доступ даёт сам токен в ссылке
+pub async fn calendar_ics(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let body = services::render_calendar(pool.get_ref(), &path.into_inner(), &config.app_url).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(body))
+}
+
+// ============ Tasks ============
+
+pub async fn create_task(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    broadcaster: web::Data<Broadcaster>,
+    http_req: HttpRequest,
+    req: web::Json<CreateTaskRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::CreateTask, Resource::None)?;
+
+    let task =
+        services::create_task(pool.get_ref(), broadcaster.get_ref(), req.into_inner(), user.id)
+            .await?;
+    Ok(HttpResponse::Created().json(task))
+}
+
+pub async fn get_all_tasks(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    query: web::Query<TaskListQuery>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ViewTask, Resource::None)?;
+
+    let tasks = services::get_all_tasks(pool.get_ref(), query.into_inner(), &user).await?;
+    Ok(HttpResponse::Ok().json(tasks))
+}
+
+pub async fn get_task(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let task = services::get_task_by_id(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::ViewTask, Resource::Task(&task))?;
+    Ok(HttpResponse::Ok().json(task))
+}
+
+pub async fn update_task(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    broadcaster: web::Data<Broadcaster>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+    req: web::Json<UpdateTaskRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let task = services::get_task_by_id(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::UpdateTask, Resource::Task(&task))?;
+
+    let task = services::update_task(
+        pool.get_ref(),
+        broadcaster.get_ref(),
+        task.id,
+        req.into_inner(),
+        user.id,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(task))
+}
+
+pub async fn delete_task(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    broadcaster: web::Data<Broadcaster>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let task = services::get_task_by_id(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::DeleteTask, Resource::Task(&task))?;
+
+    services::delete_task(pool.get_ref(), broadcaster.get_ref(), task.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// ============ Realtime ============
+
+pub async fn task_events(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    broadcaster: web::Data<Broadcaster>,
+    http_req: HttpRequest,
+    query: web::Query<EventsQuery>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    let token = match &query.token {
+        Some(token) => token.as_str(),
+        None => bearer_token(&http_req)?,
+    };
+    let user = user_for_token(&http_req, token, &config, &pool, false).await?;
+    policy::authorize(&user, Action::ViewTask, Resource::None)?;
+
+    // Браузер при переподключении сам присылает Last-Event-ID
+    let last_event_id = http_req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .or(query.last_event_id);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(broadcaster.stream(pool.get_ref().clone(), last_event_id)))
+}
+
+// ============ Notifications ============
+
+pub async fn get_notifications(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    query: web::Query<NotificationsQuery>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let notifications = services::get_notifications(pool.get_ref(), user.id, query.unread).await?;
+    Ok(HttpResponse::Ok().json(notifications))
+}
+
+pub async fn get_unread_count(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let unread = services::count_unread_notifications(pool.get_ref(), user.id).await?;
+    Ok(HttpResponse::Ok().json(UnreadCount { unread }))
+}
+
+pub async fn mark_notification_read(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let notification =
+        services::mark_notification_read(pool.get_ref(), user.id, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(notification))
+}
+
+pub async fn mark_all_notifications_read(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let marked = services::mark_all_notifications_read(pool.get_ref(), user.id).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"marked": marked})))
+}
+
+// ============ Saved views ============
+
+pub async fn get_views(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let views = services::get_views(pool.get_ref(), user.id).await?;
+    Ok(HttpResponse::Ok().json(views))
+}
+
+pub async fn get_view(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let view = services::get_view(pool.get_ref(), path.into_inner(), &user).await?;
+    Ok(HttpResponse::Ok().json(view))
+}
+
+pub async fn create_view(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: web::Json<CreateViewRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let view = services::create_view(pool.get_ref(), user.id, req.into_inner()).await?;
+    Ok(HttpResponse::Created().json(view))
+}
+
+pub async fn update_view(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+    req: web::Json<UpdateViewRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let view = services::get_view(pool.get_ref(), path.into_inner(), &user).await?;
+    policy::authorize(&user, Action::ManageView, Resource::View(&view))?;
+
+    let view = services::update_view(pool.get_ref(), view, req.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(view))
+}
+
+pub async fn delete_view(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let view = services::get_view(pool.get_ref(), path.into_inner(), &user).await?;
+    policy::authorize(&user, Action::ManageView, Resource::View(&view))?;
+
+    services::delete_view(pool.get_ref(), view.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// ============ Watchers ============
+
+pub async fn watch_task(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let task = services::get_task_by_id(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::WatchTask, Resource::Task(&task))?;
+
+    services::watch_task(pool.get_ref(), task.id, user.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn unwatch_task(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let task = services::get_task_by_id(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::WatchTask, Resource::Task(&task))?;
+
+    services::unwatch_task(pool.get_ref(), task.id, user.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_task_watchers(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let task = services::get_task_by_id(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::ViewTask, Resource::Task(&task))?;
+
+    let watchers = services::get_task_watchers(pool.get_ref(), task.id).await?;
+    Ok(HttpResponse::Ok().json(policy::present_users(&user, watchers)))
+}
+
+// ============ Comments ============
+
+pub async fn get_task_comments(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let task = services::get_task_by_id(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::ViewTask, Resource::Task(&task))?;
+
+    let comments = services::get_task_comments(pool.get_ref(), task.id).await?;
+    Ok(HttpResponse::Ok().json(comments))
+}
+
+pub async fn create_comment(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+    req: web::Json<CreateCommentRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    let task = services::get_task_by_id(pool.get_ref(), path.into_inner()).await?;
+    policy::authorize(&user, Action::CommentTask, Resource::Task(&task))?;
+
+    let comment =
+        services::create_comment(pool.get_ref(), task.id, user.id, req.into_inner()).await?;
+    Ok(HttpResponse::Created().json(comment))
+}
+
+// ============ Import / Export ============
+
+pub async fn export_tasks(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    query: web::Query<ExportQuery>,
+    filter: web::Query<TaskFilter>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ExportTasks, Resource::None)?;
+
+    let format = query.format;
+    let encoder = TaskEncoder::new(format);
+    let body = services::export_tasks(pool.get_ref().clone(), filter.into_inner(), format);
+
+    Ok(HttpResponse::Ok()
+        .content_type(encoder.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"tasks.{}\"", encoder.file_extension()),
+        ))
+        .streaming(body))
+}
+
+pub async fn import_tasks(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: web::Json<ImportTasksRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ImportTasks, Resource::None)?;
+
+    let report = services::import_tasks(pool.get_ref(), req.into_inner(), user.id).await?;
+
+    if !report.dry_run && !report.errors.is_empty() {
+        return Ok(HttpResponse::UnprocessableEntity().json(report));
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub async fn import_external(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<ImportSource>,
+    req: web::Json<ExternalImportRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ImportExternal, Resource::None)?;
+
+    let report =
+        services::import_external(pool.get_ref(), path.into_inner(), req.into_inner(), user.id)
+            .await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// ============ Webhooks ============
+
+pub async fn create_webhook(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: web::Json<CreateWebhookRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageWebhooks, Resource::None)?;
+
+    let webhook = services::create_webhook(pool.get_ref(), req.into_inner(), user.id).await?;
+    Ok(HttpResponse::Created().json(webhook))
+}
+
+pub async fn get_all_webhooks(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageWebhooks, Resource::None)?;
+
+    let webhooks = services::get_all_webhooks(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+pub async fn update_webhook(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+    req: web::Json<UpdateWebhookRequest>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageWebhooks, Resource::None)?;
+
+    let webhook =
+        services::update_webhook(pool.get_ref(), path.into_inner(), req.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(webhook))
+}
+
+pub async fn delete_webhook(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageWebhooks, Resource::None)?;
+
+    services::delete_webhook(pool.get_ref(), path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_webhook_deliveries(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageWebhooks, Resource::None)?;
+
+    let deliveries = services::get_webhook_deliveries(pool.get_ref(), path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+pub async fn redeliver_webhook(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageWebhooks, Resource::None)?;
+
+    let delivery = services::redeliver_webhook(pool.get_ref(), path.into_inner()).await?;
+    Ok(HttpResponse::Accepted().json(delivery))
+}
+
+// ============ Jobs ============
+
+pub async fn get_jobs(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    query: web::Query<JobsQuery>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageJobs, Resource::None)?;
+
+    let jobs = services::get_jobs(pool.get_ref(), query.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+pub async fn get_job(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageJobs, Resource::None)?;
+
+    let job = services::get_job(pool.get_ref(), path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+pub async fn retry_job(
+    pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let user = extract_user(&http_req, &config, &pool).await?;
+    policy::authorize(&user, Action::ManageJobs, Resource::None)?;
+
+    let job = services::retry_job(pool.get_ref(), path.into_inner()).await?;
+    Ok(HttpResponse::Accepted().json(job))
+}
+
+// ============ Health ============
+
+/// Процесс жив и отвечает. Зависимости не проверяются: их сбой - повод не слать
+/// запросы, а не перезапускать сервер
+async fn live() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
+}
+
+/// Готовность по компонентам. 503 только если недоступно обязательное.
+/// Причины сбоев видят только те, кому открыты метрики: остальным хватит статусов
+async fn ready(
+    http_req: HttpRequest,
+    pool: web::Data<SqlitePool>,
+    ml_client: web::Data<MlClient>,
+    access: web::Data<MetricsAccess>,
+) -> HttpResponse {
+    let mut report = services::check_readiness(pool.get_ref(), ml_client.get_ref()).await;
+    if access.check(&http_req).is_err() {
+        for component in report.components.values_mut() {
+            component.error = None;
+        }
+    }
+    if report.status == HealthStatus::Down {
+        HttpResponse::ServiceUnavailable().json(report)
+    } else {
+        HttpResponse::Ok().json(report)
+    }
+}
+
+// ============ Metrics ============
+
+/// Текстовый формат Prometheus. Доступ - по адресу или токену из настроек
+async fn metrics(
+    http_req: HttpRequest,
+    pool: web::Data<SqlitePool>,
+    access: web::Data<MetricsAccess>,
+    http_metrics: web::Data<HttpMetrics>,
+    ml_client: web::Data<MlClient>,
+) -> Result<HttpResponse, AppError> {
+    access.check(&http_req)?;
+    let tasks = services::get_task_counts(pool.get_ref()).await?;
+    let body = crate::metrics::render(&http_metrics, ml_client.metrics(), &pool, &tasks);
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(body))
+}
+
+// ============ Routes ============
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    // /health оставлен для старых проверок и значит то же, что /health/live
+    cfg.route("/health", web::get().to(live))
+        .route("/health/live", web::get().to(live))
+        .route("/health/ready", web::get().to(ready))
+        .route("/metrics", web::get().to(metrics));
+    cfg.service(
+        web::scope("/api")
+            // Auth
+            .route("/login", web::post().to(login))
+            .route("/login/2fa", web::post().to(login_two_factor))
+            .route("/oidc", web::get().to(get_oidc_status))
+            .route("/oidc/start", web::post().to(start_oidc_login))
+            .route("/oidc/callback", web::post().to(finish_oidc_login))
+            .route("/refresh", web::post().to(refresh))
+            .route("/logout", web::post().to(logout))
+            .route("/logout-all", web::post().to(logout_everywhere))
+            .route("/change-password", web::post().to(change_password))
+            .route("/password/forgot", web::post().to(forgot_password))
+            .route("/password/reset", web::post().to(reset_password))
+            .route("/me", web::get().to(get_me))
+            .route("/me/sessions", web::get().to(get_my_sessions))
+            .route("/me/2fa", web::get().to(get_two_factor))
+            .route("/me/2fa", web::delete().to(disable_two_factor))
+            .route("/me/2fa/setup", web::post().to(setup_two_factor))
+            .route("/me/2fa/confirm", web::post().to(confirm_two_factor))
+            .route("/me/2fa/recovery-codes", web::post().to(regenerate_recovery_codes))
+            .route("/sessions/{id}", web::delete().to(revoke_session))
+            .route("/me/tokens", web::get().to(get_my_access_tokens))
+            .route("/me/tokens", web::post().to(create_access_token))
+            .route("/tokens/{id}", web::delete().to(revoke_access_token))
+            .route("/me/calendar", web::get().to(get_calendar_feed))
+            .route("/me/calendar", web::post().to(create_calendar_feed))
+            .route("/me/calendar", web::delete().to(revoke_calendar_feed))
+            .route("/me/email-preferences", web::get().to(get_email_preferences))
+            .route("/me/email-preferences", web::put().to(update_email_preferences))
+            .route("/calendar/{token}.ics", web::get().to(calendar_ics))
+            // Users
             .route("/users", web::post().to(create_user))
             .route("/users", web::get().to(get_all_users))
             .route("/users/{id}", web::get().to(get_user))
             .route("/users/{id}", web::delete().to(delete_user))
+            .route("/users/{id}/sessions", web::get().to(get_user_sessions))
+            .route("/users/{id}/tokens", web::get().to(get_user_access_tokens))
             // Tasks
             .route("/tasks", web::post().to(create_task))
             .route("/tasks", web::get().to(get_all_tasks))
+            .route("/tasks/export", web::get().to(export_tasks))
+            .service(
+                web::resource("/tasks/import")
+                    .app_data(web::JsonConfig::default().limit(IMPORT_MAX_BYTES))
+                    .route(web::post().to(import_tasks)),
+            )
             .route("/tasks/{id}", web::get().to(get_task))
             .route("/tasks/{id}", web::put().to(update_task))
-            .route("/tasks/{id}", web::delete().to(delete_task)),
+            .route("/tasks/{id}", web::delete().to(delete_task))
+            .route("/tasks/{id}/comments", web::get().to(get_task_comments))
+            .route("/tasks/{id}/comments", web::post().to(create_comment))
+            .route("/tasks/{id}/watchers", web::get().to(get_task_watchers))
+            .route("/tasks/{id}/watch", web::post().to(watch_task))
+            .route("/tasks/{id}/watch", web::delete().to(unwatch_task))
+            // Saved views
+            .route("/views", web::get().to(get_views))
+            .route("/views", web::post().to(create_view))
+            .route("/views/{id}", web::get().to(get_view))
+            .route("/views/{id}", web::put().to(update_view))
+            .route("/views/{id}", web::delete().to(delete_view))
+            // Notifications
+            .route("/notifications", web::get().to(get_notifications))
+            .route("/notifications/unread-count", web::get().to(get_unread_count))
+            .route("/notifications/read-all", web::post().to(mark_all_notifications_read))
+            .route("/notifications/{id}/read", web::post().to(mark_notification_read))
+            // Realtime
+            .route("/events", web::get().to(task_events))
+            // Admin
+            .route("/admin/users/{id}/unlock", web::post().to(unlock_user))
+            .route("/admin/users/{id}/reset-password", web::post().to(force_password_reset))
+            .route("/admin/audit-log", web::get().to(get_audit_log))
+            .route("/admin/roles", web::get().to(get_roles))
+            .route("/admin/roles", web::post().to(create_role))
+            .route("/admin/roles/{name}", web::put().to(update_role))
+            .route("/admin/roles/{name}", web::delete().to(delete_role))
+            .route("/admin/permissions", web::get().to(get_permissions))
+            .route("/admin/webhooks", web::post().to(create_webhook))
+            .route("/admin/webhooks", web::get().to(get_all_webhooks))
+            .route("/admin/webhooks/{id}", web::put().to(update_webhook))
+            .route("/admin/webhooks/{id}", web::delete().to(delete_webhook))
+            .route("/admin/webhooks/{id}/deliveries", web::get().to(get_webhook_deliveries))
+            .route(
+                "/admin/webhook-deliveries/{id}/redeliver",
+                web::post().to(redeliver_webhook),
+            )
+            .route("/admin/jobs", web::get().to(get_jobs))
+            .route("/admin/jobs/{id}", web::get().to(get_job))
+            .route("/admin/jobs/{id}/retry", web::post().to(retry_job))
+            .service(
+                web::resource("/admin/import/{source}")
+                    .app_data(web::JsonConfig::default().limit(IMPORT_MAX_BYTES))
+                    .route(web::post().to(import_external)),
+            ),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth;
+    use crate::jobs::{self, Job};
+    use crate::mock_idp;
+    use crate::models::{CreateAccessTokenRequest, CreateViewRequest, LoginResponse};
+    use crate::passwords::LogNotifier;
+    use crate::repository;
+    use crate::totp;
+    use actix_web::http::Method;
+    use actix_web::{App, test};
+    use serde_json::{Value, json};
+    use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteSynchronous};
+    use std::path::PathBuf;
+    use std::sync::{Arc, OnceLock};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::OnceCell;
+
+    const PASSWORD: &str = "correct horse battery";
+
+    const ADMIN_ID: i64 = 1;
+    const CREATOR_ID: i64 = 2;
+    const ASSIGNEE_ID: i64 = 3;
+    const OTHER_ID: i64 = 4;
+    /// Запасной пользователь с включённой 2FA
+    const SPARE_ID: i64 = 5;
+    const RECOVERY_CODE: &str = "abcde-12345";
+
+    /// От чьего имени идёт запрос. Задача 1 и представления принадлежат Creator
+    #[derive(Debug, Clone, Copy)]
+    enum Persona {
+        Anonymous,
+        Other,
+        Assignee,
+        Creator,
+        Admin,
+    }
+
+    const PERSONAS: [Persona; 5] = [
+        Persona::Anonymous,
+        Persona::Other,
+        Persona::Assignee,
+        Persona::Creator,
+        Persona::Admin,
+    ];
+
+    impl Persona {
+        fn user(self) -> Option<(i64, &'static str)> {
+            match self {
+                Persona::Anonymous => None,
+                Persona::Other => Some((OTHER_ID, policy::ROLE_MEMBER)),
+                Persona::Assignee => Some((ASSIGNEE_ID, policy::ROLE_MEMBER)),
+                Persona::Creator => Some((CREATOR_ID, policy::ROLE_MEMBER)),
+                Persona::Admin => Some((ADMIN_ID, policy::ROLE_ADMIN)),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Expect {
+        Allowed,
+        Unauthorized,
+        Forbidden,
+        /// Ресурса для пользователя нет: чужое личное представление, выключенный SSO
+        Hidden,
+        /// Доступ есть, но запрос отклонён как неверный
+        Invalid,
+    }
+
+    use Expect::{Allowed, Forbidden, Hidden, Invalid, Unauthorized};
+
+    // Ожидания в порядке PERSONAS: аноним, посторонний, исполнитель, автор, админ
+    const PUBLIC: [Expect; 5] = [Allowed; 5];
+    const MEMBERS: [Expect; 5] = [Unauthorized, Allowed, Allowed, Allowed, Allowed];
+    const MEMBERS_INVALID: [Expect; 5] = [Unauthorized, Invalid, Invalid, Invalid, Invalid];
+    const PARTICIPANTS: [Expect; 5] = [Unauthorized, Forbidden, Allowed, Allowed, Allowed];
+    const OWNER: [Expect; 5] = [Unauthorized, Forbidden, Forbidden, Allowed, Allowed];
+    const OWNER_PRIVATE: [Expect; 5] = [Unauthorized, Hidden, Hidden, Allowed, Allowed];
+    const ADMIN: [Expect; 5] = [Unauthorized, Forbidden, Forbidden, Forbidden, Allowed];
+    const SSO_DISABLED: [Expect; 5] = [Hidden; 5];
+
+    struct Case {
+        method: Method,
+        path: &'static str,
+        body: Option<Value>,
+        expect: [Expect; 5],
+    }
+
+    fn case(method: Method, path: &'static str, body: Option<Value>, expect: [Expect; 5]) -> Case {
+        Case { method, path, body, expect }
+    }
+
+    /// Все маршруты из `configure`
+    fn cases() -> Vec<Case> {
+        vec![
+            // Auth
+            case(Method::POST, "/api/login", Some(json!({"email": "admin@example.test", "password": PASSWORD})), PUBLIC),
+            case(Method::POST, "/api/login/2fa", Some(json!({"challenge": "{challenge}", "code": RECOVERY_CODE})), PUBLIC),
+            // SSO в тестовой конфигурации выключен
+            case(Method::GET, "/api/oidc", None, PUBLIC),
+            case(Method::POST, "/api/oidc/start", None, SSO_DISABLED),
+            case(Method::POST, "/api/oidc/callback", Some(json!({"code": "code", "state": "state"})), SSO_DISABLED),
+            case(Method::POST, "/api/password/forgot", Some(json!({"email": "creator@example.test"})), PUBLIC),
+            case(Method::POST, "/api/password/reset", Some(json!({"token": "{reset}", "new_password": "staple battery horse"})), PUBLIC),
+            case(Method::POST, "/api/refresh", Some(json!({"refresh_token": "{refresh}"})), PUBLIC),
+            case(Method::POST, "/api/logout", None, MEMBERS),
+            case(Method::POST, "/api/logout-all", None, MEMBERS),
+            case(Method::POST, "/api/change-password", Some(json!({"current_password": PASSWORD, "new_password": "staple battery horse"})), MEMBERS),
+            case(Method::GET, "/api/me", None, MEMBERS),
+            case(Method::GET, "/api/me/sessions", None, MEMBERS),
+            case(Method::GET, "/api/me/2fa", None, MEMBERS),
+            case(Method::POST, "/api/me/2fa/setup", None, MEMBERS),
+            // Без начатой настройки и без включённой 2FA подтверждать нечего
+            case(Method::POST, "/api/me/2fa/confirm", Some(json!({"code": "000000"})), MEMBERS_INVALID),
+            case(Method::POST, "/api/me/2fa/recovery-codes", Some(json!({"code": "000000"})), MEMBERS_INVALID),
+            case(Method::DELETE, "/api/me/2fa", Some(json!({"password": PASSWORD})), MEMBERS),
+            // Сессия 1 - вход автора при подготовке базы
+            case(Method::DELETE, "/api/sessions/1", None, OWNER),
+            case(Method::GET, "/api/me/tokens", None, MEMBERS),
+            case(Method::POST, "/api/me/tokens", Some(json!({"name": "CI", "scope": "read", "expires_in_days": 30})), MEMBERS),
+            // Токен 1 - токен автора на чтение
+            case(Method::DELETE, "/api/tokens/1", None, OWNER),
+            case(Method::GET, "/api/me/calendar", None, MEMBERS),
+            case(Method::POST, "/api/me/calendar", None, MEMBERS),
+            case(Method::DELETE, "/api/me/calendar", None, MEMBERS),
+            case(Method::GET, "/api/me/email-preferences", None, MEMBERS),
+            case(Method::PUT, "/api/me/email-preferences", Some(json!({"mode": "off"})), MEMBERS),
+            case(Method::GET, "/api/calendar/{calendar}.ics", None, PUBLIC),
+            // Users
+            case(Method::POST, "/api/users", Some(json!({"email": "new@example.test", "password": PASSWORD, "name": "New"})), ADMIN),
+            case(Method::GET, "/api/users", None, MEMBERS),
+            case(Method::GET, "/api/users/2", None, MEMBERS),
+            // Запасного пользователя можно удалить
+            case(Method::DELETE, "/api/users/5", None, ADMIN),
+            case(Method::GET, "/api/users/2/sessions", None, OWNER),
+            case(Method::GET, "/api/users/2/tokens", None, OWNER),
+            // Tasks
+            case(Method::POST, "/api/tasks", Some(json!({"title": "New task"})), MEMBERS),
+            case(Method::GET, "/api/tasks", None, MEMBERS),
+            case(Method::GET, "/api/tasks?q=status%20%3D%20todo", None, MEMBERS),
+            case(Method::GET, "/api/tasks?view=1", None, OWNER_PRIVATE),
+            case(Method::GET, "/api/tasks?view=2", None, MEMBERS),
+            case(Method::GET, "/api/tasks/export", None, MEMBERS),
+            case(Method::POST, "/api/tasks/import", Some(json!({"format": "csv", "data": "title\nImported\n"})), MEMBERS),
+            case(Method::GET, "/api/tasks/1", None, MEMBERS),
+            case(Method::PUT, "/api/tasks/1", Some(json!({"status": "in_progress"})), PARTICIPANTS),
+            case(Method::DELETE, "/api/tasks/1", None, OWNER),
+            case(Method::GET, "/api/tasks/1/comments", None, MEMBERS),
+            case(Method::POST, "/api/tasks/1/comments", Some(json!({"body": "Looks good"})), MEMBERS),
+            case(Method::GET, "/api/tasks/1/watchers", None, MEMBERS),
+            case(Method::POST, "/api/tasks/1/watch", None, MEMBERS),
+            case(Method::DELETE, "/api/tasks/1/watch", None, MEMBERS),
+            // Saved views: 1 - личное, 2 - общее
+            case(Method::GET, "/api/views", None, MEMBERS),
+            case(Method::POST, "/api/views", Some(json!({"name": "Mine"})), MEMBERS),
+            case(Method::GET, "/api/views/1", None, OWNER_PRIVATE),
+            case(Method::GET, "/api/views/2", None, MEMBERS),
+            case(Method::PUT, "/api/views/1", Some(json!({"name": "Renamed"})), OWNER_PRIVATE),
+            case(Method::PUT, "/api/views/2", Some(json!({"name": "Renamed"})), OWNER),
+            case(Method::DELETE, "/api/views/1", None, OWNER_PRIVATE),
+            case(Method::DELETE, "/api/views/2", None, OWNER),
+            // Notifications: у каждого пользователя своё уведомление с id, равным его id
+            case(Method::GET, "/api/notifications", None, MEMBERS),
+            case(Method::GET, "/api/notifications/unread-count", None, MEMBERS),
+            case(Method::POST, "/api/notifications/read-all", None, MEMBERS),
+            case(Method::POST, "/api/notifications/{me}/read", None, MEMBERS),
+            // Realtime
+            case(Method::GET, "/api/events", None, MEMBERS),
+            // Admin
+            case(Method::POST, "/api/admin/users/2/unlock", None, ADMIN),
+            case(Method::POST, "/api/admin/users/2/reset-password", None, ADMIN),
+            case(Method::GET, "/api/admin/audit-log?limit=10", None, ADMIN),
+            case(Method::GET, "/api/admin/roles", None, ADMIN),
+            case(Method::POST, "/api/admin/roles", Some(json!({"name": "reviewer", "permissions": ["task.view"]})), ADMIN),
+            case(Method::PUT, "/api/admin/roles/member", Some(json!({"description": "Member"})), ADMIN),
+            case(Method::DELETE, "/api/admin/roles/triager", None, ADMIN),
+            case(Method::GET, "/api/admin/permissions", None, ADMIN),
+            case(Method::POST, "/api/admin/webhooks", Some(json!({"url": "https://example.test/hook", "events": ["task.created"]})), ADMIN),
+            case(Method::GET, "/api/admin/webhooks", None, ADMIN),
+            case(Method::PUT, "/api/admin/webhooks/1", Some(json!({"active": false})), ADMIN),
+            case(Method::DELETE, "/api/admin/webhooks/1", None, ADMIN),
+            case(Method::GET, "/api/admin/webhooks/1/deliveries", None, ADMIN),
+            case(Method::POST, "/api/admin/webhook-deliveries/1/redeliver", None, ADMIN),
+            case(Method::GET, "/api/admin/jobs?status=dead", None, ADMIN),
+            case(Method::GET, "/api/admin/jobs/1", None, ADMIN),
+            case(Method::POST, "/api/admin/jobs/1/retry", None, ADMIN),
+            case(Method::POST, "/api/admin/import/trello", Some(json!({"data": "{}"})), ADMIN),
+        ]
+    }
+
+    struct Fixture {
+        pool: SqlitePool,
+        path: PathBuf,
+        config: Config,
+        calendar_token: String,
+        refresh_token: String,
+        reset_token: String,
+        challenge: String,
+        access_token: String,
+    }
+
+    impl Fixture {
+        async fn remove(self) {
+            self.pool.close().await;
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    /// Готовая база с пользователями, задачей, представлениями и вебхуком. Миграции
+    /// и наполнение делаются один раз, каждому запросу достаётся своя копия файла
+    struct Template {
+        data: Vec<u8>,
+        calendar_token: String,
+        refresh_token: String,
+        reset_token: String,
+        challenge: String,
+        access_token: String,
+    }
+
+    async fn template() -> &'static Template {
+        static TEMPLATE: OnceCell<Template> = OnceCell::const_new();
+        TEMPLATE.get_or_init(seed_template).await
+    }
+
+    fn temp_db(suffix: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("backend-policy-{}-{}.db", std::process::id(), suffix))
+    }
+
+    async fn connect(path: &PathBuf) -> SqlitePool {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Memory)
+            .synchronous(SqliteSynchronous::Off);
+        SqlitePool::connect_with(options).await.expect("connect")
+    }
+
+    async fn seed_template() -> Template {
+        let path = temp_db("template");
+        let pool = connect(&path).await;
+        repository::MIGRATOR.run(&pool).await.expect("migrate");
+
+        let permissions = vec!["task.view".to_string(), "task.update.any".to_string()];
+        repository::create_role(&pool, "triager", "", &permissions, false)
+            .await
+            .expect("create role");
+
+        let password_hash = auth::hash_password(PASSWORD).expect("hash password");
+        for (email, role) in [
+            ("admin@example.test", policy::ROLE_ADMIN),
+            ("creator@example.test", policy::ROLE_MEMBER),
+            ("assignee@example.test", policy::ROLE_MEMBER),
+            ("other@example.test", policy::ROLE_MEMBER),
+            ("spare@example.test", policy::ROLE_MEMBER),
+        ] {
+            repository::create_user(&pool, email, &password_hash, email, role)
+                .await
+                .expect("create user");
+        }
+
+        let task = CreateTaskRequest {
+            title: "Task".to_string(),
+            description: None,
+            assignee_id: Some(ASSIGNEE_ID),
+            due_date: None,
+            planned_date: None,
+        };
+        repository::create_task(&pool, &task, CREATOR_ID, None)
+            .await
+            .expect("create task");
+
+        for shared in [false, true] {
+            let view: CreateViewRequest =
+                serde_json::from_value(json!({"name": "View", "shared": shared})).expect("view");
+            repository::create_view(&pool, CREATOR_ID, &view)
+                .await
+                .expect("create view");
+        }
+
+        for user_id in [ADMIN_ID, CREATOR_ID, ASSIGNEE_ID, OTHER_ID] {
+            repository::create_notification(&pool, user_id, "assigned", Some(1), None, "Assigned")
+                .await
+                .expect("create notification");
+        }
+
+        let events = vec!["task.created".to_string()];
+        repository::create_webhook(&pool, "https://example.test/hook", "secret", &events, true, ADMIN_ID)
+            .await
+            .expect("create webhook");
+        repository::enqueue_webhook_delivery(&pool, 1, "task.created", &json!({}))
+            .await
+            .expect("enqueue delivery");
+        let job_id = jobs::enqueue(&pool, &Job::PruneJobs).await.expect("enqueue job");
+        repository::fail_job(&pool, job_id, "Failed", None).await.expect("dead job");
+
+        let calendar_token = services::create_calendar_token(&pool, CREATOR_ID)
+            .await
+            .expect("calendar token");
+
+        let login = LoginRequest {
+            email: "creator@example.test".to_string(),
+            password: PASSWORD.to_string(),
+        };
+        let client = ClientInfo::default();
+        let response = services::login(&pool, login, &client, &Config::for_tests().jwt_secret)
+            .await
+            .expect("login");
+        let refresh_token = match response {
+            LoginResponse::Authenticated(auth) => auth.refresh_token,
+            LoginResponse::TwoFactorRequired(_) => panic!("creator has no 2FA"),
+        };
+
+        let reset_token = auth::generate_token();
+        let expires_at = chrono::Utc::now() + chrono::Duration::hours(1);
+        let reset_hash = auth::hash_token(&reset_token);
+        repository::create_password_reset_token(&pool, ASSIGNEE_ID, &reset_hash, expires_at)
+            .await
+            .expect("reset token");
+
+        let read_token: CreateAccessTokenRequest =
+            serde_json::from_value(json!({"name": "Reports", "scope": "read"})).expect("token");
+        let access_token = services::create_access_token(&pool, CREATOR_ID, read_token)
+            .await
+            .expect("access token")
+            .token;
+
+        repository::save_totp_secret(&pool, SPARE_ID, &totp::generate_secret())
+            .await
+            .expect("totp secret");
+        let recovery_hash = auth::hash_token(&totp::normalize_recovery_code(RECOVERY_CODE));
+        repository::enable_totp(&pool, SPARE_ID, 0, &[recovery_hash])
+            .await
+            .expect("enable totp");
+        let challenge = auth::generate_token();
+        let challenge_hash = auth::hash_token(&challenge);
+        repository::create_login_challenge(&pool, SPARE_ID, &challenge_hash, expires_at)
+            .await
+            .expect("login challenge");
+
+        pool.close().await;
+        let data = std::fs::read(&path).expect("read template");
+        let _ = std::fs::remove_file(&path);
+        Template {
+            data,
+            calendar_token,
+            refresh_token,
+            reset_token,
+            challenge,
+            access_token,
+        }
+    }
+
+    /// Недоступный ML-сервис: задачи создаются без прогноза. Клиент общий -
+    /// его создание дороже самого запроса
+    fn ml_client() -> &'static MlClient {
+        static CLIENT: OnceLock<MlClient> = OnceLock::new();
+        CLIENT.get_or_init(|| MlClient::new("http://127.0.0.1:9".to_string()))
+    }
+
+    /// SSO выключен, но клиент с HTTP-клиентом внутри тоже создаём один раз
+    fn oidc_client() -> &'static OidcClient {
+        static CLIENT: OnceLock<OidcClient> = OnceLock::new();
+        CLIENT.get_or_init(|| OidcClient::from_config(&Config::for_tests()))
+    }
+
+    /// Свежая база на каждый запрос: проверки не должны влиять друг на друга
+    async fn fixture() -> Fixture {
+        static COPIES: AtomicUsize = AtomicUsize::new(0);
+
+        let template = template().await;
+        let path = temp_db(&COPIES.fetch_add(1, Ordering::Relaxed).to_string());
+        std::fs::write(&path, &template.data).expect("copy template");
+
+        Fixture {
+            pool: connect(&path).await,
+            path,
+            config: Config::for_tests(),
+            calendar_token: template.calendar_token.clone(),
+            refresh_token: template.refresh_token.clone(),
+            reset_token: template.reset_token.clone(),
+            challenge: template.challenge.clone(),
+            access_token: template.access_token.clone(),
+        }
+    }
+
+    /// Токен новой сессии с правами роли из базы, как его выдал бы вход
+    async fn token(fixture: &Fixture, id: i64, role: &str) -> String {
+        let permissions = services::role_permissions(&fixture.pool, role)
+            .await
+            .expect("role permissions");
+        let session_id = repository::create_session(&fixture.pool, id, &ClientInfo::default())
+            .await
+            .expect("create session");
+        auth::create_token(id, role, &permissions, session_id, false, &fixture.config.jwt_secret)
+            .expect("token")
+    }
+
+    async fn call(case: &Case, persona: Persona) -> actix_web::http::StatusCode {
+        let fixture = fixture().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(fixture.config.clone()))
+                .app_data(web::Data::new(ml_client().clone()))
+                .app_data(web::Data::new(oidc_client().clone()))
+                .app_data(web::Data::new(Broadcaster::new()))
+                .app_data(web::Data::new(PasswordPolicy::new(10, Vec::new())))
+                .app_data(web::Data::from(Arc::new(LogNotifier) as Arc<dyn ResetNotifier>))
+                .configure(configure),
+        )
+        .await;
+
+        let me = persona.user().map(|(id, _)| id).unwrap_or(ADMIN_ID);
+        let path = case
+            .path
+            .replace("{calendar}", &fixture.calendar_token)
+            .replace("{me}", &me.to_string());
+
+        let mut req = test::TestRequest::default().method(case.method.clone()).uri(&path);
+        if let Some((id, role)) = persona.user() {
+            let token = token(&fixture, id, role).await;
+            req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+        }
+        if let Some(body) = &case.body {
+            let body = body
+                .to_string()
+                .replace("{refresh}", &fixture.refresh_token)
+                .replace("{reset}", &fixture.reset_token)
+                .replace("{challenge}", &fixture.challenge);
+            req = req
+                .insert_header(("Content-Type", "application/json"))
+                .set_payload(body);
+        }
+
+        let status = test::call_service(&app, req.to_request()).await.status();
+        fixture.remove().await;
+        status
+    }
+
+    #[actix_web::test]
+    async fn every_endpoint_follows_policy() {
+        let mut failures = Vec::new();
+
+        for case in cases() {
+            for (persona, expect) in PERSONAS.into_iter().zip(case.expect) {
+                let status = call(&case, persona).await;
+                let ok = match expect {
+                    Allowed => status.is_success(),
+                    Unauthorized => status.as_u16() == 401,
+                    Forbidden => status.as_u16() == 403,
+                    Hidden => status.as_u16() == 404,
+                    Invalid => status.as_u16() == 400,
+                };
+                if !ok {
+                    failures.push(format!(
+                        "{} {} as {:?}: expected {:?}, got {}",
+                        case.method, case.path, persona, expect, status
+                    ));
+                }
+            }
+        }
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[actix_web::test]
+    async fn user_emails_are_visible_to_owner_and_admins() {
+        for persona in [Persona::Other, Persona::Admin] {
+            let fixture = fixture().await;
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(fixture.pool.clone()))
+                    .app_data(web::Data::new(fixture.config.clone()))
+                    .configure(configure),
+            )
+            .await;
+
+            let (id, role) = persona.user().expect("persona with account");
+            let token = token(&fixture, id, role).await;
+            let req = test::TestRequest::get()
+                .uri("/api/users")
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request();
+            let users: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+            fixture.remove().await;
+
+            for user in users {
+                let visible = user["id"].as_i64() == Some(id) || matches!(persona, Persona::Admin);
+                assert_eq!(user.get("email").is_some(), visible, "{:?} sees {}", persona, user);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn creating_users_cannot_grant_more_than_the_creator_has() {
+        let fixture = fixture().await;
+        let permissions = vec!["user.create".to_string()];
+        repository::create_role(&fixture.pool, "recruiter", "", &permissions, false)
+            .await
+            .expect("create role");
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(fixture.config.clone()))
+                .app_data(web::Data::new(PasswordPolicy::new(10, Vec::new())))
+                .configure(configure),
+        )
+        .await;
+
+        let recruiter = token(&fixture, OTHER_ID, "recruiter").await;
+        let create = |email: &str, role: &str| {
+            let body = json!({"email": email, "password": PASSWORD, "name": "New", "role": role});
+            test::TestRequest::post()
+                .uri("/api/users")
+                .insert_header(("Authorization", format!("Bearer {}", recruiter)))
+                .set_json(body)
+                .to_request()
+        };
+        let admin = test::call_service(&app, create("boss@example.test", policy::ROLE_ADMIN)).await;
+        let peer = test::call_service(&app, create("peer@example.test", "recruiter")).await;
+        let created = repository::get_user_by_email(&fixture.pool, "boss@example.test").await;
+        fixture.remove().await;
+
+        assert_eq!(admin.status().as_u16(), 403);
+        assert!(created.expect("lookup").is_none());
+        assert_eq!(peer.status().as_u16(), 201);
+    }
+
+    #[actix_web::test]
+    async fn reused_refresh_token_revokes_session() {
+        let fixture = fixture().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(fixture.config.clone()))
+                .configure(configure),
+        )
+        .await;
+
+        let refresh = |token: &str| {
+            test::TestRequest::post()
+                .uri("/api/refresh")
+                .set_json(json!({"refresh_token": token}))
+                .to_request()
+        };
+
+        let first = test::call_service(&app, refresh(&fixture.refresh_token)).await;
+        assert!(first.status().is_success());
+        let rotated: Value = test::read_body_json(first).await;
+        let next = rotated["refresh_token"].as_str().expect("refresh token").to_string();
+
+        // Старый токен предъявлен второй раз: сессия отзывается вместе с новым
+        let reused = test::call_service(&app, refresh(&fixture.refresh_token)).await;
+        let after_reuse = test::call_service(&app, refresh(&next)).await;
+        fixture.remove().await;
+
+        assert_eq!(reused.status().as_u16(), 401);
+        assert_eq!(after_reuse.status().as_u16(), 401);
+    }
+
+    #[actix_web::test]
+    async fn revoked_session_rejects_access_token() {
+        let fixture = fixture().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(fixture.config.clone()))
+                .configure(configure),
+        )
+        .await;
+
+        let token = token(&fixture, CREATOR_ID, policy::ROLE_MEMBER).await;
+        let request = |method: Method, uri: &str| {
+            test::TestRequest::default()
+                .method(method)
+                .uri(uri)
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
+        };
+
+        let before = test::call_service(&app, request(Method::GET, "/api/me")).await;
+        let logout = test::call_service(&app, request(Method::POST, "/api/logout")).await;
+        let after = test::call_service(&app, request(Method::GET, "/api/me")).await;
+        fixture.remove().await;
+
+        assert!(before.status().is_success());
+        assert!(logout.status().is_success());
+        assert_eq!(after.status().as_u16(), 401);
+    }
+
+    #[actix_web::test]
+    async fn two_factor_login_needs_code() {
+        let fixture = fixture().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(fixture.config.clone()))
+                .configure(configure),
+        )
+        .await;
+
+        let login = || {
+            test::TestRequest::post()
+                .uri("/api/login")
+                .set_json(json!({"email": "spare@example.test", "password": PASSWORD}))
+                .to_request()
+        };
+        let second_step = |challenge: &str, code: &str| {
+            test::TestRequest::post()
+                .uri("/api/login/2fa")
+                .set_json(json!({"challenge": challenge, "code": code}))
+                .to_request()
+        };
+
+        let first: Value = test::call_and_read_body_json(&app, login()).await;
+        let challenge = first["challenge"].as_str().expect("challenge").to_string();
+        let wrong = test::call_service(&app, second_step(&challenge, "000000")).await;
+        let right: Value =
+            test::call_and_read_body_json(&app, second_step(&challenge, "ABCDE 12345")).await;
+
+        // Резервный код одноразовый
+        let again: Value = test::call_and_read_body_json(&app, login()).await;
+        let again_challenge = again["challenge"].as_str().expect("challenge");
+        let reused = test::call_service(&app, second_step(again_challenge, RECOVERY_CODE)).await;
+        fixture.remove().await;
+
+        assert!(first.get("token").is_none());
+        assert_eq!(wrong.status().as_u16(), 401);
+        assert!(right["token"].is_string());
+        assert_eq!(reused.status().as_u16(), 401);
+    }
+
+    #[actix_web::test]
+    async fn required_two_factor_limits_token_to_setup() {
+        let fixture = fixture().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(fixture.config.clone()))
+                .app_data(web::Data::new(Broadcaster::new()))
+                .configure(configure),
+        )
+        .await;
+
+        let admin = token(&fixture, ADMIN_ID, policy::ROLE_ADMIN).await;
+        let require = test::TestRequest::put()
+            .uri("/api/admin/roles/admin")
+            .insert_header(("Authorization", format!("Bearer {}", admin)))
+            .set_json(json!({"require_two_factor": true}))
+            .to_request();
+        assert!(test::call_service(&app, require).await.status().is_success());
+
+        let login = test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(json!({"email": "admin@example.test", "password": PASSWORD}))
+            .to_request();
+        let auth: Value = test::call_and_read_body_json(&app, login).await;
+        assert_eq!(auth["two_factor_setup_required"], json!(true));
+
+        let token = auth["token"].as_str().expect("token").to_string();
+        let request = |method: Method, uri: &str| {
+            test::TestRequest::default()
+                .method(method)
+                .uri(uri)
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
+        };
+        let tasks = test::call_service(&app, request(Method::GET, "/api/tasks")).await;
+        // У EventSource токен в адресе, проверки те же, что и для заголовка
+        let events_uri = format!("/api/events?token={}", token);
+        let events = test::TestRequest::get().uri(&events_uri).to_request();
+        let events = test::call_service(&app, events).await;
+        let setup = test::call_service(&app, request(Method::POST, "/api/me/2fa/setup")).await;
+        fixture.remove().await;
+
+        assert_eq!(tasks.status().as_u16(), 403);
+        assert_eq!(events.status().as_u16(), 403);
+        assert!(setup.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn read_only_access_token_cannot_write() {
+        let fixture = fixture().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(fixture.config.clone()))
+                .app_data(web::Data::new(ml_client().clone()))
+                .app_data(web::Data::new(Broadcaster::new()))
+                .configure(configure),
+        )
+        .await;
+
+        let request = |method: Method, uri: &str, token: &str| {
+            test::TestRequest::default()
+                .method(method)
+                .uri(uri)
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .set_json(json!({"title": "From script"}))
+                .to_request()
+        };
+        let pat = fixture.access_token.clone();
+        let session = token(&fixture, CREATOR_ID, policy::ROLE_MEMBER).await;
+
+        let read = test::call_service(&app, request(Method::GET, "/api/tasks", &pat)).await;
+        let write = test::call_service(&app, request(Method::POST, "/api/tasks", &pat)).await;
+        let revoke = request(Method::DELETE, "/api/tokens/1", &session);
+        let revoke = test::call_service(&app, revoke).await;
+        let after = test::call_service(&app, request(Method::GET, "/api/tasks", &pat)).await;
+        fixture.remove().await;
+
+        assert!(read.status().is_success());
+        assert_eq!(write.status().as_u16(), 403);
+        assert!(revoke.status().is_success());
+        assert_eq!(after.status().as_u16(), 401);
+    }
+
+    #[actix_web::test]
+    async fn dead_jobs_can_be_retried_once() {
+        let fixture = fixture().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(fixture.config.clone()))
+                .configure(configure),
+        )
+        .await;
+        let admin = token(&fixture, ADMIN_ID, policy::ROLE_ADMIN).await;
+        let request = |req: test::TestRequest| {
+            req.insert_header(("Authorization", format!("Bearer {}", admin))).to_request()
+        };
+
+        // Задача 1 - dead-задача из подготовки базы
+        let dead = test::TestRequest::get().uri("/api/admin/jobs?status=dead&kind=prune_jobs");
+        let dead: Value = test::call_and_read_body_json(&app, request(dead)).await;
+        let retry = || test::TestRequest::post().uri("/api/admin/jobs/1/retry");
+        let retried = test::call_service(&app, request(retry())).await;
+        let retried_status = retried.status();
+        let retried: Value = test::read_body_json(retried).await;
+        let again = test::call_service(&app, request(retry())).await;
+        let unknown = test::TestRequest::get().uri("/api/admin/jobs?status=stuck");
+        let unknown = test::call_service(&app, request(unknown)).await;
+        fixture.remove().await;
+
+        let dead = dead.as_array().expect("jobs");
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0]["id"], 1);
+        assert_eq!(dead[0]["last_error"], "Failed");
+        assert_eq!(retried_status.as_u16(), 202);
+        assert_eq!(retried["status"], "pending");
+        assert_eq!(retried["attempts"], 0);
+        assert_eq!(again.status().as_u16(), 400);
+        assert_eq!(unknown.status().as_u16(), 400);
+    }
+
+    #[actix_web::test]
+    async fn readiness_hides_details_from_anonymous_callers() {
+        let mut fixture = fixture().await;
+        fixture.config.metrics_token = Some("scrape-secret".to_string());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(ml_client().clone()))
+                .app_data(web::Data::new(MetricsAccess::from_config(&fixture.config)))
+                .configure(configure),
+        )
+        .await;
+        let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+        let get_with_token = |uri: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("Authorization", "Bearer scrape-secret"))
+                .to_request()
+        };
+
+        let live = test::call_service(&app, get("/health/live")).await;
+        let degraded = test::call_service(&app, get("/health/ready")).await;
+        let degraded_status = degraded.status();
+        let degraded: Value = test::read_body_json(degraded).await;
+        let latest = repository::MIGRATOR.iter().map(|m| m.version).max().expect("migrations");
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = ?")
+            .bind(latest)
+            .execute(&fixture.pool)
+            .await
+            .expect("forget migration");
+        let down = test::call_service(&app, get("/health/ready")).await;
+        let down_status = down.status();
+        let down: Value = test::read_body_json(down).await;
+        let detailed = test::call_service(&app, get_with_token("/health/ready")).await;
+        let detailed: Value = test::read_body_json(detailed).await;
+        fixture.remove().await;
+
+        assert!(live.status().is_success());
+        // Сводку по компонентам считает services::check_readiness, здесь - только ответ
+        assert!(degraded_status.is_success());
+        assert_eq!(degraded["status"], "degraded");
+        assert!(degraded["components"]["database"]["latency_ms"].is_u64());
+
+        assert_eq!(down_status.as_u16(), 503);
+        assert_eq!(down["components"]["migrations"]["status"], "down");
+        // Версии миграций и прочие подробности - только с доступом к метрикам
+        assert!(down["components"]["migrations"].get("error").is_none());
+        let pending = format!("Pending migrations: {}", latest);
+        assert_eq!(detailed["components"]["migrations"]["error"], pending.as_str());
+    }
+
+    #[actix_web::test]
+    async fn metrics_need_allowed_address_or_token() {
+        let mut fixture = fixture().await;
+        fixture.config.metrics_token = Some("scrape-secret".to_string());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(fixture.config.clone()))
+                .app_data(web::Data::new(ml_client().clone()))
+                .app_data(web::Data::new(MetricsAccess::from_config(&fixture.config)))
+                .app_data(web::Data::new(HttpMetrics::default()))
+                .configure(configure)
+                .wrap(actix_web::middleware::from_fn(crate::metrics::track_requests)),
+        )
+        .await;
+
+        let scrape = |peer: &str, token: Option<&str>| {
+            let mut req = test::TestRequest::get()
+                .uri("/metrics")
+                .peer_addr(peer.parse().expect("peer address"));
+            if let Some(token) = token {
+                req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+            }
+            req.to_request()
+        };
+        test::call_service(&app, test::TestRequest::get().uri("/api/tasks").to_request()).await;
+        let remote = "203.0.113.7:5000";
+        let outsider = test::call_service(&app, scrape(remote, None)).await;
+        let token = test::call_service(&app, scrape(remote, Some("scrape-secret"))).await;
+        fixture.remove().await;
+
+        assert_eq!(outsider.status().as_u16(), 403);
+        assert!(token.status().is_success());
+        let body = test::read_body(token).await;
+        let body = String::from_utf8_lossy(&body);
+        for line in [
+            "http_requests_total{method=\"GET\",route=\"/api/tasks\",status=\"401\"} 1",
+            "http_requests_total{method=\"GET\",route=\"/metrics\",status=\"403\"} 1",
+            "tasks{status=\"todo\"} 1",
+            "tasks_overdue 0",
+        ] {
+            assert!(body.lines().any(|l| l == line), "missing {}\n{}", line, body);
+        }
+    }
+
+    /// Учебный провайдер SSO на свободном порту, возвращает его адрес
+    fn start_mock_idp() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind mock idp");
+        let issuer = format!("http://{}", listener.local_addr().expect("mock idp address"));
+        actix_web::rt::spawn(mock_idp::server(listener).expect("start mock idp"));
+        issuer
+    }
+
+    #[actix_web::test]
+    async fn single_sign_on_links_and_provisions_users() {
+        let fixture = fixture().await;
+        let mut config = fixture.config.clone();
+        config.oidc_issuer = Some(start_mock_idp());
+        config.oidc_group_roles =
+            vec![("tracker-admins".to_string(), policy::ROLE_ADMIN.to_string())];
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(fixture.pool.clone()))
+                .app_data(web::Data::new(OidcClient::from_config(&config)))
+                .app_data(web::Data::new(config))
+                .configure(configure),
+        )
+        .await;
+        // Браузер: за редиректами не идёт, их адреса разбираем сами
+        let browser = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("http client");
+
+        let mut logins = Vec::new();
+        let users = [("creator@example.test", ""), ("newcomer@example.test", "tracker-admins")];
+        for (email, groups) in users {
+            let start = test::TestRequest::post().uri("/api/oidc/start").to_request();
+            let start: Value = test::call_and_read_body_json(&app, start).await;
+            let url = start["authorization_url"].as_str().expect("authorization url");
+            let mut authorize = reqwest::Url::parse(url).expect("parse authorization url");
+
+            // Пользователь входит на странице провайдера
+            let mut form: Vec<(String, String)> = authorize.query_pairs().into_owned().collect();
+            for (name, value) in [("email", email), ("name", "SSO User"), ("groups", groups)] {
+                form.push((name.to_string(), value.to_string()));
+            }
+            authorize.set_query(None);
+            let redirect = browser.post(authorize).form(&form).send().await.expect("authorize");
+            let location = redirect.headers()["Location"].to_str().expect("location");
+            let back = reqwest::Url::parse(location).expect("parse redirect");
+            let param = |name: &str| {
+                back.query_pairs()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.into_owned())
+                    .expect("redirect parameter")
+            };
+            assert_eq!(json!(param("state")), start["state"]);
+
+            let callback = json!({"code": param("code"), "state": param("state")});
+            let finish = test::TestRequest::post()
+                .uri("/api/oidc/callback")
+                .set_json(&callback)
+                .to_request();
+            logins.push(test::call_and_read_body_json::<_, _, Value>(&app, finish).await);
+        }
+        fixture.remove().await;
+
+        let linked = &logins[0];
+        assert_eq!(linked["user"]["id"], json!(CREATOR_ID));
+        assert_eq!(linked["user"]["role"], json!(policy::ROLE_MEMBER));
+        assert!(linked["token"].is_string());
+
+        let provisioned = &logins[1];
+        assert_eq!(provisioned["user"]["email"], json!("newcomer@example.test"));
+        assert_eq!(provisioned["user"]["role"], json!(policy::ROLE_ADMIN));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::errors::AppError;
+use crate::models::{ExportedTask, TransferFormat};
+use actix_web::web::Bytes;
+use std::collections::HashMap;
+
+/// Колонки экспорта. Те же имена по умолчанию ожидает импорт
+const CSV_HEADER: [&str; 13] = [
+    "id",
+    "title",
+    "description",
+    "status",
+    "predicted_hours",
+    "actual_hours",
+    "assignee_id",
+    "assignee_email",
+    "created_by",
+    "created_at",
+    "updated_at",
+    "due_date",
+    "planned_date",
+];
+
+/// Поля задачи, которые можно заполнить при импорте
+pub const IMPORT_FIELDS: [&str; 8] = [
+    "title",
+    "description",
+    "status",
+    "assignee_email",
+    "predicted_hours",
+    "actual_hours",
+    "due_date",
+    "planned_date",
+];
+
+/// Одна строка исходного файла: имя колонки -> значение
+pub type Record = HashMap<String, String>;
+
+// ============ Export ============
+
+pub struct TaskEncoder {
+    format: TransferFormat,
+    first: bool,
+}
+
+impl TaskEncoder {
+    pub fn new(format: TransferFormat) -> Self {
+        Self { format, first: true }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self.format {
+            TransferFormat::Csv => "text/csv; charset=utf-8",
+            TransferFormat::Json => "application/json",
+            TransferFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self.format {
+            TransferFormat::Csv => "csv",
+            TransferFormat::Json => "json",
+            TransferFormat::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn header(&self) -> Result<Option<Bytes>, AppError> {
+        match self.format {
+            TransferFormat::Csv => csv_line(CSV_HEADER).map(Some),
+            TransferFormat::Json => Ok(Some(Bytes::from_static(b"["))),
+            TransferFormat::Ndjson => Ok(None),
+        }
+    }
+
+    pub fn encode(&mut self, row: &ExportedTask) -> Result<Bytes, AppError> {
+        let first = std::mem::replace(&mut self.first, false);
+
+        match self.format {
+            TransferFormat::Csv => {
+                let task = &row.task;
+                csv_line([
+                    task.id.to_string(),
+                    task.title.clone(),
+                    task.description.clone().unwrap_or_default(),
+                    task.status.clone(),
+                    opt_to_string(task.predicted_hours),
+                    opt_to_string(task.actual_hours),
+                    opt_to_string(task.assignee_id),
+                    row.assignee_email.clone().unwrap_or_default(),
+                    task.created_by.to_string(),
+                    task.created_at.to_rfc3339(),
+                    task.updated_at.to_rfc3339(),
+                    opt_to_string(task.due_date),
+                    opt_to_string(task.planned_date),
+                ])
+            }
+            TransferFormat::Json => {
+                let mut buf = if first { Vec::new() } else { b",".to_vec() };
+                serde_json::to_writer(&mut buf, row)
+                    .map_err(|e| AppError::Internal(format!("Failed to encode task: {}", e)))?;
+                Ok(Bytes::from(buf))
+            }
+            TransferFormat::Ndjson => {
+                let mut buf = serde_json::to_vec(row)
+                    .map_err(|e| AppError::Internal(format!("Failed to encode task: {}", e)))?;
+                buf.push(b'\n');
+                Ok(Bytes::from(buf))
+            }
+        }
+    }
+
+    pub fn footer(&self) -> Option<Bytes> {
+        match self.format {
+            TransferFormat::Json => Some(Bytes::from_static(b"]")),
+            _ => None,
+        }
+    }
+}
+
+fn opt_to_string<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn csv_line<I, T>(fields: I) -> Result<Bytes, AppError>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(fields)
+        .map_err(|e| AppError::Internal(format!("Failed to encode CSV: {}", e)))?;
+    writer
+        .into_inner()
+        .map(Bytes::from)
+        .map_err(|e| AppError::Internal(format!("Failed to encode CSV: {}", e)))
+}
+
+// ============ Import ============
+
+/// Записи файла с номерами строк, по которым их узнает пользователь: элемент
+/// массива JSON, строка данных CSV без заголовка, строка файла NDJSON
+pub fn parse_records(
+    format: TransferFormat,
+    data: &str,
+) -> Result<Vec<(usize, Record)>, AppError> {
+    match format {
+        TransferFormat::Csv => parse_csv(data),
+        TransferFormat::Json => {
+            let values: Vec<serde_json::Value> = serde_json::from_str(data)
+                .map_err(|e| AppError::BadRequest(format!("Invalid JSON: {}", e)))?;
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| Ok((i + 1, json_record(i + 1, value)?)))
+                .collect()
+        }
+        // Пустые строки пропускаются, но номер остаётся номером строки в файле
+        TransferFormat::Ndjson => data
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let value = serde_json::from_str(line).map_err(|e| {
+                    AppError::BadRequest(format!("Invalid JSON in row {}: {}", i + 1, e))
+                })?;
+                Ok((i + 1, json_record(i + 1, value)?))
+            })
+            .collect(),
+    }
+}
+
+fn parse_csv(data: &str) -> Result<Vec<(usize, Record)>, AppError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| AppError::BadRequest(format!("Invalid CSV header: {}", e)))?
+        .clone();
+
+    reader
+        .records()
+        .enumerate()
+        .map(|(i, record)| {
+            let record = record
+                .map_err(|e| AppError::BadRequest(format!("Invalid CSV in row {}: {}", i + 1, e)))?;
+            let record = headers
+                .iter()
+                .zip(record.iter())
+                .map(|(h, v)| (h.to_string(), v.to_string()))
+                .collect();
+            Ok((i + 1, record))
+        })
+        .collect()
+}
+
+fn json_record(row: usize, value: serde_json::Value) -> Result<Record, AppError> {
+    let serde_json::Value::Object(object) = value else {
+        return Err(AppError::BadRequest(format!("Row {} is not an object", row)));
+    };
+
+    Ok(object
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::Null => return None,
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            Some((key, value))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ndjson_rows_keep_file_line_numbers() {
+        let data = "{\"title\": \"First\"}\n\n   \n{\"title\": \"Second\"}\n";
+        let records = parse_records(TransferFormat::Ndjson, data).expect("records");
+        let rows: Vec<usize> = records.iter().map(|(row, _)| *row).collect();
+        assert_eq!(rows, vec![1, 4]);
+        assert_eq!(records[1].1["title"], "Second");
+
+        let error = parse_records(TransferFormat::Ndjson, "{}\n\n{oops}\n").unwrap_err();
+        assert!(error.to_string().contains("Invalid JSON in row 3"), "{}", error);
+    }
+
+    #[test]
+    fn csv_rows_are_trimmed_and_keyed_by_header() {
+        let data = "title , status,due_date\n  Write docs , todo ,2026-01-31\nShip,done,\n";
+        let records = parse_records(TransferFormat::Csv, data).expect("records");
+        assert_eq!(records.len(), 2);
+        let (row, first) = &records[0];
+        assert_eq!(*row, 1);
+        assert_eq!(first["title"], "Write docs");
+        assert_eq!(first["status"], "todo");
+        assert_eq!(records[1].1["due_date"], "");
+
+        let error = parse_records(TransferFormat::Csv, "title,status\nA,todo,extra\n").unwrap_err();
+        assert!(error.to_string().contains("Invalid CSV in row 1"), "{}", error);
+    }
+
+    #[test]
+    fn json_values_become_strings_and_nulls_are_dropped() {
+        let data = r#"[{"title": "A", "predicted_hours": 2.5, "description": null}, 7]"#;
+        let error = parse_records(TransferFormat::Json, data).unwrap_err();
+        assert!(error.to_string().contains("Row 2 is not an object"), "{}", error);
+
+        let records = parse_records(TransferFormat::Json, &data.replace(", 7]", "]"))
+            .expect("records");
+        let (_, record) = &records[0];
+        assert_eq!(record["predicted_hours"], "2.5");
+        assert!(!record.contains_key("description"));
+    }
+}
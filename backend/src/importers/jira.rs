@@ -0,0 +1,379 @@
+use super::{ExternalComment, ExternalTask, ExternalUser, non_empty, strip_html};
+use crate::errors::AppError;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Принимает как JSON (ответ REST API /search), так и XML (RSS-выгрузку)
+pub fn parse(data: &str) -> Result<Vec<ExternalTask>, AppError> {
+    if data.trim_start().starts_with('<') {
+        parse_xml(data)
+    } else {
+        parse_json(data)
+    }
+}
+
+// ============ JSON ============
+
+fn parse_json(data: &str) -> Result<Vec<ExternalTask>, AppError> {
+    let root: Value = serde_json::from_str(data)
+        .map_err(|e| AppError::BadRequest(format!("Invalid Jira JSON: {}", e)))?;
+
+    let issues = match &root {
+        Value::Array(issues) => issues,
+        _ => root
+            .get("issues")
+            .and_then(Value::as_array)
+            .ok_or_else(|| AppError::BadRequest("Jira JSON has no issues".to_string()))?,
+    };
+
+    issues.iter().map(json_issue).collect()
+}
+
+fn json_issue(issue: &Value) -> Result<ExternalTask, AppError> {
+    let external_id = issue
+        .get("id")
+        .or_else(|| issue.get("key"))
+        .and_then(value_to_string)
+        .ok_or_else(|| AppError::BadRequest("Jira issue without id".to_string()))?;
+
+    let fields = issue.get("fields").unwrap_or(&Value::Null);
+    let key = issue.get("key").and_then(Value::as_str);
+
+    let title = fields
+        .get("summary")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .or_else(|| key.map(str::to_string))
+        .ok_or_else(|| AppError::BadRequest(format!("Jira issue {} has no summary", external_id)))?;
+
+    let status = fields.get("status").unwrap_or(&Value::Null);
+    let status_name = status
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let status_hint = status
+        .get("statusCategory")
+        .and_then(|c| c.get("key"))
+        .and_then(Value::as_str)
+        .and_then(category_to_status);
+
+    let time_spent = fields
+        .get("timespent")
+        .or_else(|| fields.get("aggregatetimespent"))
+        .and_then(Value::as_f64);
+
+    let comments = fields
+        .get("comment")
+        .and_then(|c| c.get("comments"))
+        .and_then(Value::as_array)
+        .map(|comments| comments.iter().filter_map(json_comment).collect())
+        .unwrap_or_default();
+
+    Ok(ExternalTask {
+        external_id,
+        title,
+        description: fields.get("description").and_then(rich_text),
+        status_name,
+        status_hint,
+        assignee: fields.get("assignee").and_then(json_user),
+        reporter: fields.get("reporter").and_then(json_user),
+        actual_hours: time_spent.map(|seconds| seconds / 3600.0),
+        due_date: fields
+            .get("duedate")
+            .and_then(Value::as_str)
+            .and_then(|d| d.parse().ok()),
+        comments,
+    })
+}
+
+fn json_user(user: &Value) -> Option<ExternalUser> {
+    let external_id = user
+        .get("accountId")
+        .or_else(|| user.get("key"))
+        .or_else(|| user.get("name"))
+        .and_then(value_to_string)?;
+    let email = non_empty(user.get("emailAddress").and_then(Value::as_str));
+    let name = user
+        .get("displayName")
+        .and_then(Value::as_str)
+        .unwrap_or(&external_id)
+        .to_string();
+
+    Some(ExternalUser {
+        external_id,
+        email,
+        username: non_empty(user.get("name").and_then(Value::as_str)),
+        name,
+    })
+}
+
+fn json_comment(comment: &Value) -> Option<ExternalComment> {
+    Some(ExternalComment {
+        external_id: comment.get("id").and_then(value_to_string)?,
+        author: comment.get("author").and_then(json_user),
+        body: comment.get("body").and_then(rich_text)?,
+        created_at: comment
+            .get("created")
+            .and_then(Value::as_str)
+            .and_then(parse_json_date),
+    })
+}
+
+/// В API v2 текст - обычная строка, в v3 - документ ADF, из которого собираем текстовые узлы
+fn rich_text(value: &Value) -> Option<String> {
+    fn collect(node: &Value, out: &mut String) {
+        if let Some(text) = node.get("text").and_then(Value::as_str) {
+            out.push_str(text);
+        }
+        if let Some(children) = node.get("content").and_then(Value::as_array) {
+            for child in children {
+                collect(child, out);
+            }
+            if node.get("type").and_then(Value::as_str) == Some("paragraph") {
+                out.push('\n');
+            }
+        }
+    }
+
+    match value {
+        Value::String(s) => non_empty(Some(s)),
+        Value::Object(_) => {
+            let mut text = String::new();
+            collect(value, &mut text);
+            non_empty(Some(&text))
+        }
+        _ => None,
+    }
+}
+
+fn parse_json_date(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f%z")
+        .or_else(|_| DateTime::parse_from_rfc3339(s))
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn category_to_status(key: &str) -> Option<&'static str> {
+    match key {
+        "new" => Some("todo"),
+        "indeterminate" => Some("in_progress"),
+        "done" => Some("done"),
+        _ => None,
+    }
+}
+
+// ============ XML ============
+
+fn parse_xml(data: &str) -> Result<Vec<ExternalTask>, AppError> {
+    let doc = roxmltree::Document::parse(data)
+        .map_err(|e| AppError::BadRequest(format!("Invalid Jira XML: {}", e)))?;
+
+    doc.descendants()
+        .filter(|n| n.has_tag_name("item"))
+        .map(xml_item)
+        .collect()
+}
+
+fn xml_item(item: roxmltree::Node) -> Result<ExternalTask, AppError> {
+    let child = |name: &str| item.children().find(|n| n.has_tag_name(name));
+    let text = |name: &str| non_empty(child(name).and_then(|n| n.text()));
+
+    let key = child("key");
+    let external_id = key
+        .and_then(|k| k.attribute("id"))
+        .map(str::to_string)
+        .or_else(|| text("key"))
+        .ok_or_else(|| AppError::BadRequest("Jira item without key".to_string()))?;
+
+    let title = text("summary")
+        .or_else(|| text("title"))
+        .ok_or_else(|| AppError::BadRequest(format!("Jira item {} has no summary", external_id)))?;
+
+    let status_hint = child("statusCategory")
+        .and_then(|c| c.attribute("key"))
+        .and_then(category_to_status);
+
+    let time_spent = child("timespent")
+        .and_then(|t| t.attribute("seconds"))
+        .and_then(|s| s.parse::<f64>().ok());
+
+    let comments = child("comments")
+        .map(|c| {
+            c.children()
+                .filter(|n| n.has_tag_name("comment"))
+                .filter_map(|n| {
+                    Some(ExternalComment {
+                        external_id: n.attribute("id")?.to_string(),
+                        author: n.attribute("author").map(|author| ExternalUser {
+                            external_id: author.to_string(),
+                            email: None,
+                            username: Some(author.to_string()),
+                            name: author.to_string(),
+                        }),
+                        body: non_empty(Some(&strip_html(n.text().unwrap_or_default())))?,
+                        created_at: n
+                            .attribute("created")
+                            .and_then(|d| DateTime::parse_from_rfc2822(d).ok())
+                            .map(|d| d.with_timezone(&Utc)),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(ExternalTask {
+        external_id,
+        title,
+        description: text("description").and_then(|d| non_empty(Some(&strip_html(&d)))),
+        status_name: text("status").unwrap_or_default(),
+        status_hint,
+        assignee: child("assignee").and_then(xml_user),
+        reporter: child("reporter").and_then(xml_user),
+        actual_hours: time_spent.map(|seconds| seconds / 3600.0),
+        due_date: child("due")
+            .and_then(|n| n.text())
+            .and_then(|d| DateTime::parse_from_rfc2822(d.trim()).ok())
+            .map(|d| d.date_naive()),
+        comments,
+    })
+}
+
+fn xml_user(node: roxmltree::Node) -> Option<ExternalUser> {
+    let username = node
+        .attribute("accountid")
+        .or_else(|| node.attribute("username"))
+        .filter(|u| *u != "-1")?;
+    let name = non_empty(node.text()).unwrap_or_else(|| username.to_string());
+
+    Some(ExternalUser {
+        external_id: username.to_string(),
+        email: None,
+        username: node.attribute("username").map(str::to_string),
+        name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_issue_maps_fields_and_adf_text() {
+        let data = r#"{"issues": [{
+            "id": "10001",
+            "key": "PRJ-1",
+            "fields": {
+                "summary": "Починить вход",
+                "description": {"type": "doc", "content": [
+                    {"type": "paragraph", "content": [{"type": "text", "text": "Первая"}]},
+                    {"type": "paragraph", "content": [{"type": "text", "text": "Вторая"}]}
+                ]},
+                "status": {"name": "Code Review", "statusCategory": {"key": "indeterminate"}},
+                "assignee": {"accountId": "a-1", "displayName": "Ann",
+                             "emailAddress": "ann@example.test"},
+                "reporter": {"accountId": "b-2", "emailAddress": ""},
+                "timespent": 5400,
+                "duedate": "2026-03-01",
+                "comment": {"comments": [
+                    {"id": "7", "body": "Готово", "created": "2026-02-01T10:00:00.000+0300"},
+                    {"id": "8", "body": "   "}
+                ]}
+            }
+        }]}"#;
+
+        let tasks = parse(data).unwrap();
+        assert_eq!(tasks.len(), 1);
+        let task = &tasks[0];
+        assert_eq!(task.external_id, "10001");
+        assert_eq!(task.title, "Починить вход");
+        assert_eq!(task.description.as_deref(), Some("Первая\nВторая"));
+        assert_eq!(task.status_name, "Code Review");
+        assert_eq!(task.status_hint, Some("in_progress"));
+        assert_eq!(task.actual_hours, Some(1.5));
+        assert_eq!(task.due_date, "2026-03-01".parse().ok());
+
+        let assignee = task.assignee.as_ref().unwrap();
+        assert_eq!(assignee.email.as_deref(), Some("ann@example.test"));
+        assert_eq!(assignee.name, "Ann");
+        // Пустая почта - не почта, имя по умолчанию - идентификатор
+        let reporter = task.reporter.as_ref().unwrap();
+        assert_eq!(reporter.email, None);
+        assert_eq!(reporter.name, "b-2");
+
+        // Комментарий без текста пропускаем, дату приводим к UTC
+        assert_eq!(task.comments.len(), 1);
+        assert_eq!(task.comments[0].body, "Готово");
+        assert_eq!(
+            task.comments[0].created_at.unwrap().to_rfc3339(),
+            "2026-02-01T07:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn json_accepts_bare_array_and_falls_back_to_key() {
+        let tasks = parse(r#"[{"key": "PRJ-2", "fields": {}}]"#).unwrap();
+        assert_eq!(tasks[0].external_id, "PRJ-2");
+        assert_eq!(tasks[0].title, "PRJ-2");
+        assert_eq!(tasks[0].status_hint, None);
+    }
+
+    #[test]
+    fn json_rejects_issues_without_identity() {
+        assert!(matches!(parse(r#"{"total": 0}"#), Err(AppError::BadRequest(_))));
+        assert!(matches!(parse(r#"[{"fields": {}}]"#), Err(AppError::BadRequest(_))));
+        assert!(matches!(parse("{"), Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn xml_item_maps_fields_and_strips_html() {
+        let data = r#"<rss><channel><item>
+            <title>[PRJ-3] Отчёт</title>
+            <key id="10003">PRJ-3</key>
+            <summary>Отчёт</summary>
+            <description>&lt;p&gt;Это &lt;b&gt;план&lt;/b&gt; &amp;amp; код&lt;/p&gt;</description>
+            <status>Closed</status>
+            <statusCategory key="done"/>
+            <assignee username="ann">Ann</assignee>
+            <reporter username="-1">Anonymous</reporter>
+            <due>Sun, 1 Mar 2026 00:00:00 +0000</due>
+            <timespent seconds="7200">2 hours</timespent>
+            <comments>
+                <comment id="9" author="bob"
+                    created="Sun, 1 Feb 2026 10:00:00 +0000">&lt;p&gt;Ок&lt;/p&gt;</comment>
+            </comments>
+        </item></channel></rss>"#;
+
+        let tasks = parse(data).unwrap();
+        let task = &tasks[0];
+        assert_eq!(task.external_id, "10003");
+        assert_eq!(task.title, "Отчёт");
+        assert_eq!(task.description.as_deref(), Some("Это план & код"));
+        assert_eq!(task.status_name, "Closed");
+        assert_eq!(task.status_hint, Some("done"));
+        assert_eq!(task.actual_hours, Some(2.0));
+        assert_eq!(task.due_date, "2026-03-01".parse().ok());
+        assert_eq!(task.assignee.as_ref().unwrap().username.as_deref(), Some("ann"));
+        // Анонимный автор Jira (-1) - не пользователь
+        assert!(task.reporter.is_none());
+        assert_eq!(task.comments[0].body, "Ок");
+        assert_eq!(task.comments[0].author.as_ref().unwrap().name, "bob");
+    }
+
+    #[test]
+    fn xml_requires_key_and_summary() {
+        let no_key = "<rss><item><summary>x</summary></item></rss>";
+        assert!(matches!(parse(no_key), Err(AppError::BadRequest(_))));
+        let no_summary = "<rss><item><key>PRJ-4</key></item></rss>";
+        assert!(matches!(parse(no_summary), Err(AppError::BadRequest(_))));
+        assert!(matches!(parse("<rss>"), Err(AppError::BadRequest(_))));
+    }
+}
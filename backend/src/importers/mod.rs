@@ -0,0 +1,434 @@
+mod jira;
+mod trello;
+
+use crate::auth;
+use crate::errors::AppError;
+use crate::jobs::{self, Job};
+use crate::models::{ExternalImportReport, ExternalImportRequest, ImportSource};
+use crate::repository;
+use crate::services::TASK_STATUSES;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+
+const ENTITY_TASK: &str = "task";
+const ENTITY_USER: &str = "user";
+const ENTITY_COMMENT: &str = "comment";
+
+#[derive(Debug, Clone)]
+pub struct ExternalUser {
+    pub external_id: String,
+    pub email: Option<String>,
+    pub username: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExternalComment {
+    pub external_id: String,
+    pub author: Option<ExternalUser>,
+    pub body: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExternalTask {
+    pub external_id: String,
+    pub title: String,
+    pub description: Option<String>,
+    /// Название статуса (Jira) или колонки (Trello)
+    pub status_name: String,
+    /// Наш статус, если его можно определить однозначно (категория статуса Jira)
+    pub status_hint: Option<&'static str>,
+    pub assignee: Option<ExternalUser>,
+    pub reporter: Option<ExternalUser>,
+    pub actual_hours: Option<f64>,
+    pub due_date: Option<NaiveDate>,
+    pub comments: Vec<ExternalComment>,
+}
+
+pub fn parse(source: ImportSource, data: &str) -> Result<Vec<ExternalTask>, AppError> {
+    match source {
+        ImportSource::Jira => jira::parse(data),
+        ImportSource::Trello => trello::parse(data),
+    }
+}
+
+/// Импортирует задачи из файла экспорта Jira/Trello.
+/// Повторный импорт того же файла обновляет ранее созданные задачи
+pub async fn run(
+    pool: &SqlitePool,
+    source: ImportSource,
+    req: ExternalImportRequest,
+    importer_id: i64,
+) -> Result<ExternalImportReport, AppError> {
+    for status in req.status_mapping.values() {
+        if !TASK_STATUSES.contains(&status.as_str()) {
+            return Err(AppError::BadRequest(format!("Unknown status in mapping: {}", status)));
+        }
+    }
+
+    let tasks = parse(source, &req.data)?;
+    let status_mapping: HashMap<String, String> = req
+        .status_mapping
+        .into_iter()
+        .map(|(k, v)| (k.to_lowercase(), v))
+        .collect();
+    let user_mapping: HashMap<String, String> = req
+        .user_mapping
+        .into_iter()
+        .map(|(k, v)| (k.to_lowercase(), v))
+        .collect();
+
+    let mut importer = Importer {
+        source,
+        importer_id,
+        user_mapping,
+        users: HashMap::new(),
+        report: ExternalImportReport {
+            source: source.as_str().to_string(),
+            ..Default::default()
+        },
+    };
+
+    let mut tx = pool.begin().await?;
+
+    for task in &tasks {
+        let status = resolve_status(task, &status_mapping);
+        let assignee_id = match &task.assignee {
+            Some(user) => Some(importer.resolve_user(&mut tx, user).await?),
+            None => None,
+        };
+
+        let existing = repository::find_import_mapping(
+            &mut *tx,
+            source.as_str(),
+            ENTITY_TASK,
+            &task.external_id,
+        )
+        .await?;
+
+        let task_id = match existing {
+            Some(id) if repository::task_exists(&mut *tx, id).await? => {
+                repository::update_external_task(
+                    &mut tx,
+                    id,
+                    &task.title,
+                    task.description.as_deref(),
+                    status,
+                    assignee_id,
+                    task.actual_hours,
+                    task.due_date,
+                )
+                .await?;
+                importer.report.tasks_updated += 1;
+                id
+            }
+            _ => {
+                let created_by = match &task.reporter {
+                    Some(user) => importer.resolve_user(&mut tx, user).await?,
+                    None => importer_id,
+                };
+                let id = repository::insert_external_task(
+                    &mut tx,
+                    &task.title,
+                    task.description.as_deref(),
+                    status,
+                    assignee_id,
+                    created_by,
+                    task.actual_hours,
+                    task.due_date,
+                )
+                .await?;
+                repository::save_import_mapping(
+                    &mut *tx,
+                    source.as_str(),
+                    ENTITY_TASK,
+                    &task.external_id,
+                    id,
+                )
+                .await?;
+                // Оценку ML задача получит из очереди, когда транзакция закоммитится
+                jobs::enqueue(&mut *tx, &Job::PredictTask { task_id: id }).await?;
+                importer.report.tasks_created += 1;
+                id
+            }
+        };
+
+        for comment in &task.comments {
+            importer.import_comment(&mut tx, task_id, comment).await?;
+        }
+    }
+
+    tx.commit().await?;
+    jobs::wake();
+
+    tracing::info!(
+        "Imported from {}: {} created, {} updated, {} comments, {} new users",
+        source.as_str(),
+        importer.report.tasks_created,
+        importer.report.tasks_updated,
+        importer.report.comments_imported,
+        importer.report.users_created
+    );
+    Ok(importer.report)
+}
+
+fn resolve_status(task: &ExternalTask, mapping: &HashMap<String, String>) -> &'static str {
+    let name = task.status_name.to_lowercase();
+
+    if let Some(mapped) = mapping.get(&name) {
+        return TASK_STATUSES
+            .iter()
+            .find(|s| *s == mapped)
+            .copied()
+            .unwrap_or("todo");
+    }
+    if let Some(hint) = task.status_hint {
+        return hint;
+    }
+
+    if ["done", "complete", "closed", "resolved"]
+        .iter()
+        .any(|w| name.contains(w))
+    {
+        "done"
+    } else if ["progress", "doing", "review", "testing"]
+        .iter()
+        .any(|w| name.contains(w))
+    {
+        "in_progress"
+    } else {
+        "todo"
+    }
+}
+
+struct Importer {
+    source: ImportSource,
+    importer_id: i64,
+    user_mapping: HashMap<String, String>,
+    /// Кэш уже сопоставленных пользователей в рамках одного импорта
+    users: HashMap<String, i64>,
+    report: ExternalImportReport,
+}
+
+impl Importer {
+    async fn resolve_user(
+        &mut self,
+        conn: &mut SqliteConnection,
+        user: &ExternalUser,
+    ) -> Result<i64, AppError> {
+        if let Some(id) = self.users.get(&user.external_id) {
+            return Ok(*id);
+        }
+
+        let source = self.source.as_str();
+        let mapped =
+            repository::find_import_mapping(&mut *conn, source, ENTITY_USER, &user.external_id)
+                .await?;
+
+        let id = match mapped {
+            Some(id) if repository::user_exists(&mut *conn, id).await? => id,
+            _ => {
+                let id = self.find_or_create_user(conn, user).await?;
+                repository::save_import_mapping(
+                    &mut *conn,
+                    source,
+                    ENTITY_USER,
+                    &user.external_id,
+                    id,
+                )
+                .await?;
+                id
+            }
+        };
+
+        self.users.insert(user.external_id.clone(), id);
+        Ok(id)
+    }
+
+    async fn find_or_create_user(
+        &mut self,
+        conn: &mut SqliteConnection,
+        user: &ExternalUser,
+    ) -> Result<i64, AppError> {
+        let keys = [Some(&user.external_id), user.username.as_ref(), user.email.as_ref()];
+        let mapped_email = keys
+            .into_iter()
+            .flatten()
+            .find_map(|key| self.user_mapping.get(&key.to_lowercase()));
+
+        if let Some(email) = mapped_email {
+            return repository::get_user_by_email(&mut *conn, email)
+                .await?
+                .map(|u| u.id)
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!("User mapping points to unknown email {}", email))
+                });
+        }
+
+        if let Some(email) = &user.email
+            && let Some(existing) = repository::get_user_by_email(&mut *conn, email).await?
+        {
+            return Ok(existing.id);
+        }
+
+        // Пользователя нет - заводим учётку без известного пароля.
+        // Войти в неё можно будет после смены пароля администратором
+        let email = user.email.clone().unwrap_or_else(|| {
+            let local: String = user
+                .external_id
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect();
+            format!("{}-{}@import.invalid", self.source.as_str(), local.to_lowercase())
+        });
+        let password_hash = auth::hash_password(&auth::generate_token())?;
+
+        let created =
+            repository::create_user(&mut *conn, &email, &password_hash, &user.name, "member")
+                .await?;
+        self.report.users_created += 1;
+        Ok(created.id)
+    }
+
+    async fn import_comment(
+        &mut self,
+        conn: &mut SqliteConnection,
+        task_id: i64,
+        comment: &ExternalComment,
+    ) -> Result<(), AppError> {
+        let source = self.source.as_str();
+        if repository::find_import_mapping(&mut *conn, source, ENTITY_COMMENT, &comment.external_id)
+            .await?
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        let author_id = match &comment.author {
+            Some(author) => self.resolve_user(conn, author).await?,
+            None => self.importer_id,
+        };
+
+        let id = repository::insert_comment(
+            &mut *conn,
+            task_id,
+            author_id,
+            &comment.body,
+            comment.created_at,
+        )
+        .await?;
+        repository::save_import_mapping(&mut *conn, source, ENTITY_COMMENT, &comment.external_id, id)
+            .await?;
+
+        self.report.comments_imported += 1;
+        Ok(())
+    }
+}
+
+/// Грубое удаление HTML-тегов из описаний в XML-экспорте Jira
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy;
+
+    fn task(status_name: &str, status_hint: Option<&'static str>) -> ExternalTask {
+        ExternalTask {
+            external_id: "1".to_string(),
+            title: "t".to_string(),
+            description: None,
+            status_name: status_name.to_string(),
+            status_hint,
+            assignee: None,
+            reporter: None,
+            actual_hours: None,
+            due_date: None,
+            comments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn status_prefers_mapping_then_hint_then_name() {
+        let mapping = HashMap::from([
+            ("qa".to_string(), "in_progress".to_string()),
+            ("later".to_string(), "someday".to_string()),
+        ]);
+        assert_eq!(resolve_status(&task("QA", Some("done")), &mapping), "in_progress");
+        // Неизвестный статус в сопоставлении не пропускаем
+        assert_eq!(resolve_status(&task("Later", None), &mapping), "todo");
+        assert_eq!(resolve_status(&task("Backlog", Some("done")), &mapping), "done");
+        assert_eq!(resolve_status(&task("Resolved", None), &mapping), "done");
+        assert_eq!(resolve_status(&task("In Review", None), &mapping), "in_progress");
+        assert_eq!(resolve_status(&task("Backlog", None), &mapping), "todo");
+    }
+
+    #[test]
+    fn html_is_reduced_to_text() {
+        assert_eq!(strip_html("<p>a&nbsp;<b>b</b> &lt;c&gt; &amp;amp;</p>"), "a b <c> &amp;");
+        assert_eq!(non_empty(Some("  ")), None);
+        assert_eq!(non_empty(Some(" x ")), Some("x".to_string()));
+    }
+    #[actix_web::test]
+    async fn reimport_after_delete_creates_and_estimates_the_task_again() {
+        let pool = repository::test_pool().await;
+        let importer =
+            repository::create_user(&pool, "ann@example.test", "", "Ann", policy::ROLE_MEMBER)
+                .await
+                .expect("user");
+        let import = || {
+            let req = ExternalImportRequest {
+                data: r#"{"lists": [{"id": "l1", "name": "Doing"}],
+                          "cards": [{"id": "c1", "name": "Макет", "idList": "l1"}]}"#
+                    .to_string(),
+                status_mapping: HashMap::new(),
+                user_mapping: HashMap::new(),
+            };
+            run(&pool, ImportSource::Trello, req, importer.id)
+        };
+        let mapped = || repository::find_import_mapping(&pool, "trello", ENTITY_TASK, "c1");
+
+        import().await.expect("first import");
+        let deleted = mapped().await.unwrap().expect("mapping");
+        repository::delete_task(&pool, deleted).await.unwrap();
+        let report = import().await.expect("second import");
+        let task_id = mapped().await.unwrap().expect("mapping");
+
+        assert_eq!((report.tasks_created, report.tasks_updated), (1, 0));
+        assert_ne!(task_id, deleted);
+        let jobs = repository::get_jobs(&pool, None, Some("predict_task"), 10).await.unwrap();
+        let jobs: Vec<Job> =
+            jobs.into_iter().map(|j| serde_json::from_value(j.payload.0).unwrap()).collect();
+        assert!(jobs.contains(&Job::PredictTask { task_id }), "{:?}", jobs);
+    }
+}
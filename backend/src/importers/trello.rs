@@ -0,0 +1,246 @@
+use super::{ExternalComment, ExternalTask, ExternalUser, non_empty};
+use crate::errors::AppError;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Board {
+    #[serde(default)]
+    lists: Vec<List>,
+    #[serde(default)]
+    members: Vec<Member>,
+    #[serde(default)]
+    cards: Vec<Card>,
+    #[serde(default)]
+    actions: Vec<Action>,
+    #[serde(default)]
+    custom_fields: Vec<CustomField>,
+}
+
+#[derive(Deserialize)]
+struct List {
+    id: String,
+    name: String,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Member {
+    id: String,
+    #[serde(default)]
+    full_name: String,
+    username: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Card {
+    id: String,
+    name: String,
+    #[serde(default)]
+    desc: String,
+    id_list: String,
+    due: Option<DateTime<Utc>>,
+    #[serde(default)]
+    id_members: Vec<String>,
+    #[serde(default)]
+    custom_field_items: Vec<CustomFieldItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Action {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: ActionData,
+    member_creator: Option<Member>,
+    date: Option<DateTime<Utc>>,
+}
+
+#[derive(Default, Deserialize)]
+struct ActionData {
+    card: Option<CardRef>,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CardRef {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct CustomField {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CustomFieldItem {
+    id_custom_field: String,
+    value: Option<HashMap<String, serde_json::Value>>,
+}
+
+/// Названия пользовательских полей Trello, в которых обычно хранят затраченное время (в часах)
+const TIME_SPENT_FIELDS: [&str; 3] = ["time spent", "spent", "hours"];
+
+pub fn parse(data: &str) -> Result<Vec<ExternalTask>, AppError> {
+    let board: Board = serde_json::from_str(data)
+        .map_err(|e| AppError::BadRequest(format!("Invalid Trello JSON: {}", e)))?;
+
+    let lists: HashMap<&str, &str> = board
+        .lists
+        .iter()
+        .map(|l| (l.id.as_str(), l.name.as_str()))
+        .collect();
+    let members: HashMap<&str, &Member> =
+        board.members.iter().map(|m| (m.id.as_str(), m)).collect();
+    let time_fields: Vec<&str> = board
+        .custom_fields
+        .iter()
+        .filter(|f| TIME_SPENT_FIELDS.contains(&f.name.to_lowercase().as_str()))
+        .map(|f| f.id.as_str())
+        .collect();
+
+    let mut comments: HashMap<&str, Vec<ExternalComment>> = HashMap::new();
+    let mut creators: HashMap<&str, ExternalUser> = HashMap::new();
+    for action in &board.actions {
+        let Some(card) = &action.data.card else {
+            continue;
+        };
+        match action.kind.as_str() {
+            "commentCard" => {
+                let Some(body) = non_empty(action.data.text.as_deref()) else {
+                    continue;
+                };
+                comments.entry(card.id.as_str()).or_default().push(ExternalComment {
+                    external_id: action.id.clone(),
+                    author: action.member_creator.as_ref().map(member_to_user),
+                    body,
+                    created_at: action.date,
+                });
+            }
+            "createCard" => {
+                if let Some(member) = &action.member_creator {
+                    creators.insert(card.id.as_str(), member_to_user(member));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(board
+        .cards
+        .iter()
+        .map(|card| {
+            let actual_hours = card
+                .custom_field_items
+                .iter()
+                .filter(|item| time_fields.contains(&item.id_custom_field.as_str()))
+                .find_map(|item| item.value.as_ref()?.get("number")?.as_str()?.parse().ok());
+
+            ExternalTask {
+                external_id: card.id.clone(),
+                title: card.name.clone(),
+                description: non_empty(Some(&card.desc)),
+                status_name: lists
+                    .get(card.id_list.as_str())
+                    .copied()
+                    .unwrap_or_default()
+                    .to_string(),
+                status_hint: None,
+                assignee: card
+                    .id_members
+                    .first()
+                    .and_then(|id| members.get(id.as_str()))
+                    .map(|m| member_to_user(m)),
+                reporter: creators.get(card.id.as_str()).cloned(),
+                actual_hours,
+                due_date: card.due.map(|d| d.date_naive()),
+                comments: comments.remove(card.id.as_str()).unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+fn member_to_user(member: &Member) -> ExternalUser {
+    let name = if member.full_name.is_empty() {
+        member.username.clone().unwrap_or_else(|| member.id.clone())
+    } else {
+        member.full_name.clone()
+    };
+
+    ExternalUser {
+        external_id: member.id.clone(),
+        email: None,
+        username: member.username.clone(),
+        name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOARD: &str = r#"{
+        "lists": [{"id": "l1", "name": "Doing"}],
+        "members": [
+            {"id": "m1", "fullName": "Ann", "username": "ann"},
+            {"id": "m2", "fullName": "", "username": "bob"}
+        ],
+        "customFields": [{"id": "f1", "name": "Time Spent"}, {"id": "f2", "name": "Points"}],
+        "cards": [
+            {"id": "c1", "name": "Макет", "desc": "  ", "idList": "l1",
+             "due": "2026-03-01T12:00:00.000Z", "idMembers": ["m2", "m1"],
+             "customFieldItems": [
+                 {"idCustomField": "f2", "value": {"number": "8"}},
+                 {"idCustomField": "f1", "value": {"number": "2.5"}}
+             ]},
+            {"id": "c2", "name": "Без колонки", "idList": "gone"}
+        ],
+        "actions": [
+            {"id": "a1", "type": "commentCard", "data": {"card": {"id": "c1"}, "text": "Ок"},
+             "memberCreator": {"id": "m1", "fullName": "Ann"}, "date": "2026-02-01T10:00:00Z"},
+            {"id": "a2", "type": "commentCard", "data": {"card": {"id": "c1"}, "text": " "}},
+            {"id": "a3", "type": "createCard", "data": {"card": {"id": "c2"}},
+             "memberCreator": {"id": "m2", "username": "bob"}},
+            {"id": "a4", "type": "updateBoard", "data": {}}
+        ]
+    }"#;
+
+    #[test]
+    fn cards_map_lists_members_and_time_field() {
+        let tasks = parse(BOARD).unwrap();
+        assert_eq!(tasks.len(), 2);
+
+        let card = &tasks[0];
+        assert_eq!(card.external_id, "c1");
+        assert_eq!(card.description, None);
+        assert_eq!(card.status_name, "Doing");
+        assert_eq!(card.status_hint, None);
+        // Исполнитель - первый участник карточки, без полного имени берём логин
+        assert_eq!(card.assignee.as_ref().unwrap().name, "bob");
+        // Часы - только из поля с подходящим названием
+        assert_eq!(card.actual_hours, Some(2.5));
+        assert_eq!(card.due_date, "2026-03-01".parse().ok());
+        assert_eq!(card.comments.len(), 1);
+        assert_eq!(card.comments[0].external_id, "a1");
+        assert!(card.reporter.is_none());
+
+        let orphan = &tasks[1];
+        assert_eq!(orphan.status_name, "");
+        assert_eq!(orphan.reporter.as_ref().unwrap().external_id, "m2");
+        assert!(orphan.comments.is_empty());
+    }
+
+    #[test]
+    fn invalid_board_is_bad_request() {
+        assert!(matches!(parse(r#""board""#), Err(AppError::BadRequest(_))));
+        let card_without_list = r#"{"cards": [{"id": "c1", "name": "x"}]}"#;
+        assert!(matches!(parse(card_without_list), Err(AppError::BadRequest(_))));
+    }
+}
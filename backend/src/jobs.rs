@@ -0,0 +1,421 @@
+//! Очередь фоновой работы в SQLite: оценка ML, доставка вебхуков, письма и
+//! периодические рассылки. Задача переживает перезапуск, неудачная попытка
+//! повторяется с растущей паузой, а исчерпавшая попытки ждёт администратора.
+
+use actix_web::rt::task::JoinHandle;
+use futures_util::future::join_all;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Sqlite, SqlitePool};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, watch};
+use tracing::Instrument;
+use crate::email::{self, Mailer};
+use crate::errors::AppError;
+use crate::ml_client::MlClient;
+use crate::models::JobRecord;
+use crate::realtime::Broadcaster;
+use crate::{notifications, repository, services, telemetry, webhooks};
+
+/// Пауза между проверками очереди, если никто не разбудил воркер раньше
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Дольше одна попытка не выполняется: зависшую прерываем и повторяем
+const JOB_TIMEOUT: Duration = Duration::from_secs(60);
+/// Аренда с запасом больше JOB_TIMEOUT, чтобы живой воркер не потерял задачу
+const LEASE_SECS: i64 = 120;
+const MAX_BACKOFF_SECS: i64 = 6 * 60 * 60;
+/// Сколько дней хранить выполненные задачи
+const DONE_RETENTION_DAYS: i64 = 7;
+
+/// Будит свободный воркер, когда появилась новая задача
+static WAKE: Notify = Notify::const_new();
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Job {
+    /// Оценка трудоёмкости новой задачи от ML-сервиса
+    PredictTask { task_id: i64 },
+    DeliverWebhook { delivery_id: i64 },
+    SendEmail { email_id: i64 },
+    /// Периодические: после выполнения ставят следующий запуск сами
+    SendDigests,
+    SendDueReminders,
+    PruneJobs,
+}
+
+/// Номер попытки и пауза перед следующей, если она будет
+pub struct Attempt {
+    pub number: i64,
+    pub retry_in: Option<i64>,
+}
+
+impl Job {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Job::PredictTask { .. } => "predict_task",
+            Job::DeliverWebhook { .. } => "deliver_webhook",
+            Job::SendEmail { .. } => "send_email",
+            Job::SendDigests => "send_digests",
+            Job::SendDueReminders => "send_due_reminders",
+            Job::PruneJobs => "prune_jobs",
+        }
+    }
+
+    /// Сколько всего попыток и пауза после первой неудачи, в секундах
+    fn retry_policy(&self) -> (i64, i64) {
+        match self {
+            Job::PredictTask { .. } => (5, 30),
+            Job::DeliverWebhook { .. } => (8, 30),
+            Job::SendEmail { .. } => (6, 60),
+            Job::SendDigests | Job::SendDueReminders | Job::PruneJobs => (3, 60),
+        }
+    }
+
+    fn recurrence(&self) -> Option<Duration> {
+        match self {
+            Job::SendDigests => Some(email::DIGEST_CHECK_INTERVAL),
+            Job::SendDueReminders => Some(notifications::DUE_CHECK_INTERVAL),
+            Job::PruneJobs => Some(Duration::from_secs(24 * 60 * 60)),
+            _ => None,
+        }
+    }
+
+    async fn run(&self, ctx: &JobContext, attempt: &Attempt) -> Result<(), String> {
+        let result = match self {
+            Job::PredictTask { task_id } => {
+                services::predict_task_hours(&ctx.pool, &ctx.ml_client, &ctx.broadcaster, *task_id)
+                    .await
+            }
+            Job::DeliverWebhook { delivery_id } => {
+                return webhooks::deliver(&ctx.pool, &ctx.http, *delivery_id, attempt).await;
+            }
+            Job::SendEmail { email_id } => {
+                return email::deliver(&ctx.pool, &ctx.mailer, *email_id, attempt).await;
+            }
+            Job::SendDigests => email::send_digests(&ctx.pool, ctx.digest_hour).await,
+            Job::SendDueReminders => notifications::send_due_reminders(&ctx.pool).await,
+            Job::PruneJobs => {
+                repository::prune_finished_jobs(&ctx.pool, DONE_RETENTION_DAYS).await.map(|_| ())
+            }
+        };
+        result.map_err(|e| e.to_string())
+    }
+}
+
+fn backoff_secs(base: i64, failed_attempts: i64) -> i64 {
+    let exp = (failed_attempts - 1).clamp(0, 20) as u32;
+    (base * 2i64.pow(exp)).min(MAX_BACKOFF_SECS)
+}
+
+/// Ставит задачу в очередь. Внутри транзакции задача появится вместе с данными,
+/// к которым относится, - или не появится вовсе. Воркер будит вызывающий через
+/// [`wake`], когда запись уже видна, то есть после коммита
+pub async fn enqueue<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    job: &Job,
+) -> Result<i64, AppError> {
+    enqueue_in(executor, job, 0).await
+}
+
+async fn enqueue_in<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    job: &Job,
+    delay_secs: i64,
+) -> Result<i64, AppError> {
+    let payload =
+        serde_json::to_value(job).map_err(|e| AppError::Internal(format!("Invalid job: {}", e)))?;
+    let (max_attempts, _) = job.retry_policy();
+    let request_id = telemetry::current_request_id();
+    let id = repository::enqueue_job(
+        executor,
+        job.kind(),
+        &payload,
+        max_attempts,
+        delay_secs,
+        request_id.as_deref(),
+    )
+    .await?;
+    Ok(id)
+}
+
+/// Будит воркер: задача добавлена или возвращена в очередь
+pub fn wake() {
+    WAKE.notify_one();
+}
+
+/// Ставит периодические задачи, которых ещё нет в очереди. Вызывается при старте
+pub async fn schedule_recurring(pool: &SqlitePool) -> Result<(), AppError> {
+    for job in [Job::SendDigests, Job::SendDueReminders, Job::PruneJobs] {
+        if !repository::has_unfinished_job(pool, job.kind()).await? {
+            enqueue(pool, &job).await?;
+        }
+    }
+    wake();
+    Ok(())
+}
+
+/// Всё, что нужно обработчикам задач
+#[derive(Clone)]
+pub struct JobContext {
+    pub pool: SqlitePool,
+    pub ml_client: MlClient,
+    pub broadcaster: Broadcaster,
+    pub mailer: Arc<Mailer>,
+    /// Клиент для вебхуков
+    pub http: Client,
+    pub digest_hour: u32,
+}
+
+/// Выполняет одну готовую задачу. `false` - очередь пуста
+pub async fn run_next(ctx: &JobContext) -> Result<bool, AppError> {
+    let Some(record) = repository::claim_job(&ctx.pool, LEASE_SECS).await? else {
+        return Ok(false);
+    };
+
+    let span = tracing::info_span!(
+        "job",
+        job_id = record.id,
+        kind = %record.kind,
+        attempt = record.attempts,
+        request_id = record.request_id.as_deref(),
+    );
+    let job = serde_json::from_value::<Job>(record.payload.0.clone()).ok();
+    let retry_in = job
+        .as_ref()
+        .filter(|_| record.attempts < record.max_attempts)
+        .map(|job| backoff_secs(job.retry_policy().1, record.attempts));
+
+    let result = match &job {
+        Some(job) => {
+            let attempt = Attempt {
+                number: record.attempts,
+                retry_in,
+            };
+            let run = telemetry::with_request_id(record.request_id.clone(), job.run(ctx, &attempt));
+            match tokio::time::timeout(JOB_TIMEOUT, run).instrument(span.clone()).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("Timed out after {}s", JOB_TIMEOUT.as_secs())),
+            }
+        }
+        // Повторять бессмысленно: такой задачи эта версия не знает
+        None => Err(format!("Unknown job kind: {}", record.kind)),
+    };
+
+    finish(&ctx.pool, &record, job.as_ref(), result, retry_in).instrument(span).await?;
+    Ok(true)
+}
+
+async fn finish(
+    pool: &SqlitePool,
+    record: &JobRecord,
+    job: Option<&Job>,
+    result: Result<(), String>,
+    retry_in: Option<i64>,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    let finished = match &result {
+        Ok(()) => {
+            repository::complete_job(&mut *tx, record.id).await?;
+            true
+        }
+        Err(error) => {
+            match retry_in {
+                Some(secs) => tracing::warn!("Job failed, retrying in {}s: {}", secs, error),
+                None => tracing::error!("Job failed for good: {}", error),
+            }
+            repository::fail_job(&mut *tx, record.id, error, retry_in).await?;
+            retry_in.is_none()
+        }
+    };
+    // Следующий запуск периодической задачи - когда эта закончилась совсем.
+    // Если следующий запуск уже в очереди (dead-задачу вернули вручную), второй не нужен
+    let next = job.and_then(|job| job.recurrence().map(|every| (job, every)));
+    if let Some((job, every)) = next.filter(|_| finished)
+        && !repository::has_unfinished_job(&mut *tx, job.kind()).await?
+    {
+        enqueue_in(&mut *tx, job, every.as_secs() as i64).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Запущенные воркеры
+pub struct Workers {
+    shutdown: watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+pub fn start(ctx: JobContext, count: usize) -> Workers {
+    let (shutdown, stopped) = watch::channel(false);
+    let handles = (0..count)
+        .map(|_| actix_web::rt::spawn(work(ctx.clone(), stopped.clone())))
+        .collect();
+    Workers { shutdown, handles }
+}
+
+impl Workers {
+    /// Перестаёт брать новые задачи и ждёт начатые не дольше `grace`. Не успевшие
+    /// закончиться подхватит следующий запуск, когда истечёт их аренда
+    pub async fn shutdown(self, grace: Duration) {
+        let _ = self.shutdown.send(true);
+        if tokio::time::timeout(grace, join_all(self.handles)).await.is_err() {
+            let secs = grace.as_secs();
+            tracing::warn!("Background jobs still running after {}s, leaving them", secs);
+        }
+    }
+}
+
+async fn work(ctx: JobContext, mut stopped: watch::Receiver<bool>) {
+    while !*stopped.borrow() {
+        match run_next(&ctx).await {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to run background job: {}", e),
+        }
+        tokio::select! {
+            _ = WAKE.notified() => {}
+            _ = actix_web::rt::time::sleep(POLL_INTERVAL) => {}
+            _ = stopped.changed() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::CreateTaskRequest;
+    use actix_web::{App, HttpRequest, HttpResponse, web};
+    use serde_json::json;
+
+    /// Очередь без воркеров: тест сам выполняет задачи через run_next
+    fn context(pool: &SqlitePool, ml_client: MlClient) -> JobContext {
+        let config = Config::for_tests();
+        JobContext {
+            pool: pool.clone(),
+            ml_client,
+            broadcaster: Broadcaster::new(),
+            mailer: Arc::new(Mailer::from_config(&config).expect("mailer")),
+            http: webhooks::client(),
+            digest_hour: config.digest_hour,
+        }
+    }
+
+    async fn create_task(pool: &SqlitePool) -> i64 {
+        let user = repository::create_user(pool, "ann@example.test", "hash", "Ann", "member")
+            .await
+            .expect("user");
+        let req = CreateTaskRequest {
+            title: "Estimate me".to_string(),
+            description: None,
+            assignee_id: None,
+            due_date: None,
+            planned_date: None,
+        };
+        repository::create_task(pool, &req, user.id, None).await.expect("task").id
+    }
+
+    /// ML-сервис, который запоминает X-Request-Id пришедших запросов
+    fn start_recording_ml() -> (MlClient, Arc<std::sync::Mutex<Vec<String>>>) {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind ml");
+        let url = format!("http://{}", listener.local_addr().expect("ml address"));
+        let recorded = seen.clone();
+        let server = actix_web::HttpServer::new(move || {
+            let recorded = recorded.clone();
+            App::new().route(
+                "/predict",
+                web::post().to(move |req: HttpRequest| {
+                    let id = req.headers().get(telemetry::REQUEST_ID_HEADER);
+                    let id = id.and_then(|v| v.to_str().ok()).unwrap_or_default();
+                    recorded.lock().expect("recorded ids").push(id.to_string());
+                    async { HttpResponse::Ok().json(json!({"predicted_hours": 2.0})) }
+                }),
+            )
+        })
+        .workers(1)
+        .listen(listener)
+        .expect("listen ml")
+        .run();
+        actix_web::rt::spawn(server);
+        (MlClient::new(url), seen)
+    }
+
+    #[actix_web::test]
+    async fn job_runs_with_the_request_id_that_enqueued_it() {
+        let pool = repository::test_pool().await;
+        let task_id = create_task(&pool).await;
+        let (ml_client, seen) = start_recording_ml();
+
+        let job = Job::PredictTask { task_id };
+        telemetry::with_request_id(Some("trace-42".to_string()), enqueue(&pool, &job))
+            .await
+            .expect("enqueue");
+        assert!(run_next(&context(&pool, ml_client)).await.expect("run job"));
+
+        let task = repository::get_task_by_id(&pool, task_id).await.expect("task");
+        assert_eq!(task.predicted_hours, Some(2.0));
+        assert_eq!(*seen.lock().expect("recorded ids"), ["trace-42"]);
+    }
+
+    #[actix_web::test]
+    async fn failing_job_is_retried_then_left_dead() {
+        let pool = repository::test_pool().await;
+        let task_id = create_task(&pool).await;
+        let ctx = context(&pool, MlClient::new("http://127.0.0.1:9".to_string()));
+        let job_id = enqueue(&pool, &Job::PredictTask { task_id }).await.expect("enqueue");
+
+        // ML-сервис недоступен: каждая попытка падает. Паузу перед повтором пропускаем
+        let mut attempts = 0;
+        loop {
+            sqlx::query("UPDATE jobs SET run_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(job_id)
+                .execute(&pool)
+                .await
+                .expect("skip backoff");
+            if !run_next(&ctx).await.expect("run job") {
+                break;
+            }
+            attempts += 1;
+        }
+
+        let record = repository::get_job(&pool, job_id).await.expect("job");
+        assert_eq!(attempts, 5);
+        assert_eq!(record.status, "dead");
+        assert_eq!(record.attempts, 5);
+        assert!(record.last_error.is_some_and(|e| e.contains("ML service")));
+        assert!(record.finished_at.is_some());
+    }
+
+    #[test]
+    fn webhook_retries_back_off_exponentially_up_to_cap() {
+        let (max_attempts, base) = Job::DeliverWebhook { delivery_id: 1 }.retry_policy();
+        let delays: Vec<i64> = (1..max_attempts).map(|n| backoff_secs(base, n)).collect();
+        assert_eq!(delays, [30, 60, 120, 240, 480, 960, 1920]);
+        assert_eq!(backoff_secs(base, 0), base);
+        assert_eq!(backoff_secs(base, 40), MAX_BACKOFF_SECS);
+    }
+
+    #[actix_web::test]
+    async fn retried_dead_recurring_job_keeps_a_single_schedule() {
+        let pool = repository::test_pool().await;
+        let job = Job::PruneJobs;
+        let pending = || repository::get_jobs(&pool, Some("pending"), Some(job.kind()), 10);
+
+        enqueue(&pool, &job).await.unwrap();
+        let record = repository::claim_job(&pool, LEASE_SECS).await.unwrap().unwrap();
+        finish(&pool, &record, Some(&job), Err("boom".to_string()), None).await.unwrap();
+        // Отказ не останавливает расписание: следующий запуск уже в очереди
+        assert_eq!(pending().await.unwrap().len(), 1);
+
+        repository::retry_dead_job(&pool, record.id).await.unwrap().unwrap();
+        let retried = repository::claim_job(&pool, LEASE_SECS).await.unwrap().unwrap();
+        assert_eq!(retried.id, record.id);
+        finish(&pool, &retried, Some(&job), Ok(()), None).await.unwrap();
+
+        let scheduled = pending().await.unwrap();
+        assert_eq!(scheduled.len(), 1);
+        assert_ne!(scheduled[0].id, record.id);
+    }
+}
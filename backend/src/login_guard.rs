@@ -0,0 +1,224 @@
+//! Защита входа от перебора паролей. Неудачи считаются отдельно для аккаунта
+//! (по введённому email, существует он или нет) и для IP-адреса. После нескольких
+//! бесплатных попыток каждая следующая возможна только после растущей паузы,
+//! а за серией ошибок следует временная блокировка. Аккаунт разблокирует
+//! администратор, IP-адрес освобождается сам.
+//!
+//! Запросы ссылки сброса пароля ограничиваются теми же счётчиками: иначе через
+//! них можно засыпать чужой ящик письмами.
+
+use crate::audit;
+use crate::auth;
+use crate::errors::AppError;
+use crate::models::LoginFailures;
+use crate::repository;
+use chrono::{Duration, Utc};
+use sqlx::SqlitePool;
+use std::sync::LazyLock;
+
+const SCOPE_ACCOUNT: &str = "account";
+const SCOPE_IP: &str = "ip";
+
+/// Столько ошибок подряд проходят без задержки
+const FREE_ATTEMPTS: i64 = 3;
+const MAX_DELAY_SECS: i64 = 60;
+const ACCOUNT_LOCK_AFTER: i64 = 10;
+/// С одного адреса могут входить многие пользователи, порог выше
+const IP_LOCK_AFTER: i64 = 50;
+const LOCK_MINUTES: i64 = 15;
+/// Через столько минут без ошибок счётчик забывается
+const WINDOW_MINUTES: i64 = 15;
+
+const SCOPE_RESET_ACCOUNT: &str = "reset_account";
+const SCOPE_RESET_IP: &str = "reset_ip";
+/// Столько ссылок сброса за окно можно запросить для одного email
+const RESET_ACCOUNT_LIMIT: i64 = 3;
+const RESET_IP_LIMIT: i64 = 20;
+const RESET_WINDOW_MINUTES: i64 = 60;
+
+const EVENT_LOGIN_SUCCEEDED: &str = "login.succeeded";
+const EVENT_LOGIN_FAILED: &str = "login.failed";
+const EVENT_LOGIN_THROTTLED: &str = "login.throttled";
+const EVENT_LOGIN_LOCKED: &str = "login.locked";
+const EVENT_LOGIN_UNLOCKED: &str = "login.unlocked";
+const EVENT_RESET_THROTTLED: &str = "password.reset_throttled";
+
+/// Хэш, с которым сверяется пароль для несуществующего email: ответ приходит
+/// за то же время, что и при неверном пароле, и не выдаёт, есть ли аккаунт
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    auth::hash_password(&auth::generate_token()).expect("hash dummy password")
+});
+
+pub fn dummy_hash() -> &'static str {
+    &DUMMY_HASH
+}
+
+/// Хэш считается при старте, иначе первый вход с неизвестным email был бы медленнее
+pub fn warm_up() {
+    LazyLock::force(&DUMMY_HASH);
+}
+
+/// Ключ аккаунта - email в том виде, в каком его набирают по-разному
+pub fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Пауза перед следующей попыткой: 1, 2, 4... секунды после бесплатных
+fn delay(failures: i64) -> Duration {
+    if failures < FREE_ATTEMPTS {
+        return Duration::zero();
+    }
+    let exponent = (failures - FREE_ATTEMPTS).min(6) as u32;
+    Duration::seconds(2_i64.pow(exponent).min(MAX_DELAY_SECS))
+}
+
+/// Сколько секунд ждать до следующей попытки; `None` - можно сейчас
+fn retry_after(record: &LoginFailures) -> Option<i64> {
+    let now = Utc::now();
+    let until = match record.locked_until {
+        Some(locked_until) => locked_until,
+        None if now - record.last_failed_at > Duration::minutes(WINDOW_MINUTES) => return None,
+        None => record.last_failed_at + delay(record.failures),
+    };
+    (until > now).then(|| (until - now).num_seconds().max(1))
+}
+
+/// Отказывает, пока для аккаунта или адреса действует пауза или блокировка.
+/// Проверка идёт до Argon2, так что перебор не нагружает сервер
+pub async fn check(pool: &SqlitePool, email: &str, ip: Option<&str>) -> Result<(), AppError> {
+    let account = repository::get_login_failures(pool, SCOPE_ACCOUNT, &account_key(email)).await?;
+    let address = match ip {
+        Some(ip) => repository::get_login_failures(pool, SCOPE_IP, ip).await?,
+        None => None,
+    };
+
+    let wait = [account, address]
+        .iter()
+        .flatten()
+        .filter_map(retry_after)
+        .max();
+    match wait {
+        Some(secs) => {
+            audit::record(pool, EVENT_LOGIN_THROTTLED, None, Some(email), ip, None).await;
+            Err(AppError::TooManyRequests(secs))
+        }
+        None => Ok(()),
+    }
+}
+
+pub async fn record_failure(
+    pool: &SqlitePool,
+    email: &str,
+    ip: Option<&str>,
+) -> Result<(), AppError> {
+    audit::record(pool, EVENT_LOGIN_FAILED, None, Some(email), ip, None).await;
+
+    let account = account_key(email);
+    let mut counters = vec![(SCOPE_ACCOUNT, account.as_str(), ACCOUNT_LOCK_AFTER)];
+    if let Some(ip) = ip {
+        counters.push((SCOPE_IP, ip, IP_LOCK_AFTER));
+    }
+
+    for (scope, subject, lock_after) in counters {
+        let record = repository::record_login_failure(pool, scope, subject, WINDOW_MINUTES).await?;
+        if record.failures >= lock_after && record.locked_until.is_none() {
+            repository::lock_login(pool, scope, subject, LOCK_MINUTES).await?;
+            tracing::warn!("Login locked for {} {} after {} failures", scope, subject, record.failures);
+            let details = format!("{} locked for {} minutes", scope, LOCK_MINUTES);
+            audit::record(pool, EVENT_LOGIN_LOCKED, None, Some(email), ip, Some(&details)).await;
+        }
+    }
+    Ok(())
+}
+
+/// Удачный вход обнуляет счётчик аккаунта. Счётчик адреса остаётся: иначе
+/// перебор чужих паролей можно было бы перемежать входом в свой аккаунт
+pub async fn record_success(
+    pool: &SqlitePool,
+    user_id: i64,
+    email: &str,
+    ip: Option<&str>,
+) -> Result<(), AppError> {
+    repository::clear_login_failures(pool, SCOPE_ACCOUNT, &account_key(email)).await?;
+    audit::record(pool, EVENT_LOGIN_SUCCEEDED, Some(user_id), Some(email), ip, None).await;
+    Ok(())
+}
+
+/// Снимает паузу и блокировку с аккаунта
+pub async fn unlock(
+    pool: &SqlitePool,
+    user_id: i64,
+    email: &str,
+    actor_id: i64,
+) -> Result<(), AppError> {
+    repository::clear_login_failures(pool, SCOPE_ACCOUNT, &account_key(email)).await?;
+    let details = format!("by user {}", actor_id);
+    audit::record(pool, EVENT_LOGIN_UNLOCKED, Some(user_id), Some(email), None, Some(&details))
+        .await;
+    Ok(())
+}
+
+/// Засчитывает запрос ссылки сброса для email и адреса. Исчерпавший лимит
+/// получает отказ до конца окна, есть такой аккаунт или нет
+pub async fn check_reset_request(
+    pool: &SqlitePool,
+    email: &str,
+    ip: Option<&str>,
+) -> Result<(), AppError> {
+    let account = account_key(email);
+    let mut counters = vec![(SCOPE_RESET_ACCOUNT, account.as_str(), RESET_ACCOUNT_LIMIT)];
+    if let Some(ip) = ip {
+        counters.push((SCOPE_RESET_IP, ip, RESET_IP_LIMIT));
+    }
+
+    let now = Utc::now();
+    for (scope, subject, _) in &counters {
+        let locked_until = repository::get_login_failures(pool, scope, subject)
+            .await?
+            .and_then(|record| record.locked_until)
+            .filter(|until| *until > now);
+        if let Some(until) = locked_until {
+            audit::record(pool, EVENT_RESET_THROTTLED, None, Some(email), ip, None).await;
+            return Err(AppError::TooManyRequests((until - now).num_seconds().max(1)));
+        }
+    }
+
+    for (scope, subject, limit) in counters {
+        let record =
+            repository::record_login_failure(pool, scope, subject, RESET_WINDOW_MINUTES).await?;
+        if record.failures >= limit && record.locked_until.is_none() {
+            repository::lock_login(pool, scope, subject, RESET_WINDOW_MINUTES).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn reset_requests_are_limited_per_email() {
+        let pool = repository::test_pool().await;
+        for _ in 0..RESET_ACCOUNT_LIMIT {
+            check_reset_request(&pool, "Ann@Example.test", None).await.expect("allowed");
+        }
+        // Ключ - email без учёта регистра и пробелов
+        let refused = check_reset_request(&pool, " ann@example.test", None).await;
+        assert!(matches!(refused, Err(AppError::TooManyRequests(secs)) if secs > 0));
+        assert!(check_reset_request(&pool, "bob@example.test", None).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn reset_requests_are_limited_per_ip() {
+        let pool = repository::test_pool().await;
+        let ip = Some("203.0.113.7");
+        for n in 0..RESET_IP_LIMIT {
+            let email = format!("user{}@example.test", n);
+            check_reset_request(&pool, &email, ip).await.expect("allowed");
+        }
+        let refused = check_reset_request(&pool, "new@example.test", ip).await;
+        assert!(matches!(refused, Err(AppError::TooManyRequests(_))));
+        assert!(check_reset_request(&pool, "new@example.test", Some("198.51.100.1")).await.is_ok());
+    }
+}
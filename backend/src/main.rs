@@ -1,54 +1,145 @@
 use actix_cors::Cors;
-use actix_web::{App, HttpResponse, HttpServer, middleware::Logger, web};
+use actix_web::{App, HttpServer, middleware, web};
 use sqlx::sqlite::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
 
+mod audit;
 mod auth;
+mod calendar;
+mod cli;
 mod config;
+mod email;
 mod errors;
 mod handlers;
+mod import_export;
+mod importers;
+mod jobs;
+mod login_guard;
+mod mentions;
+mod metrics;
 mod ml_client;
+#[cfg(any(test, feature = "mock-idp"))]
+mod mock_idp;
 mod models;
+mod notifications;
+mod oidc;
+mod passwords;
+mod policy;
+mod query_language;
+mod realtime;
 mod repository;
 mod services;
+mod telemetry;
+mod totp;
+mod webhooks;
 
-async fn health() -> HttpResponse {
-    HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
+/// Неверные настройки или аргументы: сообщение и выход без запуска
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2)
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    tracing_subscriber::fmt::init();
+    let args = match config::Cli::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => exit_with(&format!("{}\n{}", e, cli::USAGE)),
+    };
+    let layers = config::Layers::collect(&args);
+    if args.print_config {
+        print!("{}", layers.render());
+    }
+    let config = layers.build().unwrap_or_else(|e| exit_with(&e.to_string()));
+    if args.print_config {
+        return Ok(());
+    }
 
-    let config = config::Config::from_env();
+    telemetry::init(&config);
+    // Учебному провайдеру база не нужна
+    if let [command, rest @ ..] = args.command.as_slice()
+        && command == "mock-idp"
+    {
+        return cli::run_mock_idp(rest).await;
+    }
+    if config.uses_default_secrets() {
+        tracing::warn!("Default secrets in use: never run this configuration in production");
+    }
 
     tracing::info!("Connecting to database...");
     let pool = SqlitePool::connect(&config.database_url)
         .await
         .expect("Failed to connect to database");
 
+    repository::MIGRATOR
+        .run(&pool)
+        .await
+        .expect("Failed to run migrations");
+
     services::init_admin(&pool, &config.admin_email, &config.admin_password)
         .await
         .expect("Failed to initialize admin");
+    login_guard::warm_up();
 
     let ml_client = ml_client::MlClient::new(config.ml_service_url.clone());
-        
+    let oidc_client = oidc::OidcClient::from_config(&config);
+    let metrics_access = web::Data::new(metrics::MetricsAccess::from_config(&config));
+    let http_metrics = web::Data::new(metrics::HttpMetrics::default());
+
+    if !args.command.is_empty() {
+        return cli::run(&args.command, &pool, &config).await;
+    }
+
+    let password_policy = web::Data::new(passwords::PasswordPolicy::from_config(&config));
+    let reset_notifier = web::Data::from(
+        passwords::notifier_from_config(&config).expect("Invalid password reset configuration"),
+    );
+
+    realtime::prune_events(&pool).await;
+    let broadcaster = realtime::Broadcaster::new();
+
+    let mailer = email::Mailer::from_config(&config).expect("Invalid SMTP configuration");
+    jobs::schedule_recurring(&pool)
+        .await
+        .expect("Failed to schedule recurring jobs");
+    let job_context = jobs::JobContext {
+        pool: pool.clone(),
+        ml_client: ml_client.clone(),
+        broadcaster: broadcaster.clone(),
+        mailer: Arc::new(mailer),
+        http: webhooks::client(),
+        digest_hour: config.digest_hour,
+    };
+    let workers = jobs::start(job_context, config.job_workers);
+
     tracing::info!("Starting server at http://{}:{}", config.host, config.port);
 
     let config_data = config.clone();
 
-    HttpServer::new(move || {
+    let result = HttpServer::new(move || {
         let cors = Cors::permissive();
 
         App::new()
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(config_data.clone()))
             .app_data(web::Data::new(ml_client.clone()))
-            .route("/health", web::get().to(health))
+            .app_data(web::Data::new(oidc_client.clone()))
+            .app_data(web::Data::new(broadcaster.clone()))
+            .app_data(password_policy.clone())
+            .app_data(reset_notifier.clone())
+            .app_data(metrics_access.clone())
+            .app_data(http_metrics.clone())
             .configure(handlers::configure)
-            .wrap(Logger::new("%a | %r | %s").log_target("http_log"))
+            .wrap(middleware::from_fn(metrics::track_requests))
+            .wrap(middleware::from_fn(telemetry::request_context))
             .wrap(cors)
     })
     .bind((config.host.as_str(), config.port))?
     .run()
-    .await
+    .await;
+
+    // Сервер уже не принимает запросы: доделываем начатые задачи, остальные дождутся запуска
+    tracing::info!("Draining background jobs...");
+    workers.shutdown(Duration::from_secs(config.job_drain_secs)).await;
+    result
 }
\ No newline at end of file
@@ -0,0 +1,239 @@
+use crate::models::{Comment, Task, User};
+use crate::notifications;
+use crate::repository;
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+
+/// Достаёт из текста упоминания `@alice`, `@alice.smith` или `@alice@example.com`.
+/// `@` должна стоять в начале слова, поэтому обычный адрес почты упоминанием не считается
+pub fn parse(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut mentions = Vec::new();
+
+    let mut i = 0;
+    while i < chars.len() {
+        let at_word_start = i == 0 || !is_mention_char(chars[i - 1]);
+        if chars[i] != '@' || !at_word_start {
+            i += 1;
+            continue;
+        }
+
+        let start = i + 1;
+        let mut end = start;
+        let mut seen_at = false;
+        while end < chars.len() {
+            let c = chars[end];
+            if is_mention_char(c) {
+                end += 1;
+            } else if c == '@' && !seen_at && end > start {
+                seen_at = true;
+                end += 1;
+            } else {
+                break;
+            }
+        }
+
+        // Точка или @ в конце - это пунктуация, а не часть имени
+        let token: String = chars[start..end].iter().collect();
+        let token = token.trim_end_matches(['.', '@']).to_lowercase();
+        if !token.is_empty() && !mentions.contains(&token) {
+            mentions.push(token);
+        }
+        i = end.max(start);
+    }
+
+    mentions
+}
+
+fn is_mention_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '+')
+}
+
+/// Сопоставляет упоминания с пользователями: по email, по имени без пробелов
+/// или по локальной части email. Неоднозначные упоминания пропускаются
+pub fn resolve(mentions: &[String], users: &[User]) -> Vec<i64> {
+    let mut ids = Vec::new();
+
+    for mention in mentions {
+        let matches: Vec<&User> = if mention.contains('@') {
+            users.iter().filter(|u| u.email.to_lowercase() == *mention).collect()
+        } else {
+            let by_name: Vec<&User> = users
+                .iter()
+                .filter(|u| compact_name(&u.name) == *mention)
+                .collect();
+            if by_name.is_empty() {
+                users
+                    .iter()
+                    .filter(|u| {
+                        u.email
+                            .split('@')
+                            .next()
+                            .is_some_and(|local| local.to_lowercase() == *mention)
+                    })
+                    .collect()
+            } else {
+                by_name
+            }
+        };
+
+        if let [user] = matches.as_slice()
+            && !ids.contains(&user.id)
+        {
+            ids.push(user.id);
+        }
+    }
+
+    ids
+}
+
+/// "Alice Smith" -> "alicesmith": так имя с пробелами можно упомянуть одним словом
+fn compact_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+async fn resolve_text(pool: &SqlitePool, text: &str) -> Vec<i64> {
+    let mentions = parse(text);
+    if mentions.is_empty() {
+        return Vec::new();
+    }
+
+    match repository::get_all_users(pool).await {
+        Ok(users) => resolve(&mentions, &users),
+        Err(e) => {
+            tracing::error!("Failed to resolve mentions: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Пересобирает упоминания из описания задачи. Уведомление получают
+/// только новые упомянутые, автор изменения себя не уведомляет.
+/// Возвращает тех, кого уведомили
+pub async fn sync_task_mentions(pool: &SqlitePool, actor_id: i64, task: &Task) -> Vec<i64> {
+    let mentioned = resolve_text(pool, task.description.as_deref().unwrap_or_default()).await;
+
+    let previous: HashSet<i64> = match repository::get_description_mentions(pool, task.id).await {
+        Ok(previous) => previous.into_iter().collect(),
+        Err(e) => {
+            tracing::error!("Failed to load mentions of task {}: {}", task.id, e);
+            return Vec::new();
+        }
+    };
+
+    if let Err(e) =
+        repository::replace_description_mentions(pool, task.id, actor_id, &mentioned).await
+    {
+        tracing::error!("Failed to save mentions of task {}: {}", task.id, e);
+        return Vec::new();
+    }
+
+    let notified: Vec<i64> = mentioned
+        .into_iter()
+        .filter(|id| *id != actor_id && !previous.contains(id))
+        .collect();
+
+    let actor = notifications::actor_name(pool, actor_id).await;
+    let message = format!("{} mentioned you in \"{}\"", actor, task.title);
+    for &user_id in &notified {
+        notifications::notify(
+            pool,
+            user_id,
+            notifications::KIND_MENTIONED,
+            Some(task.id),
+            Some(actor_id),
+            &message,
+        )
+        .await;
+    }
+    notified
+}
+
+/// Сохраняет упоминания из нового комментария и уведомляет упомянутых.
+/// Возвращает всех упомянутых
+pub async fn record_comment_mentions(pool: &SqlitePool, task: &Task, comment: &Comment) -> Vec<i64> {
+    let mentioned = resolve_text(pool, &comment.body).await;
+    if mentioned.is_empty() {
+        return mentioned;
+    }
+
+    if let Err(e) = repository::insert_comment_mentions(pool, comment, &mentioned).await {
+        tracing::error!("Failed to save mentions of comment {}: {}", comment.id, e);
+        return Vec::new();
+    }
+
+    let actor = notifications::actor_name(pool, comment.author_id).await;
+    let message = format!("{} mentioned you in a comment on \"{}\"", actor, task.title);
+    for &user_id in &mentioned {
+        if user_id != comment.author_id {
+            notifications::notify(
+                pool,
+                user_id,
+                notifications::KIND_MENTIONED,
+                Some(task.id),
+                Some(comment.author_id),
+                &message,
+            )
+            .await;
+        }
+    }
+    mentioned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn user(id: i64, email: &str, name: &str) -> User {
+        User {
+            id,
+            email: email.to_string(),
+            password_hash: String::new(),
+            name: name.to_string(),
+            role: crate::policy::ROLE_MEMBER.to_string(),
+            created_at: Utc::now(),
+            password_reset_required: false,
+        }
+    }
+
+    #[test]
+    fn parse_finds_names_and_emails_at_word_start() {
+        assert_eq!(
+            parse("@Alice, см. @bob.smith. и @Carol@Example.com!"),
+            ["alice", "bob.smith", "carol@example.com"]
+        );
+        // Обычный адрес почты - не упоминание
+        assert!(parse("пишите на team@example.com").is_empty());
+        assert!(parse("@ @@ @.").is_empty());
+    }
+
+    #[test]
+    fn parse_drops_repeats_and_trailing_punctuation() {
+        assert_eq!(parse("@ann @ANN @ann@"), ["ann"]);
+        assert_eq!(parse("(@dev-ops_1+qa)"), ["dev-ops_1+qa"]);
+    }
+
+    #[test]
+    fn resolve_prefers_exact_email_then_name_then_local_part() {
+        let users = [
+            user(1, "alice@example.com", "Alice Smith"),
+            user(2, "bob@example.com", "Bobby"),
+            user(3, "bob@other.org", "Robert"),
+            user(4, "alicesmith@example.com", "Someone"),
+        ];
+        let mentions = |text: &str| resolve(&parse(text), &users);
+
+        assert_eq!(mentions("@bob@other.org"), [3]);
+        // Имя без пробелов важнее совпадения с локальной частью почты
+        assert_eq!(mentions("@alicesmith"), [1]);
+        assert_eq!(mentions("@alice"), [1]);
+        // Два пользователя с локальной частью bob - упоминание неоднозначно
+        assert_eq!(mentions("@robert @bob"), [3]);
+        assert_eq!(mentions("@alice @alice@example.com"), [1]);
+        assert!(mentions("@nobody").is_empty());
+    }
+}
@@ -0,0 +1,401 @@
+//! Метрики для Prometheus в текстовом формате: запросы HTTP по маршрутам,
+//! пул соединений с базой, вызовы ML-сервиса и счётчики задач. Считаются в
+//! памяти процесса и обнуляются при перезапуске, как и положено счётчикам.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{HttpRequest, web};
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::auth;
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::models::TaskCounts;
+
+/// Границы корзин гистограмм времени, в секундах
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Clone, Default)]
+struct Histogram {
+    /// Наблюдения по корзинам LATENCY_BUCKETS, последняя - всё, что дольше
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS.iter().position(|le| seconds <= *le);
+        self.buckets[bucket.unwrap_or(LATENCY_BUCKETS.len())] += 1;
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Строки `_bucket`, `_sum` и `_count`; `labels` - уже в формате `a="b",`
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (le, observed) in LATENCY_BUCKETS.iter().zip(self.buckets) {
+            cumulative += observed;
+            let _ = writeln!(out, "{}_bucket{{{}le=\"{}\"}} {}", name, labels, le, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}le=\"+Inf\"}} {}", name, labels, self.count);
+        let labels = match labels.trim_end_matches(',') {
+            "" => String::new(),
+            labels => format!("{{{}}}", labels),
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+/// Метод, шаблон маршрута и статус ответа
+type RouteKey = (String, String, u16);
+
+/// Запросы HTTP, общие для всех воркеров сервера
+#[derive(Clone, Default)]
+pub struct HttpMetrics {
+    routes: Arc<Mutex<BTreeMap<RouteKey, Histogram>>>,
+}
+
+impl HttpMetrics {
+    fn observe(&self, key: RouteKey, elapsed: Duration) {
+        let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        routes.entry(key).or_default().observe(elapsed);
+    }
+}
+
+/// Middleware: время и статус каждого запроса по шаблону маршрута - не по пути,
+/// иначе у метрики будет по ряду на каждую задачу. Ошибка внутреннего middleware
+/// считается со статусом, которым она станет в ответе
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let metrics = req.app_data::<web::Data<HttpMetrics>>().cloned();
+    let method = req.method().to_string();
+    let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    let started = Instant::now();
+    let result = next.call(req).await;
+
+    if let Some(metrics) = metrics {
+        let status = match &result {
+            Ok(res) => res.status(),
+            Err(e) => e.as_response_error().status_code(),
+        };
+        metrics.observe((method, route, status.as_u16()), started.elapsed());
+    }
+    result
+}
+
+/// Кто может читать /metrics: адреса из METRICS_ALLOW или запрос с METRICS_TOKEN
+#[derive(Clone)]
+pub struct MetricsAccess {
+    allowed: Vec<(IpAddr, u8)>,
+    token: Option<String>,
+}
+
+impl MetricsAccess {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            allowed: config.metrics_allow.clone(),
+            token: config.metrics_token.clone(),
+        }
+    }
+
+    /// Адрес берётся у соединения: X-Forwarded-For подделывается
+    pub fn check(&self, req: &HttpRequest) -> Result<(), AppError> {
+        let bearer = req
+            .headers()
+            .get("Authorization")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "));
+        if let (Some(token), Some(given)) = (&self.token, bearer) {
+            // Сравниваем хэши: время сравнения не выдаёт совпавшее начало токена
+            return if auth::hash_token(token) == auth::hash_token(given) {
+                Ok(())
+            } else {
+                Err(AppError::Unauthorized)
+            };
+        }
+
+        let peer = req.peer_addr().map(|addr| addr.ip());
+        let allowed = peer.is_some_and(|ip| {
+            self.allowed.iter().any(|(network, prefix)| in_network(ip, *network, *prefix))
+        });
+        if allowed { Ok(()) } else { Err(AppError::Forbidden) }
+    }
+}
+
+/// `10.0.0.0/8`, `::1` или `192.168.1.5`: адрес без маски - ровно этот адрес
+pub fn parse_network(text: &str) -> Option<(IpAddr, u8)> {
+    let (address, prefix) = match text.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (text, None),
+    };
+    let address: IpAddr = address.trim().parse().ok()?;
+    let max = if address.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix.trim().parse().ok().filter(|p| *p <= max)?,
+        None => max,
+    };
+    Some((address, prefix))
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    // IPv4-клиент на сокете IPv6 приходит как ::ffff:a.b.c.d
+    let ip = ip.to_canonical();
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Вызовы ML-сервиса: время ответа и исходы
+#[derive(Clone, Default)]
+pub struct MlMetrics {
+    inner: Arc<Mutex<MlCounters>>,
+}
+
+#[derive(Default)]
+struct MlCounters {
+    latency: Histogram,
+    failures: u64,
+}
+
+impl MlMetrics {
+    pub fn observe(&self, elapsed: Duration, success: bool) {
+        let mut counters = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        counters.latency.observe(elapsed);
+        if !success {
+            counters.failures += 1;
+        }
+    }
+}
+
+/// Все метрики в текстовом формате Prometheus
+pub fn render(http: &HttpMetrics, ml: &MlMetrics, pool: &SqlitePool, tasks: &TaskCounts) -> String {
+    let mut out = String::new();
+
+    let routes = http.routes.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    header(&mut out, "http_requests_total", "counter", "HTTP requests by route and status");
+    for ((method, route, status), histogram) in &routes {
+        let _ = writeln!(
+            out,
+            "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+            method,
+            escape_label(route),
+            status,
+            histogram.count()
+        );
+    }
+    let name = "http_request_duration_seconds";
+    header(&mut out, name, "histogram", "HTTP request latency by route and status");
+    for ((method, route, status), histogram) in &routes {
+        let route = escape_label(route);
+        let labels = format!("method=\"{}\",route=\"{}\",status=\"{}\",", method, route, status);
+        histogram.render(&mut out, name, &labels);
+    }
+
+    let idle = pool.num_idle() as u32;
+    header(&mut out, "db_pool_connections", "gauge", "Database pool connections by state");
+    let _ = writeln!(out, "db_pool_connections{{state=\"idle\"}} {}", idle);
+    let in_use = pool.size().saturating_sub(idle);
+    let _ = writeln!(out, "db_pool_connections{{state=\"in_use\"}} {}", in_use);
+    header(&mut out, "db_pool_max_connections", "gauge", "Database pool size limit");
+    let _ = writeln!(out, "db_pool_max_connections {}", pool.options().get_max_connections());
+
+    let ml = ml.inner.lock().unwrap_or_else(|e| e.into_inner());
+    header(&mut out, "ml_predictions_total", "counter", "ML service prediction calls by outcome");
+    let succeeded = ml.latency.count() - ml.failures;
+    let _ = writeln!(out, "ml_predictions_total{{outcome=\"success\"}} {}", succeeded);
+    let _ = writeln!(out, "ml_predictions_total{{outcome=\"failure\"}} {}", ml.failures);
+    let name = "ml_prediction_duration_seconds";
+    header(&mut out, name, "histogram", "ML service prediction latency");
+    ml.latency.render(&mut out, name, "");
+
+    header(&mut out, "tasks", "gauge", "Tasks by status");
+    for (status, count) in &tasks.by_status {
+        let _ = writeln!(out, "tasks{{status=\"{}\"}} {}", escape_label(status), count);
+    }
+    header(&mut out, "tasks_overdue", "gauge", "Unfinished tasks past their due date");
+    let _ = writeln!(out, "tasks_overdue {}", tasks.overdue);
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{TestRequest, init_service, try_call_service};
+    use actix_web::{App, HttpResponse};
+
+    fn counts(metrics: &HttpMetrics) -> Vec<(RouteKey, u64)> {
+        let routes = metrics.routes.lock().unwrap();
+        routes.iter().map(|(key, histogram)| (key.clone(), histogram.count())).collect()
+    }
+
+    async fn reject_admin(
+        req: ServiceRequest,
+        next: Next<impl MessageBody>,
+    ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+        if req.path().starts_with("/admin") {
+            return Err(actix_web::error::ErrorForbidden("no"));
+        }
+        next.call(req).await
+    }
+
+    #[actix_web::test]
+    async fn requests_are_counted_by_route_template_including_errors() {
+        let metrics = HttpMetrics::default();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(metrics.clone()))
+                .wrap(from_fn(reject_admin))
+                .wrap(from_fn(track_requests))
+                .route("/tasks/{id}", web::get().to(HttpResponse::Ok))
+                .route("/admin/{id}", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for uri in ["/tasks/1", "/tasks/2", "/admin/1", "/missing"] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let _ = try_call_service(&app, req).await;
+        }
+
+        let key = |route: &str, status| ("GET".to_string(), route.to_string(), status);
+        assert_eq!(
+            counts(&metrics),
+            [
+                (key("/admin/{id}", 403), 1),
+                (key("/tasks/{id}", 200), 2),
+                (key("unmatched", 404), 1),
+            ]
+        );
+    }
+
+    fn network(text: &str) -> (IpAddr, u8) {
+        parse_network(text).unwrap_or_else(|| panic!("{} is a network", text))
+    }
+
+    #[test]
+    fn networks_parse_with_optional_prefix() {
+        assert_eq!(network("10.0.0.0/8"), ("10.0.0.0".parse().unwrap(), 8));
+        assert_eq!(network(" 192.168.1.5 "), ("192.168.1.5".parse().unwrap(), 32));
+        assert_eq!(network("::1"), ("::1".parse().unwrap(), 128));
+        assert_eq!(network("fd00::/8"), ("fd00::".parse().unwrap(), 8));
+        for text in ["10.0.0.0/33", "::/129", "10.0.0.0/x", "localhost", ""] {
+            assert_eq!(parse_network(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn addresses_match_networks_by_prefix() {
+        let inside = |ip: &str, net: &str| {
+            let (network, prefix) = network(net);
+            in_network(ip.parse().unwrap(), network, prefix)
+        };
+        assert!(inside("10.20.30.40", "10.0.0.0/8"));
+        assert!(!inside("11.0.0.1", "10.0.0.0/8"));
+        assert!(inside("203.0.113.7", "0.0.0.0/0"));
+        assert!(!inside("127.0.0.2", "127.0.0.1"));
+        assert!(inside("fd00::1", "fd00::/8"));
+        // IPv4-клиент на сокете IPv6 и IPv4-сеть в настройках
+        assert!(inside("::ffff:127.0.0.1", "127.0.0.1"));
+        assert!(!inside("::1", "127.0.0.1"));
+    }
+
+    #[test]
+    fn scrape_needs_allowed_address_or_token() {
+        let access = |token: Option<&str>| MetricsAccess {
+            allowed: vec![network("127.0.0.1")],
+            token: token.map(String::from),
+        };
+        let scrape = |peer: &str, bearer: Option<&str>| {
+            let mut req = TestRequest::get().peer_addr(peer.parse().unwrap());
+            if let Some(bearer) = bearer {
+                req = req.insert_header(("Authorization", format!("Bearer {}", bearer)));
+            }
+            req.to_http_request()
+        };
+        let remote = "203.0.113.7:5000";
+        let local = "127.0.0.1:5000";
+        let with_token = access(Some("scrape-secret"));
+
+        assert!(with_token.check(&scrape(remote, Some("scrape-secret"))).is_ok());
+        let guessed = with_token.check(&scrape(remote, Some("guess")));
+        assert!(matches!(guessed, Err(AppError::Unauthorized)));
+        assert!(matches!(with_token.check(&scrape(remote, None)), Err(AppError::Forbidden)));
+        assert!(with_token.check(&scrape(local, None)).is_ok());
+        // Без METRICS_TOKEN заголовок ничего не решает
+        let without_token = access(None);
+        let guessed = without_token.check(&scrape(remote, Some("scrape-secret")));
+        assert!(matches!(guessed, Err(AppError::Forbidden)));
+        assert!(without_token.check(&scrape(local, Some("anything"))).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn render_writes_prometheus_text() {
+        let http = HttpMetrics::default();
+        let key = |route: &str| ("GET".to_string(), route.to_string(), 200);
+        http.observe(key("/tasks/{id}"), Duration::from_millis(20));
+        http.observe(key("/tasks/{id}"), Duration::from_secs(20));
+        http.observe(key("/\"quoted\""), Duration::from_millis(1));
+        let ml = MlMetrics::default();
+        ml.observe(Duration::from_millis(300), true);
+        ml.observe(Duration::from_millis(300), false);
+        let tasks = TaskCounts {
+            by_status: vec![("todo".to_string(), 3)],
+            overdue: 1,
+        };
+        let pool = repository::test_pool().await;
+
+        let out = render(&http, &ml, &pool, &tasks);
+        let labels = r#"method="GET",route="/tasks/{id}",status="200""#;
+        for line in [
+            "# TYPE http_requests_total counter".to_string(),
+            format!("http_requests_total{{{}}} 2", labels),
+            format!("http_request_duration_seconds_bucket{{{},le=\"0.01\"}} 0", labels),
+            format!("http_request_duration_seconds_bucket{{{},le=\"0.025\"}} 1", labels),
+            format!("http_request_duration_seconds_bucket{{{},le=\"10\"}} 1", labels),
+            format!("http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 2", labels),
+            format!("http_request_duration_seconds_count{{{}}} 2", labels),
+            r#"http_requests_total{method="GET",route="/\"quoted\"",status="200"} 1"#.to_string(),
+            "db_pool_max_connections 1".to_string(),
+            r#"ml_predictions_total{outcome="success"} 1"#.to_string(),
+            r#"ml_predictions_total{outcome="failure"} 1"#.to_string(),
+            "ml_prediction_duration_seconds_count 2".to_string(),
+            r#"tasks{status="todo"} 3"#.to_string(),
+            "tasks_overdue 1".to_string(),
+        ] {
+            assert!(out.lines().any(|l| l == line), "missing {}\n{}", line, out);
+        }
+    }
+}
@@ -1,11 +1,18 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
 use crate::errors::AppError;
+use crate::metrics::MlMetrics;
+use crate::telemetry;
+
+/// Проверка готовности не должна ждать зависший сервис
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[derive(Clone)]
 pub struct MlClient {
     base_url: String,
     client: Client,
+    metrics: MlMetrics,
 }
 
 #[derive(Serialize)]
@@ -24,13 +31,29 @@ impl MlClient {
         Self {
             base_url,
             client: Client::new(),
+            metrics: MlMetrics::default(),
         }
     }
 
+    pub fn metrics(&self) -> &MlMetrics {
+        &self.metrics
+    }
+
     pub async fn predict_time(
         &self,
         title: &str,
         description: Option<&str>,
+    ) -> Result<f64, AppError> {
+        let started = Instant::now();
+        let result = self.request_prediction(title, description).await;
+        self.metrics.observe(started.elapsed(), result.is_ok());
+        result
+    }
+
+    async fn request_prediction(
+        &self,
+        title: &str,
+        description: Option<&str>,
     ) -> Result<f64, AppError> {
         let request = PredictRequest {
             title: title.to_string(),
@@ -38,9 +61,7 @@ impl MlClient {
         };
 
         let response = self
-            .client
-            .post(format!("{}/predict", self.base_url))
-            .json(&request)
+            .request(self.client.post(format!("{}/predict", self.base_url)).json(&request))
             .send()
             .await
             .map_err(|e| {
@@ -61,12 +82,29 @@ impl MlClient {
         Ok(result.predicted_hours)
     }
 
-    /// Версия которая не падает при ошибке, просто возвращает None
-    pub async fn predict_time_safe(
-        &self,
-        title: &str,
-        description: Option<&str>,
-    ) -> Option<f64> {
-        self.predict_time(title, description).await.ok()
+    /// Отвечает ли сервис на /health
+    pub async fn check_health(&self) -> Result<(), AppError> {
+        let response = self
+            .request(self.client.get(format!("{}/health", self.base_url)))
+            .timeout(HEALTH_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::warn!("ML service health check failed: {}", e);
+                AppError::Internal("ML service unavailable".to_string())
+            })?;
+        if !response.status().is_success() {
+            tracing::warn!("ML service health check returned {}", response.status());
+            return Err(AppError::Internal("ML service error".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Запрос к ML-сервису связан в логах с запросом пользователя
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match telemetry::current_request_id() {
+            Some(request_id) => builder.header(telemetry::REQUEST_ID_HEADER, request_id),
+            None => builder,
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,267 @@
+//! Учебный провайдер OpenID Connect для разработки и тестов: `backend mock-idp`
+//! в сборке с `--features mock-idp`.
+//! Пароля не спрашивает - на странице входа вводятся email, имя и группы, и
+//! провайдер выдаёт на них подписанный ID-токен. Ключ подписи лежит в репозитории,
+//! поэтому доверять такому провайдеру можно только локально.
+
+use actix_web::dev::Server;
+use actix_web::{App, HttpResponse, HttpServer, web};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::Mutex;
+use crate::oidc;
+
+const SIGNING_KEY: &str = include_str!("mock_idp_key.pem");
+const KEY_ID: &str = "mock-idp-key";
+/// Модуль открытого ключа в base64url, пара к SIGNING_KEY
+const KEY_MODULUS: &str = concat!(
+    "qSJBZfDg3KoHfPtaOpjatWiwHZnVW1n9DmUgL_kQbk5tuIVssGQBfxETiJI0am4D3UTXTu-M8dPIOfQU",
+    "mAYOdOfD0TvAOd3d7sqBY41eMnkiv_T3ScBA8GWZjwLUjrQpJ5aaRog1P07sNC4H1zstjrfTfM5e4ZyE",
+    "edfU3aYkxPgN_JiEzSVAU-RG8gCradvw2kegrQqfSgQWa3dGqxXWIUa9pmt4NnFLZ6pmf00vxYDSF23W",
+    "LlIH3QGQsTx5Jgv_qzsY72aSlzKC9u4dLTX0l-sPajdx4hIiazsi1zcc7wZDgZ6FwXFN_Pvwyn84wz6X",
+    "7zzXXg_3RW4kE75t165N9Q",
+);
+const KEY_EXPONENT: &str = "AQAB";
+const TOKEN_SECS: i64 = 300;
+
+struct MockIdp {
+    issuer: String,
+    /// Выданные, но ещё не обменянные коды
+    grants: Mutex<HashMap<String, Grant>>,
+}
+
+struct Grant {
+    client_id: String,
+    redirect_uri: String,
+    code_challenge: String,
+    nonce: Option<String>,
+    email: String,
+    name: String,
+    groups: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct AuthorizeParams {
+    response_type: String,
+    client_id: String,
+    redirect_uri: String,
+    state: String,
+    nonce: Option<String>,
+    code_challenge: String,
+    code_challenge_method: String,
+    scope: Option<String>,
+    login_hint: Option<String>,
+}
+
+/// Параметры запроса входа плюс то, что ввёл пользователь
+#[derive(Deserialize)]
+struct AuthorizeForm {
+    #[serde(flatten)]
+    params: AuthorizeParams,
+    email: String,
+    name: String,
+    /// Через запятую
+    #[serde(default)]
+    groups: String,
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    client_id: String,
+    code_verifier: String,
+}
+
+/// Сервер на уже открытом сокете: издатель - адрес этого сокета
+pub fn server(listener: TcpListener) -> std::io::Result<Server> {
+    let idp = web::Data::new(MockIdp {
+        issuer: format!("http://{}", listener.local_addr()?),
+        grants: Mutex::new(HashMap::new()),
+    });
+    tracing::info!("Mock identity provider at {}", idp.issuer);
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(idp.clone())
+            .route("/.well-known/openid-configuration", web::get().to(discovery))
+            .route("/jwks", web::get().to(jwks))
+            .route("/authorize", web::get().to(login_page))
+            .route("/authorize", web::post().to(authorize))
+            .route("/token", web::post().to(token))
+    })
+    .workers(1)
+    .listen(listener)?
+    .run();
+    Ok(server)
+}
+
+async fn discovery(idp: web::Data<MockIdp>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "issuer": idp.issuer,
+        "authorization_endpoint": format!("{}/authorize", idp.issuer),
+        "token_endpoint": format!("{}/token", idp.issuer),
+        "jwks_uri": format!("{}/jwks", idp.issuer),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+        "code_challenge_methods_supported": ["S256"],
+    }))
+}
+
+async fn jwks() -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "keys": [{
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": KEY_ID,
+            "n": KEY_MODULUS,
+            "e": KEY_EXPONENT,
+        }]
+    }))
+}
+
+fn check_params(params: &AuthorizeParams) -> Result<(), HttpResponse> {
+    if params.response_type != "code" || params.code_challenge_method != "S256" {
+        return Err(HttpResponse::BadRequest()
+            .body("Only the authorization code flow with S256 PKCE is supported"));
+    }
+    Ok(())
+}
+
+async fn login_page(params: web::Query<AuthorizeParams>) -> HttpResponse {
+    if let Err(response) = check_params(&params) {
+        return response;
+    }
+
+    let hidden = [
+        ("response_type", params.response_type.as_str()),
+        ("client_id", &params.client_id),
+        ("redirect_uri", &params.redirect_uri),
+        ("state", &params.state),
+        ("nonce", params.nonce.as_deref().unwrap_or_default()),
+        ("code_challenge", &params.code_challenge),
+        ("code_challenge_method", &params.code_challenge_method),
+        ("scope", params.scope.as_deref().unwrap_or_default()),
+    ]
+    .iter()
+    .map(|(name, value)| {
+        format!(r#"<input type="hidden" name="{}" value="{}">"#, name, escape_html(value))
+    })
+    .collect::<String>();
+    let email = escape_html(params.login_hint.as_deref().unwrap_or_default());
+
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(format!(
+        r#"<!doctype html>
+<title>Mock identity provider</title>
+<h1>Mock identity provider</h1>
+<p>Signing in to {client}. Anyone can be anyone here.</p>
+<form method="post">{hidden}
+<p><label>Email <input name="email" type="email" value="{email}" required></label></p>
+<p><label>Name <input name="name" required></label></p>
+<p><label>Groups (comma separated) <input name="groups"></label></p>
+<p><button>Sign in</button></p>
+</form>"#,
+        client = escape_html(&params.client_id),
+    ))
+}
+
+async fn authorize(idp: web::Data<MockIdp>, form: web::Form<AuthorizeForm>) -> HttpResponse {
+    let form = form.into_inner();
+    if let Err(response) = check_params(&form.params) {
+        return response;
+    }
+
+    let code = oidc::generate_secret();
+    let grant = Grant {
+        client_id: form.params.client_id,
+        redirect_uri: form.params.redirect_uri.clone(),
+        code_challenge: form.params.code_challenge,
+        nonce: form.params.nonce.filter(|n| !n.is_empty()),
+        email: form.email,
+        name: form.name,
+        groups: form
+            .groups
+            .split(',')
+            .map(str::trim)
+            .filter(|g| !g.is_empty())
+            .map(String::from)
+            .collect(),
+    };
+    idp.grants.lock().unwrap_or_else(|e| e.into_inner()).insert(code.clone(), grant);
+
+    let Ok(mut location) = reqwest::Url::parse(&form.params.redirect_uri) else {
+        return HttpResponse::BadRequest().body("Invalid redirect_uri");
+    };
+    location
+        .query_pairs_mut()
+        .append_pair("code", &code)
+        .append_pair("state", &form.params.state);
+    HttpResponse::Found()
+        .insert_header(("Location", location.as_str()))
+        .finish()
+}
+
+async fn token(idp: web::Data<MockIdp>, form: web::Form<TokenForm>) -> HttpResponse {
+    let invalid = |description: &str| {
+        HttpResponse::BadRequest()
+            .json(json!({"error": "invalid_grant", "error_description": description}))
+    };
+    if form.grant_type != "authorization_code" {
+        return HttpResponse::BadRequest().json(json!({"error": "unsupported_grant_type"}));
+    }
+    // Код одноразовый: забираем его до проверок
+    let grant = idp.grants.lock().unwrap_or_else(|e| e.into_inner()).remove(&form.code);
+    let Some(grant) = grant else {
+        return invalid("Unknown or used code");
+    };
+    if grant.client_id != form.client_id || grant.redirect_uri != form.redirect_uri {
+        return invalid("Client or redirect_uri does not match the authorization request");
+    }
+    if oidc::pkce_challenge(&form.code_verifier) != grant.code_challenge {
+        return invalid("PKCE verification failed");
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let claims = json!({
+        "iss": idp.issuer,
+        "sub": format!("mock|{}", grant.email),
+        "aud": grant.client_id,
+        "iat": now,
+        "exp": now + TOKEN_SECS,
+        "nonce": grant.nonce,
+        "email": grant.email,
+        "email_verified": true,
+        "name": grant.name,
+        "groups": grant.groups,
+    });
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(KEY_ID.to_string());
+    let id_token = EncodingKey::from_rsa_pem(SIGNING_KEY.as_bytes())
+        .and_then(|key| jsonwebtoken::encode(&header, &claims, &key));
+
+    match id_token {
+        Ok(id_token) => HttpResponse::Ok().json(json!({
+            "access_token": oidc::generate_secret(),
+            "token_type": "Bearer",
+            "expires_in": TOKEN_SECS,
+            "id_token": id_token,
+        })),
+        Err(e) => {
+            tracing::error!("Mock identity provider failed to sign a token: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,5 +1,7 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use std::collections::{BTreeMap, HashMap};
 
 // ============ User ============
 
@@ -12,6 +14,20 @@ pub struct User {
     pub name: String,
     pub role: String,
     pub created_at: DateTime<Utc>,
+    /// Вход закрыт, пока пароль не сброшен по ссылке
+    #[serde(skip_serializing)]
+    pub password_reset_required: bool,
+}
+
+/// Пользователь в чужих глазах: email видят только он сам и администраторы
+#[derive(Debug, Serialize)]
+pub struct PublicUser {
+    pub id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub name: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -24,7 +40,7 @@ pub struct CreateUserRequest {
 }
 
 fn default_role() -> String {
-    "member".to_string()
+    crate::policy::ROLE_MEMBER.to_string()
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,7 +52,40 @@ pub struct LoginRequest {
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub token: String,
+    /// Одноразовый: при обмене выдаётся новый
+    pub refresh_token: String,
+    /// Через сколько секунд истечёт access-токен
+    pub expires_in: i64,
     pub user: User,
+    /// Роль требует второй фактор, а он не настроен: токен годится только для настройки
+    pub two_factor_setup_required: bool,
+}
+
+/// Ответ на вход: пара токенов или, при включённой 2FA, вызов для второго шага
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub challenge: String,
+    pub expires_in: i64,
+}
+
+/// Второй шаг входа: код из приложения или резервный код
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge: String,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,9 +94,107 @@ pub struct ChangePasswordRequest {
     pub new_password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PasswordResetToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+// ============ Two-factor ============
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct LoginChallenge {
+    pub id: i64,
+    pub user_id: i64,
+    pub expires_at: DateTime<Utc>,
+    pub attempts: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct UserTotp {
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_left: i64,
+    /// Роль пользователя требует второй фактор
+    pub required: bool,
+}
+
+/// Секрет показывается один раз, до подтверждения кодом
+#[derive(Debug, Serialize)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisableTwoFactorRequest {
+    pub password: String,
+    /// Код из приложения или неиспользованный резервный код
+    #[serde(default)]
+    pub code: String,
+}
+
+/// Резервные коды в открытом виде показываются только в этом ответе
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+// ============ Single sign-on ============
+
+#[derive(Debug, Serialize)]
+pub struct OidcStatus {
+    pub enabled: bool,
+}
+
+/// Куда отправить браузер. `state` клиент запоминает и сверяет по возвращении
+#[derive(Debug, Serialize)]
+pub struct OidcStartResponse {
+    pub authorization_url: String,
+    pub state: String,
+}
+
+/// Параметры, с которыми провайдер вернул браузер на фронтенд
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackRequest {
+    pub code: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OidcLoginState {
+    pub nonce: String,
+    pub code_verifier: String,
+    pub expires_at: DateTime<Utc>,
+}
+
 // ============ Task ============
 
-#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+#[derive(Debug, Clone, sqlx::FromRow, Serialize, Deserialize)]
 pub struct Task {
     pub id: i64,
     pub title: String,
@@ -59,6 +206,8 @@ pub struct Task {
     pub created_by: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub due_date: Option<NaiveDate>,
+    pub planned_date: Option<NaiveDate>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,6 +215,8 @@ pub struct CreateTaskRequest {
     pub title: String,
     pub description: Option<String>,
     pub assignee_id: Option<i64>,
+    pub due_date: Option<NaiveDate>,
+    pub planned_date: Option<NaiveDate>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -77,6 +228,10 @@ pub struct UpdateTaskRequest {
     #[serde(default, deserialize_with = "deserialize_optional_field")]
     pub assignee_id: Option<Option<i64>>,
     pub actual_hours: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    pub due_date: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    pub planned_date: Option<Option<NaiveDate>>,
 }
 
 // Позволяет различать отсутствие поля и явный null
@@ -88,10 +243,610 @@ where
     Ok(Some(Option::deserialize(deserializer)?))
 }
 
+/// Параметры списка задач
+#[derive(Debug, Default, Deserialize)]
+pub struct TaskListQuery {
+    /// Только задачи, за которыми следит текущий пользователь
+    #[serde(default)]
+    pub watching: bool,
+    /// Применить сохранённое представление
+    pub view: Option<i64>,
+    /// Запрос на языке запросов, например `status = todo AND assignee = me`
+    pub q: Option<String>,
+}
+
+/// Условия выборки задач, в которых "я" уже заменено на конкретного пользователя
+#[derive(Debug)]
+pub struct TaskQuery {
+    pub status: Option<String>,
+    pub assignee_id: Option<i64>,
+    pub unassigned: bool,
+    pub created_by: Option<i64>,
+    pub watcher_id: Option<i64>,
+    pub search: Option<String>,
+    pub sort_by: String,
+    pub sort_desc: bool,
+}
+
+impl Default for TaskQuery {
+    fn default() -> Self {
+        Self {
+            status: None,
+            assignee_id: None,
+            unassigned: false,
+            created_by: None,
+            watcher_id: None,
+            search: None,
+            sort_by: "created_at".to_string(),
+            sort_desc: true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TaskFilter {
+    pub status: Option<String>,
+    pub assignee_id: Option<i64>,
+    pub created_by: Option<i64>,
+}
+
+/// Сводка по задачам для метрик
+#[derive(Debug, Default)]
+pub struct TaskCounts {
+    pub by_status: Vec<(String, i64)>,
+    /// Незавершённые задачи с прошедшим сроком
+    pub overdue: i64,
+}
+
+// ============ Import / Export ============
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferFormat {
+    #[default]
+    Csv,
+    Json,
+    Ndjson,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: TransferFormat,
+}
+
+/// Задача вместе с email исполнителя - в таком виде она попадает в экспорт
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct ExportedTask {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub task: Task,
+    pub assignee_email: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportTasksRequest {
+    #[serde(default)]
+    pub format: TransferFormat,
+    /// Содержимое файла (CSV, JSON-массив или NDJSON)
+    pub data: String,
+    /// Поле задачи -> имя колонки в файле. Не указанные поля ищутся по своему имени
+    #[serde(default)]
+    pub mapping: HashMap<String, String>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Строка импорта, прошедшая валидацию
+#[derive(Debug, Clone)]
+pub struct ImportedTask {
+    pub title: String,
+    pub description: Option<String>,
+    pub status: String,
+    pub assignee_id: Option<i64>,
+    pub predicted_hours: Option<f64>,
+    pub actual_hours: Option<f64>,
+    pub due_date: Option<NaiveDate>,
+    pub planned_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    pub row: usize,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub imported: usize,
+    pub errors: Vec<ImportRowError>,
+}
+
+// ============ Comments ============
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct Comment {
+    pub id: i64,
+    pub task_id: i64,
+    pub author_id: i64,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCommentRequest {
+    pub body: String,
+}
+
+// ============ Jira / Trello import ============
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportSource {
+    Jira,
+    Trello,
+}
+
+impl ImportSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportSource::Jira => "jira",
+            ImportSource::Trello => "trello",
+        }
+    }
+}
+
+impl std::str::FromStr for ImportSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jira" => Ok(ImportSource::Jira),
+            "trello" => Ok(ImportSource::Trello),
+            other => Err(format!("Unknown import source: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExternalImportRequest {
+    /// Содержимое файла экспорта (Jira JSON/XML или Trello JSON)
+    pub data: String,
+    /// Статус/колонка во внешней системе -> наш статус
+    #[serde(default)]
+    pub status_mapping: HashMap<String, String>,
+    /// Внешний пользователь (id, логин или email) -> email нашего пользователя
+    #[serde(default)]
+    pub user_mapping: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ExternalImportReport {
+    pub source: String,
+    pub tasks_created: usize,
+    pub tasks_updated: usize,
+    pub comments_imported: usize,
+    pub users_created: usize,
+}
+
+// ============ Calendar ============
+
+#[derive(Debug, Serialize)]
+pub struct CalendarFeedStatus {
+    pub enabled: bool,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalendarFeedResponse {
+    /// Ссылка на ленту. Показывается только сразу после создания
+    pub url: String,
+}
+
+// ============ Webhooks ============
+
+pub const WEBHOOK_EVENTS: [&str; 4] = [
+    "task.created",
+    "task.updated",
+    "task.completed",
+    "task.deleted",
+];
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct WebhookSubscription {
+    pub id: i64,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub events: Json<Vec<String>>,
+    pub active: bool,
+    pub created_by: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub events: Vec<String>,
+    /// Если не задан, секрет генерируется сервером
+    pub secret: Option<String>,
+    #[serde(default = "default_true")]
+    pub active: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub active: Option<bool>,
+}
+
+/// Ответ на создание подписки - единственный раз, когда секрет отдаётся клиенту
+#[derive(Debug, Serialize)]
+pub struct CreatedWebhookResponse {
+    #[serde(flatten)]
+    pub subscription: WebhookSubscription,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub event: String,
+    pub payload: Json<serde_json::Value>,
+    pub status: String,
+    pub attempts: i64,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_status_code: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload<'a> {
+    pub event: &'a str,
+    pub occurred_at: DateTime<Utc>,
+    pub before: Option<&'a Task>,
+    pub after: Option<&'a Task>,
+}
+
+// ============ Jobs ============
+
+pub const JOB_PENDING: &str = "pending";
+pub const JOB_RUNNING: &str = "running";
+pub const JOB_DONE: &str = "done";
+/// Попытки кончились: задача лежит, пока администратор её не перезапустит
+pub const JOB_DEAD: &str = "dead";
+pub const JOB_STATUSES: [&str; 4] = [JOB_PENDING, JOB_RUNNING, JOB_DONE, JOB_DEAD];
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct JobRecord {
+    pub id: i64,
+    pub kind: String,
+    pub payload: Json<serde_json::Value>,
+    pub status: String,
+    pub attempts: i64,
+    pub max_attempts: i64,
+    pub run_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub request_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobsQuery {
+    pub status: Option<String>,
+    pub kind: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// ============ Realtime ============
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct TaskEvent {
+    pub id: i64,
+    pub event: String,
+    pub task_id: i64,
+    /// Состояние задачи после изменения, для удаления - null
+    pub task: Option<Json<Task>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// EventSource не умеет передавать заголовки, поэтому токен можно передать в query
+    pub token: Option<String>,
+    pub last_event_id: Option<i64>,
+}
+
+// ============ Notifications ============
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: i64,
+    pub user_id: i64,
+    pub kind: String,
+    pub task_id: Option<i64>,
+    pub actor_id: Option<i64>,
+    pub message: String,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NotificationsQuery {
+    #[serde(default)]
+    pub unread: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnreadCount {
+    pub unread: i64,
+}
+
+// ============ Email ============
+
+pub const EMAIL_MODES: [&str; 3] = ["immediate", "digest", "off"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailPreferences {
+    pub mode: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OutgoingEmail {
+    pub id: i64,
+    pub user_id: i64,
+    pub to_address: String,
+    pub template: String,
+    pub payload: Json<Vec<Notification>>,
+}
+
+// ============ Saved views ============
+
+pub const VIEW_SORT_FIELDS: [&str; 6] = [
+    "created_at",
+    "updated_at",
+    "due_date",
+    "title",
+    "status",
+    "predicted_hours",
+];
+
+pub const VIEW_DISPLAY_MODES: [&str; 2] = ["kanban", "list"];
+
+/// Условия отбора представления. Флаги "_me" считаются относительно того,
+/// кто открыл представление, поэтому общие представления работают для всех
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewFilters {
+    pub status: Option<String>,
+    pub assignee_id: Option<i64>,
+    pub assigned_to_me: bool,
+    pub unassigned: bool,
+    pub created_by_me: bool,
+    pub watching: bool,
+    /// Подстрока в названии задачи
+    pub search: Option<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct SavedView {
+    pub id: i64,
+    pub owner_id: i64,
+    pub name: String,
+    pub shared: bool,
+    pub filters: Json<ViewFilters>,
+    pub sort_by: String,
+    pub sort_desc: bool,
+    pub display: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn default_sort_by() -> String {
+    "created_at".to_string()
+}
+
+fn default_display() -> String {
+    "kanban".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateViewRequest {
+    pub name: String,
+    #[serde(default)]
+    pub shared: bool,
+    #[serde(default)]
+    pub filters: ViewFilters,
+    #[serde(default = "default_sort_by")]
+    pub sort_by: String,
+    #[serde(default = "default_true")]
+    pub sort_desc: bool,
+    #[serde(default = "default_display")]
+    pub display: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateViewRequest {
+    pub name: Option<String>,
+    pub shared: Option<bool>,
+    pub filters: Option<ViewFilters>,
+    pub sort_by: Option<String>,
+    pub sort_desc: Option<bool>,
+    pub display: Option<String>,
+}
+
 // ============ Auth ============
 
 #[derive(Debug, Clone)]
 pub struct AuthenticatedUser {
     pub id: i64,
-    pub role: String,
-}
\ No newline at end of file
+    /// `None` - запрос с личным токеном доступа, а не из сессии входа
+    pub session_id: Option<i64>,
+    /// Права роли на момент выдачи токена
+    pub permissions: Vec<String>,
+    /// Токен выдан до настройки обязательной 2FA
+    pub two_factor_setup_required: bool,
+    /// Личный токен с правом только на чтение
+    pub read_only: bool,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct Session {
+    pub id: i64,
+    pub user_id: i64,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Сессия, из которой пришёл запрос
+    #[sqlx(skip)]
+    pub current: bool,
+}
+
+/// Откуда пришёл запрос на вход
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// Refresh-токен вместе с состоянием его сессии
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RefreshToken {
+    pub id: i64,
+    pub session_id: i64,
+    pub user_id: i64,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+pub const TOKEN_SCOPE_READ: &str = "read";
+pub const TOKEN_SCOPE_WRITE: &str = "write";
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct PersonalAccessToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub scope: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAccessTokenRequest {
+    pub name: String,
+    pub scope: String,
+    /// Без срока токен действует до отзыва
+    pub expires_in_days: Option<i64>,
+}
+
+/// Ответ на создание токена - единственный раз, когда он отдаётся клиенту
+#[derive(Debug, Serialize)]
+pub struct CreatedAccessTokenResponse {
+    #[serde(flatten)]
+    pub access_token: PersonalAccessToken,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct LoginFailures {
+    pub failures: i64,
+    pub last_failed_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub event: String,
+    pub user_id: Option<i64>,
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub limit: Option<i64>,
+}
+
+// ============ Roles ============
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct Role {
+    pub name: String,
+    pub description: String,
+    pub permissions: Json<Vec<String>>,
+    pub builtin: bool,
+    /// Вход только со вторым фактором
+    pub require_two_factor: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRoleRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub require_two_factor: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRoleRequest {
+    pub description: Option<String>,
+    pub permissions: Option<Vec<String>>,
+    pub require_two_factor: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PermissionInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+// ============ Health ============
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    /// Работает без необязательной части, например без прогнозов ML
+    Degraded,
+    Down,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    /// Без обязательной части сервис не готов принимать запросы
+    pub required: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessReport {
+    pub status: HealthStatus,
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
@@ -0,0 +1,275 @@
+use crate::email;
+use crate::errors::AppError;
+use crate::models::{Comment, Task};
+use crate::repository;
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+pub const KIND_ASSIGNED: &str = "assigned";
+pub const KIND_STATUS_CHANGED: &str = "status_changed";
+pub const KIND_UPDATED: &str = "updated";
+pub const KIND_COMMENTED: &str = "commented";
+pub const KIND_MENTIONED: &str = "mentioned";
+pub const KIND_DUE_SOON: &str = "due_soon";
+
+pub const DUE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Создаёт уведомления по изменению задачи: новому исполнителю - о назначении,
+/// наблюдателям - о смене статуса или правке. Автор изменения и уже уведомлённые
+/// через упоминание (`mentioned`) второго уведомления не получают
+pub async fn on_task_changed(
+    pool: &SqlitePool,
+    actor_id: i64,
+    before: Option<&Task>,
+    after: &Task,
+    mentioned: &[i64],
+) {
+    let actor = actor_name(pool, actor_id).await;
+
+    let newly_assigned = after
+        .assignee_id
+        .filter(|id| before.and_then(|b| b.assignee_id) != Some(*id));
+
+    if let Some(assignee) = newly_assigned.filter(|id| *id != actor_id) {
+        let message = format!("{} assigned you to \"{}\"", actor, after.title);
+        notify(pool, assignee, KIND_ASSIGNED, Some(after.id), Some(actor_id), &message).await;
+    }
+
+    let Some(before) = before else {
+        return;
+    };
+    let (kind, message) = if before.status != after.status {
+        let message = format!("{} moved \"{}\" to {}", actor, after.title, after.status);
+        (KIND_STATUS_CHANGED, message)
+    } else if is_edited(before, after) {
+        (KIND_UPDATED, format!("{} updated \"{}\"", actor, after.title))
+    } else {
+        return;
+    };
+
+    let mut skip = vec![actor_id];
+    skip.extend(newly_assigned);
+    skip.extend_from_slice(mentioned);
+    notify_watchers(pool, after.id, kind, actor_id, &message, &skip).await;
+}
+
+/// Новый комментарий: наблюдателям, кроме автора и упомянутых в нём
+pub async fn on_comment(pool: &SqlitePool, task: &Task, comment: &Comment, mentioned: &[i64]) {
+    let actor = actor_name(pool, comment.author_id).await;
+    let message = format!("{} commented on \"{}\"", actor, task.title);
+
+    let mut skip = vec![comment.author_id];
+    skip.extend_from_slice(mentioned);
+    notify_watchers(pool, task.id, KIND_COMMENTED, comment.author_id, &message, &skip).await;
+}
+
+fn is_edited(before: &Task, after: &Task) -> bool {
+    before.title != after.title
+        || before.description != after.description
+        || before.assignee_id != after.assignee_id
+        || before.actual_hours != after.actual_hours
+        || before.due_date != after.due_date
+        || before.planned_date != after.planned_date
+}
+
+async fn notify_watchers(
+    pool: &SqlitePool,
+    task_id: i64,
+    kind: &str,
+    actor_id: i64,
+    message: &str,
+    skip: &[i64],
+) {
+    let watchers = match repository::get_watcher_ids(pool, task_id).await {
+        Ok(watchers) => watchers,
+        Err(e) => {
+            tracing::error!("Failed to load watchers of task {}: {}", task_id, e);
+            return;
+        }
+    };
+
+    for user_id in watchers {
+        if !skip.contains(&user_id) {
+            notify(pool, user_id, kind, Some(task_id), Some(actor_id), message).await;
+        }
+    }
+}
+
+/// Имя автора изменения для текста уведомления
+pub async fn actor_name(pool: &SqlitePool, actor_id: i64) -> String {
+    match repository::get_user_by_id(pool, actor_id).await {
+        Ok(user) => user.name,
+        Err(_) => "Someone".to_string(),
+    }
+}
+
+pub async fn notify(
+    pool: &SqlitePool,
+    user_id: i64,
+    kind: &str,
+    task_id: Option<i64>,
+    actor_id: Option<i64>,
+    message: &str,
+) {
+    match repository::create_notification(pool, user_id, kind, task_id, actor_id, message).await {
+        Ok(notification) => email::on_notification(pool, &notification).await,
+        Err(e) => tracing::error!("Failed to create notification for user {}: {}", user_id, e),
+    }
+}
+
+/// Напоминает исполнителям о задачах со сроком на завтра. Запускается из очереди
+/// раз в DUE_CHECK_INTERVAL
+pub async fn send_due_reminders(pool: &SqlitePool) -> Result<(), AppError> {
+    let tasks = repository::get_tasks_due_for_reminder(pool, KIND_DUE_SOON).await?;
+    for task in tasks {
+        let Some(assignee) = task.assignee_id else {
+            continue;
+        };
+        let message = format!("\"{}\" is due tomorrow", task.title);
+        notify(pool, assignee, KIND_DUE_SOON, Some(task.id), None, &message).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateTaskRequest;
+    use crate::policy;
+    use chrono::{Days, Utc};
+
+    const ACTOR: i64 = 1;
+    const ASSIGNEE: i64 = 2;
+    const WATCHER: i64 = 3;
+
+    async fn setup() -> SqlitePool {
+        let pool = repository::test_pool().await;
+        for name in ["Actor", "Assignee", "Watcher"] {
+            let email = format!("{}@example.test", name.to_lowercase());
+            repository::create_user(&pool, &email, "", name, policy::ROLE_MEMBER)
+                .await
+                .expect("user");
+        }
+        pool
+    }
+
+    async fn create_task(pool: &SqlitePool, assignee_id: Option<i64>) -> Task {
+        let req = CreateTaskRequest {
+            title: "Отчёт".to_string(),
+            description: None,
+            assignee_id,
+            due_date: Utc::now().date_naive().checked_add_days(Days::new(1)),
+            planned_date: None,
+        };
+        repository::create_task(pool, &req, ACTOR, None).await.expect("task")
+    }
+
+    async fn kinds(pool: &SqlitePool, user_id: i64) -> Vec<String> {
+        let notifications = repository::get_notifications(pool, user_id, false).await.unwrap();
+        notifications.into_iter().map(|n| n.kind).collect()
+    }
+
+    #[actix_web::test]
+    async fn new_assignee_is_notified_unless_they_assigned_themselves() {
+        let pool = setup().await;
+        let task = create_task(&pool, Some(ASSIGNEE)).await;
+        on_task_changed(&pool, ACTOR, None, &task, &[]).await;
+        assert_eq!(kinds(&pool, ASSIGNEE).await, [KIND_ASSIGNED]);
+        let message = &repository::get_notifications(&pool, ASSIGNEE, false).await.unwrap()[0];
+        assert_eq!(message.message, "Actor assigned you to \"Отчёт\"");
+
+        let own = create_task(&pool, Some(ACTOR)).await;
+        on_task_changed(&pool, ACTOR, None, &own, &[]).await;
+        assert!(kinds(&pool, ACTOR).await.is_empty());
+    }
+
+    async fn watched_by_everyone(pool: &SqlitePool) -> Task {
+        let task = create_task(pool, None).await;
+        for user_id in [ACTOR, ASSIGNEE, WATCHER] {
+            repository::add_watcher(pool, task.id, user_id).await.expect("watch");
+        }
+        task
+    }
+
+    #[actix_web::test]
+    async fn watchers_get_one_notification_per_change_except_actor() {
+        let pool = setup().await;
+        let before = watched_by_everyone(&pool).await;
+        let after = Task {
+            status: "in_progress".to_string(),
+            assignee_id: Some(ASSIGNEE),
+            ..before.clone()
+        };
+        on_task_changed(&pool, ACTOR, Some(&before), &after, &[]).await;
+
+        assert!(kinds(&pool, ACTOR).await.is_empty());
+        // Новому исполнителю хватит уведомления о назначении
+        assert_eq!(kinds(&pool, ASSIGNEE).await, [KIND_ASSIGNED]);
+        assert_eq!(kinds(&pool, WATCHER).await, [KIND_STATUS_CHANGED]);
+
+        // Без видимых изменений наблюдателей не беспокоим
+        on_task_changed(&pool, ACTOR, Some(&after), &after, &[]).await;
+        assert_eq!(kinds(&pool, WATCHER).await, [KIND_STATUS_CHANGED]);
+    }
+
+    #[actix_web::test]
+    async fn mentioned_watchers_are_not_notified_twice() {
+        let pool = setup().await;
+        let task = watched_by_everyone(&pool).await;
+        let comment = Comment {
+            id: 1,
+            task_id: task.id,
+            author_id: ACTOR,
+            body: "@watcher".to_string(),
+            created_at: Utc::now(),
+        };
+        on_comment(&pool, &task, &comment, &[WATCHER]).await;
+
+        assert!(kinds(&pool, ACTOR).await.is_empty());
+        assert_eq!(kinds(&pool, ASSIGNEE).await, [KIND_COMMENTED]);
+        assert!(kinds(&pool, WATCHER).await.is_empty());
+
+        let renamed = Task { title: "Новый отчёт".to_string(), ..task.clone() };
+        on_task_changed(&pool, ACTOR, Some(&task), &renamed, &[ASSIGNEE]).await;
+        assert_eq!(kinds(&pool, ASSIGNEE).await, [KIND_COMMENTED]);
+        assert_eq!(kinds(&pool, WATCHER).await, [KIND_UPDATED]);
+    }
+
+    #[actix_web::test]
+    async fn due_reminder_is_sent_once_per_day() {
+        let pool = setup().await;
+        create_task(&pool, Some(ASSIGNEE)).await;
+        create_task(&pool, None).await;
+
+        send_due_reminders(&pool).await.unwrap();
+        send_due_reminders(&pool).await.unwrap();
+        assert_eq!(kinds(&pool, ASSIGNEE).await, [KIND_DUE_SOON]);
+    }
+
+    #[test]
+    fn status_and_prediction_are_not_edits() {
+        let updated_at = Utc::now();
+        let task = Task {
+            id: 1,
+            title: "t".to_string(),
+            description: None,
+            status: "todo".to_string(),
+            predicted_hours: None,
+            actual_hours: None,
+            assignee_id: None,
+            created_by: ACTOR,
+            created_at: updated_at,
+            updated_at,
+            due_date: None,
+            planned_date: None,
+        };
+        let predicted = Task {
+            predicted_hours: Some(3.0),
+            status: "done".to_string(),
+            ..task.clone()
+        };
+        assert!(!is_edited(&task, &predicted));
+        let renamed = Task { title: "t2".to_string(), ..task.clone() };
+        assert!(is_edited(&task, &renamed));
+    }
+}
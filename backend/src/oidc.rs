@@ -0,0 +1,497 @@
+//! Вход через внешний провайдер по OpenID Connect: authorization code с PKCE.
+//! Адреса провайдера берутся из discovery-документа издателя, ключи подписи
+//! ID-токенов - из его JWKS. Оба кэшируются; ключи перечитываются, когда токен
+//! подписан ключом, которого ещё нет в кэше.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{AlgorithmFamily, DecodingKey, Validation};
+use rand::distr::{Alphanumeric, SampleString};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use crate::config::Config;
+use crate::errors::AppError;
+
+/// RFC 7636 требует от 43 до 128 символов
+const CODE_VERIFIER_LENGTH: usize = 64;
+
+#[derive(Clone)]
+struct Settings {
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: String,
+    scopes: String,
+    auto_provision: bool,
+    default_role: String,
+    groups_claim: String,
+    group_roles: Vec<(String, String)>,
+}
+
+#[derive(Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+struct Provider {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+    name: Option<String>,
+    preferred_username: Option<String>,
+    #[serde(flatten)]
+    other: HashMap<String, Value>,
+}
+
+/// Проверенный ID-токен: кто вошёл у провайдера
+#[derive(Debug)]
+pub struct Identity {
+    pub issuer: String,
+    pub subject: String,
+    /// Только подтверждённый провайдером адрес
+    pub email: Option<String>,
+    pub name: Option<String>,
+    /// `None`, если провайдер не прислал claim с группами
+    pub groups: Option<Vec<String>>,
+}
+
+/// Клиент провайдера. Без OIDC_ISSUER выключен, и вход через SSO недоступен
+#[derive(Clone)]
+pub struct OidcClient {
+    settings: Option<Settings>,
+    client: Client,
+    provider: Arc<RwLock<Option<Arc<Provider>>>>,
+}
+
+impl OidcClient {
+    pub fn from_config(config: &Config) -> Self {
+        let settings = config.oidc_issuer.as_ref().map(|issuer| Settings {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: config.oidc_client_id.clone(),
+            client_secret: config.oidc_client_secret.clone(),
+            redirect_url: config.oidc_redirect_url.clone(),
+            scopes: config.oidc_scopes.clone(),
+            auto_provision: config.oidc_auto_provision,
+            default_role: config.oidc_default_role.clone(),
+            groups_claim: config.oidc_groups_claim.clone(),
+            group_roles: config.oidc_group_roles.clone(),
+        });
+        Self {
+            settings,
+            client: Client::new(),
+            provider: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.settings.is_some()
+    }
+
+    /// `NotFound`, если SSO не настроен
+    pub fn check_enabled(&self) -> Result<(), AppError> {
+        self.settings().map(|_| ())
+    }
+
+    fn settings(&self) -> Result<&Settings, AppError> {
+        self.settings
+            .as_ref()
+            .ok_or_else(|| AppError::NotFound("Single sign-on is not configured".to_string()))
+    }
+
+    /// Адрес страницы входа провайдера, куда отправляется браузер
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String, AppError> {
+        let settings = self.settings()?;
+        let provider = self.provider(false).await?;
+
+        let mut url = Url::parse(&provider.metadata.authorization_endpoint).map_err(|e| {
+            tracing::warn!("Invalid OIDC authorization endpoint: {}", e);
+            AppError::Internal("Invalid identity provider configuration".to_string())
+        })?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &settings.client_id)
+            .append_pair("redirect_uri", &settings.redirect_url)
+            .append_pair("scope", &settings.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", &pkce_challenge(code_verifier))
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    /// Обменивает код на ID-токен и проверяет его: подпись, издатель, получатель,
+    /// срок и nonce из начала входа
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<Identity, AppError> {
+        let settings = self.settings()?;
+        let provider = self.provider(false).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", settings.redirect_url.as_str()),
+            ("client_id", settings.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &settings.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let response = self
+            .client
+            .post(&provider.metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(unavailable)?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            tracing::warn!("OIDC token endpoint returned {}: {}", status, body);
+            return Err(AppError::Unauthorized);
+        }
+        let tokens: TokenResponse = response.json().await.map_err(unavailable)?;
+
+        let claims = self.validate_id_token(settings, &tokens.id_token).await?;
+        if claims.nonce.as_deref() != Some(nonce) {
+            tracing::warn!("OIDC ID token nonce mismatch");
+            return Err(AppError::Unauthorized);
+        }
+        Ok(identity(settings, claims))
+    }
+
+    /// Создавать ли пользователя, которого ещё нет
+    pub fn auto_provision(&self) -> bool {
+        self.settings.as_ref().is_some_and(|s| s.auto_provision)
+    }
+
+    pub fn default_role(&self) -> &str {
+        self.settings.as_ref().map_or("", |s| s.default_role.as_str())
+    }
+
+    /// Роль по группам пользователя: первая подходящая из OIDC_GROUP_ROLES, иначе
+    /// роль по умолчанию. `None`, если сопоставление не настроено - тогда роль
+    /// ведётся в приложении, а не у провайдера
+    pub fn role_for_groups(&self, groups: &[String]) -> Option<&str> {
+        let settings = self.settings.as_ref().filter(|s| !s.group_roles.is_empty())?;
+        let mapped = settings
+            .group_roles
+            .iter()
+            .find(|(group, _)| groups.contains(group))
+            .map(|(_, role)| role.as_str());
+        Some(mapped.unwrap_or(&settings.default_role))
+    }
+
+    async fn validate_id_token(
+        &self,
+        settings: &Settings,
+        token: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| {
+            tracing::warn!("Malformed OIDC ID token: {}", e);
+            AppError::Unauthorized
+        })?;
+        // Общий секрет клиента не годится: проверяем только подпись ключом провайдера
+        if AlgorithmFamily::Hmac.algorithms().contains(&header.alg) {
+            tracing::warn!("OIDC ID token signed with {:?}, expected a provider key", header.alg);
+            return Err(AppError::Unauthorized);
+        }
+
+        let mut provider = self.provider(false).await?;
+        if find_key(&provider.jwks, header.kid.as_deref()).is_none() {
+            provider = self.provider(true).await?;
+        }
+        let jwk = find_key(&provider.jwks, header.kid.as_deref()).ok_or_else(|| {
+            tracing::warn!("OIDC ID token signed with unknown key {:?}", header.kid);
+            AppError::Unauthorized
+        })?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| {
+            tracing::warn!("Unsupported OIDC signing key: {}", e);
+            AppError::Unauthorized
+        })?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&provider.metadata.issuer]);
+        validation.set_audience(&[&settings.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        jsonwebtoken::decode::<IdTokenClaims>(token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| {
+                tracing::warn!("Invalid OIDC ID token: {}", e);
+                AppError::Unauthorized
+            })
+    }
+
+    /// Метаданные и ключи провайдера из кэша; `refresh_keys` перечитывает JWKS
+    async fn provider(&self, refresh_keys: bool) -> Result<Arc<Provider>, AppError> {
+        let settings = self.settings()?;
+        let cached = self.provider.read().await.clone();
+        let metadata = match cached {
+            Some(provider) if !refresh_keys => return Ok(provider),
+            Some(provider) => provider.metadata.clone(),
+            None => self.discover(settings).await?,
+        };
+        let jwks: JwkSet = self.fetch_json(&metadata.jwks_uri).await?;
+
+        let provider = Arc::new(Provider { metadata, jwks });
+        *self.provider.write().await = Some(provider.clone());
+        Ok(provider)
+    }
+
+    async fn discover(&self, settings: &Settings) -> Result<ProviderMetadata, AppError> {
+        let url = format!("{}/.well-known/openid-configuration", settings.issuer);
+        let metadata: ProviderMetadata = self.fetch_json(&url).await?;
+        // Документ должен описывать именно настроенного издателя
+        if metadata.issuer.trim_end_matches('/') != settings.issuer {
+            tracing::warn!(
+                "OIDC discovery issuer {} does not match {}",
+                metadata.issuer,
+                settings.issuer
+            );
+            return Err(AppError::Internal("Invalid identity provider configuration".to_string()));
+        }
+        Ok(metadata)
+    }
+
+    async fn fetch_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, AppError> {
+        let response = self.client.get(url).send().await.map_err(unavailable)?;
+        if !response.status().is_success() {
+            tracing::warn!("OIDC provider returned {} for {}", response.status(), url);
+            return Err(AppError::Internal("Identity provider unavailable".to_string()));
+        }
+        response.json().await.map_err(unavailable)
+    }
+}
+
+/// Одноразовый секрет для state, nonce и code_verifier
+pub fn generate_secret() -> String {
+    Alphanumeric.sample_string(&mut rand::rng(), CODE_VERIFIER_LENGTH)
+}
+
+/// code_challenge метода S256: base64url(SHA-256(code_verifier))
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+fn find_key<'a>(jwks: &'a JwkSet, kid: Option<&str>) -> Option<&'a jsonwebtoken::jwk::Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid),
+        // Без kid подпись однозначна, только если ключ у провайдера один
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+}
+
+fn identity(settings: &Settings, mut claims: IdTokenClaims) -> Identity {
+    let groups = claims.other.remove(&settings.groups_claim).map(|value| match value {
+        Value::Array(items) => items
+            .into_iter()
+            .filter_map(|item| item.as_str().map(String::from))
+            .collect(),
+        Value::String(group) => vec![group],
+        _ => Vec::new(),
+    });
+    // Адрес без подтверждения нельзя использовать для связи с чужим аккаунтом
+    let email = claims
+        .email
+        .filter(|_| claims.email_verified == Some(true))
+        .map(|email| email.trim().to_string());
+
+    Identity {
+        issuer: settings.issuer.clone(),
+        subject: claims.sub,
+        email,
+        name: claims.name.or(claims.preferred_username),
+        groups,
+    }
+}
+
+fn unavailable(e: reqwest::Error) -> AppError {
+    tracing::warn!("Identity provider request failed: {}", e);
+    AppError::Internal("Identity provider unavailable".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_idp;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    fn settings(issuer: &str) -> Settings {
+        Settings {
+            issuer: issuer.to_string(),
+            client_id: "task-tracker".to_string(),
+            client_secret: Some("client-secret".to_string()),
+            redirect_url: "http://localhost:3000/".to_string(),
+            scopes: "openid email profile".to_string(),
+            auto_provision: true,
+            default_role: "member".to_string(),
+            groups_claim: "groups".to_string(),
+            group_roles: vec![
+                ("admins".to_string(), "admin".to_string()),
+                ("triage".to_string(), "triager".to_string()),
+            ],
+        }
+    }
+
+    fn client(settings: Settings) -> OidcClient {
+        OidcClient {
+            settings: Some(settings),
+            client: Client::new(),
+            provider: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Учебный провайдер на свободном порту и клиент, настроенный на него
+    fn start_provider() -> OidcClient {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind mock idp");
+        let issuer = format!("http://{}", listener.local_addr().expect("mock idp address"));
+        actix_web::rt::spawn(mock_idp::server(listener).expect("start mock idp"));
+        client(settings(&issuer))
+    }
+
+    /// Вход у провайдера за пользователя: возвращает код из редиректа
+    async fn sign_in(oidc: &OidcClient, nonce: &str, code_verifier: &str) -> String {
+        let url = oidc.authorization_url("state", nonce, code_verifier).await.expect("url");
+        let mut authorize = Url::parse(&url).expect("authorization url");
+        let mut form: Vec<(String, String)> = authorize.query_pairs().into_owned().collect();
+        let user = [("email", "ann@example.test"), ("name", "Ann"), ("groups", "triage")];
+        for (name, value) in user {
+            form.push((name.to_string(), value.to_string()));
+        }
+        authorize.set_query(None);
+
+        let browser = Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("http client");
+        let redirect = browser.post(authorize).form(&form).send().await.expect("authorize");
+        let location = redirect.headers()["Location"].to_str().expect("location");
+        let back = Url::parse(location).expect("redirect");
+        let code = back.query_pairs().find(|(key, _)| key == "code").expect("code").1;
+        code.into_owned()
+    }
+
+    fn claims(other: Value) -> IdTokenClaims {
+        let mut claims = json!({"sub": "42", "email": " ann@example.test ", "name": "Ann"});
+        if let (Some(claims), Some(other)) = (claims.as_object_mut(), other.as_object()) {
+            claims.extend(other.clone());
+        }
+        serde_json::from_value(claims).expect("claims")
+    }
+
+    #[actix_web::test]
+    async fn signed_id_token_becomes_identity_once() {
+        let oidc = start_provider();
+        let verifier = generate_secret();
+        let code = sign_in(&oidc, "nonce", &verifier).await;
+
+        let identity = oidc.exchange_code(&code, &verifier, "nonce").await.expect("identity");
+        assert_eq!(identity.subject, "mock|ann@example.test");
+        assert_eq!(identity.email.as_deref(), Some("ann@example.test"));
+        assert_eq!(identity.name.as_deref(), Some("Ann"));
+        assert_eq!(identity.groups, Some(vec!["triage".to_string()]));
+        assert_eq!(identity.issuer, oidc.settings().expect("settings").issuer);
+
+        // Код одноразовый
+        let replay = oidc.exchange_code(&code, &verifier, "nonce").await;
+        assert!(matches!(replay, Err(AppError::Unauthorized)));
+    }
+
+    #[actix_web::test]
+    async fn nonce_and_verifier_must_come_from_the_same_login() {
+        let oidc = start_provider();
+        let verifier = generate_secret();
+
+        let code = sign_in(&oidc, "nonce", &verifier).await;
+        let other_nonce = oidc.exchange_code(&code, &verifier, "other").await;
+        assert!(matches!(other_nonce, Err(AppError::Unauthorized)));
+
+        let code = sign_in(&oidc, "nonce", &verifier).await;
+        let other_verifier = oidc.exchange_code(&code, &generate_secret(), "nonce").await;
+        assert!(matches!(other_verifier, Err(AppError::Unauthorized)));
+    }
+
+    #[actix_web::test]
+    async fn token_signed_with_client_secret_is_rejected() {
+        // Провайдер недоступен: отказ должен прийти раньше, чем запрос ключей
+        let settings = settings("http://127.0.0.1:9");
+        let claims = json!({
+            "iss": settings.issuer,
+            "aud": settings.client_id,
+            "sub": "42",
+            "exp": chrono::Utc::now().timestamp() + 300,
+        });
+        let key = EncodingKey::from_secret(b"client-secret");
+        let token = jsonwebtoken::encode(&Header::default(), &claims, &key).expect("token");
+
+        let oidc = client(settings.clone());
+        let result = oidc.validate_id_token(&settings, &token).await;
+        assert!(matches!(result, Err(AppError::Unauthorized)));
+    }
+
+    #[test]
+    fn unverified_email_is_dropped_and_groups_accept_a_string() {
+        let settings = settings("https://idp.example.test");
+
+        let verified = identity(&settings, claims(json!({"email_verified": true})));
+        assert_eq!(verified.email.as_deref(), Some("ann@example.test"));
+        assert_eq!(verified.groups, None);
+
+        let unverified = identity(&settings, claims(json!({"groups": "admins"})));
+        assert_eq!(unverified.email, None);
+        assert_eq!(unverified.groups, Some(vec!["admins".to_string()]));
+    }
+
+    #[test]
+    fn groups_map_to_the_first_configured_role() {
+        let oidc = client(settings("https://idp.example.test"));
+        let groups = |names: &[&str]| names.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(oidc.role_for_groups(&groups(&["triage", "admins"])), Some("admin"));
+        assert_eq!(oidc.role_for_groups(&groups(&["triage"])), Some("triager"));
+        assert_eq!(oidc.role_for_groups(&groups(&["staff"])), Some("member"));
+
+        let mut unmapped = settings("https://idp.example.test");
+        unmapped.group_roles.clear();
+        assert_eq!(client(unmapped).role_for_groups(&groups(&["admins"])), None);
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc_7636_example() {
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        assert_eq!(pkce_challenge(verifier), "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    }
+}
@@ -0,0 +1,97 @@
+//! Требования к паролям и доставка ссылок для сброса забытого пароля.
+
+use crate::config::Config;
+use crate::email::Mailer;
+use crate::errors::AppError;
+use crate::models::User;
+use futures_util::future::BoxFuture;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Сколько живёт ссылка сброса пароля
+pub const RESET_TOKEN_MINUTES: i64 = 60;
+/// Argon2 от очень длинной строки - лишняя нагрузка, а не безопасность
+const MAX_LENGTH: usize = 128;
+
+pub struct PasswordPolicy {
+    min_length: usize,
+    /// В нижнем регистре
+    breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn new(min_length: usize, breached: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            min_length,
+            breached: breached.into_iter().map(|p| p.to_lowercase()).collect(),
+        }
+    }
+
+    /// Без файла со списком утечек проверяется только длина
+    pub fn from_config(config: &Config) -> Self {
+        let breached = match std::fs::read_to_string(&config.breached_passwords_file) {
+            Ok(text) => text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect(),
+            Err(e) => {
+                tracing::warn!(
+                    "Breached password list {} not loaded: {}",
+                    config.breached_passwords_file,
+                    e
+                );
+                Vec::new()
+            }
+        };
+        Self::new(config.password_min_length, breached)
+    }
+
+    pub fn check(&self, password: &str) -> Result<(), AppError> {
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(AppError::BadRequest(format!(
+                "Password must be at least {} characters",
+                self.min_length
+            )));
+        }
+        if length > MAX_LENGTH {
+            return Err(AppError::BadRequest(format!(
+                "Password must be at most {} characters",
+                MAX_LENGTH
+            )));
+        }
+        if self.breached.contains(&password.to_lowercase()) {
+            return Err(AppError::BadRequest(
+                "This password appears in known data breaches, choose another one".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Куда отправить ссылку сброса пароля
+pub trait ResetNotifier: Send + Sync {
+    fn send_reset_link<'a>(&'a self, user: &'a User, link: &'a str) -> BoxFuture<'a, Result<(), String>>;
+}
+
+/// Для разработки и установок без почты: ссылку передаёт администратор из лога
+pub struct LogNotifier;
+
+impl ResetNotifier for LogNotifier {
+    fn send_reset_link<'a>(&'a self, user: &'a User, link: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            tracing::info!("Password reset link for {}: {}", user.email, link);
+            Ok(())
+        })
+    }
+}
+
+pub fn notifier_from_config(config: &Config) -> Result<Arc<dyn ResetNotifier>, String> {
+    match config.reset_notifier.as_str() {
+        "email" => Ok(Arc::new(Mailer::from_config(config)?)),
+        "log" => Ok(Arc::new(LogNotifier)),
+        other => Err(format!("Unknown RESET_NOTIFIER: {}", other)),
+    }
+}
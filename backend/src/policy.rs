@@ -0,0 +1,280 @@
+//! Кто что может делать. Решение принимается по правам роли пользователя и по его
+//! отношению к ресурсу: автор или исполнитель задачи, владелец представления.
+//! Обработчики проверяют каждое действие через `authorize`.
+
+use crate::errors::AppError;
+use crate::models::{AuthenticatedUser, PublicUser, Role, SavedView, Task, User};
+
+pub const ROLE_ADMIN: &str = "admin";
+pub const ROLE_MEMBER: &str = "member";
+
+/// Все права, из которых собираются роли
+pub const PERMISSIONS: [(&str, &str); 23] = [
+    ("user.view", "See the list of users"),
+    ("user.email.view", "See other users' emails"),
+    ("user.create", "Create users"),
+    ("user.delete", "Delete users"),
+    ("user.unlock", "Unlock accounts locked after failed logins"),
+    ("user.password.reset", "Force users to reset their password"),
+    ("role.manage", "Manage roles and their permissions"),
+    ("session.manage", "See and revoke other users' sessions and access tokens"),
+    ("audit.view", "Read the security audit log"),
+    ("task.create", "Create tasks"),
+    ("task.view", "See tasks, comments and watchers"),
+    ("task.update.own", "Edit tasks you created or are assigned to"),
+    ("task.update.any", "Edit any task"),
+    ("task.delete.own", "Delete tasks you created"),
+    ("task.delete.any", "Delete any task"),
+    ("task.comment", "Comment on tasks"),
+    ("task.watch", "Watch tasks"),
+    ("task.import", "Import tasks from CSV or JSON"),
+    ("task.export", "Export tasks"),
+    ("view.manage.any", "Edit and delete other users' saved views"),
+    ("webhook.manage", "Manage webhooks"),
+    ("import.external", "Import from Jira and Trello"),
+    ("job.manage", "Inspect and retry background jobs"),
+];
+
+pub fn is_known_permission(name: &str) -> bool {
+    PERMISSIONS.iter().any(|(p, _)| *p == name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Список участников и профиль без email
+    ViewUsers,
+    ViewUserEmail,
+    CreateUser,
+    DeleteUser,
+    UnlockUser,
+    ResetUserPassword,
+    ManageRoles,
+    /// Просмотр и отзыв сессий и личных токенов пользователя
+    ManageSessions,
+    ViewAuditLog,
+    CreateTask,
+    ViewTask,
+    UpdateTask,
+    DeleteTask,
+    CommentTask,
+    WatchTask,
+    ImportTasks,
+    ExportTasks,
+    ViewView,
+    /// Изменение и удаление сохранённого представления
+    ManageView,
+    ManageWebhooks,
+    ImportExternal,
+    /// Очередь фоновых задач: просмотр и перезапуск
+    ManageJobs,
+}
+
+/// Над чем выполняется действие
+#[derive(Debug, Clone, Copy)]
+pub enum Resource<'a> {
+    None,
+    User(i64),
+    Task(&'a Task),
+    View(&'a SavedView),
+}
+
+pub fn has_permission(user: &AuthenticatedUser, permission: &str) -> bool {
+    user.permissions.iter().any(|p| p == permission)
+}
+
+pub fn is_allowed(user: &AuthenticatedUser, action: Action, resource: Resource) -> bool {
+    let can = |permission| has_permission(user, permission);
+
+    match (action, resource) {
+        (Action::ViewUsers, _) => can("user.view"),
+        (Action::ViewUserEmail, Resource::User(id)) => id == user.id || can("user.email.view"),
+        (Action::CreateUser, _) => can("user.create"),
+        (Action::DeleteUser, _) => can("user.delete"),
+        (Action::UnlockUser, _) => can("user.unlock"),
+        (Action::ResetUserPassword, _) => can("user.password.reset"),
+        (Action::ManageRoles, _) => can("role.manage"),
+        (Action::ViewAuditLog, _) => can("audit.view"),
+        (Action::ManageSessions, Resource::User(id)) => id == user.id || can("session.manage"),
+        (Action::CreateTask, _) => can("task.create"),
+        (Action::ViewTask, _) => can("task.view"),
+        // Свои задачи - те, за которые пользователь отвечает: автор и исполнитель
+        (Action::UpdateTask, Resource::Task(task)) => {
+            can("task.update.any")
+                || (can("task.update.own")
+                    && (task.created_by == user.id || task.assignee_id == Some(user.id)))
+        }
+        (Action::DeleteTask, Resource::Task(task)) => {
+            can("task.delete.any") || (can("task.delete.own") && task.created_by == user.id)
+        }
+        (Action::CommentTask, _) => can("task.comment"),
+        (Action::WatchTask, _) => can("task.watch"),
+        (Action::ImportTasks, _) => can("task.import"),
+        (Action::ExportTasks, _) => can("task.export"),
+        (Action::ViewView, Resource::View(view)) => {
+            view.shared || view.owner_id == user.id || can("view.manage.any")
+        }
+        (Action::ManageView, Resource::View(view)) => {
+            view.owner_id == user.id || can("view.manage.any")
+        }
+        (Action::ManageWebhooks, _) => can("webhook.manage"),
+        (Action::ImportExternal, _) => can("import.external"),
+        (Action::ManageJobs, _) => can("job.manage"),
+        // Действие над ресурсом не того типа - ошибка вызова, разрешать нечего
+        _ => false,
+    }
+}
+
+/// Выдать роль можно, только если все её права уже есть у самого пользователя или он
+/// управляет ролями: иначе права `user.create` хватило бы, чтобы завести администратора
+pub fn can_assign_role(user: &AuthenticatedUser, role: &Role) -> bool {
+    has_permission(user, "role.manage") || role.permissions.iter().all(|p| has_permission(user, p))
+}
+
+pub fn authorize(
+    user: &AuthenticatedUser,
+    action: Action,
+    resource: Resource,
+) -> Result<(), AppError> {
+    if is_allowed(user, action, resource) {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden)
+    }
+}
+
+/// Профиль в том виде, в каком его можно показать `viewer`
+pub fn present_user(viewer: &AuthenticatedUser, user: User) -> PublicUser {
+    let email = is_allowed(viewer, Action::ViewUserEmail, Resource::User(user.id)).then_some(user.email);
+    PublicUser {
+        id: user.id,
+        email,
+        name: user.name,
+        role: user.role,
+        created_at: user.created_at,
+    }
+}
+
+pub fn present_users(viewer: &AuthenticatedUser, users: Vec<User>) -> Vec<PublicUser> {
+    users.into_iter().map(|u| present_user(viewer, u)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use sqlx::types::Json;
+
+    const ADMIN: i64 = 1;
+    const CREATOR: i64 = 2;
+    const ASSIGNEE: i64 = 3;
+    const OTHER: i64 = 4;
+    /// Пользователь с произвольной ролью: правит любые задачи, но больше ничего
+    const TRIAGER: i64 = 5;
+
+    /// Права встроенной роли member, как в миграции 011_roles.sql
+    const MEMBER_PERMISSIONS: [&str; 9] = [
+        "user.view",
+        "task.create",
+        "task.view",
+        "task.update.own",
+        "task.delete.own",
+        "task.comment",
+        "task.watch",
+        "task.import",
+        "task.export",
+    ];
+
+    fn user(id: i64) -> AuthenticatedUser {
+        let permissions: Vec<&str> = match id {
+            ADMIN => PERMISSIONS.iter().map(|(p, _)| *p).collect(),
+            TRIAGER => vec!["task.view", "task.update.any"],
+            _ => MEMBER_PERMISSIONS.to_vec(),
+        };
+        AuthenticatedUser {
+            id,
+            session_id: Some(id),
+            two_factor_setup_required: false,
+            read_only: false,
+            permissions: permissions.into_iter().map(String::from).collect(),
+        }
+    }
+
+    fn task() -> Task {
+        Task {
+            id: 1,
+            title: "Task".to_string(),
+            description: None,
+            status: "todo".to_string(),
+            predicted_hours: None,
+            actual_hours: None,
+            assignee_id: Some(ASSIGNEE),
+            created_by: CREATOR,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            due_date: None,
+            planned_date: None,
+        }
+    }
+
+    fn view(shared: bool) -> SavedView {
+        SavedView {
+            id: 1,
+            owner_id: CREATOR,
+            name: "View".to_string(),
+            shared,
+            filters: Json(Default::default()),
+            sort_by: "created_at".to_string(),
+            sort_desc: true,
+            display: "kanban".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn decisions_by_role_and_relation() {
+        let task = task();
+        let private_view = view(false);
+        let shared_view = view(true);
+
+        // Разрешено ли действие: админ, автор, исполнитель, посторонний, triager
+        let cases: Vec<(&str, Action, Resource, [bool; 5])> = vec![
+            ("view users", Action::ViewUsers, Resource::None, [true, true, true, true, false]),
+            ("own email", Action::ViewUserEmail, Resource::User(CREATOR), [true, true, false, false, false]),
+            ("create user", Action::CreateUser, Resource::None, [true, false, false, false, false]),
+            ("delete user", Action::DeleteUser, Resource::None, [true, false, false, false, false]),
+            ("unlock user", Action::UnlockUser, Resource::User(CREATOR), [true, false, false, false, false]),
+            ("force reset", Action::ResetUserPassword, Resource::User(CREATOR), [true, false, false, false, false]),
+            ("audit log", Action::ViewAuditLog, Resource::None, [true, false, false, false, false]),
+            ("manage roles", Action::ManageRoles, Resource::None, [true, false, false, false, false]),
+            ("own sessions", Action::ManageSessions, Resource::User(CREATOR), [true, true, false, false, false]),
+            ("create task", Action::CreateTask, Resource::None, [true, true, true, true, false]),
+            ("view task", Action::ViewTask, Resource::Task(&task), [true, true, true, true, true]),
+            ("update task", Action::UpdateTask, Resource::Task(&task), [true, true, true, false, true]),
+            ("delete task", Action::DeleteTask, Resource::Task(&task), [true, true, false, false, false]),
+            ("comment", Action::CommentTask, Resource::Task(&task), [true, true, true, true, false]),
+            ("watch", Action::WatchTask, Resource::Task(&task), [true, true, true, true, false]),
+            ("import", Action::ImportTasks, Resource::None, [true, true, true, true, false]),
+            ("export", Action::ExportTasks, Resource::None, [true, true, true, true, false]),
+            ("private view", Action::ViewView, Resource::View(&private_view), [true, true, false, false, false]),
+            ("shared view", Action::ViewView, Resource::View(&shared_view), [true, true, true, true, true]),
+            ("manage view", Action::ManageView, Resource::View(&shared_view), [true, true, false, false, false]),
+            ("webhooks", Action::ManageWebhooks, Resource::None, [true, false, false, false, false]),
+            ("external import", Action::ImportExternal, Resource::None, [true, false, false, false, false]),
+            ("jobs", Action::ManageJobs, Resource::None, [true, false, false, false, false]),
+            ("wrong resource", Action::UpdateTask, Resource::None, [false, false, false, false, false]),
+        ];
+
+        for (name, action, resource, expected) in cases {
+            for (id, allowed) in [ADMIN, CREATOR, ASSIGNEE, OTHER, TRIAGER].into_iter().zip(expected) {
+                assert_eq!(
+                    is_allowed(&user(id), action, resource),
+                    allowed,
+                    "{}: user {}",
+                    name,
+                    id
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,1022 @@
+//! Язык запросов к задачам:
+//! `status = in_progress AND assignee = me AND predicted_hours > 4 ORDER BY updated_at DESC`.
+//!
+//! Запрос разбирается в дерево и компилируется в условие WHERE с плейсхолдерами `?N`:
+//! значения из запроса в SQL никогда не подставляются.
+
+use crate::errors::AppError;
+use crate::services::TASK_STATUSES;
+use chrono::NaiveDate;
+
+const MAX_QUERY_LENGTH: usize = 2000;
+/// Ограничение вложенности скобок и NOT, чтобы рекурсивный разбор не переполнил стек
+const MAX_DEPTH: usize = 32;
+
+/// Ошибка разбора. `position` - номер символа в запросе, начиная с 1
+#[derive(Debug)]
+pub struct QueryError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<QueryError> for AppError {
+    fn from(e: QueryError) -> Self {
+        AppError::BadRequest(format!(
+            "Invalid query at position {}: {}",
+            e.position, e.message
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum QueryParam {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// Скомпилированный запрос. Плейсхолдеры `?N` в `condition` идут в порядке `params`
+#[derive(Debug, Default)]
+pub struct CompiledQuery {
+    pub condition: Option<String>,
+    pub params: Vec<QueryParam>,
+    pub order_by: Vec<String>,
+}
+
+// ============ Fields ============
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldType {
+    Text,
+    Status,
+    User,
+    Number,
+    Date,
+    DateTime,
+    Watcher,
+}
+
+#[derive(Debug)]
+struct Field {
+    name: &'static str,
+    column: &'static str,
+    ty: FieldType,
+    nullable: bool,
+}
+
+const FIELDS: [Field; 13] = [
+    Field {
+        name: "id",
+        column: "t.id",
+        ty: FieldType::Number,
+        nullable: false,
+    },
+    Field {
+        name: "title",
+        column: "t.title",
+        ty: FieldType::Text,
+        nullable: false,
+    },
+    Field {
+        name: "description",
+        column: "t.description",
+        ty: FieldType::Text,
+        nullable: true,
+    },
+    Field {
+        name: "status",
+        column: "t.status",
+        ty: FieldType::Status,
+        nullable: false,
+    },
+    Field {
+        name: "assignee",
+        column: "t.assignee_id",
+        ty: FieldType::User,
+        nullable: true,
+    },
+    Field {
+        name: "creator",
+        column: "t.created_by",
+        ty: FieldType::User,
+        nullable: false,
+    },
+    Field {
+        name: "predicted_hours",
+        column: "t.predicted_hours",
+        ty: FieldType::Number,
+        nullable: true,
+    },
+    Field {
+        name: "actual_hours",
+        column: "t.actual_hours",
+        ty: FieldType::Number,
+        nullable: true,
+    },
+    Field {
+        name: "created_at",
+        column: "t.created_at",
+        ty: FieldType::DateTime,
+        nullable: false,
+    },
+    Field {
+        name: "updated_at",
+        column: "t.updated_at",
+        ty: FieldType::DateTime,
+        nullable: false,
+    },
+    Field {
+        name: "due_date",
+        column: "t.due_date",
+        ty: FieldType::Date,
+        nullable: true,
+    },
+    Field {
+        name: "planned_date",
+        column: "t.planned_date",
+        ty: FieldType::Date,
+        nullable: true,
+    },
+    Field {
+        name: "watcher",
+        column: "",
+        ty: FieldType::Watcher,
+        nullable: false,
+    },
+];
+
+fn find_field(name: &str) -> Option<&'static Field> {
+    let name = name.to_lowercase();
+    let name = match name.as_str() {
+        "assignee_id" => "assignee",
+        "created_by" => "creator",
+        other => other,
+    };
+    FIELDS.iter().find(|f| f.name == name)
+}
+
+fn field_names() -> String {
+    FIELDS.iter().map(|f| f.name).collect::<Vec<_>>().join(", ")
+}
+
+// ============ Lexer ============
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    position: usize,
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(s) | Token::Number(s) => format!("'{}'", s),
+        Token::Str(s) => format!("string '{}'", s),
+        Token::Op(op) => format!("'{}'", op),
+        Token::LParen => "'('".to_string(),
+        Token::RParen => "')'".to_string(),
+        Token::Comma => "','".to_string(),
+        Token::End => "end of query".to_string(),
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '@' | '-')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Spanned>, QueryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let position = i + 1;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let next = chars.get(i + 1).copied();
+        let token = match c {
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '=' => Token::Op("="),
+            '~' => Token::Op("~"),
+            '!' if next == Some('=') => Token::Op("!="),
+            '!' if next == Some('~') => Token::Op("!~"),
+            '<' if next == Some('=') => Token::Op("<="),
+            '<' if next == Some('>') => Token::Op("!="),
+            '<' => Token::Op("<"),
+            '>' if next == Some('=') => Token::Op(">="),
+            '>' => Token::Op(">"),
+            '\'' | '"' => {
+                let quote = c;
+                let mut value = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j) {
+                        None => return Err(QueryError::new(position, "unterminated string")),
+                        // Кавычка внутри строки удваивается: 'it''s'
+                        Some(&ch) if ch == quote && chars.get(j + 1) == Some(&quote) => {
+                            value.push(quote);
+                            j += 2;
+                        }
+                        Some(&ch) if ch == quote => break,
+                        Some(&ch) => {
+                            value.push(ch);
+                            j += 1;
+                        }
+                    }
+                }
+                tokens.push(Spanned {
+                    token: Token::Str(value),
+                    position,
+                });
+                i = j + 1;
+                continue;
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let mut j = i + 1;
+                while j < chars.len() && (chars[j].is_ascii_digit() || chars[j] == '.') {
+                    j += 1;
+                }
+                // 2024-01-31 без кавычек - это дата, а не число
+                if j < chars.len() && is_ident_char(chars[j]) {
+                    while j < chars.len() && is_ident_char(chars[j]) {
+                        j += 1;
+                    }
+                    tokens.push(Spanned {
+                        token: Token::Ident(chars[i..j].iter().collect()),
+                        position,
+                    });
+                } else {
+                    tokens.push(Spanned {
+                        token: Token::Number(chars[i..j].iter().collect()),
+                        position,
+                    });
+                }
+                i = j;
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut j = i + 1;
+                while j < chars.len() && is_ident_char(chars[j]) {
+                    j += 1;
+                }
+                tokens.push(Spanned {
+                    token: Token::Ident(chars[i..j].iter().collect()),
+                    position,
+                });
+                i = j;
+                continue;
+            }
+            other => {
+                return Err(QueryError::new(
+                    position,
+                    format!("unexpected character '{}'", other),
+                ));
+            }
+        };
+
+        i += match token {
+            Token::Op(op) if op.len() == 2 => 2,
+            _ => 1,
+        };
+        tokens.push(Spanned { token, position });
+    }
+
+    tokens.push(Spanned {
+        token: Token::End,
+        position: chars.len() + 1,
+    });
+    Ok(tokens)
+}
+
+// ============ Parser ============
+
+#[derive(Debug, Clone)]
+struct Value {
+    token: Token,
+    position: usize,
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        field: &'static Field,
+        op: &'static str,
+        op_position: usize,
+        value: Value,
+    },
+    In {
+        field: &'static Field,
+        negated: bool,
+        values: Vec<Value>,
+    },
+    IsNull {
+        field: &'static Field,
+        field_position: usize,
+        negated: bool,
+    },
+}
+
+/// Поле сортировки и признак DESC
+type OrderKey = (&'static Field, bool);
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Spanned {
+        &self.tokens[self.pos]
+    }
+
+    fn advance(&mut self) -> Spanned {
+        let token = self.tokens[self.pos].clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().token, Token::Ident(s) if s.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), QueryError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("expected {}", keyword)))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> QueryError {
+        let token = self.peek();
+        QueryError::new(
+            token.position,
+            format!("{}, found {}", expected, describe(&token.token)),
+        )
+    }
+
+    fn enter(&mut self) -> Result<(), QueryError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(QueryError::new(
+                self.peek().position,
+                "query is nested too deeply",
+            ));
+        }
+        Ok(())
+    }
+
+    fn parse_query(&mut self) -> Result<(Option<Expr>, Vec<OrderKey>), QueryError> {
+        let filter = if self.is_keyword("ORDER") || self.peek().token == Token::End {
+            None
+        } else {
+            Some(self.parse_or()?)
+        };
+
+        let mut order = Vec::new();
+        if self.eat_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                let (field, position) = self.parse_field()?;
+                if field.ty == FieldType::Watcher {
+                    return Err(QueryError::new(position, "cannot sort by watcher"));
+                }
+                let desc = if self.eat_keyword("DESC") {
+                    true
+                } else {
+                    self.eat_keyword("ASC");
+                    false
+                };
+                order.push((field, desc));
+
+                if self.peek().token == Token::Comma {
+                    self.advance();
+                } else {
+                    break;
+                }
+            }
+        }
+
+        if self.peek().token != Token::End {
+            let expected = if order.is_empty() {
+                "expected AND, OR or ORDER BY"
+            } else {
+                "expected ',' or end of query"
+            };
+            return Err(self.unexpected(expected));
+        }
+        Ok((filter, order))
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_and()?;
+        while self.eat_keyword("OR") {
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_not()?;
+        while self.eat_keyword("AND") {
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, QueryError> {
+        if self.eat_keyword("NOT") {
+            self.enter()?;
+            let inner = self.parse_not()?;
+            self.depth -= 1;
+            return Ok(Expr::Not(Box::new(inner)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        if self.peek().token == Token::LParen {
+            self.advance();
+            self.enter()?;
+            let inner = self.parse_or()?;
+            self.depth -= 1;
+            if self.peek().token != Token::RParen {
+                return Err(self.unexpected("expected ')'"));
+            }
+            self.advance();
+            return Ok(inner);
+        }
+
+        let (field, field_position) = self.parse_field()?;
+
+        if self.eat_keyword("IS") {
+            let negated = self.eat_keyword("NOT");
+            if !(self.eat_keyword("NULL") || self.eat_keyword("EMPTY")) {
+                return Err(self.unexpected("expected NULL or EMPTY"));
+            }
+            return Ok(Expr::IsNull {
+                field,
+                field_position,
+                negated,
+            });
+        }
+
+        let negated = self.eat_keyword("NOT");
+        if self.eat_keyword("IN") {
+            return Ok(Expr::In {
+                field,
+                negated,
+                values: self.parse_list()?,
+            });
+        }
+        if negated {
+            return Err(self.unexpected("expected IN after NOT"));
+        }
+
+        let Token::Op(op) = self.peek().token else {
+            return Err(self.unexpected(&format!("expected operator after '{}'", field.name)));
+        };
+        let op_position = self.advance().position;
+        let value = self.parse_value()?;
+        Ok(Expr::Compare {
+            field,
+            op,
+            op_position,
+            value,
+        })
+    }
+
+    fn parse_field(&mut self) -> Result<(&'static Field, usize), QueryError> {
+        let token = self.peek().clone();
+        let Token::Ident(name) = &token.token else {
+            return Err(self.unexpected("expected field name"));
+        };
+        let field = find_field(name).ok_or_else(|| {
+            QueryError::new(
+                token.position,
+                format!(
+                    "unknown field '{}', expected one of: {}",
+                    name,
+                    field_names()
+                ),
+            )
+        })?;
+        self.advance();
+        Ok((field, token.position))
+    }
+
+    fn parse_value(&mut self) -> Result<Value, QueryError> {
+        match &self.peek().token {
+            Token::Str(_) | Token::Number(_) | Token::Ident(_) => {
+                let token = self.advance();
+                Ok(Value {
+                    token: token.token,
+                    position: token.position,
+                })
+            }
+            _ => Err(self.unexpected("expected value")),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Vec<Value>, QueryError> {
+        if self.peek().token != Token::LParen {
+            return Err(self.unexpected("expected '(' after IN"));
+        }
+        self.advance();
+
+        let mut values = vec![self.parse_value()?];
+        while self.peek().token == Token::Comma {
+            self.advance();
+            values.push(self.parse_value()?);
+        }
+
+        if self.peek().token != Token::RParen {
+            return Err(self.unexpected("expected ',' or ')'"));
+        }
+        self.advance();
+        Ok(values)
+    }
+}
+
+// ============ Compiler ============
+
+struct Compiler {
+    me: i64,
+    first_param: usize,
+    params: Vec<QueryParam>,
+}
+
+impl Compiler {
+    /// Добавляет параметр и возвращает его нумерованный плейсхолдер
+    fn bind(&mut self, param: QueryParam) -> String {
+        self.params.push(param);
+        format!("?{}", self.first_param + self.params.len() - 1)
+    }
+
+    fn compile(&mut self, expr: &Expr) -> Result<String, QueryError> {
+        match expr {
+            Expr::And(l, r) => Ok(format!("({} AND {})", self.compile(l)?, self.compile(r)?)),
+            Expr::Or(l, r) => Ok(format!("({} OR {})", self.compile(l)?, self.compile(r)?)),
+            Expr::Not(inner) => Ok(format!("(NOT {})", self.compile(inner)?)),
+            Expr::IsNull {
+                field,
+                field_position,
+                negated,
+            } => {
+                if !field.nullable {
+                    return Err(QueryError::new(
+                        *field_position,
+                        format!("field '{}' is never empty", field.name),
+                    ));
+                }
+                let not = if *negated { " NOT" } else { "" };
+                Ok(format!("{} IS{} NULL", field.column, not))
+            }
+            Expr::In {
+                field,
+                negated,
+                values,
+                ..
+            } => self.compile_in(field, *negated, values),
+            Expr::Compare {
+                field,
+                op,
+                op_position,
+                value,
+            } => self.compile_compare(field, op, *op_position, value),
+        }
+    }
+
+    fn compile_compare(
+        &mut self,
+        field: &Field,
+        op: &str,
+        op_position: usize,
+        value: &Value,
+    ) -> Result<String, QueryError> {
+        let allowed: &[&str] = match field.ty {
+            FieldType::Text => &["=", "!=", "~", "!~"],
+            FieldType::Status | FieldType::User | FieldType::Watcher => &["=", "!="],
+            FieldType::Number | FieldType::Date | FieldType::DateTime => {
+                &["=", "!=", "<", "<=", ">", ">="]
+            }
+        };
+        if !allowed.contains(&op) {
+            return Err(QueryError::new(
+                op_position,
+                format!(
+                    "operator '{}' is not supported for '{}', use one of: {}",
+                    op,
+                    field.name,
+                    allowed.join(" ")
+                ),
+            ));
+        }
+
+        if field.ty == FieldType::Watcher {
+            let user = self.operand(field, value)?;
+            let exists = format!(
+                "EXISTS (SELECT 1 FROM task_watchers w WHERE w.task_id = t.id AND w.user_id = {})",
+                user
+            );
+            return Ok(if op == "=" {
+                exists
+            } else {
+                format!("NOT {}", exists)
+            });
+        }
+
+        let column = column_expr(field);
+        match op {
+            "~" | "!~" => {
+                let text = self.text_value(value)?;
+                let pattern = format!("%{}%", escape_like(&text));
+                let pattern = self.bind(QueryParam::Text(pattern));
+                if op == "~" {
+                    Ok(format!("{} LIKE {} ESCAPE '\\'", column, pattern))
+                } else {
+                    Ok(format!(
+                        "({} IS NULL OR {} NOT LIKE {} ESCAPE '\\')",
+                        column, column, pattern
+                    ))
+                }
+            }
+            // != пропускает и пустые значения: "assignee != me" включает задачи без исполнителя
+            "!=" => Ok(format!("{} IS NOT {}", column, self.operand(field, value)?)),
+            _ => Ok(format!("{} {} {}", column, op, self.operand(field, value)?)),
+        }
+    }
+
+    fn compile_in(
+        &mut self,
+        field: &Field,
+        negated: bool,
+        values: &[Value],
+    ) -> Result<String, QueryError> {
+        let operands = values
+            .iter()
+            .map(|v| self.operand(field, v))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(", ");
+
+        if field.ty == FieldType::Watcher {
+            let exists = format!(
+                "EXISTS (SELECT 1 FROM task_watchers w WHERE w.task_id = t.id AND w.user_id IN ({}))",
+                operands
+            );
+            return Ok(if negated {
+                format!("NOT {}", exists)
+            } else {
+                exists
+            });
+        }
+
+        let column = column_expr(field);
+        if negated {
+            Ok(format!(
+                "({} IS NULL OR {} NOT IN ({}))",
+                column, column, operands
+            ))
+        } else {
+            Ok(format!("{} IN ({})", column, operands))
+        }
+    }
+
+    /// SQL для значения справа от оператора: плейсхолдер, подзапрос или date('now')
+    fn operand(&mut self, field: &Field, value: &Value) -> Result<String, QueryError> {
+        match field.ty {
+            FieldType::Text => {
+                let text = self.text_value(value)?;
+                Ok(self.bind(QueryParam::Text(text)))
+            }
+            FieldType::Status => {
+                let text = self.text_value(value)?;
+                if !TASK_STATUSES.contains(&text.as_str()) {
+                    return Err(QueryError::new(
+                        value.position,
+                        format!(
+                            "unknown status '{}', expected one of: {}",
+                            text,
+                            TASK_STATUSES.join(", ")
+                        ),
+                    ));
+                }
+                Ok(self.bind(QueryParam::Text(text)))
+            }
+            FieldType::User | FieldType::Watcher => match &value.token {
+                Token::Ident(s) if s.eq_ignore_ascii_case("me") => {
+                    Ok(self.bind(QueryParam::Int(self.me)))
+                }
+                Token::Number(n) => {
+                    let id = n.parse().map_err(|_| {
+                        QueryError::new(value.position, format!("'{}' is not a user id", n))
+                    })?;
+                    Ok(self.bind(QueryParam::Int(id)))
+                }
+                Token::Ident(s) | Token::Str(s) if s.contains('@') => {
+                    let email = self.bind(QueryParam::Text(s.clone()));
+                    Ok(format!(
+                        "(SELECT id FROM users WHERE email = {} COLLATE NOCASE)",
+                        email
+                    ))
+                }
+                other => Err(QueryError::new(
+                    value.position,
+                    format!(
+                        "expected me, a user id or an email, found {}",
+                        describe(other)
+                    ),
+                )),
+            },
+            FieldType::Number => match &value.token {
+                Token::Number(n) => {
+                    let param = match n.parse::<i64>() {
+                        Ok(i) => QueryParam::Int(i),
+                        Err(_) => QueryParam::Float(n.parse().map_err(|_| {
+                            QueryError::new(value.position, format!("'{}' is not a number", n))
+                        })?),
+                    };
+                    Ok(self.bind(param))
+                }
+                other => Err(QueryError::new(
+                    value.position,
+                    format!("expected a number, found {}", describe(other)),
+                )),
+            },
+            FieldType::Date | FieldType::DateTime => {
+                let text = self.text_value(value)?;
+                let relative = match text.to_lowercase().as_str() {
+                    "today" => Some("date('now')"),
+                    "yesterday" => Some("date('now', '-1 day')"),
+                    "tomorrow" => Some("date('now', '+1 day')"),
+                    _ => None,
+                };
+                if let Some(sql) = relative {
+                    return Ok(sql.to_string());
+                }
+                let date = NaiveDate::parse_from_str(&text, "%Y-%m-%d").map_err(|_| {
+                    QueryError::new(
+                        value.position,
+                        format!("expected a date like 2024-01-31 or today, found '{}'", text),
+                    )
+                })?;
+                Ok(self.bind(QueryParam::Text(date.to_string())))
+            }
+        }
+    }
+
+    fn text_value(&self, value: &Value) -> Result<String, QueryError> {
+        match &value.token {
+            Token::Str(s) | Token::Ident(s) | Token::Number(s) => Ok(s.clone()),
+            other => Err(QueryError::new(
+                value.position,
+                format!("expected value, found {}", describe(other)),
+            )),
+        }
+    }
+}
+
+/// Даты сравниваются через date(): так совпадают форматы CURRENT_TIMESTAMP и RFC 3339
+fn column_expr(field: &Field) -> String {
+    match field.ty {
+        FieldType::Date | FieldType::DateTime => format!("date({})", field.column),
+        _ => field.column.to_string(),
+    }
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Разбирает и компилирует запрос. `me` подставляется вместо `me` в полях пользователей,
+/// плейсхолдеры нумеруются с `first_param`, чтобы не пересечься с параметрами основного запроса
+pub fn compile(input: &str, me: i64, first_param: usize) -> Result<CompiledQuery, QueryError> {
+    if input.chars().count() > MAX_QUERY_LENGTH {
+        return Err(QueryError::new(
+            MAX_QUERY_LENGTH + 1,
+            format!("query is longer than {} characters", MAX_QUERY_LENGTH),
+        ));
+    }
+
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        depth: 0,
+    };
+    let (filter, order) = parser.parse_query()?;
+
+    let mut compiler = Compiler {
+        me,
+        first_param,
+        params: Vec::new(),
+    };
+    let condition = filter.map(|expr| compiler.compile(&expr)).transpose()?;
+
+    let order_by = order
+        .into_iter()
+        .map(|(field, desc)| {
+            let column = match field.ty {
+                FieldType::Text => format!("{} COLLATE NOCASE", field.column),
+                _ => field.column.to_string(),
+            };
+            format!("{} {}", column, if desc { "DESC" } else { "ASC" })
+        })
+        .collect();
+
+    Ok(CompiledQuery {
+        condition,
+        params: compiler.params,
+        order_by,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ME: i64 = 7;
+
+    fn condition(input: &str) -> String {
+        let query = compile(input, ME, 1).expect("valid query");
+        query.condition.expect("condition")
+    }
+
+    fn params(input: &str) -> Vec<String> {
+        let query = compile(input, ME, 1).expect("valid query");
+        query.params.iter().map(|p| format!("{:?}", p)).collect()
+    }
+
+    fn error(input: &str) -> (usize, String) {
+        let e = compile(input, ME, 1).expect_err("invalid query");
+        (e.position, e.message)
+    }
+
+    #[test]
+    fn tokenizer_errors_point_at_character() {
+        assert_eq!(error("title = 'abc"), (9, "unterminated string".to_string()));
+        assert_eq!(error("status # todo"), (8, "unexpected character '#'".to_string()));
+        // Позиция - номер символа, а не байта
+        assert_eq!(error("title = 'ж' $").0, 13);
+        assert_eq!(error("title ="), (8, "expected value, found end of query".to_string()));
+
+        let e = AppError::from(compile("title = 'abc", ME, 1).unwrap_err());
+        assert_eq!(e.to_string(), "Bad request: Invalid query at position 9: unterminated string");
+    }
+
+    #[test]
+    fn strings_numbers_and_dates_tokenize() {
+        assert_eq!(params("title = 'it''s'"), [r#"Text("it's")"#]);
+        assert_eq!(params(r#"title = "a b""#), [r#"Text("a b")"#]);
+        assert_eq!(params("actual_hours >= -1.5"), ["Float(-1.5)"]);
+        assert_eq!(condition("id <> 3"), "t.id IS NOT ?1");
+        // Дата без кавычек не разваливается на число и минусы
+        assert_eq!(condition("due_date >= 2024-01-31"), "date(t.due_date) >= ?1");
+        assert_eq!(params("due_date >= 2024-01-31"), [r#"Text("2024-01-31")"#]);
+        assert_eq!(condition("due_date < today"), "date(t.due_date) < date('now')");
+        assert_eq!(error("due_date = 2024-02-30").0, 12);
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            condition("status = todo OR status = done AND assignee = me"),
+            "(t.status = ?1 OR (t.status = ?2 AND t.assignee_id = ?3))"
+        );
+        assert_eq!(
+            condition("(status = todo or status = done) and not assignee = me"),
+            "((t.status = ?1 OR t.status = ?2) AND (NOT t.assignee_id = ?3))"
+        );
+        assert_eq!(condition("NOT NOT id = 1"), "(NOT (NOT t.id = ?1))");
+        assert_eq!(error("(id = 1"), (8, "expected ')', found end of query".to_string()));
+        assert_eq!(
+            error("id = 1 status = done"),
+            (8, "expected AND, OR or ORDER BY, found 'status'".to_string())
+        );
+    }
+
+    #[test]
+    fn in_lists_and_null_checks() {
+        assert_eq!(condition("status IN (todo, done)"), "t.status IN (?1, ?2)");
+        assert_eq!(
+            condition("assignee NOT IN (me, 3)"),
+            "(t.assignee_id IS NULL OR t.assignee_id NOT IN (?1, ?2))"
+        );
+        assert_eq!(params("assignee NOT IN (me, 3)"), ["Int(7)", "Int(3)"]);
+        assert_eq!(condition("assignee IS NULL"), "t.assignee_id IS NULL");
+        assert_eq!(condition("due_date is not empty"), "t.due_date IS NOT NULL");
+        assert_eq!(error("title IS NULL"), (1, "field 'title' is never empty".to_string()));
+        assert_eq!(error("status IN todo").0, 11);
+        assert_eq!(error("status NOT = todo").0, 12);
+        assert_eq!(error("status = blocked").0, 10);
+    }
+
+    #[test]
+    fn user_values_resolve_me_ids_and_emails() {
+        assert_eq!(condition("assignee = ME"), "t.assignee_id = ?1");
+        assert_eq!(params("assignee = ME"), ["Int(7)"]);
+        assert_eq!(
+            condition("created_by = Ann@Example.test"),
+            "t.created_by = (SELECT id FROM users WHERE email = ?1 COLLATE NOCASE)"
+        );
+        assert_eq!(params("creator = 'ann@example.test'"), [r#"Text("ann@example.test")"#]);
+        assert_eq!(
+            condition("watcher != me"),
+            "NOT EXISTS (SELECT 1 FROM task_watchers w WHERE w.task_id = t.id AND w.user_id = ?1)"
+        );
+        assert_eq!(
+            error("assignee = bob"),
+            (12, "expected me, a user id or an email, found 'bob'".to_string())
+        );
+        assert_eq!(error("assignee > 3").0, 10);
+    }
+
+    #[test]
+    fn like_patterns_escape_wildcards() {
+        assert_eq!(condition("title ~ '50%_off'"), "t.title LIKE ?1 ESCAPE '\\'");
+        assert_eq!(params(r"title ~ '50%_a\b'"), [r#"Text("%50\\%\\_a\\\\b%")"#]);
+        assert_eq!(
+            condition("description !~ x"),
+            "(t.description IS NULL OR t.description NOT LIKE ?1 ESCAPE '\\')"
+        );
+        assert_eq!(error("status ~ todo").0, 8);
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth: usize| format!("{}id = 1{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(compile(&nested(MAX_DEPTH), ME, 1).is_ok());
+        assert_eq!(error(&nested(MAX_DEPTH + 1)).1, "query is nested too deeply");
+        assert_eq!(error(&"NOT ".repeat(MAX_DEPTH + 1)).1, "query is nested too deeply");
+        assert_eq!(error(&"x".repeat(MAX_QUERY_LENGTH + 1)).0, MAX_QUERY_LENGTH + 1);
+    }
+
+    #[test]
+    fn placeholders_start_at_first_param() {
+        let query = compile("id = 1 AND title ~ a OR status IN (todo, done)", ME, 4).unwrap();
+        assert_eq!(
+            query.condition.as_deref(),
+            Some("((t.id = ?4 AND t.title LIKE ?5 ESCAPE '\\') OR t.status IN (?6, ?7))")
+        );
+        assert_eq!(query.params.len(), 4);
+        // Относительные даты параметров не занимают
+        let query = compile("due_date = today AND id = 1", ME, 3).unwrap();
+        assert_eq!(
+            query.condition.as_deref(),
+            Some("(date(t.due_date) = date('now') AND t.id = ?3)")
+        );
+    }
+
+    #[test]
+    fn order_by_without_filter() {
+        let query = compile("ORDER BY Title DESC, due_date", ME, 1).unwrap();
+        assert!(query.condition.is_none());
+        assert_eq!(query.order_by, ["t.title COLLATE NOCASE DESC", "t.due_date ASC"]);
+        assert_eq!(error("order by watcher"), (10, "cannot sort by watcher".to_string()));
+        assert!(compile("", ME, 1).unwrap().condition.is_none());
+    }
+}
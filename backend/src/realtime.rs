@@ -0,0 +1,197 @@
+use crate::errors::AppError;
+use crate::models::{Task, TaskEvent};
+use crate::repository;
+use actix_web::web::Bytes;
+use futures_util::stream::Stream;
+use sqlx::SqlitePool;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+
+pub const EVENT_CREATED: &str = "task.created";
+pub const EVENT_UPDATED: &str = "task.updated";
+pub const EVENT_DELETED: &str = "task.deleted";
+
+const CHANNEL_CAPACITY: usize = 256;
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const BACKLOG_BATCH: i64 = 500;
+/// Сколько дней хранить журнал событий для догоняющих клиентов
+const EVENT_RETENTION_DAYS: i64 = 7;
+
+/// Рассылка событий о задачах подключённым клиентам.
+/// Каждое событие сначала пишется в `task_events`, поэтому его id годится как Last-Event-ID
+#[derive(Clone)]
+pub struct Broadcaster {
+    sender: broadcast::Sender<TaskEvent>,
+}
+
+impl Default for Broadcaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Broadcaster {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    /// Ошибки только логируются: недоставленное событие не должно ломать изменение задачи
+    pub async fn publish(&self, pool: &SqlitePool, event: &str, task_id: i64, task: Option<&Task>) {
+        match repository::insert_task_event(pool, event, task_id, task).await {
+            // Ошибка отправки значит лишь, что сейчас никто не подписан
+            Ok(event) => {
+                let _ = self.sender.send(event);
+            }
+            Err(e) => tracing::error!("Failed to record task event: {}", e),
+        }
+    }
+
+    /// Поток Server-Sent Events. Если передан `last_event_id`, сначала отдаются пропущенные события
+    pub fn stream(
+        &self,
+        pool: SqlitePool,
+        last_event_id: Option<i64>,
+    ) -> impl Stream<Item = Result<Bytes, AppError>> + use<> {
+        let (tx, rx) = mpsc::channel::<Result<Bytes, AppError>>(64);
+        let mut live = self.sender.subscribe();
+
+        actix_web::rt::spawn(async move {
+            let mut last_id = match last_event_id {
+                Some(id) => id,
+                None => match repository::get_latest_task_event_id(&pool).await {
+                    Ok(id) => id,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                },
+            };
+
+            if tx.send(Ok(Bytes::from_static(b"retry: 3000\n\n"))).await.is_err()
+                || !send_backlog(&pool, &tx, &mut last_id).await
+            {
+                return;
+            }
+
+            // Первый тик interval срабатывает сразу, а комментарий нужен только после паузы
+            let first_tick = actix_web::rt::time::Instant::now() + KEEP_ALIVE_INTERVAL;
+            let mut keep_alive = actix_web::rt::time::interval_at(first_tick, KEEP_ALIVE_INTERVAL);
+            loop {
+                tokio::select! {
+                    received = live.recv() => match received {
+                        Ok(event) if event.id > last_id => {
+                            last_id = event.id;
+                            if tx.send(Ok(encode(&event))).await.is_err() {
+                                return;
+                            }
+                        }
+                        Ok(_) => {}
+                        // Клиент не успевал читать - догоняем по журналу
+                        Err(broadcast::error::RecvError::Lagged(_)) => {
+                            if !send_backlog(&pool, &tx, &mut last_id).await {
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    },
+                    _ = keep_alive.tick() => {
+                        if tx.send(Ok(Bytes::from_static(b": keep-alive\n\n"))).await.is_err() {
+                            return;
+                        }
+                    }
+                    _ = tx.closed() => return,
+                }
+            }
+        });
+
+        futures_util::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|chunk| (chunk, rx))
+        })
+    }
+}
+
+/// Возвращает false, если клиент отключился или журнал не прочитался
+async fn send_backlog(
+    pool: &SqlitePool,
+    tx: &mpsc::Sender<Result<Bytes, AppError>>,
+    last_id: &mut i64,
+) -> bool {
+    loop {
+        let events = match repository::get_task_events_after(pool, *last_id, BACKLOG_BATCH).await {
+            Ok(events) => events,
+            Err(e) => {
+                let _ = tx.send(Err(e)).await;
+                return false;
+            }
+        };
+        let done = (events.len() as i64) < BACKLOG_BATCH;
+
+        for event in events {
+            *last_id = event.id;
+            if tx.send(Ok(encode(&event))).await.is_err() {
+                return false;
+            }
+        }
+        if done {
+            return true;
+        }
+    }
+}
+
+fn encode(event: &TaskEvent) -> Bytes {
+    let data = serde_json::to_string(event).unwrap_or_else(|_| "null".to_string());
+    Bytes::from(format!("id: {}\nevent: {}\ndata: {}\n\n", event.id, event.event, data))
+}
+
+pub async fn prune_events(pool: &SqlitePool) {
+    match repository::delete_task_events_older_than(pool, EVENT_RETENTION_DAYS).await {
+        Ok(0) => {}
+        Ok(n) => tracing::info!("Pruned {} old task events", n),
+        Err(e) => tracing::warn!("Failed to prune task events: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn text(chunk: Option<Result<Bytes, AppError>>) -> String {
+        let bytes = chunk.expect("stream ended").expect("stream error");
+        String::from_utf8(bytes.to_vec()).expect("utf-8")
+    }
+
+    #[actix_web::test]
+    async fn reconnect_replays_events_after_last_seen_then_goes_live() {
+        let pool = repository::test_pool().await;
+        let broadcaster = Broadcaster::new();
+        for task_id in 1..=3 {
+            broadcaster.publish(&pool, EVENT_DELETED, task_id, None).await;
+        }
+
+        let mut stream = Box::pin(broadcaster.stream(pool.clone(), Some(1)));
+        assert_eq!(text(stream.next().await), "retry: 3000\n\n");
+        for id in 2..=3 {
+            let chunk = text(stream.next().await);
+            assert!(chunk.starts_with(&format!("id: {}\nevent: task.deleted\n", id)), "{}", chunk);
+        }
+
+        broadcaster.publish(&pool, EVENT_DELETED, 4, None).await;
+        let chunk = text(stream.next().await);
+        assert!(chunk.starts_with("id: 4\n"), "{}", chunk);
+        assert!(chunk.contains(r#""task_id":4"#), "{}", chunk);
+    }
+
+    #[actix_web::test]
+    async fn new_client_skips_history() {
+        let pool = repository::test_pool().await;
+        let broadcaster = Broadcaster::new();
+        broadcaster.publish(&pool, EVENT_DELETED, 1, None).await;
+
+        let mut stream = Box::pin(broadcaster.stream(pool.clone(), None));
+        assert_eq!(text(stream.next().await), "retry: 3000\n\n");
+        broadcaster.publish(&pool, EVENT_DELETED, 2, None).await;
+        assert!(text(stream.next().await).starts_with("id: 2\n"));
+    }
+}
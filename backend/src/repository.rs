@@ -1,11 +1,25 @@
 use crate::errors::AppError;
-use crate::models::{CreateTaskRequest, Task, UpdateTaskRequest, User};
-use sqlx::SqlitePool;
+use crate::models::{
+    AuditEvent, ClientInfo, Comment, CreateTaskRequest, CreateViewRequest, EmailPreferences,
+    ExportedTask, ImportedTask, JobRecord, LoginChallenge, LoginFailures, Notification,
+    OidcLoginState, OutgoingEmail, PasswordResetToken, PersonalAccessToken, RefreshToken, Role,
+    SavedView, Session, Task, TaskEvent, TaskFilter, TaskQuery, UpdateTaskRequest, User, UserTotp,
+    WebhookDelivery, WebhookSubscription,
+};
+use crate::query_language::{CompiledQuery, QueryParam};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::stream::{BoxStream, StreamExt};
+use sqlx::migrate::Migrator;
+use sqlx::types::Json;
+use sqlx::{Executor, Sqlite, SqliteConnection, SqlitePool};
+
+/// Миграции, встроенные в бинарник при сборке
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
 
 // ============ Users ============
 
-pub async fn create_user(
-    pool: &SqlitePool,
+pub async fn create_user<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
     email: &str,
     password_hash: &str,
     name: &str,
@@ -22,7 +36,7 @@ pub async fn create_user(
     .bind(password_hash)
     .bind(name)
     .bind(role)
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await
     .map_err(|e| {
         if e.to_string().contains("UNIQUE") {
@@ -41,15 +55,29 @@ pub async fn get_user_by_id(pool: &SqlitePool, id: i64) -> Result<User, AppError
         .ok_or_else(|| AppError::NotFound("User not found".to_string()))
 }
 
-pub async fn get_user_by_email(pool: &SqlitePool, email: &str) -> Result<Option<User>, AppError> {
+pub async fn get_user_by_email<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    email: &str,
+) -> Result<Option<User>, AppError> {
     Ok(
         sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = ?")
             .bind(email)
-            .fetch_optional(pool)
+            .fetch_optional(executor)
             .await?,
     )
 }
 
+pub async fn user_exists<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    id: i64,
+) -> Result<bool, AppError> {
+    let row: (bool,) = sqlx::query_as("SELECT EXISTS(SELECT 1 FROM users WHERE id = ?)")
+        .bind(id)
+        .fetch_one(executor)
+        .await?;
+    Ok(row.0)
+}
+
 pub async fn get_all_users(pool: &SqlitePool) -> Result<Vec<User>, AppError> {
     Ok(
         sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY created_at")
@@ -77,126 +105,2263 @@ pub async fn delete_user(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
     Ok(())
 }
 
-// ============ Tasks ============
+// ============ Password reset ============
 
-pub async fn create_task(
+/// Новая ссылка отменяет все прежние неиспользованные
+pub async fn create_password_reset_token(
     pool: &SqlitePool,
-    req: &CreateTaskRequest,
-    created_by: i64,
-    predicted_hours: Option<f64>,
-) -> Result<Task, AppError> {
-    Ok(sqlx::query_as::<_, Task>(
-        r#"
-        INSERT INTO tasks (title, description, assignee_id, created_by, predicted_hours)
-        VALUES (?, ?, ?, ?, ?)
-        RETURNING *
-        "#,
+    user_id: i64,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP WHERE user_id = ? AND used_at IS NULL",
     )
-    .bind(&req.title)
-    .bind(&req.description)
-    .bind(req.assignee_id)
-    .bind(created_by)
-    .bind(predicted_hours)
-    .fetch_one(pool)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES (?, ?, ?)",
+    )
+    .bind(user_id)
+    .bind(token_hash)
+    .bind(expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn find_password_reset_token(
+    pool: &SqlitePool,
+    token_hash: &str,
+) -> Result<Option<PasswordResetToken>, AppError> {
+    Ok(sqlx::query_as::<_, PasswordResetToken>(
+        "SELECT id, user_id, expires_at, used_at FROM password_reset_tokens WHERE token_hash = ?",
+    )
+    .bind(token_hash)
+    .fetch_optional(pool)
     .await?)
 }
 
-pub async fn get_task_by_id(pool: &SqlitePool, id: i64) -> Result<Task, AppError> {
-    sqlx::query_as::<_, Task>("SELECT * FROM tasks WHERE id = ?")
-        .bind(id)
-        .fetch_optional(pool)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Task not found".to_string()))
+/// Гасит токен и меняет пароль одной транзакцией. `false`, если токен уже
+/// использован: из двух одновременных запросов сработает только один
+pub async fn reset_password_with_token(
+    pool: &SqlitePool,
+    token: &PasswordResetToken,
+    new_password_hash: &str,
+) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let used = sqlx::query(
+        "UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP WHERE id = ? AND used_at IS NULL",
+    )
+    .bind(token.id)
+    .execute(&mut *tx)
+    .await?;
+    if used.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query("UPDATE users SET password_hash = ?, password_reset_required = 0 WHERE id = ?")
+        .bind(new_password_hash)
+        .bind(token.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(true)
 }
 
-pub async fn get_all_tasks(pool: &SqlitePool) -> Result<Vec<Task>, AppError> {
-    Ok(
-        sqlx::query_as::<_, Task>("SELECT * FROM tasks ORDER BY created_at DESC")
-            .fetch_all(pool)
-            .await?,
+pub async fn set_password_reset_required(pool: &SqlitePool, user_id: i64) -> Result<(), AppError> {
+    sqlx::query("UPDATE users SET password_reset_required = 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ============ Two-factor ============
+
+pub async fn get_user_totp(pool: &SqlitePool, user_id: i64) -> Result<Option<UserTotp>, AppError> {
+    Ok(sqlx::query_as::<_, UserTotp>(
+        "SELECT secret, enabled, last_used_step FROM user_totp WHERE user_id = ?",
     )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?)
 }
 
-pub async fn get_tasks_by_status(pool: &SqlitePool, status: &str) -> Result<Vec<Task>, AppError> {
-    Ok(
-        sqlx::query_as::<_, Task>("SELECT * FROM tasks WHERE status = ? ORDER BY created_at DESC")
-            .bind(status)
-            .fetch_all(pool)
-            .await?,
+/// Новый неподтверждённый секрет заменяет прежний неподтверждённый
+pub async fn save_totp_secret(
+    pool: &SqlitePool,
+    user_id: i64,
+    secret: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_totp (user_id, secret) VALUES (?1, ?2)
+        ON CONFLICT(user_id) DO UPDATE
+        SET secret = ?2, enabled = 0, last_used_step = NULL,
+            created_at = CURRENT_TIMESTAMP, enabled_at = NULL
+        "#,
     )
+    .bind(user_id)
+    .bind(secret)
+    .execute(pool)
+    .await?;
+    Ok(())
 }
 
-pub async fn get_tasks_by_assignee(pool: &SqlitePool, user_id: i64) -> Result<Vec<Task>, AppError> {
-    Ok(sqlx::query_as::<_, Task>(
-        "SELECT * FROM tasks WHERE assignee_id = ? ORDER BY created_at DESC",
+async fn insert_recovery_codes(
+    conn: &mut SqliteConnection,
+    user_id: i64,
+    code_hashes: &[String],
+) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for code_hash in code_hashes {
+        sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) VALUES (?, ?)")
+            .bind(user_id)
+            .bind(code_hash)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Включает 2FA вместе с первым набором резервных кодов. `false`, если
+/// включать нечего: секрета нет или он уже подтверждён
+pub async fn enable_totp(
+    pool: &SqlitePool,
+    user_id: i64,
+    step: i64,
+    code_hashes: &[String],
+) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let enabled = sqlx::query(
+        r#"
+        UPDATE user_totp
+        SET enabled = 1, enabled_at = CURRENT_TIMESTAMP, last_used_step = ?
+        WHERE user_id = ? AND enabled = 0
+        "#,
     )
+    .bind(step)
     .bind(user_id)
-    .fetch_all(pool)
-    .await?)
+    .execute(&mut *tx)
+    .await?;
+    if enabled.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    insert_recovery_codes(&mut tx, user_id, code_hashes).await?;
+    tx.commit().await?;
+    Ok(true)
 }
 
-pub async fn update_task(
+/// Прежние резервные коды, использованные или нет, перестают действовать
+pub async fn replace_recovery_codes(
     pool: &SqlitePool,
-    id: i64,
-    req: &UpdateTaskRequest,
-) -> Result<Task, AppError> {
-    let current = get_task_by_id(pool, id).await?;
+    user_id: i64,
+    code_hashes: &[String],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    insert_recovery_codes(&mut tx, user_id, code_hashes).await?;
+    tx.commit().await?;
+    Ok(())
+}
 
-    let new_description = match &req.description {
-        Some(desc) => desc.clone(), // явно передано (может быть Some или None)
-        None => current.description,                 // не передано - оставляем старое
-    };
+pub async fn delete_totp(pool: &SqlitePool, user_id: i64) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
 
-    let new_assignee = match &req.assignee_id {
-        Some(id) => *id, // явно передано
-        None => current.assignee_id,   // не передано
-    };
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM user_totp WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
 
-    sqlx::query_as::<_, Task>(
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Запоминает шаг принятого кода. `false`, если этот или более поздний шаг
+/// уже использован: код, перехваченный по пути, второй раз не сработает
+pub async fn use_totp_step(pool: &SqlitePool, user_id: i64, step: i64) -> Result<bool, AppError> {
+    let result = sqlx::query(
         r#"
-        UPDATE tasks
-        SET title = ?, description = ?, status = ?,
-            assignee_id = ?, actual_hours = ?, updated_at = CURRENT_TIMESTAMP
-        WHERE id = ?
-        RETURNING *
-        "#
+        UPDATE user_totp SET last_used_step = ?2
+        WHERE user_id = ?1 AND enabled = 1 AND (last_used_step IS NULL OR last_used_step < ?2)
+        "#,
     )
-    .bind(req.title.as_ref().unwrap_or(&current.title))
-    .bind(&new_description)
-    .bind(req.status.as_ref().unwrap_or(&current.status))
-    .bind(new_assignee)
-    .bind(req.actual_hours.or(current.actual_hours))
-    .bind(id)
+    .bind(user_id)
+    .bind(step)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Гасит резервный код. `false`, если такого неиспользованного кода нет
+pub async fn use_recovery_code(
+    pool: &SqlitePool,
+    user_id: i64,
+    code_hash: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE recovery_codes SET used_at = CURRENT_TIMESTAMP
+        WHERE id = (
+            SELECT id FROM recovery_codes
+            WHERE user_id = ? AND code_hash = ? AND used_at IS NULL
+            LIMIT 1
+        )
+        "#,
+    )
+    .bind(user_id)
+    .bind(code_hash)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn count_recovery_codes(pool: &SqlitePool, user_id: i64) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM recovery_codes WHERE user_id = ? AND used_at IS NULL",
+    )
+    .bind(user_id)
     .fetch_one(pool)
-    .await
-    .map_err(|e| e.into())
+    .await?;
+    Ok(row.0)
 }
 
-pub async fn delete_task(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
-    let result = sqlx::query("DELETE FROM tasks WHERE id = ?")
+/// Истёкшие вызовы пользователя удаляются заодно с созданием нового
+pub async fn create_login_challenge(
+    pool: &SqlitePool,
+    user_id: i64,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM login_challenges WHERE user_id = ? AND expires_at < ?")
+        .bind(user_id)
+        .bind(Utc::now())
+        .execute(pool)
+        .await?;
+
+    sqlx::query("INSERT INTO login_challenges (user_id, token_hash, expires_at) VALUES (?, ?, ?)")
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn find_login_challenge(
+    pool: &SqlitePool,
+    token_hash: &str,
+) -> Result<Option<LoginChallenge>, AppError> {
+    Ok(sqlx::query_as::<_, LoginChallenge>(
+        "SELECT id, user_id, expires_at, attempts FROM login_challenges WHERE token_hash = ?",
+    )
+    .bind(token_hash)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn record_challenge_attempt(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    sqlx::query("UPDATE login_challenges SET attempts = attempts + 1 WHERE id = ?")
         .bind(id)
         .execute(pool)
         .await?;
+    Ok(())
+}
 
-    if result.rows_affected() == 0 {
-        return Err(AppError::NotFound("Task not found".to_string()));
-    }
+/// `false`, если вызов уже использован: из двух одновременных запросов
+/// вход получит только один
+pub async fn delete_login_challenge(pool: &SqlitePool, id: i64) -> Result<bool, AppError> {
+    let result = sqlx::query("DELETE FROM login_challenges WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// ============ Single sign-on ============
+
+pub async fn create_oidc_login_state(
+    pool: &SqlitePool,
+    state_hash: &str,
+    nonce: &str,
+    code_verifier: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    // Брошенные входы копятся, пока их не вычистит следующий
+    sqlx::query("DELETE FROM oidc_login_states WHERE expires_at < ?")
+        .bind(Utc::now())
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO oidc_login_states (state_hash, nonce, code_verifier, expires_at)
+        VALUES (?, ?, ?, ?)
+        "#,
+    )
+    .bind(state_hash)
+    .bind(nonce)
+    .bind(code_verifier)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
     Ok(())
 }
 
-pub async fn update_password(
+/// Состояние удаляется при чтении: с одним state завершить вход можно один раз
+pub async fn take_oidc_login_state(
+    pool: &SqlitePool,
+    state_hash: &str,
+) -> Result<Option<OidcLoginState>, AppError> {
+    Ok(sqlx::query_as::<_, OidcLoginState>(
+        r#"
+        DELETE FROM oidc_login_states WHERE state_hash = ?
+        RETURNING nonce, code_verifier, expires_at
+        "#,
+    )
+    .bind(state_hash)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn get_user_by_identity(
     pool: &SqlitePool,
+    issuer: &str,
+    subject: &str,
+) -> Result<Option<User>, AppError> {
+    Ok(sqlx::query_as::<_, User>(
+        r#"
+        SELECT u.* FROM users u
+        JOIN user_identities i ON i.user_id = u.id
+        WHERE i.issuer = ? AND i.subject = ?
+        "#,
+    )
+    .bind(issuer)
+    .bind(subject)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn link_identity<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
     user_id: i64,
-    new_password_hash: &str,
+    issuer: &str,
+    subject: &str,
 ) -> Result<(), AppError> {
-    let result = sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
-        .bind(new_password_hash)
+    sqlx::query(
+        r#"
+        INSERT INTO user_identities (user_id, issuer, subject, last_login_at)
+        VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+        "#,
+    )
+    .bind(user_id)
+    .bind(issuer)
+    .bind(subject)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub async fn touch_identity(
+    pool: &SqlitePool,
+    issuer: &str,
+    subject: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE user_identities SET last_login_at = CURRENT_TIMESTAMP
+        WHERE issuer = ? AND subject = ?
+        "#,
+    )
+    .bind(issuer)
+    .bind(subject)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Пользователь, впервые вошедший через провайдер, вместе со связью с ним
+pub async fn create_identity_user(
+    pool: &SqlitePool,
+    email: &str,
+    password_hash: &str,
+    name: &str,
+    role: &str,
+    issuer: &str,
+    subject: &str,
+) -> Result<User, AppError> {
+    let mut tx = pool.begin().await?;
+    let user = create_user(&mut *tx, email, password_hash, name, role).await?;
+    link_identity(&mut *tx, user.id, issuer, subject).await?;
+    tx.commit().await?;
+    Ok(user)
+}
+
+pub async fn set_user_role(pool: &SqlitePool, user_id: i64, role: &str) -> Result<(), AppError> {
+    sqlx::query("UPDATE users SET role = ? WHERE id = ?")
+        .bind(role)
         .bind(user_id)
         .execute(pool)
         .await?;
-
-    if result.rows_affected() == 0 {
-        return Err(AppError::NotFound("User not found".to_string()));
-    }
     Ok(())
-}
\ No newline at end of file
+}
+
+// ============ Sessions ============
+
+pub async fn create_session<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    user_id: i64,
+    client: &ClientInfo,
+) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO sessions (user_id, user_agent, ip, last_used_at)
+        VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(&client.user_agent)
+    .bind(&client.ip)
+    .fetch_one(executor)
+    .await?;
+    Ok(row.0)
+}
+
+/// Неотозванные сессии, которыми пользовались не дольше `idle_days` назад:
+/// более старые уже нельзя продлить
+pub async fn get_user_sessions(
+    pool: &SqlitePool,
+    user_id: i64,
+    idle_days: i64,
+) -> Result<Vec<Session>, AppError> {
+    Ok(sqlx::query_as::<_, Session>(
+        r#"
+        SELECT id, user_id, user_agent, ip, created_at, last_used_at
+        FROM sessions
+        WHERE user_id = ?1
+          AND revoked_at IS NULL
+          AND last_used_at > datetime('now', '-' || ?2 || ' days')
+        ORDER BY last_used_at DESC, id DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(idle_days)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Только действующая сессия: отозванной для приложения больше нет
+pub async fn get_session(pool: &SqlitePool, id: i64) -> Result<Option<Session>, AppError> {
+    Ok(sqlx::query_as::<_, Session>(
+        r#"
+        SELECT id, user_id, user_agent, ip, created_at, last_used_at
+        FROM sessions
+        WHERE id = ? AND revoked_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn touch_session(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    sqlx::query("UPDATE sessions SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn revoke_session(pool: &SqlitePool, session_id: i64) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE id = ? AND revoked_at IS NULL",
+    )
+    .bind(session_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn revoke_user_sessions(pool: &SqlitePool, user_id: i64) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = ? AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn insert_refresh_token<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    session_id: i64,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    sqlx::query("INSERT INTO refresh_tokens (session_id, token_hash, expires_at) VALUES (?, ?, ?)")
+        .bind(session_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+pub async fn find_refresh_token(
+    pool: &SqlitePool,
+    token_hash: &str,
+) -> Result<Option<RefreshToken>, AppError> {
+    Ok(sqlx::query_as::<_, RefreshToken>(
+        r#"
+        SELECT r.id, r.session_id, s.user_id, r.expires_at, r.used_at, s.revoked_at
+        FROM refresh_tokens r
+        JOIN sessions s ON s.id = r.session_id
+        WHERE r.token_hash = ?
+        "#,
+    )
+    .bind(token_hash)
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Помечает токен использованным и выдаёт следующий в той же сессии. `false`, если
+/// токен уже обменяли: из двух одновременных запросов выигрывает только один
+pub async fn rotate_refresh_token(
+    pool: &SqlitePool,
+    token: &RefreshToken,
+    new_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let used = sqlx::query(
+        "UPDATE refresh_tokens SET used_at = CURRENT_TIMESTAMP WHERE id = ? AND used_at IS NULL",
+    )
+    .bind(token.id)
+    .execute(&mut *tx)
+    .await?;
+    if used.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    insert_refresh_token(&mut *tx, token.session_id, new_hash, expires_at).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+// ============ Personal access tokens ============
+
+pub async fn create_access_token(
+    pool: &SqlitePool,
+    user_id: i64,
+    name: &str,
+    token_hash: &str,
+    scope: &str,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<PersonalAccessToken, AppError> {
+    Ok(sqlx::query_as::<_, PersonalAccessToken>(
+        r#"
+        INSERT INTO personal_access_tokens (user_id, name, token_hash, scope, expires_at)
+        VALUES (?, ?, ?, ?, ?)
+        RETURNING id, user_id, name, scope, expires_at, last_used_at, created_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(token_hash)
+    .bind(scope)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Неотозванные токены пользователя, включая истёкшие: их видно, пока не удалят
+pub async fn get_user_access_tokens(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<Vec<PersonalAccessToken>, AppError> {
+    Ok(sqlx::query_as::<_, PersonalAccessToken>(
+        r#"
+        SELECT id, user_id, name, scope, expires_at, last_used_at, created_at
+        FROM personal_access_tokens
+        WHERE user_id = ? AND revoked_at IS NULL
+        ORDER BY id DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_access_token(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<Option<PersonalAccessToken>, AppError> {
+    Ok(sqlx::query_as::<_, PersonalAccessToken>(
+        r#"
+        SELECT id, user_id, name, scope, expires_at, last_used_at, created_at
+        FROM personal_access_tokens
+        WHERE id = ? AND revoked_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn find_access_token(
+    pool: &SqlitePool,
+    token_hash: &str,
+) -> Result<Option<PersonalAccessToken>, AppError> {
+    Ok(sqlx::query_as::<_, PersonalAccessToken>(
+        r#"
+        SELECT id, user_id, name, scope, expires_at, last_used_at, created_at
+        FROM personal_access_tokens
+        WHERE token_hash = ? AND revoked_at IS NULL
+        "#,
+    )
+    .bind(token_hash)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn touch_access_token(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    sqlx::query("UPDATE personal_access_tokens SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn revoke_access_token(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE personal_access_tokens SET revoked_at = CURRENT_TIMESTAMP
+        WHERE id = ? AND revoked_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// ============ Login protection ============
+
+pub async fn get_login_failures(
+    pool: &SqlitePool,
+    scope: &str,
+    subject: &str,
+) -> Result<Option<LoginFailures>, AppError> {
+    Ok(sqlx::query_as::<_, LoginFailures>(
+        r#"
+        SELECT failures, last_failed_at, locked_until
+        FROM login_failures
+        WHERE scope = ? AND subject = ?
+        "#,
+    )
+    .bind(scope)
+    .bind(subject)
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Засчитывает неудачу. Счёт начинается заново, если прошлая была раньше
+/// `window_minutes` назад или блокировка уже истекла
+pub async fn record_login_failure(
+    pool: &SqlitePool,
+    scope: &str,
+    subject: &str,
+    window_minutes: i64,
+) -> Result<LoginFailures, AppError> {
+    Ok(sqlx::query_as::<_, LoginFailures>(
+        r#"
+        INSERT INTO login_failures (scope, subject, failures)
+        VALUES (?1, ?2, 1)
+        ON CONFLICT (scope, subject) DO UPDATE SET
+            failures = CASE
+                WHEN last_failed_at < datetime('now', '-' || ?3 || ' minutes')
+                  OR locked_until <= datetime('now')
+                THEN 1
+                ELSE failures + 1
+            END,
+            locked_until = CASE WHEN locked_until <= datetime('now') THEN NULL ELSE locked_until END,
+            last_failed_at = CURRENT_TIMESTAMP
+        RETURNING failures, last_failed_at, locked_until
+        "#,
+    )
+    .bind(scope)
+    .bind(subject)
+    .bind(window_minutes)
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn lock_login(
+    pool: &SqlitePool,
+    scope: &str,
+    subject: &str,
+    minutes: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE login_failures
+        SET locked_until = datetime('now', '+' || ?3 || ' minutes')
+        WHERE scope = ?1 AND subject = ?2
+        "#,
+    )
+    .bind(scope)
+    .bind(subject)
+    .bind(minutes)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn clear_login_failures(
+    pool: &SqlitePool,
+    scope: &str,
+    subject: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query("DELETE FROM login_failures WHERE scope = ? AND subject = ?")
+        .bind(scope)
+        .bind(subject)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// ============ Audit log ============
+
+pub async fn insert_audit_event(
+    pool: &SqlitePool,
+    event: &str,
+    user_id: Option<i64>,
+    email: Option<&str>,
+    ip: Option<&str>,
+    details: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (event, user_id, email, ip, details)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(event)
+    .bind(user_id)
+    .bind(email)
+    .bind(ip)
+    .bind(details)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_audit_log(pool: &SqlitePool, limit: i64) -> Result<Vec<AuditEvent>, AppError> {
+    Ok(
+        sqlx::query_as::<_, AuditEvent>("SELECT * FROM audit_log ORDER BY id DESC LIMIT ?")
+            .bind(limit)
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+// ============ Roles ============
+
+pub async fn get_all_roles(pool: &SqlitePool) -> Result<Vec<Role>, AppError> {
+    Ok(
+        sqlx::query_as::<_, Role>("SELECT * FROM roles ORDER BY builtin DESC, name")
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+pub async fn get_role(pool: &SqlitePool, name: &str) -> Result<Option<Role>, AppError> {
+    Ok(sqlx::query_as::<_, Role>("SELECT * FROM roles WHERE name = ?")
+        .bind(name)
+        .fetch_optional(pool)
+        .await?)
+}
+
+pub async fn create_role(
+    pool: &SqlitePool,
+    name: &str,
+    description: &str,
+    permissions: &[String],
+    require_two_factor: bool,
+) -> Result<Role, AppError> {
+    sqlx::query_as::<_, Role>(
+        r#"
+        INSERT INTO roles (name, description, permissions, require_two_factor)
+        VALUES (?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(name)
+    .bind(description)
+    .bind(Json(permissions))
+    .bind(require_two_factor)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("UNIQUE") {
+            AppError::BadRequest("Role already exists".to_string())
+        } else {
+            e.into()
+        }
+    })
+}
+
+pub async fn update_role(
+    pool: &SqlitePool,
+    name: &str,
+    description: &str,
+    permissions: &[String],
+    require_two_factor: bool,
+) -> Result<Role, AppError> {
+    sqlx::query_as::<_, Role>(
+        r#"
+        UPDATE roles
+        SET description = ?, permissions = ?, require_two_factor = ?,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE name = ?
+        RETURNING *
+        "#,
+    )
+    .bind(description)
+    .bind(Json(permissions))
+    .bind(require_two_factor)
+    .bind(name)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Role not found".to_string()))
+}
+
+pub async fn delete_role(pool: &SqlitePool, name: &str) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM roles WHERE name = ?")
+        .bind(name)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Role not found".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn count_users_with_role(pool: &SqlitePool, role: &str) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE role = ?")
+        .bind(role)
+        .fetch_one(pool)
+        .await?;
+    Ok(row.0)
+}
+
+// ============ Tasks ============
+
+pub async fn create_task(
+    pool: &SqlitePool,
+    req: &CreateTaskRequest,
+    created_by: i64,
+    predicted_hours: Option<f64>,
+) -> Result<Task, AppError> {
+    Ok(sqlx::query_as::<_, Task>(
+        r#"
+        INSERT INTO tasks (title, description, assignee_id, created_by, predicted_hours,
+                           due_date, planned_date)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(&req.title)
+    .bind(&req.description)
+    .bind(req.assignee_id)
+    .bind(created_by)
+    .bind(predicted_hours)
+    .bind(req.due_date)
+    .bind(req.planned_date)
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn get_task_by_id(pool: &SqlitePool, id: i64) -> Result<Task, AppError> {
+    sqlx::query_as::<_, Task>("SELECT * FROM tasks WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Task not found".to_string()))
+}
+
+/// Оценка ML, полученная уже после создания задачи
+pub async fn set_predicted_hours(pool: &SqlitePool, id: i64, hours: f64) -> Result<Task, AppError> {
+    sqlx::query_as::<_, Task>(
+        r#"
+        UPDATE tasks SET predicted_hours = ?
+        WHERE id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(hours)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Task not found".to_string()))
+}
+
+pub async fn task_exists<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    id: i64,
+) -> Result<bool, AppError> {
+    let row: (bool,) = sqlx::query_as("SELECT EXISTS(SELECT 1 FROM tasks WHERE id = ?)")
+        .bind(id)
+        .fetch_one(executor)
+        .await?;
+    Ok(row.0)
+}
+
+/// Сколько нумерованных параметров занимает основной запрос query_tasks
+pub const TASK_QUERY_PARAMS: usize = 6;
+
+/// Выборка для списка задач. Все условия необязательные, сортировка - только по
+/// колонкам из белого списка, поэтому её можно подставить в SQL напрямую.
+/// Условие из языка запросов добавляется через AND, его параметры идут после ?6
+pub async fn query_tasks(
+    pool: &SqlitePool,
+    query: &TaskQuery,
+    compiled: Option<&CompiledQuery>,
+) -> Result<Vec<Task>, AppError> {
+    let sort_column = match query.sort_by.as_str() {
+        "updated_at" => "t.updated_at",
+        "due_date" => "t.due_date",
+        "title" => "t.title COLLATE NOCASE",
+        "status" => "t.status",
+        "predicted_hours" => "t.predicted_hours",
+        _ => "t.created_at",
+    };
+    let direction = if query.sort_desc { "DESC" } else { "ASC" };
+    let order_by = match compiled {
+        Some(c) if !c.order_by.is_empty() => format!("{}, t.id", c.order_by.join(", ")),
+        _ => format!("{} {}, t.id {}", sort_column, direction, direction),
+    };
+    let condition = compiled
+        .and_then(|c| c.condition.as_deref())
+        .map(|c| format!("AND {}", c))
+        .unwrap_or_default();
+
+    let sql = format!(
+        r#"
+        SELECT t.* FROM tasks t
+        WHERE (?1 IS NULL OR t.status = ?1)
+          AND (?2 IS NULL OR t.assignee_id = ?2)
+          AND (?3 = 0 OR t.assignee_id IS NULL)
+          AND (?4 IS NULL OR t.created_by = ?4)
+          AND (?5 IS NULL OR EXISTS (
+              SELECT 1 FROM task_watchers w WHERE w.task_id = t.id AND w.user_id = ?5
+          ))
+          AND (?6 IS NULL OR t.title LIKE '%' || ?6 || '%')
+          {}
+        ORDER BY {}
+        "#,
+        condition, order_by
+    );
+
+    let mut q = sqlx::query_as::<_, Task>(&sql)
+        .bind(query.status.clone())
+        .bind(query.assignee_id)
+        .bind(query.unassigned)
+        .bind(query.created_by)
+        .bind(query.watcher_id)
+        .bind(query.search.clone());
+    for param in compiled.map(|c| c.params.as_slice()).unwrap_or_default() {
+        q = match param {
+            QueryParam::Int(v) => q.bind(*v),
+            QueryParam::Float(v) => q.bind(*v),
+            QueryParam::Text(v) => q.bind(v.clone()),
+        };
+    }
+
+    Ok(q.fetch_all(pool).await?)
+}
+
+pub fn stream_tasks<'a>(
+    pool: &'a SqlitePool,
+    filter: &TaskFilter,
+) -> BoxStream<'a, Result<ExportedTask, AppError>> {
+    sqlx::query_as::<_, ExportedTask>(
+        r#"
+        SELECT t.*, u.email AS assignee_email
+        FROM tasks t
+        LEFT JOIN users u ON u.id = t.assignee_id
+        WHERE (?1 IS NULL OR t.status = ?1)
+          AND (?2 IS NULL OR t.assignee_id = ?2)
+          AND (?3 IS NULL OR t.created_by = ?3)
+        ORDER BY t.id
+        "#,
+    )
+    .bind(filter.status.clone())
+    .bind(filter.assignee_id)
+    .bind(filter.created_by)
+    .fetch(pool)
+    .map(|row| row.map_err(AppError::from))
+    .boxed()
+}
+
+/// Вставляет все задачи в одной транзакции: либо импортируются все строки, либо ни одной.
+/// Возвращает id новых задач в порядке строк
+pub async fn insert_imported_tasks(
+    pool: &SqlitePool,
+    tasks: &[ImportedTask],
+    created_by: i64,
+) -> Result<Vec<i64>, AppError> {
+    let mut tx = pool.begin().await?;
+    let mut ids = Vec::with_capacity(tasks.len());
+
+    for task in tasks {
+        let row: (i64,) = sqlx::query_as(
+            r#"
+            INSERT INTO tasks (title, description, status, assignee_id, created_by,
+                               predicted_hours, actual_hours, due_date, planned_date)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id
+            "#,
+        )
+        .bind(&task.title)
+        .bind(&task.description)
+        .bind(&task.status)
+        .bind(task.assignee_id)
+        .bind(created_by)
+        .bind(task.predicted_hours)
+        .bind(task.actual_hours)
+        .bind(task.due_date)
+        .bind(task.planned_date)
+        .fetch_one(&mut *tx)
+        .await?;
+        ids.push(row.0);
+    }
+
+    tx.commit().await?;
+    Ok(ids)
+}
+
+/// Задачи пользователя, у которых есть срок или запланированная дата - для ленты календаря
+pub async fn get_calendar_tasks(pool: &SqlitePool, user_id: i64) -> Result<Vec<Task>, AppError> {
+    Ok(sqlx::query_as::<_, Task>(
+        r#"
+        SELECT * FROM tasks
+        WHERE assignee_id = ? AND (due_date IS NOT NULL OR planned_date IS NOT NULL)
+        ORDER BY COALESCE(due_date, planned_date)
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn update_task(
+    pool: &SqlitePool,
+    id: i64,
+    req: &UpdateTaskRequest,
+) -> Result<Task, AppError> {
+    let current = get_task_by_id(pool, id).await?;
+
+    let new_description = match &req.description {
+        Some(desc) => desc.clone(), // явно передано (может быть Some или None)
+        None => current.description,                 // не передано - оставляем старое
+    };
+
+    let new_assignee = match &req.assignee_id {
+        Some(id) => *id, // явно передано
+        None => current.assignee_id,   // не передано
+    };
+
+    let new_due_date = req.due_date.unwrap_or(current.due_date);
+    let new_planned_date = req.planned_date.unwrap_or(current.planned_date);
+
+    sqlx::query_as::<_, Task>(
+        r#"
+        UPDATE tasks
+        SET title = ?, description = ?, status = ?,
+            assignee_id = ?, actual_hours = ?, due_date = ?, planned_date = ?,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        RETURNING *
+        "#
+    )
+    .bind(req.title.as_ref().unwrap_or(&current.title))
+    .bind(&new_description)
+    .bind(req.status.as_ref().unwrap_or(&current.status))
+    .bind(new_assignee)
+    .bind(req.actual_hours.or(current.actual_hours))
+    .bind(new_due_date)
+    .bind(new_planned_date)
+    .bind(id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn delete_task(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM tasks WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Task not found".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn count_tasks_by_status(pool: &SqlitePool) -> Result<Vec<(String, i64)>, AppError> {
+    Ok(sqlx::query_as("SELECT status, COUNT(*) FROM tasks GROUP BY status ORDER BY status")
+        .fetch_all(pool)
+        .await?)
+}
+
+pub async fn count_overdue_tasks(pool: &SqlitePool) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM tasks WHERE status != 'done' AND due_date < date('now')",
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.0)
+}
+
+pub async fn update_password(
+    pool: &SqlitePool,
+    user_id: i64,
+    new_password_hash: &str,
+) -> Result<(), AppError> {
+    let result = sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+        .bind(new_password_hash)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+    Ok(())
+}
+
+// ============ Calendar ============
+
+pub async fn set_calendar_token(
+    pool: &SqlitePool,
+    user_id: i64,
+    token_hash: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO calendar_tokens (user_id, token_hash)
+        VALUES (?, ?)
+        ON CONFLICT (user_id)
+        DO UPDATE SET token_hash = excluded.token_hash, created_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(user_id)
+    .bind(token_hash)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_calendar_token_created_at(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let row: Option<(DateTime<Utc>,)> =
+        sqlx::query_as("SELECT created_at FROM calendar_tokens WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+    Ok(row.map(|r| r.0))
+}
+
+pub async fn find_user_by_calendar_token(
+    pool: &SqlitePool,
+    token_hash: &str,
+) -> Result<Option<i64>, AppError> {
+    let row: Option<(i64,)> =
+        sqlx::query_as("SELECT user_id FROM calendar_tokens WHERE token_hash = ?")
+            .bind(token_hash)
+            .fetch_optional(pool)
+            .await?;
+    Ok(row.map(|r| r.0))
+}
+
+pub async fn delete_calendar_token(pool: &SqlitePool, user_id: i64) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM calendar_tokens WHERE user_id = ?")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ============ Comments ============
+
+pub async fn insert_comment<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    task_id: i64,
+    author_id: i64,
+    body: &str,
+    created_at: Option<DateTime<Utc>>,
+) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO comments (task_id, author_id, body, created_at)
+        VALUES (?, ?, ?, COALESCE(?, CURRENT_TIMESTAMP))
+        RETURNING id
+        "#,
+    )
+    .bind(task_id)
+    .bind(author_id)
+    .bind(body)
+    .bind(created_at)
+    .fetch_one(executor)
+    .await?;
+    Ok(row.0)
+}
+
+pub async fn create_comment(
+    pool: &SqlitePool,
+    task_id: i64,
+    author_id: i64,
+    body: &str,
+) -> Result<Comment, AppError> {
+    Ok(sqlx::query_as::<_, Comment>(
+        "INSERT INTO comments (task_id, author_id, body) VALUES (?, ?, ?) RETURNING *",
+    )
+    .bind(task_id)
+    .bind(author_id)
+    .bind(body)
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn get_task_comments(pool: &SqlitePool, task_id: i64) -> Result<Vec<Comment>, AppError> {
+    Ok(sqlx::query_as::<_, Comment>(
+        "SELECT * FROM comments WHERE task_id = ? ORDER BY created_at, id",
+    )
+    .bind(task_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+// ============ Saved views ============
+
+/// Свои представления и общие представления остальных
+pub async fn get_views_for_user(pool: &SqlitePool, user_id: i64) -> Result<Vec<SavedView>, AppError> {
+    Ok(sqlx::query_as::<_, SavedView>(
+        "SELECT * FROM saved_views WHERE owner_id = ? OR shared = 1 ORDER BY name COLLATE NOCASE, id",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_view_by_id(pool: &SqlitePool, id: i64) -> Result<SavedView, AppError> {
+    sqlx::query_as::<_, SavedView>("SELECT * FROM saved_views WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("View not found".to_string()))
+}
+
+pub async fn create_view(
+    pool: &SqlitePool,
+    owner_id: i64,
+    req: &CreateViewRequest,
+) -> Result<SavedView, AppError> {
+    Ok(sqlx::query_as::<_, SavedView>(
+        r#"
+        INSERT INTO saved_views (owner_id, name, shared, filters, sort_by, sort_desc, display)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(owner_id)
+    .bind(&req.name)
+    .bind(req.shared)
+    .bind(Json(&req.filters))
+    .bind(&req.sort_by)
+    .bind(req.sort_desc)
+    .bind(&req.display)
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn update_view(
+    pool: &SqlitePool,
+    id: i64,
+    req: &CreateViewRequest,
+) -> Result<SavedView, AppError> {
+    sqlx::query_as::<_, SavedView>(
+        r#"
+        UPDATE saved_views
+        SET name = ?, shared = ?, filters = ?, sort_by = ?, sort_desc = ?, display = ?,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(&req.name)
+    .bind(req.shared)
+    .bind(Json(&req.filters))
+    .bind(&req.sort_by)
+    .bind(req.sort_desc)
+    .bind(&req.display)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("View not found".to_string()))
+}
+
+pub async fn delete_view(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM saved_views WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("View not found".to_string()));
+    }
+    Ok(())
+}
+
+// ============ Watchers ============
+
+pub async fn add_watcher(pool: &SqlitePool, task_id: i64, user_id: i64) -> Result<(), AppError> {
+    sqlx::query("INSERT OR IGNORE INTO task_watchers (task_id, user_id) VALUES (?, ?)")
+        .bind(task_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_watcher(pool: &SqlitePool, task_id: i64, user_id: i64) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM task_watchers WHERE task_id = ? AND user_id = ?")
+        .bind(task_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_task_watchers(pool: &SqlitePool, task_id: i64) -> Result<Vec<User>, AppError> {
+    Ok(sqlx::query_as::<_, User>(
+        r#"
+        SELECT u.* FROM users u
+        JOIN task_watchers w ON w.user_id = u.id
+        WHERE w.task_id = ?
+        ORDER BY w.created_at, u.id
+        "#,
+    )
+    .bind(task_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_watcher_ids(pool: &SqlitePool, task_id: i64) -> Result<Vec<i64>, AppError> {
+    let rows: Vec<(i64,)> = sqlx::query_as("SELECT user_id FROM task_watchers WHERE task_id = ?")
+        .bind(task_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| r.0).collect())
+}
+
+// ============ Mentions ============
+
+pub async fn get_description_mentions(pool: &SqlitePool, task_id: i64) -> Result<Vec<i64>, AppError> {
+    let rows: Vec<(i64,)> = sqlx::query_as(
+        "SELECT user_id FROM mentions WHERE task_id = ? AND comment_id IS NULL",
+    )
+    .bind(task_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|r| r.0).collect())
+}
+
+pub async fn replace_description_mentions(
+    pool: &SqlitePool,
+    task_id: i64,
+    author_id: i64,
+    user_ids: &[i64],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM mentions WHERE task_id = ? AND comment_id IS NULL")
+        .bind(task_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for user_id in user_ids {
+        sqlx::query("INSERT INTO mentions (user_id, task_id, author_id) VALUES (?, ?, ?)")
+            .bind(user_id)
+            .bind(task_id)
+            .bind(author_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn insert_comment_mentions(
+    pool: &SqlitePool,
+    comment: &Comment,
+    user_ids: &[i64],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    for user_id in user_ids {
+        sqlx::query(
+            "INSERT INTO mentions (user_id, task_id, comment_id, author_id) VALUES (?, ?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(comment.task_id)
+        .bind(comment.id)
+        .bind(comment.author_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+// ============ External import ============
+
+pub async fn find_import_mapping<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    source: &str,
+    entity_type: &str,
+    external_id: &str,
+) -> Result<Option<i64>, AppError> {
+    let row: Option<(i64,)> = sqlx::query_as(
+        "SELECT local_id FROM import_mappings WHERE source = ? AND entity_type = ? AND external_id = ?",
+    )
+    .bind(source)
+    .bind(entity_type)
+    .bind(external_id)
+    .fetch_optional(executor)
+    .await?;
+    Ok(row.map(|r| r.0))
+}
+
+pub async fn save_import_mapping<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    source: &str,
+    entity_type: &str,
+    external_id: &str,
+    local_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO import_mappings (source, entity_type, external_id, local_id)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT (source, entity_type, external_id)
+        DO UPDATE SET local_id = excluded.local_id, imported_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(source)
+    .bind(entity_type)
+    .bind(external_id)
+    .bind(local_id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_external_task(
+    conn: &mut SqliteConnection,
+    title: &str,
+    description: Option<&str>,
+    status: &str,
+    assignee_id: Option<i64>,
+    created_by: i64,
+    actual_hours: Option<f64>,
+    due_date: Option<NaiveDate>,
+) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO tasks (title, description, status, assignee_id, created_by,
+                           actual_hours, due_date)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING id
+        "#,
+    )
+    .bind(title)
+    .bind(description)
+    .bind(status)
+    .bind(assignee_id)
+    .bind(created_by)
+    .bind(actual_hours)
+    .bind(due_date)
+    .fetch_one(conn)
+    .await?;
+    Ok(row.0)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn update_external_task(
+    conn: &mut SqliteConnection,
+    id: i64,
+    title: &str,
+    description: Option<&str>,
+    status: &str,
+    assignee_id: Option<i64>,
+    actual_hours: Option<f64>,
+    due_date: Option<NaiveDate>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE tasks
+        SET title = ?, description = ?, status = ?, assignee_id = ?,
+            actual_hours = COALESCE(?, actual_hours), due_date = ?,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        "#,
+    )
+    .bind(title)
+    .bind(description)
+    .bind(status)
+    .bind(assignee_id)
+    .bind(actual_hours)
+    .bind(due_date)
+    .bind(id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+// ============ Webhooks ============
+
+pub async fn create_webhook(
+    pool: &SqlitePool,
+    url: &str,
+    secret: &str,
+    events: &[String],
+    active: bool,
+    created_by: i64,
+) -> Result<WebhookSubscription, AppError> {
+    Ok(sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        INSERT INTO webhook_subscriptions (url, secret, events, active, created_by)
+        VALUES (?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(url)
+    .bind(secret)
+    .bind(Json(events))
+    .bind(active)
+    .bind(created_by)
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn get_all_webhooks(pool: &SqlitePool) -> Result<Vec<WebhookSubscription>, AppError> {
+    Ok(
+        sqlx::query_as::<_, WebhookSubscription>("SELECT * FROM webhook_subscriptions ORDER BY id")
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+pub async fn get_webhook_by_id(pool: &SqlitePool, id: i64) -> Result<WebhookSubscription, AppError> {
+    sqlx::query_as::<_, WebhookSubscription>("SELECT * FROM webhook_subscriptions WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))
+}
+
+pub async fn get_webhooks_for_event(
+    pool: &SqlitePool,
+    event: &str,
+) -> Result<Vec<WebhookSubscription>, AppError> {
+    Ok(sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        SELECT * FROM webhook_subscriptions s
+        WHERE s.active = 1
+          AND EXISTS (SELECT 1 FROM json_each(s.events) WHERE json_each.value = ?)
+        "#,
+    )
+    .bind(event)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn update_webhook(
+    pool: &SqlitePool,
+    id: i64,
+    url: &str,
+    events: &[String],
+    active: bool,
+) -> Result<WebhookSubscription, AppError> {
+    sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        UPDATE webhook_subscriptions
+        SET url = ?, events = ?, active = ?
+        WHERE id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(url)
+    .bind(Json(events))
+    .bind(active)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))
+}
+
+pub async fn delete_webhook(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM webhook_subscriptions WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Webhook not found".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn enqueue_webhook_delivery<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    subscription_id: i64,
+    event: &str,
+    payload: &serde_json::Value,
+) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO webhook_deliveries (subscription_id, event, payload)
+        VALUES (?, ?, ?)
+        RETURNING id
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(event)
+    .bind(Json(payload))
+    .fetch_one(executor)
+    .await?;
+    Ok(row.0)
+}
+
+/// Доставка, которую ещё есть смысл отправлять: не доставленная и не удалённая с подпиской
+pub async fn get_undelivered_webhook_delivery(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<Option<WebhookDelivery>, AppError> {
+    Ok(sqlx::query_as::<_, WebhookDelivery>(
+        "SELECT * FROM webhook_deliveries WHERE id = ? AND status != 'delivered'",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn get_webhook_deliveries(
+    pool: &SqlitePool,
+    subscription_id: i64,
+) -> Result<Vec<WebhookDelivery>, AppError> {
+    Ok(sqlx::query_as::<_, WebhookDelivery>(
+        "SELECT * FROM webhook_deliveries WHERE subscription_id = ? ORDER BY id DESC LIMIT 100",
+    )
+    .bind(subscription_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn mark_webhook_delivered(
+    pool: &SqlitePool,
+    id: i64,
+    status_code: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = 'delivered', attempts = attempts + 1, last_status_code = ?,
+            last_error = NULL, delivered_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        "#,
+    )
+    .bind(status_code)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Фиксирует неудачную попытку. Без `retry_in_secs` доставка окончательно помечается failed
+pub async fn mark_webhook_attempt_failed(
+    pool: &SqlitePool,
+    id: i64,
+    status_code: Option<i64>,
+    error: &str,
+    retry_in_secs: Option<i64>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries
+        SET attempts = attempts + 1, last_status_code = ?1, last_error = ?2,
+            status = CASE WHEN ?3 IS NULL THEN 'failed' ELSE 'pending' END,
+            next_attempt_at = CASE WHEN ?3 IS NULL THEN NULL
+                                   ELSE datetime('now', '+' || ?3 || ' seconds') END
+        WHERE id = ?4
+        "#,
+    )
+    .bind(status_code)
+    .bind(error)
+    .bind(retry_in_secs)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Ставит доставку в очередь заново с обнулённым счётчиком попыток
+pub async fn reset_webhook_delivery(pool: &SqlitePool, id: i64) -> Result<WebhookDelivery, AppError> {
+    sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = 'pending', attempts = 0, next_attempt_at = CURRENT_TIMESTAMP,
+            delivered_at = NULL
+        WHERE id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Delivery not found".to_string()))
+}
+
+// ============ Jobs ============
+
+pub async fn enqueue_job<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    kind: &str,
+    payload: &serde_json::Value,
+    max_attempts: i64,
+    delay_secs: i64,
+    request_id: Option<&str>,
+) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO jobs (kind, payload, max_attempts, run_at, request_id)
+        VALUES (?, ?, ?, datetime('now', '+' || ? || ' seconds'), ?)
+        RETURNING id
+        "#,
+    )
+    .bind(kind)
+    .bind(Json(payload))
+    .bind(max_attempts)
+    .bind(delay_secs)
+    .bind(request_id)
+    .fetch_one(executor)
+    .await?;
+    Ok(row.0)
+}
+
+/// Забирает самую раннюю готовую задачу и берёт её в аренду на `lease_secs`.
+/// Один UPDATE: два воркера не получат одну задачу. Задачи упавшего процесса
+/// возвращаются в работу, когда истекает их аренда
+pub async fn claim_job(pool: &SqlitePool, lease_secs: i64) -> Result<Option<JobRecord>, AppError> {
+    Ok(sqlx::query_as::<_, JobRecord>(
+        r#"
+        UPDATE jobs
+        SET status = 'running', attempts = attempts + 1,
+            locked_until = datetime('now', '+' || ?1 || ' seconds')
+        WHERE id = (
+            SELECT id FROM jobs
+            WHERE (status = 'pending' AND run_at <= CURRENT_TIMESTAMP)
+               OR (status = 'running' AND locked_until <= CURRENT_TIMESTAMP)
+            ORDER BY run_at, id
+            LIMIT 1
+        )
+        RETURNING *
+        "#,
+    )
+    .bind(lease_secs)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn complete_job<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE jobs
+        SET status = 'done', locked_until = NULL, last_error = NULL,
+            finished_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        "#,
+    )
+    .bind(id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Фиксирует неудачную попытку. Без `retry_in_secs` задача уходит в dead
+pub async fn fail_job<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    id: i64,
+    error: &str,
+    retry_in_secs: Option<i64>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE jobs
+        SET last_error = ?1, locked_until = NULL,
+            status = CASE WHEN ?2 IS NULL THEN 'dead' ELSE 'pending' END,
+            run_at = CASE WHEN ?2 IS NULL THEN run_at
+                          ELSE datetime('now', '+' || ?2 || ' seconds') END,
+            finished_at = CASE WHEN ?2 IS NULL THEN CURRENT_TIMESTAMP ELSE NULL END
+        WHERE id = ?3
+        "#,
+    )
+    .bind(error)
+    .bind(retry_in_secs)
+    .bind(id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_jobs(
+    pool: &SqlitePool,
+    status: Option<&str>,
+    kind: Option<&str>,
+    limit: i64,
+) -> Result<Vec<JobRecord>, AppError> {
+    Ok(sqlx::query_as::<_, JobRecord>(
+        r#"
+        SELECT * FROM jobs
+        WHERE (?1 IS NULL OR status = ?1) AND (?2 IS NULL OR kind = ?2)
+        ORDER BY id DESC
+        LIMIT ?3
+        "#,
+    )
+    .bind(status)
+    .bind(kind)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_job(pool: &SqlitePool, id: i64) -> Result<JobRecord, AppError> {
+    sqlx::query_as::<_, JobRecord>("SELECT * FROM jobs WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Job not found".to_string()))
+}
+
+/// Возвращает dead-задачу в очередь с обнулённым счётчиком попыток
+pub async fn retry_dead_job(pool: &SqlitePool, id: i64) -> Result<Option<JobRecord>, AppError> {
+    Ok(sqlx::query_as::<_, JobRecord>(
+        r#"
+        UPDATE jobs
+        SET status = 'pending', attempts = 0, run_at = CURRENT_TIMESTAMP, finished_at = NULL
+        WHERE id = ? AND status = 'dead'
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Есть ли задача этого вида, которая ещё не завершена
+pub async fn has_unfinished_job<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    kind: &str,
+) -> Result<bool, AppError> {
+    let row: (bool,) = sqlx::query_as(
+        "SELECT EXISTS(SELECT 1 FROM jobs WHERE kind = ? AND status IN ('pending', 'running'))",
+    )
+    .bind(kind)
+    .fetch_one(executor)
+    .await?;
+    Ok(row.0)
+}
+
+/// Удаляет выполненные задачи старше `days` дней. Dead-задачи остаются до разбора
+pub async fn prune_finished_jobs(pool: &SqlitePool, days: i64) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM jobs
+        WHERE status = 'done' AND finished_at < datetime('now', '-' || ? || ' days')
+        "#,
+    )
+    .bind(days)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// ============ Realtime ============
+
+pub async fn insert_task_event(
+    pool: &SqlitePool,
+    event: &str,
+    task_id: i64,
+    task: Option<&Task>,
+) -> Result<TaskEvent, AppError> {
+    Ok(sqlx::query_as::<_, TaskEvent>(
+        "INSERT INTO task_events (event, task_id, task) VALUES (?, ?, ?) RETURNING *",
+    )
+    .bind(event)
+    .bind(task_id)
+    .bind(task.map(Json))
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn get_task_events_after(
+    pool: &SqlitePool,
+    after_id: i64,
+    limit: i64,
+) -> Result<Vec<TaskEvent>, AppError> {
+    Ok(sqlx::query_as::<_, TaskEvent>(
+        "SELECT * FROM task_events WHERE id > ? ORDER BY id LIMIT ?",
+    )
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_latest_task_event_id(pool: &SqlitePool) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as("SELECT COALESCE(MAX(id), 0) FROM task_events")
+        .fetch_one(pool)
+        .await?;
+    Ok(row.0)
+}
+
+pub async fn delete_task_events_older_than(pool: &SqlitePool, days: i64) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        "DELETE FROM task_events WHERE created_at < datetime('now', '-' || ? || ' days')",
+    )
+    .bind(days)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// ============ Notifications ============
+
+pub async fn create_notification(
+    pool: &SqlitePool,
+    user_id: i64,
+    kind: &str,
+    task_id: Option<i64>,
+    actor_id: Option<i64>,
+    message: &str,
+) -> Result<Notification, AppError> {
+    Ok(sqlx::query_as::<_, Notification>(
+        r#"
+        INSERT INTO notifications (user_id, kind, task_id, actor_id, message)
+        VALUES (?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(user_id)
+    .bind(kind)
+    .bind(task_id)
+    .bind(actor_id)
+    .bind(message)
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn get_notifications(
+    pool: &SqlitePool,
+    user_id: i64,
+    unread_only: bool,
+) -> Result<Vec<Notification>, AppError> {
+    Ok(sqlx::query_as::<_, Notification>(
+        r#"
+        SELECT * FROM notifications
+        WHERE user_id = ? AND (? = 0 OR read_at IS NULL)
+        ORDER BY id DESC
+        LIMIT 100
+        "#,
+    )
+    .bind(user_id)
+    .bind(unread_only)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn count_unread_notifications(pool: &SqlitePool, user_id: i64) -> Result<i64, AppError> {
+    let row: (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM notifications WHERE user_id = ? AND read_at IS NULL")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+    Ok(row.0)
+}
+
+pub async fn mark_notification_read(
+    pool: &SqlitePool,
+    user_id: i64,
+    id: i64,
+) -> Result<Notification, AppError> {
+    sqlx::query_as::<_, Notification>(
+        r#"
+        UPDATE notifications
+        SET read_at = COALESCE(read_at, CURRENT_TIMESTAMP)
+        WHERE id = ? AND user_id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Notification not found".to_string()))
+}
+
+pub async fn mark_all_notifications_read(pool: &SqlitePool, user_id: i64) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        "UPDATE notifications SET read_at = CURRENT_TIMESTAMP WHERE user_id = ? AND read_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn get_notifications_after(
+    pool: &SqlitePool,
+    user_id: i64,
+    after_id: i64,
+) -> Result<Vec<Notification>, AppError> {
+    Ok(sqlx::query_as::<_, Notification>(
+        "SELECT * FROM notifications WHERE user_id = ? AND id > ? ORDER BY id",
+    )
+    .bind(user_id)
+    .bind(after_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Незавершённые задачи со сроком на завтра, по которым исполнитель ещё не получил напоминание
+pub async fn get_tasks_due_for_reminder(
+    pool: &SqlitePool,
+    kind: &str,
+) -> Result<Vec<Task>, AppError> {
+    Ok(sqlx::query_as::<_, Task>(
+        r#"
+        SELECT * FROM tasks t
+        WHERE t.assignee_id IS NOT NULL
+          AND t.status != 'done'
+          AND t.due_date = date('now', '+1 day')
+          AND NOT EXISTS (
+              SELECT 1 FROM notifications n
+              WHERE n.task_id = t.id AND n.user_id = t.assignee_id AND n.kind = ?
+                AND n.created_at > datetime('now', '-1 day')
+          )
+        "#,
+    )
+    .bind(kind)
+    .fetch_all(pool)
+    .await?)
+}
+
+// ============ Email ============
+
+pub async fn get_email_preferences(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<EmailPreferences, AppError> {
+    let mode: Option<(String,)> =
+        sqlx::query_as("SELECT mode FROM email_preferences WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+    Ok(EmailPreferences {
+        mode: mode.map(|m| m.0).unwrap_or_else(|| "immediate".to_string()),
+    })
+}
+
+/// При переходе на дайджест курсор ставится на последнее уведомление,
+/// чтобы в первый дайджест не попала вся история
+pub async fn set_email_mode(pool: &SqlitePool, user_id: i64, mode: &str) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO email_preferences (user_id, mode, digest_cursor)
+        VALUES (?1, ?2, (SELECT COALESCE(MAX(id), 0) FROM notifications WHERE user_id = ?1))
+        ON CONFLICT(user_id) DO UPDATE SET
+            mode = excluded.mode,
+            digest_cursor = CASE WHEN email_preferences.mode = 'digest'
+                                 THEN email_preferences.digest_cursor
+                                 ELSE excluded.digest_cursor END,
+            updated_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(user_id)
+    .bind(mode)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Пользователи в режиме дайджеста, которым сегодня его ещё не отправляли: (user_id, digest_cursor)
+pub async fn get_users_due_for_digest(pool: &SqlitePool) -> Result<Vec<(i64, i64)>, AppError> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT user_id, digest_cursor FROM email_preferences
+        WHERE mode = 'digest'
+          AND (last_digest_at IS NULL OR date(last_digest_at) < date('now'))
+        "#,
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn record_digest(pool: &SqlitePool, user_id: i64, cursor: i64) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE email_preferences
+        SET digest_cursor = MAX(digest_cursor, ?), last_digest_at = CURRENT_TIMESTAMP
+        WHERE user_id = ?
+        "#,
+    )
+    .bind(cursor)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn enqueue_email<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    user_id: i64,
+    to_address: &str,
+    template: &str,
+    notifications: &[Notification],
+) -> Result<i64, AppError> {
+    let row: (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO email_outbox (user_id, to_address, template, payload)
+        VALUES (?, ?, ?, ?)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(to_address)
+    .bind(template)
+    .bind(Json(notifications))
+    .fetch_one(executor)
+    .await?;
+    Ok(row.0)
+}
+
+pub async fn get_unsent_email(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<Option<OutgoingEmail>, AppError> {
+    Ok(sqlx::query_as::<_, OutgoingEmail>(
+        "SELECT * FROM email_outbox WHERE id = ? AND status != 'sent'",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn mark_email_sent(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE email_outbox
+        SET status = 'sent', attempts = attempts + 1, last_error = NULL, sent_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Фиксирует неудачную попытку. Без `retry_in_secs` письмо окончательно помечается failed
+pub async fn mark_email_attempt_failed(
+    pool: &SqlitePool,
+    id: i64,
+    error: &str,
+    retry_in_secs: Option<i64>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE email_outbox
+        SET attempts = attempts + 1, last_error = ?1,
+            status = CASE WHEN ?2 IS NULL THEN 'failed' ELSE 'pending' END,
+            next_attempt_at = CASE WHEN ?2 IS NULL THEN NULL
+                                   ELSE datetime('now', '+' || ?2 || ' seconds') END
+        WHERE id = ?3
+        "#,
+    )
+    .bind(error)
+    .bind(retry_in_secs)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// ============ Health ============
+
+pub async fn ping(pool: &SqlitePool) -> Result<(), AppError> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+/// Версии миграций, которые применены успешно
+pub async fn get_applied_migrations(pool: &SqlitePool) -> Result<Vec<i64>, AppError> {
+    let rows: Vec<(i64,)> = sqlx::query_as("SELECT version FROM _sqlx_migrations WHERE success = 1")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|(version,)| version).collect())
+}
+
+/// База в памяти со всеми миграциями для тестов модулей. Одно соединение:
+/// у каждого соединения с `:memory:` своя база
+#[cfg(test)]
+pub async fn test_pool() -> SqlitePool {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("connect");
+    MIGRATOR.run(&pool).await.expect("migrate");
+    pool
+}
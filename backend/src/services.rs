@@ -1,12 +1,52 @@
+use actix_web::web::Bytes;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures_util::stream::{Stream, StreamExt};
 use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use crate::audit;
 use crate::auth;
+use crate::calendar;
 use crate::errors::AppError;
+use crate::import_export::{self, IMPORT_FIELDS, Record, TaskEncoder};
+use crate::importers;
+use crate::jobs::{self, Job};
+use crate::login_guard;
+use crate::mentions;
 use crate::ml_client::MlClient;
+use crate::notifications;
+use crate::oidc::{self, Identity, OidcClient};
+use crate::passwords::{self, PasswordPolicy, ResetNotifier};
+use crate::realtime::{self, Broadcaster};
 use crate::models::{
-    AuthResponse, ChangePasswordRequest, CreateTaskRequest,
-    CreateUserRequest, LoginRequest, Task, UpdateTaskRequest, User,
+    AuditEvent, AuthenticatedUser, AuthResponse, CalendarFeedStatus, ChangePasswordRequest,
+    ClientInfo, Comment, ComponentHealth, CreateAccessTokenRequest, CreateCommentRequest,
+    CreatedAccessTokenResponse, CreatedWebhookResponse, CreateRoleRequest, CreateTaskRequest,
+    CreateUserRequest, CreateViewRequest, CreateWebhookRequest, DisableTwoFactorRequest,
+    EMAIL_MODES, EmailPreferences, ExternalImportReport, ExternalImportRequest, HealthStatus,
+    ImportedTask, ImportReport, ImportRowError, ImportSource, ImportTasksRequest, JOB_DEAD,
+    JOB_STATUSES, JobRecord, JobsQuery, LoginRequest, LoginResponse, Notification,
+    OidcCallbackRequest, OidcStartResponse, OidcStatus, PermissionInfo, PersonalAccessToken,
+    ReadinessReport, RecoveryCodes, ResetPasswordRequest, Role, SavedView, Session, Task,
+    TaskCounts, TaskFilter, TaskListQuery, TaskQuery, TOKEN_SCOPE_READ, TOKEN_SCOPE_WRITE,
+    TransferFormat, TwoFactorChallenge, TwoFactorLoginRequest, TwoFactorSetup, TwoFactorStatus,
+    UpdateRoleRequest, UpdateTaskRequest, UpdateViewRequest, UpdateWebhookRequest, User,
+    VIEW_DISPLAY_MODES, VIEW_SORT_FIELDS, WEBHOOK_EVENTS, WebhookDelivery, WebhookSubscription,
 };
+use crate::policy::{self, Action, Resource};
+use crate::query_language;
 use crate::repository;
+use crate::totp;
+use crate::webhooks;
+
+pub const TASK_STATUSES: [&str; 3] = ["todo", "in_progress", "done"];
+
+/// Сколько ждём код после верного пароля и сколько даём попыток
+const CHALLENGE_MINUTES: i64 = 5;
+const CHALLENGE_ATTEMPTS: i64 = 5;
 
 // ============ Init ============
 
@@ -25,7 +65,7 @@ pub async fn init_admin(
     tracing::info!("Creating initial admin user: {}", email);
     
     let password_hash = auth::hash_password(password)?;
-    repository::create_user(pool, email, &password_hash, "Admin", "admin").await?;
+    repository::create_user(pool, email, &password_hash, "Admin", policy::ROLE_ADMIN).await?;
 
     tracing::info!("Admin user created successfully");
     Ok(())
@@ -36,23 +76,506 @@ pub async fn init_admin(
 pub async fn login(
     pool: &SqlitePool,
     req: LoginRequest,
+    client: &ClientInfo,
+    jwt_secret: &str,
+) -> Result<LoginResponse, AppError> {
+    let ip = client.ip.as_deref();
+    login_guard::check(pool, &req.email, ip).await?;
+
+    // Для неизвестного email пароль всё равно проверяется, чтобы ответ не был быстрее
+    let user = repository::get_user_by_email(pool, &req.email).await?;
+    let hash = user
+        .as_ref()
+        .map_or(login_guard::dummy_hash(), |u| u.password_hash.as_str());
+    let password_ok = auth::verify_password(&req.password, hash)?;
+
+    let Some(user) = user.filter(|_| password_ok) else {
+        login_guard::record_failure(pool, &req.email, ip).await?;
+        return Err(AppError::Unauthorized);
+    };
+
+    // Со включённой 2FA счётчик ошибок обнуляет только верный код: иначе
+    // знающий пароль мог бы перебирать коды без пауз
+    let two_factor = two_factor_enabled(pool, user.id).await?;
+    if !two_factor {
+        login_guard::record_success(pool, user.id, &user.email, ip).await?;
+    }
+    if user.password_reset_required {
+        return Err(AppError::BadRequest(
+            "Password reset required: use the link sent to your email".to_string(),
+        ));
+    }
+
+    if two_factor {
+        return two_factor_challenge(pool, user.id).await;
+    }
+
+    let response = start_session(pool, user, client, jwt_secret).await?;
+    Ok(LoginResponse::Authenticated(response))
+}
+
+/// Первый фактор пройден: вместо токенов - вызов для второго шага
+async fn two_factor_challenge(pool: &SqlitePool, user_id: i64) -> Result<LoginResponse, AppError> {
+    let challenge = auth::generate_token();
+    let expires_at = Utc::now() + Duration::minutes(CHALLENGE_MINUTES);
+    repository::create_login_challenge(pool, user_id, &auth::hash_token(&challenge), expires_at)
+        .await?;
+    Ok(LoginResponse::TwoFactorRequired(TwoFactorChallenge {
+        two_factor_required: true,
+        challenge,
+        expires_in: CHALLENGE_MINUTES * 60,
+    }))
+}
+
+/// Второй шаг входа: вызов из первого шага и код из приложения или резервный.
+/// Неверные коды считаются вместе с неверными паролями этого аккаунта
+pub async fn login_two_factor(
+    pool: &SqlitePool,
+    req: TwoFactorLoginRequest,
+    client: &ClientInfo,
     jwt_secret: &str,
 ) -> Result<AuthResponse, AppError> {
-    let user = repository::get_user_by_email(pool, &req.email)
+    let challenge = repository::find_login_challenge(pool, &auth::hash_token(&req.challenge))
         .await?
         .ok_or(AppError::Unauthorized)?;
+    if challenge.expires_at < Utc::now() || challenge.attempts >= CHALLENGE_ATTEMPTS {
+        repository::delete_login_challenge(pool, challenge.id).await?;
+        return Err(AppError::Unauthorized);
+    }
 
-    if !auth::verify_password(&req.password, &user.password_hash)? {
+    let user = repository::get_user_by_id(pool, challenge.user_id).await?;
+    let ip = client.ip.as_deref();
+    login_guard::check(pool, &user.email, ip).await?;
+
+    if !verify_second_factor(pool, &user, &req.code, ip).await? {
+        repository::record_challenge_attempt(pool, challenge.id).await?;
+        audit::record(pool, "2fa.failed", Some(user.id), Some(&user.email), ip, None).await;
+        login_guard::record_failure(pool, &user.email, ip).await?;
+        return Err(AppError::Unauthorized);
+    }
+    if !repository::delete_login_challenge(pool, challenge.id).await? {
+        return Err(AppError::Unauthorized);
+    }
+
+    login_guard::record_success(pool, user.id, &user.email, ip).await?;
+    start_session(pool, user, client, jwt_secret).await
+}
+
+async fn start_session(
+    pool: &SqlitePool,
+    user: User,
+    client: &ClientInfo,
+    jwt_secret: &str,
+) -> Result<AuthResponse, AppError> {
+    let session_id = repository::create_session(pool, user.id, client).await?;
+    let refresh_token = auth::generate_token();
+    repository::insert_refresh_token(
+        pool,
+        session_id,
+        &auth::hash_token(&refresh_token),
+        refresh_expiry(),
+    )
+    .await?;
+
+    issue_tokens(pool, user, session_id, refresh_token, jwt_secret).await
+}
+
+fn refresh_expiry() -> DateTime<Utc> {
+    Utc::now() + Duration::days(auth::REFRESH_TOKEN_DAYS)
+}
+
+/// Права берутся из роли заново при каждом обмене, так что её изменения
+/// доходят до клиента вместе со следующим access-токеном. Так же и требование
+/// 2FA: пока она не настроена, токен годится только для её настройки
+async fn issue_tokens(
+    pool: &SqlitePool,
+    user: User,
+    session_id: i64,
+    refresh_token: String,
+    jwt_secret: &str,
+) -> Result<AuthResponse, AppError> {
+    let permissions = role_permissions(pool, &user.role).await?;
+    let setup_required = two_factor_setup_required(pool, &user).await?;
+    let token = auth::create_token(
+        user.id,
+        &user.role,
+        &permissions,
+        session_id,
+        setup_required,
+        jwt_secret,
+    )?;
+
+    Ok(AuthResponse {
+        token,
+        refresh_token,
+        expires_in: auth::ACCESS_TOKEN_MINUTES * 60,
+        user,
+        two_factor_setup_required: setup_required,
+    })
+}
+
+/// Обмен refresh-токена на новую пару. Повторно предъявленный токен значит, что
+/// его копия у кого-то ещё, - отзываем всю сессию, и вор, и владелец входят заново
+pub async fn refresh(
+    pool: &SqlitePool,
+    refresh_token: &str,
+    jwt_secret: &str,
+) -> Result<AuthResponse, AppError> {
+    let token = repository::find_refresh_token(pool, &auth::hash_token(refresh_token))
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    if token.revoked_at.is_some() || token.expires_at < Utc::now() {
+        return Err(AppError::Unauthorized);
+    }
+
+    let next = auth::generate_token();
+    let rotated = match token.used_at {
+        Some(_) => false,
+        None => {
+            repository::rotate_refresh_token(pool, &token, &auth::hash_token(&next), refresh_expiry())
+                .await?
+        }
+    };
+    if !rotated {
+        tracing::warn!(
+            "Refresh token reuse in session {} of user {}, revoking the session",
+            token.session_id,
+            token.user_id
+        );
+        repository::revoke_session(pool, token.session_id).await?;
+        return Err(AppError::Unauthorized);
+    }
+
+    repository::touch_session(pool, token.session_id).await?;
+    let user = repository::get_user_by_id(pool, token.user_id).await?;
+    issue_tokens(pool, user, token.session_id, next, jwt_secret).await
+}
+
+pub async fn logout(pool: &SqlitePool, session_id: i64) -> Result<(), AppError> {
+    repository::revoke_session(pool, session_id).await
+}
+
+/// Снимает с аккаунта паузу и блокировку после неудачных входов
+pub async fn unlock_user(pool: &SqlitePool, user_id: i64, actor_id: i64) -> Result<(), AppError> {
+    let user = repository::get_user_by_id(pool, user_id).await?;
+    login_guard::unlock(pool, user.id, &user.email, actor_id).await
+}
+
+pub async fn get_audit_log(pool: &SqlitePool, limit: Option<i64>) -> Result<Vec<AuditEvent>, AppError> {
+    repository::get_audit_log(pool, limit.unwrap_or(100).clamp(1, 1000)).await
+}
+
+// ============ Single sign-on ============
+
+/// Сколько ждём возвращения браузера от провайдера
+const OIDC_LOGIN_MINUTES: i64 = 10;
+
+pub fn get_oidc_status(oidc: &OidcClient) -> OidcStatus {
+    OidcStatus {
+        enabled: oidc.is_enabled(),
+    }
+}
+
+pub async fn start_oidc_login(
+    pool: &SqlitePool,
+    oidc: &OidcClient,
+) -> Result<OidcStartResponse, AppError> {
+    let state = oidc::generate_secret();
+    let nonce = oidc::generate_secret();
+    let code_verifier = oidc::generate_secret();
+    let authorization_url = oidc.authorization_url(&state, &nonce, &code_verifier).await?;
+
+    let expires_at = Utc::now() + Duration::minutes(OIDC_LOGIN_MINUTES);
+    repository::create_oidc_login_state(
+        pool,
+        &auth::hash_token(&state),
+        &nonce,
+        &code_verifier,
+        expires_at,
+    )
+    .await?;
+    Ok(OidcStartResponse {
+        authorization_url,
+        state,
+    })
+}
+
+/// Возврат от провайдера. Включённая в приложении 2FA спрашивается так же, как
+/// после пароля: вход через провайдер заменяет только первый фактор
+pub async fn finish_oidc_login(
+    pool: &SqlitePool,
+    oidc: &OidcClient,
+    req: OidcCallbackRequest,
+    client: &ClientInfo,
+    jwt_secret: &str,
+) -> Result<LoginResponse, AppError> {
+    oidc.check_enabled()?;
+    let ip = client.ip.as_deref();
+    let login_state = repository::take_oidc_login_state(pool, &auth::hash_token(&req.state))
+        .await?
+        .filter(|s| s.expires_at > Utc::now())
+        .ok_or(AppError::Unauthorized)?;
+
+    let identity = oidc
+        .exchange_code(&req.code, &login_state.code_verifier, &login_state.nonce)
+        .await;
+    let identity = match identity {
+        Ok(identity) => identity,
+        Err(e) => {
+            audit::record(pool, "sso.failed", None, None, ip, Some(&e.to_string())).await;
+            return Err(e);
+        }
+    };
+
+    let mut user = oidc_user(pool, oidc, &identity, ip).await?;
+    if let Some(role) = identity.groups.as_deref().and_then(|g| oidc.role_for_groups(g))
+        && role != user.role
+    {
+        existing_role(pool, role).await?;
+        repository::set_user_role(pool, user.id, role).await?;
+        let details = format!("{} -> {} by identity provider groups", user.role, role);
+        let email = Some(user.email.as_str());
+        audit::record(pool, "sso.role_changed", Some(user.id), email, ip, Some(&details)).await;
+        user.role = role.to_string();
+    }
+
+    if user.password_reset_required {
+        return Err(AppError::BadRequest(
+            "Password reset required: use the link sent to your email".to_string(),
+        ));
+    }
+    audit::record(pool, "sso.succeeded", Some(user.id), Some(&user.email), ip, None).await;
+    if two_factor_enabled(pool, user.id).await? {
+        return two_factor_challenge(pool, user.id).await;
+    }
+
+    let response = start_session(pool, user, client, jwt_secret).await?;
+    Ok(LoginResponse::Authenticated(response))
+}
+
+/// Пользователь для учётной записи провайдера: уже связанный с ней, затем
+/// найденный по подтверждённому email, иначе новый (OIDC_AUTO_PROVISION)
+async fn oidc_user(
+    pool: &SqlitePool,
+    oidc: &OidcClient,
+    identity: &Identity,
+    ip: Option<&str>,
+) -> Result<User, AppError> {
+    let (issuer, subject) = (identity.issuer.as_str(), identity.subject.as_str());
+    if let Some(user) = repository::get_user_by_identity(pool, issuer, subject).await? {
+        repository::touch_identity(pool, issuer, subject).await?;
+        return Ok(user);
+    }
+
+    let Some(email) = identity.email.as_deref() else {
+        audit::record(pool, "sso.failed", None, None, ip, Some("no verified email")).await;
+        return Err(AppError::BadRequest(
+            "The identity provider did not share a verified email".to_string(),
+        ));
+    };
+    if let Some(user) = repository::get_user_by_email(pool, email).await? {
+        repository::link_identity(pool, user.id, issuer, subject).await?;
+        audit::record(pool, "sso.linked", Some(user.id), Some(email), ip, Some(issuer)).await;
+        return Ok(user);
+    }
+
+    if !oidc.auto_provision() {
+        audit::record(pool, "sso.failed", None, Some(email), ip, Some("no such user")).await;
+        return Err(AppError::BadRequest(
+            "No account for this email: ask an administrator to create one".to_string(),
+        ));
+    }
+    let role = identity
+        .groups
+        .as_deref()
+        .and_then(|g| oidc.role_for_groups(g))
+        .unwrap_or(oidc.default_role());
+    existing_role(pool, role).await?;
+    let name = identity
+        .name
+        .clone()
+        .unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_string());
+    // Пароля у такого пользователя нет: задать его можно через сброс по ссылке
+    let password_hash = auth::hash_password(&auth::generate_token())?;
+    let user =
+        repository::create_identity_user(pool, email, &password_hash, &name, role, issuer, subject)
+            .await?;
+    audit::record(pool, "sso.provisioned", Some(user.id), Some(email), ip, Some(role)).await;
+    Ok(user)
+}
+
+/// Роль из настроек SSO должна существовать, иначе пользователь остался бы без прав
+async fn existing_role(pool: &SqlitePool, role: &str) -> Result<(), AppError> {
+    if repository::get_role(pool, role).await?.is_none() {
+        tracing::error!("SSO configuration refers to unknown role {}", role);
+        return Err(AppError::Internal("Single sign-on is misconfigured".to_string()));
+    }
+    Ok(())
+}
+
+// ============ Sessions ============
+
+/// Отозванная сессия гасит и ещё не истёкшие access-токены. Время последнего
+/// обращения пишем не чаще раза в минуту, чтобы чтение не превращалось в запись
+pub async fn check_session(pool: &SqlitePool, session_id: i64) -> Result<(), AppError> {
+    let session = repository::get_session(pool, session_id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let stale = session
+        .last_used_at
+        .is_none_or(|used| Utc::now() - used > Duration::minutes(1));
+    if stale {
+        repository::touch_session(pool, session_id).await?;
+    }
+    Ok(())
+}
+
+pub async fn get_sessions(
+    pool: &SqlitePool,
+    user_id: i64,
+    current_session: Option<i64>,
+) -> Result<Vec<Session>, AppError> {
+    let mut sessions =
+        repository::get_user_sessions(pool, user_id, auth::REFRESH_TOKEN_DAYS).await?;
+    for session in &mut sessions {
+        session.current = current_session == Some(session.id);
+    }
+    Ok(sessions)
+}
+
+pub async fn get_session(pool: &SqlitePool, id: i64) -> Result<Session, AppError> {
+    repository::get_session(pool, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".to_string()))
+}
+
+pub async fn revoke_session(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    repository::revoke_session(pool, id).await
+}
+
+pub async fn logout_everywhere(pool: &SqlitePool, user_id: i64) -> Result<(), AppError> {
+    repository::revoke_user_sessions(pool, user_id).await
+}
+
+// ============ Personal access tokens ============
+
+/// Дольше года токен не живёт: забытые в скриптах токены со временем гаснут сами
+const MAX_ACCESS_TOKEN_DAYS: i64 = 365;
+
+pub async fn create_access_token(
+    pool: &SqlitePool,
+    user_id: i64,
+    req: CreateAccessTokenRequest,
+) -> Result<CreatedAccessTokenResponse, AppError> {
+    let name = req.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::BadRequest(
+            "Token name must be 1-100 characters".to_string(),
+        ));
+    }
+    if ![TOKEN_SCOPE_READ, TOKEN_SCOPE_WRITE].contains(&req.scope.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Unknown scope: {}, expected {} or {}",
+            req.scope, TOKEN_SCOPE_READ, TOKEN_SCOPE_WRITE
+        )));
+    }
+    let expires_at = match req.expires_in_days {
+        Some(days) if (1..=MAX_ACCESS_TOKEN_DAYS).contains(&days) => {
+            Some(Utc::now() + Duration::days(days))
+        }
+        Some(_) => {
+            return Err(AppError::BadRequest(format!(
+                "Token lifetime must be 1-{} days",
+                MAX_ACCESS_TOKEN_DAYS
+            )));
+        }
+        None => None,
+    };
+
+    let token = format!("{}{}", auth::PERSONAL_TOKEN_PREFIX, auth::generate_token());
+    let access_token = repository::create_access_token(
+        pool,
+        user_id,
+        name,
+        &auth::hash_token(&token),
+        &req.scope,
+        expires_at,
+    )
+    .await?;
+
+    let details = format!(
+        "token {} ({}, {})",
+        access_token.id, access_token.name, access_token.scope
+    );
+    audit::record(pool, "access_token.created", Some(user_id), None, None, Some(&details)).await;
+    Ok(CreatedAccessTokenResponse { access_token, token })
+}
+
+pub async fn get_access_tokens(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<Vec<PersonalAccessToken>, AppError> {
+    repository::get_user_access_tokens(pool, user_id).await
+}
+
+pub async fn get_access_token(pool: &SqlitePool, id: i64) -> Result<PersonalAccessToken, AppError> {
+    repository::get_access_token(pool, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Access token not found".to_string()))
+}
+
+pub async fn revoke_access_token(
+    pool: &SqlitePool,
+    token: &PersonalAccessToken,
+    actor_id: i64,
+) -> Result<(), AppError> {
+    repository::revoke_access_token(pool, token.id).await?;
+    let details = format!("token {} by user {}", token.id, actor_id);
+    audit::record(pool, "access_token.revoked", Some(token.user_id), None, None, Some(&details))
+        .await;
+    Ok(())
+}
+
+/// Личный токен вместо JWT. Права - текущие права роли владельца, так что
+/// изменения роли действуют на токены сразу. Время использования пишем не
+/// чаще раза в минуту, как у сессий
+pub async fn authenticate_access_token(
+    pool: &SqlitePool,
+    token: &str,
+) -> Result<AuthenticatedUser, AppError> {
+    let access_token = repository::find_access_token(pool, &auth::hash_token(token))
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+    if access_token.expires_at.is_some_and(|expires| expires < Utc::now()) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let user = repository::get_user_by_id(pool, access_token.user_id).await?;
+    if user.password_reset_required {
         return Err(AppError::Unauthorized);
     }
 
-    let token = auth::create_token(user.id, &user.role, jwt_secret)?;
+    let stale = access_token
+        .last_used_at
+        .is_none_or(|used| Utc::now() - used > Duration::minutes(1));
+    if stale {
+        repository::touch_access_token(pool, access_token.id).await?;
+    }
 
-    Ok(AuthResponse { token, user })
+    Ok(AuthenticatedUser {
+        id: user.id,
+        session_id: None,
+        permissions: role_permissions(pool, &user.role).await?,
+        two_factor_setup_required: two_factor_setup_required(pool, &user).await?,
+        read_only: access_token.scope == TOKEN_SCOPE_READ,
+    })
 }
 
 pub async fn change_password(
     pool: &SqlitePool,
+    passwords: &PasswordPolicy,
     user_id: i64,
     req: ChangePasswordRequest,
 ) -> Result<(), AppError> {
@@ -61,72 +584,1380 @@ pub async fn change_password(
     if !auth::verify_password(&req.current_password, &user.password_hash)? {
         return Err(AppError::BadRequest("Current password is incorrect".to_string()));
     }
+    passwords.check(&req.new_password)?;
 
     let new_hash = auth::hash_password(&req.new_password)?;
     repository::update_password(pool, user_id, &new_hash).await
 }
 
-// ============ Users ============
+// ============ Password reset ============
 
-pub async fn create_user(
+/// Ответ одинаков для любого email, иначе по нему можно проверять, есть ли аккаунт
+pub async fn forgot_password(
     pool: &SqlitePool,
-    req: CreateUserRequest,
-) -> Result<User, AppError> {
-    let password_hash = auth::hash_password(&req.password)?;
-    repository::create_user(pool, &req.email, &password_hash, &req.name, &req.role).await
+    notifier: Arc<dyn ResetNotifier>,
+    email: &str,
+    client: &ClientInfo,
+    app_url: &str,
+) -> Result<(), AppError> {
+    let ip = client.ip.as_deref();
+    login_guard::check_reset_request(pool, email, ip).await?;
+
+    let Some(user) = repository::get_user_by_email(pool, email.trim()).await? else {
+        let details = Some("unknown email");
+        audit::record(pool, "password.reset_requested", None, Some(email), ip, details).await;
+        return Ok(());
+    };
+
+    let email = Some(user.email.as_str());
+    audit::record(pool, "password.reset_requested", Some(user.id), email, ip, None).await;
+    send_reset_link(pool, notifier, user, app_url).await
 }
 
-pub async fn get_all_users(pool: &SqlitePool) -> Result<Vec<User>, AppError> {
-    repository::get_all_users(pool).await
+/// Администратор закрывает вход по старому паролю и отправляет пользователю ссылку.
+/// Все сессии отзываются: если пароль утёк, вошедший по нему теряет доступ
+pub async fn force_password_reset(
+    pool: &SqlitePool,
+    notifier: Arc<dyn ResetNotifier>,
+    user_id: i64,
+    actor_id: i64,
+    app_url: &str,
+) -> Result<(), AppError> {
+    let user = repository::get_user_by_id(pool, user_id).await?;
+    repository::set_password_reset_required(pool, user.id).await?;
+    repository::revoke_user_sessions(pool, user.id).await?;
+
+    let details = format!("by user {}", actor_id);
+    let email = Some(user.email.as_str());
+    audit::record(pool, "password.reset_forced", Some(user.id), email, None, Some(&details)).await;
+    send_reset_link(pool, notifier, user, app_url).await
 }
 
-pub async fn get_user_by_id(pool: &SqlitePool, id: i64) -> Result<User, AppError> {
-    repository::get_user_by_id(pool, id).await
+/// Ссылка отправляется в фоне: время ответа не зависит от почтового сервера
+/// и не выдаёт, нашёлся ли пользователь
+async fn send_reset_link(
+    pool: &SqlitePool,
+    notifier: Arc<dyn ResetNotifier>,
+    user: User,
+    app_url: &str,
+) -> Result<(), AppError> {
+    let token = auth::generate_token();
+    let expires_at = Utc::now() + Duration::minutes(passwords::RESET_TOKEN_MINUTES);
+    repository::create_password_reset_token(pool, user.id, &auth::hash_token(&token), expires_at)
+        .await?;
+
+    let link = format!("{}/?reset_token={}", app_url.trim_end_matches('/'), token);
+    actix_web::rt::spawn(async move {
+        if let Err(e) = notifier.send_reset_link(&user, &link).await {
+            tracing::error!("Failed to send password reset link to user {}: {}", user.id, e);
+        }
+    });
+    Ok(())
 }
 
-pub async fn delete_user(pool: &SqlitePool, user_id: i64) -> Result<(), AppError> {
-    repository::delete_user(pool, user_id).await
+/// Новый пароль по ссылке. После сброса все сессии отзываются
+pub async fn reset_password(
+    pool: &SqlitePool,
+    passwords: &PasswordPolicy,
+    req: ResetPasswordRequest,
+) -> Result<(), AppError> {
+    let invalid = || AppError::BadRequest("Reset link is invalid or expired".to_string());
+
+    let token = repository::find_password_reset_token(pool, &auth::hash_token(&req.token))
+        .await?
+        .ok_or_else(invalid)?;
+    if token.used_at.is_some() || token.expires_at < Utc::now() {
+        return Err(invalid());
+    }
+    passwords.check(&req.new_password)?;
+
+    let new_hash = auth::hash_password(&req.new_password)?;
+    if !repository::reset_password_with_token(pool, &token, &new_hash).await? {
+        return Err(invalid());
+    }
+    repository::revoke_user_sessions(pool, token.user_id).await?;
+
+    audit::record(pool, "password.reset", Some(token.user_id), None, None, None).await;
+    Ok(())
 }
 
-// ============ Tasks ============
+// ============ Two-factor ============
 
-pub async fn create_task(
+async fn two_factor_enabled(pool: &SqlitePool, user_id: i64) -> Result<bool, AppError> {
+    Ok(repository::get_user_totp(pool, user_id)
+        .await?
+        .is_some_and(|totp| totp.enabled))
+}
+
+/// Роль требует 2FA, а пользователь её ещё не включил
+async fn two_factor_setup_required(pool: &SqlitePool, user: &User) -> Result<bool, AppError> {
+    Ok(role_requires_two_factor(pool, &user.role).await?
+        && !two_factor_enabled(pool, user.id).await?)
+}
+
+async fn role_requires_two_factor(pool: &SqlitePool, role: &str) -> Result<bool, AppError> {
+    Ok(repository::get_role(pool, role)
+        .await?
+        .is_some_and(|r| r.require_two_factor))
+}
+
+/// Код из приложения или, если не подошёл, резервный код
+async fn verify_second_factor(
     pool: &SqlitePool,
-    ml_client: &MlClient,
-    req: CreateTaskRequest,
-    created_by: i64,
-) -> Result<Task, AppError> {
-    let predicted_hours = ml_client
-        .predict_time_safe(&req.title, req.description.as_deref())
-        .await;
+    user: &User,
+    code: &str,
+    ip: Option<&str>,
+) -> Result<bool, AppError> {
+    if verify_totp_code(pool, user.id, code).await? {
+        return Ok(true);
+    }
 
-    repository::create_task(pool, &req, created_by, predicted_hours).await
+    let code_hash = auth::hash_token(&totp::normalize_recovery_code(code));
+    let used = repository::use_recovery_code(pool, user.id, &code_hash).await?;
+    if used {
+        let email = Some(user.email.as_str());
+        audit::record(pool, "2fa.recovery_code_used", Some(user.id), email, ip, None).await;
+    }
+    Ok(used)
 }
 
-pub async fn get_all_tasks(pool: &SqlitePool) -> Result<Vec<Task>, AppError> {
-    repository::get_all_tasks(pool).await
+async fn verify_totp_code(pool: &SqlitePool, user_id: i64, code: &str) -> Result<bool, AppError> {
+    let Some(secret) = repository::get_user_totp(pool, user_id)
+        .await?
+        .filter(|totp| totp.enabled)
+    else {
+        return Ok(false);
+    };
+
+    match totp::verify(&secret.secret, code, Utc::now().timestamp(), secret.last_used_step) {
+        Some(step) => repository::use_totp_step(pool, user_id, step).await,
+        None => Ok(false),
+    }
 }
 
-pub async fn get_task_by_id(pool: &SqlitePool, id: i64) -> Result<Task, AppError> {
-    repository::get_task_by_id(pool, id).await
+fn hash_recovery_codes(codes: &[String]) -> Vec<String> {
+    codes
+        .iter()
+        .map(|code| auth::hash_token(&totp::normalize_recovery_code(code)))
+        .collect()
 }
 
-pub async fn update_task(
+pub async fn get_two_factor_status(
     pool: &SqlitePool,
-    id: i64,
-    req: UpdateTaskRequest,
-) -> Result<Task, AppError> {
-    if let Some(ref status) = req.status {
-        if !["todo", "in_progress", "done"].contains(&status.as_str()) {
-            return Err(AppError::BadRequest(
-                "Status must be: todo, in_progress, done".to_string(),
-            ));
-        }
+    user_id: i64,
+) -> Result<TwoFactorStatus, AppError> {
+    let user = repository::get_user_by_id(pool, user_id).await?;
+    Ok(TwoFactorStatus {
+        enabled: two_factor_enabled(pool, user_id).await?,
+        recovery_codes_left: repository::count_recovery_codes(pool, user_id).await?,
+        required: role_requires_two_factor(pool, &user.role).await?,
+    })
+}
+
+/// Новый секрет ещё не защищает вход: сначала его нужно подтвердить кодом
+pub async fn setup_two_factor(pool: &SqlitePool, user_id: i64) -> Result<TwoFactorSetup, AppError> {
+    let user = repository::get_user_by_id(pool, user_id).await?;
+    if two_factor_enabled(pool, user.id).await? {
+        return Err(AppError::BadRequest(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let secret = totp::generate_secret();
+    repository::save_totp_secret(pool, user.id, &secret).await?;
+    Ok(TwoFactorSetup {
+        otpauth_uri: totp::otpauth_uri(totp::ISSUER, &user.email, &secret),
+        secret,
+    })
+}
+
+/// Код из приложения доказывает, что секрет сохранён. В ответе резервные коды
+pub async fn confirm_two_factor(
+    pool: &SqlitePool,
+    user_id: i64,
+    code: &str,
+) -> Result<RecoveryCodes, AppError> {
+    let pending = repository::get_user_totp(pool, user_id)
+        .await?
+        .filter(|totp| !totp.enabled)
+        .ok_or_else(|| AppError::BadRequest("Start two-factor setup first".to_string()))?;
+
+    let step = totp::verify(&pending.secret, code, Utc::now().timestamp(), None)
+        .ok_or_else(|| AppError::BadRequest("Invalid code".to_string()))?;
+
+    let codes = totp::generate_recovery_codes();
+    if !repository::enable_totp(pool, user_id, step, &hash_recovery_codes(&codes)).await? {
+        return Err(AppError::BadRequest("Start two-factor setup first".to_string()));
+    }
+
+    audit::record(pool, "2fa.enabled", Some(user_id), None, None, None).await;
+    Ok(RecoveryCodes { recovery_codes: codes })
+}
+
+/// Старые коды перестают действовать. Нужен код из приложения: резервным
+/// кодом нельзя выпустить себе новые
+pub async fn regenerate_recovery_codes(
+    pool: &SqlitePool,
+    user_id: i64,
+    code: &str,
+) -> Result<RecoveryCodes, AppError> {
+    if !two_factor_enabled(pool, user_id).await? {
+        return Err(AppError::BadRequest(
+            "Two-factor authentication is not enabled".to_string(),
+        ));
+    }
+    if !verify_totp_code(pool, user_id, code).await? {
+        return Err(AppError::BadRequest("Invalid code".to_string()));
+    }
+
+    let codes = totp::generate_recovery_codes();
+    repository::replace_recovery_codes(pool, user_id, &hash_recovery_codes(&codes)).await?;
+
+    audit::record(pool, "2fa.recovery_codes_regenerated", Some(user_id), None, None, None).await;
+    Ok(RecoveryCodes { recovery_codes: codes })
+}
+
+/// Отключить 2FA можно только с паролем и вторым фактором и только если роль
+/// её не требует: одного украденного пароля или сессии для этого мало.
+/// Неверные пароли и коды считаются вместе с ошибками входа
+pub async fn disable_two_factor(
+    pool: &SqlitePool,
+    user_id: i64,
+    req: &DisableTwoFactorRequest,
+    client: &ClientInfo,
+) -> Result<(), AppError> {
+    let user = repository::get_user_by_id(pool, user_id).await?;
+    let ip = client.ip.as_deref();
+    login_guard::check(pool, &user.email, ip).await?;
+
+    if !auth::verify_password(&req.password, &user.password_hash)? {
+        login_guard::record_failure(pool, &user.email, ip).await?;
+        return Err(AppError::BadRequest("Password is incorrect".to_string()));
+    }
+    if role_requires_two_factor(pool, &user.role).await? {
+        return Err(AppError::BadRequest(
+            "Two-factor authentication is required for your role".to_string(),
+        ));
     }
 
-    repository::update_task(pool, id, &req).await
+    // Незавершённую настройку можно отменить без кода: она ещё ничего не защищает
+    if two_factor_enabled(pool, user.id).await? {
+        if !verify_second_factor(pool, &user, &req.code, ip).await? {
+            audit::record(pool, "2fa.failed", Some(user.id), Some(&user.email), ip, None).await;
+            login_guard::record_failure(pool, &user.email, ip).await?;
+            return Err(AppError::BadRequest("Invalid code".to_string()));
+        }
+        audit::record(pool, "2fa.disabled", Some(user.id), Some(&user.email), ip, None).await;
+    }
+    repository::delete_totp(pool, user.id).await
 }
 
-pub async fn delete_task(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
-    repository::delete_task(pool, id).await
-}
\ No newline at end of file
+// ============ Users ============
+
+pub async fn create_user(
+    pool: &SqlitePool,
+    passwords: &PasswordPolicy,
+    creator: &AuthenticatedUser,
+    req: CreateUserRequest,
+) -> Result<User, AppError> {
+    let Some(role) = repository::get_role(pool, &req.role).await? else {
+        return Err(AppError::BadRequest(format!("Unknown role: {}", req.role)));
+    };
+    if !policy::can_assign_role(creator, &role) {
+        return Err(AppError::Forbidden);
+    }
+    passwords.check(&req.password)?;
+
+    let password_hash = auth::hash_password(&req.password)?;
+    repository::create_user(pool, &req.email, &password_hash, &req.name, &req.role).await
+}
+
+pub async fn get_all_users(pool: &SqlitePool) -> Result<Vec<User>, AppError> {
+    repository::get_all_users(pool).await
+}
+
+pub async fn get_user_by_id(pool: &SqlitePool, id: i64) -> Result<User, AppError> {
+    repository::get_user_by_id(pool, id).await
+}
+
+pub async fn delete_user(pool: &SqlitePool, user_id: i64) -> Result<(), AppError> {
+    repository::delete_user(pool, user_id).await
+}
+
+// ============ Roles ============
+
+/// Права роли для токена. Роль, которой нет в базе, не даёт ничего
+pub async fn role_permissions(pool: &SqlitePool, role: &str) -> Result<Vec<String>, AppError> {
+    Ok(repository::get_role(pool, role)
+        .await?
+        .map(|r| r.permissions.0)
+        .unwrap_or_default())
+}
+
+pub fn get_permissions() -> Vec<PermissionInfo> {
+    policy::PERMISSIONS
+        .iter()
+        .map(|&(name, description)| PermissionInfo { name, description })
+        .collect()
+}
+
+pub async fn get_all_roles(pool: &SqlitePool) -> Result<Vec<Role>, AppError> {
+    repository::get_all_roles(pool).await
+}
+
+async fn get_role(pool: &SqlitePool, name: &str) -> Result<Role, AppError> {
+    repository::get_role(pool, name)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Role not found".to_string()))
+}
+
+fn validate_permissions(permissions: &[String]) -> Result<Vec<String>, AppError> {
+    let unknown: Vec<&str> = permissions
+        .iter()
+        .map(String::as_str)
+        .filter(|p| !policy::is_known_permission(p))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "Unknown permissions: {}",
+            unknown.join(", ")
+        )));
+    }
+
+    let mut permissions = permissions.to_vec();
+    permissions.sort();
+    permissions.dedup();
+    Ok(permissions)
+}
+
+pub async fn create_role(pool: &SqlitePool, req: CreateRoleRequest) -> Result<Role, AppError> {
+    let name = req.name.trim().to_lowercase();
+    let valid_name = (1..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_name {
+        return Err(AppError::BadRequest(
+            "Role name must be 1-32 characters: letters, digits, '_' or '-'".to_string(),
+        ));
+    }
+
+    let permissions = validate_permissions(&req.permissions)?;
+    repository::create_role(
+        pool,
+        &name,
+        req.description.trim(),
+        &permissions,
+        req.require_two_factor,
+    )
+    .await
+}
+
+/// Права admin не меняются: иначе можно лишить доступа всех администраторов сразу.
+/// Требовать от них второй фактор можно
+pub async fn update_role(
+    pool: &SqlitePool,
+    name: &str,
+    req: UpdateRoleRequest,
+) -> Result<Role, AppError> {
+    let current = get_role(pool, name).await?;
+    if current.name == policy::ROLE_ADMIN && req.permissions.is_some() {
+        return Err(AppError::BadRequest(
+            "Permissions of the admin role cannot be changed".to_string(),
+        ));
+    }
+
+    let permissions = match req.permissions {
+        Some(permissions) => validate_permissions(&permissions)?,
+        None => current.permissions.0,
+    };
+    let description = req.description.unwrap_or(current.description);
+    let require_two_factor = req.require_two_factor.unwrap_or(current.require_two_factor);
+
+    repository::update_role(pool, name, description.trim(), &permissions, require_two_factor)
+        .await
+}
+
+pub async fn delete_role(pool: &SqlitePool, name: &str) -> Result<(), AppError> {
+    let role = get_role(pool, name).await?;
+    if role.builtin {
+        return Err(AppError::BadRequest("Built-in roles cannot be deleted".to_string()));
+    }
+
+    let users = repository::count_users_with_role(pool, name).await?;
+    if users > 0 {
+        return Err(AppError::BadRequest(format!(
+            "Role is assigned to {} user(s)",
+            users
+        )));
+    }
+
+    repository::delete_role(pool, name).await
+}
+
+// ============ Tasks ============
+
+pub async fn create_task(
+    pool: &SqlitePool,
+    broadcaster: &Broadcaster,
+    req: CreateTaskRequest,
+    created_by: i64,
+) -> Result<Task, AppError> {
+    let task = repository::create_task(pool, &req, created_by, None).await?;
+    // Оценку ML задача получит из очереди: создание не ждёт ML-сервис
+    if let Err(e) = jobs::enqueue(pool, &Job::PredictTask { task_id: task.id }).await {
+        tracing::error!("Failed to enqueue prediction for task {}: {}", task.id, e);
+    }
+    jobs::wake();
+
+    broadcaster
+        .publish(pool, realtime::EVENT_CREATED, task.id, Some(&task))
+        .await;
+    auto_watch(pool, task.id, &[Some(created_by), task.assignee_id]).await;
+    let mentioned = mentions::sync_task_mentions(pool, created_by, &task).await;
+    notifications::on_task_changed(pool, created_by, None, &task, &mentioned).await;
+    webhooks::enqueue(pool, webhooks::EVENT_CREATED, None, Some(&task)).await;
+    Ok(task)
+}
+
+/// Оценка трудоёмкости из очереди. Удалённой или уже оценённой задаче не нужна
+pub async fn predict_task_hours(
+    pool: &SqlitePool,
+    ml_client: &MlClient,
+    broadcaster: &Broadcaster,
+    task_id: i64,
+) -> Result<(), AppError> {
+    let task = match repository::get_task_by_id(pool, task_id).await {
+        Ok(task) if task.predicted_hours.is_none() => task,
+        Ok(_) | Err(AppError::NotFound(_)) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    let hours = ml_client.predict_time(&task.title, task.description.as_deref()).await?;
+    let task = repository::set_predicted_hours(pool, task_id, hours).await?;
+    broadcaster
+        .publish(pool, realtime::EVENT_UPDATED, task.id, Some(&task))
+        .await;
+    Ok(())
+}
+
+pub async fn get_all_tasks(
+    pool: &SqlitePool,
+    query: TaskListQuery,
+    user: &AuthenticatedUser,
+) -> Result<Vec<Task>, AppError> {
+    let user_id = user.id;
+    let mut task_query = match query.view {
+        Some(view_id) => {
+            let view = get_view(pool, view_id, user).await?;
+            view_task_query(&view, user_id)
+        }
+        None => TaskQuery::default(),
+    };
+    if query.watching {
+        task_query.watcher_id = Some(user_id);
+    }
+    let compiled = match query.q.as_deref().map(str::trim) {
+        Some(q) if !q.is_empty() => Some(query_language::compile(
+            q,
+            user_id,
+            repository::TASK_QUERY_PARAMS + 1,
+        )?),
+        _ => None,
+    };
+
+    repository::query_tasks(pool, &task_query, compiled.as_ref()).await
+}
+
+pub async fn get_task_by_id(pool: &SqlitePool, id: i64) -> Result<Task, AppError> {
+    repository::get_task_by_id(pool, id).await
+}
+
+pub async fn update_task(
+    pool: &SqlitePool,
+    broadcaster: &Broadcaster,
+    id: i64,
+    req: UpdateTaskRequest,
+    actor_id: i64,
+) -> Result<Task, AppError> {
+    if let Some(ref status) = req.status
+        && !TASK_STATUSES.contains(&status.as_str())
+    {
+        return Err(AppError::BadRequest(
+            "Status must be: todo, in_progress, done".to_string(),
+        ));
+    }
+
+    let before = repository::get_task_by_id(pool, id).await?;
+    let task = repository::update_task(pool, id, &req).await?;
+
+    broadcaster
+        .publish(pool, realtime::EVENT_UPDATED, task.id, Some(&task))
+        .await;
+    if task.assignee_id != before.assignee_id {
+        auto_watch(pool, task.id, &[task.assignee_id]).await;
+    }
+    let mentioned = if before.description != task.description {
+        mentions::sync_task_mentions(pool, actor_id, &task).await
+    } else {
+        Vec::new()
+    };
+    notifications::on_task_changed(pool, actor_id, Some(&before), &task, &mentioned).await;
+    webhooks::enqueue(pool, webhooks::EVENT_UPDATED, Some(&before), Some(&task)).await;
+    if before.status != "done" && task.status == "done" {
+        webhooks::enqueue(pool, webhooks::EVENT_COMPLETED, Some(&before), Some(&task)).await;
+    }
+    Ok(task)
+}
+
+pub async fn delete_task(
+    pool: &SqlitePool,
+    broadcaster: &Broadcaster,
+    id: i64,
+) -> Result<(), AppError> {
+    let before = repository::get_task_by_id(pool, id).await?;
+    repository::delete_task(pool, id).await?;
+
+    broadcaster
+        .publish(pool, realtime::EVENT_DELETED, id, None)
+        .await;
+    webhooks::enqueue(pool, webhooks::EVENT_DELETED, Some(&before), None).await;
+    Ok(())
+}
+
+pub async fn get_task_counts(pool: &SqlitePool) -> Result<TaskCounts, AppError> {
+    Ok(TaskCounts {
+        by_status: repository::count_tasks_by_status(pool).await?,
+        overdue: repository::count_overdue_tasks(pool).await?,
+    })
+}
+
+// ============ Calendar ============
+
+pub async fn get_calendar_feed_status(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<CalendarFeedStatus, AppError> {
+    let created_at = repository::get_calendar_token_created_at(pool, user_id).await?;
+    Ok(CalendarFeedStatus {
+        enabled: created_at.is_some(),
+        created_at,
+    })
+}
+
+/// Создаёт новый токен ленты. Старая ссылка, если была, перестаёт работать
+pub async fn create_calendar_token(pool: &SqlitePool, user_id: i64) -> Result<String, AppError> {
+    let token = auth::generate_token();
+    repository::set_calendar_token(pool, user_id, &auth::hash_token(&token)).await?;
+    Ok(token)
+}
+
+pub async fn revoke_calendar_token(pool: &SqlitePool, user_id: i64) -> Result<(), AppError> {
+    repository::delete_calendar_token(pool, user_id).await
+}
+
+pub async fn render_calendar(
+    pool: &SqlitePool,
+    token: &str,
+    app_url: &str,
+) -> Result<String, AppError> {
+    let user_id = repository::find_user_by_calendar_token(pool, &auth::hash_token(token))
+        .await?
+        .ok_or_else(|| AppError::NotFound("Calendar not found".to_string()))?;
+
+    let tasks = repository::get_calendar_tasks(pool, user_id).await?;
+    Ok(calendar::render(&tasks, app_url))
+}
+
+// ============ Comments ============
+
+pub async fn get_task_comments(pool: &SqlitePool, task_id: i64) -> Result<Vec<Comment>, AppError> {
+    repository::get_task_by_id(pool, task_id).await?;
+    repository::get_task_comments(pool, task_id).await
+}
+
+pub async fn create_comment(
+    pool: &SqlitePool,
+    task_id: i64,
+    author_id: i64,
+    req: CreateCommentRequest,
+) -> Result<Comment, AppError> {
+    let body = req.body.trim();
+    if body.is_empty() {
+        return Err(AppError::BadRequest("Comment must not be empty".to_string()));
+    }
+
+    let task = repository::get_task_by_id(pool, task_id).await?;
+    let comment = repository::create_comment(pool, task_id, author_id, body).await?;
+
+    auto_watch(pool, task_id, &[Some(author_id)]).await;
+    let mentioned = mentions::record_comment_mentions(pool, &task, &comment).await;
+    notifications::on_comment(pool, &task, &comment, &mentioned).await;
+    Ok(comment)
+}
+
+// ============ Saved views ============
+
+/// Переводит условия представления в выборку для конкретного пользователя
+fn view_task_query(view: &SavedView, user_id: i64) -> TaskQuery {
+    let filters = &view.filters.0;
+    TaskQuery {
+        status: filters.status.clone(),
+        assignee_id: if filters.assigned_to_me {
+            Some(user_id)
+        } else {
+            filters.assignee_id
+        },
+        unassigned: filters.unassigned,
+        created_by: filters.created_by_me.then_some(user_id),
+        watcher_id: filters.watching.then_some(user_id),
+        search: filters.search.clone().filter(|s| !s.trim().is_empty()),
+        sort_by: view.sort_by.clone(),
+        sort_desc: view.sort_desc,
+    }
+}
+
+fn validate_view(req: &CreateViewRequest) -> Result<(), AppError> {
+    if req.name.trim().is_empty() {
+        return Err(AppError::BadRequest("View name is required".to_string()));
+    }
+    if let Some(status) = &req.filters.status
+        && !TASK_STATUSES.contains(&status.as_str())
+    {
+        return Err(AppError::BadRequest(
+            "Status must be: todo, in_progress, done".to_string(),
+        ));
+    }
+    if req.filters.unassigned && (req.filters.assigned_to_me || req.filters.assignee_id.is_some()) {
+        return Err(AppError::BadRequest(
+            "Unassigned cannot be combined with an assignee".to_string(),
+        ));
+    }
+    if !VIEW_SORT_FIELDS.contains(&req.sort_by.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid sort field. Allowed: {}",
+            VIEW_SORT_FIELDS.join(", ")
+        )));
+    }
+    if !VIEW_DISPLAY_MODES.contains(&req.display.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid display mode. Allowed: {}",
+            VIEW_DISPLAY_MODES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Чужое личное представление для пользователя не существует
+pub async fn get_view(
+    pool: &SqlitePool,
+    id: i64,
+    user: &AuthenticatedUser,
+) -> Result<SavedView, AppError> {
+    let view = repository::get_view_by_id(pool, id).await?;
+    if !policy::is_allowed(user, Action::ViewView, Resource::View(&view)) {
+        return Err(AppError::NotFound("View not found".to_string()));
+    }
+    Ok(view)
+}
+
+pub async fn get_views(pool: &SqlitePool, user_id: i64) -> Result<Vec<SavedView>, AppError> {
+    repository::get_views_for_user(pool, user_id).await
+}
+
+pub async fn create_view(
+    pool: &SqlitePool,
+    owner_id: i64,
+    mut req: CreateViewRequest,
+) -> Result<SavedView, AppError> {
+    req.name = req.name.trim().to_string();
+    validate_view(&req)?;
+    repository::create_view(pool, owner_id, &req).await
+}
+
+pub async fn update_view(
+    pool: &SqlitePool,
+    current: SavedView,
+    req: UpdateViewRequest,
+) -> Result<SavedView, AppError> {
+    let merged = CreateViewRequest {
+        name: req.name.map(|n| n.trim().to_string()).unwrap_or(current.name),
+        shared: req.shared.unwrap_or(current.shared),
+        filters: req.filters.unwrap_or(current.filters.0),
+        sort_by: req.sort_by.unwrap_or(current.sort_by),
+        sort_desc: req.sort_desc.unwrap_or(current.sort_desc),
+        display: req.display.unwrap_or(current.display),
+    };
+    validate_view(&merged)?;
+
+    repository::update_view(pool, current.id, &merged).await
+}
+
+pub async fn delete_view(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    repository::delete_view(pool, id).await
+}
+
+// ============ Watchers ============
+
+/// Автор, исполнитель и комментаторы начинают следить за задачей сами.
+/// Ошибки только логируются, как и у остальных побочных эффектов
+async fn auto_watch(pool: &SqlitePool, task_id: i64, users: &[Option<i64>]) {
+    for &user_id in users.iter().flatten() {
+        if let Err(e) = repository::add_watcher(pool, task_id, user_id).await {
+            tracing::error!("Failed to add watcher {} to task {}: {}", user_id, task_id, e);
+        }
+    }
+}
+
+pub async fn watch_task(pool: &SqlitePool, task_id: i64, user_id: i64) -> Result<(), AppError> {
+    repository::get_task_by_id(pool, task_id).await?;
+    repository::add_watcher(pool, task_id, user_id).await
+}
+
+pub async fn unwatch_task(pool: &SqlitePool, task_id: i64, user_id: i64) -> Result<(), AppError> {
+    repository::get_task_by_id(pool, task_id).await?;
+    repository::remove_watcher(pool, task_id, user_id).await
+}
+
+pub async fn get_task_watchers(pool: &SqlitePool, task_id: i64) -> Result<Vec<User>, AppError> {
+    repository::get_task_by_id(pool, task_id).await?;
+    repository::get_task_watchers(pool, task_id).await
+}
+
+// ============ Import / Export ============
+
+/// Отдаёт задачи по фильтру потоком, не собирая весь экспорт в памяти
+pub fn export_tasks(
+    pool: SqlitePool,
+    filter: TaskFilter,
+    format: TransferFormat,
+) -> impl Stream<Item = Result<Bytes, AppError>> {
+    let (tx, rx) = mpsc::channel::<Result<Bytes, AppError>>(64);
+
+    actix_web::rt::spawn(async move {
+        let mut encoder = TaskEncoder::new(format);
+
+        match encoder.header() {
+            Ok(Some(header)) => {
+                if tx.send(Ok(header)).await.is_err() {
+                    return;
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+        }
+
+        let mut rows = repository::stream_tasks(&pool, &filter);
+        while let Some(row) = rows.next().await {
+            let chunk = row.and_then(|task| encoder.encode(&task));
+            let failed = chunk.is_err();
+            // Клиент отключился или строка не закодировалась - дальше не читаем
+            if tx.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+
+        if let Some(footer) = encoder.footer() {
+            let _ = tx.send(Ok(footer)).await;
+        }
+    });
+
+    futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    })
+}
+
+pub async fn import_tasks(
+    pool: &SqlitePool,
+    req: ImportTasksRequest,
+    created_by: i64,
+) -> Result<ImportReport, AppError> {
+    if let Some(field) = req.mapping.keys().find(|f| !IMPORT_FIELDS.contains(&f.as_str())) {
+        return Err(AppError::BadRequest(format!(
+            "Unknown field in mapping: {}. Allowed: {}",
+            field,
+            IMPORT_FIELDS.join(", ")
+        )));
+    }
+
+    let records = import_export::parse_records(req.format, &req.data)?;
+
+    let users_by_email: HashMap<String, i64> = repository::get_all_users(pool)
+        .await?
+        .into_iter()
+        .map(|u| (u.email.to_lowercase(), u.id))
+        .collect();
+
+    let mut tasks = Vec::with_capacity(records.len());
+    let mut errors = Vec::new();
+
+    for (row, record) in &records {
+        match validate_import_row(record, &req.mapping, &users_by_email) {
+            Ok(task) => tasks.push(task),
+            Err(row_errors) => errors.extend(row_errors.into_iter().map(|(field, message)| {
+                ImportRowError {
+                    row: *row,
+                    field,
+                    message,
+                }
+            })),
+        }
+    }
+
+    let mut report = ImportReport {
+        dry_run: req.dry_run,
+        total_rows: records.len(),
+        imported: 0,
+        errors,
+    };
+
+    if req.dry_run || !report.errors.is_empty() {
+        return Ok(report);
+    }
+
+    let ids = repository::insert_imported_tasks(pool, &tasks, created_by).await?;
+    report.imported = ids.len();
+
+    // Оценку ML получат только задачи, для которых её не было в файле
+    let unestimated = ids.into_iter().zip(&tasks).filter(|(_, t)| t.predicted_hours.is_none());
+    for (task_id, _) in unestimated {
+        if let Err(e) = jobs::enqueue(pool, &Job::PredictTask { task_id }).await {
+            tracing::error!("Failed to enqueue prediction for task {}: {}", task_id, e);
+        }
+    }
+    jobs::wake();
+
+    tracing::info!("User {} imported {} tasks", created_by, report.imported);
+    Ok(report)
+}
+
+type RowErrors = Vec<(Option<String>, String)>;
+
+fn validate_import_row(
+    record: &Record,
+    mapping: &HashMap<String, String>,
+    users_by_email: &HashMap<String, i64>,
+) -> Result<ImportedTask, RowErrors> {
+    let value = |field: &str| {
+        let column = mapping.get(field).map(String::as_str).unwrap_or(field);
+        record
+            .get(column)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+
+    let mut errors: RowErrors = Vec::new();
+    let mut error = |field: &str, message: String| errors.push((Some(field.to_string()), message));
+
+    let title = value("title");
+    if title.is_none() {
+        error("title", "Title is required".to_string());
+    }
+
+    let status = value("status").unwrap_or_else(|| "todo".to_string());
+    if !TASK_STATUSES.contains(&status.as_str()) {
+        error("status", format!("Unknown status: {}", status));
+    }
+
+    let assignee_id = match value("assignee_email") {
+        None => None,
+        Some(email) => match users_by_email.get(&email.to_lowercase()) {
+            Some(id) => Some(*id),
+            None => {
+                error("assignee_email", format!("No user with email {}", email));
+                None
+            }
+        },
+    };
+
+    let mut hours = |field: &str| match value(field).map(|v| v.parse::<f64>()) {
+        None => None,
+        Some(Ok(h)) if h.is_finite() && h >= 0.0 => Some(h),
+        Some(_) => {
+            error(field, "Must be a non-negative number".to_string());
+            None
+        }
+    };
+    let predicted_hours = hours("predicted_hours");
+    let actual_hours = hours("actual_hours");
+
+    let mut date = |field: &str| match value(field).map(|v| v.parse::<NaiveDate>()) {
+        None => None,
+        Some(Ok(d)) => Some(d),
+        Some(Err(_)) => {
+            error(field, "Must be a date in YYYY-MM-DD format".to_string());
+            None
+        }
+    };
+    let due_date = date("due_date");
+    let planned_date = date("planned_date");
+
+    match title {
+        Some(title) if errors.is_empty() => Ok(ImportedTask {
+            title,
+            description: value("description"),
+            status,
+            assignee_id,
+            predicted_hours,
+            actual_hours,
+            due_date,
+            planned_date,
+        }),
+        _ => Err(errors),
+    }
+}
+
+pub async fn import_external(
+    pool: &SqlitePool,
+    source: ImportSource,
+    req: ExternalImportRequest,
+    importer_id: i64,
+) -> Result<ExternalImportReport, AppError> {
+    importers::run(pool, source, req, importer_id).await
+}
+
+// ============ Webhooks ============
+
+fn validate_webhook(url: &str, events: &[String]) -> Result<(), AppError> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(AppError::BadRequest("Webhook URL must be http(s)".to_string()));
+    }
+    if events.is_empty() {
+        return Err(AppError::BadRequest("At least one event is required".to_string()));
+    }
+    if let Some(event) = events.iter().find(|e| !WEBHOOK_EVENTS.contains(&e.as_str())) {
+        return Err(AppError::BadRequest(format!(
+            "Unknown event: {}. Allowed: {}",
+            event,
+            WEBHOOK_EVENTS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+pub async fn create_webhook(
+    pool: &SqlitePool,
+    req: CreateWebhookRequest,
+    created_by: i64,
+) -> Result<CreatedWebhookResponse, AppError> {
+    validate_webhook(&req.url, &req.events)?;
+
+    let secret = req.secret.unwrap_or_else(auth::generate_token);
+    let subscription =
+        repository::create_webhook(pool, &req.url, &secret, &req.events, req.active, created_by)
+            .await?;
+
+    Ok(CreatedWebhookResponse {
+        subscription,
+        secret,
+    })
+}
+
+pub async fn get_all_webhooks(pool: &SqlitePool) -> Result<Vec<WebhookSubscription>, AppError> {
+    repository::get_all_webhooks(pool).await
+}
+
+pub async fn update_webhook(
+    pool: &SqlitePool,
+    id: i64,
+    req: UpdateWebhookRequest,
+) -> Result<WebhookSubscription, AppError> {
+    let current = repository::get_webhook_by_id(pool, id).await?;
+
+    let url = req.url.unwrap_or(current.url);
+    let events = req.events.unwrap_or(current.events.0);
+    validate_webhook(&url, &events)?;
+
+    repository::update_webhook(pool, id, &url, &events, req.active.unwrap_or(current.active)).await
+}
+
+pub async fn delete_webhook(pool: &SqlitePool, id: i64) -> Result<(), AppError> {
+    repository::delete_webhook(pool, id).await
+}
+
+pub async fn get_webhook_deliveries(
+    pool: &SqlitePool,
+    subscription_id: i64,
+) -> Result<Vec<WebhookDelivery>, AppError> {
+    repository::get_webhook_by_id(pool, subscription_id).await?;
+    repository::get_webhook_deliveries(pool, subscription_id).await
+}
+
+pub async fn redeliver_webhook(pool: &SqlitePool, delivery_id: i64) -> Result<WebhookDelivery, AppError> {
+    let delivery = repository::reset_webhook_delivery(pool, delivery_id).await?;
+    jobs::enqueue(pool, &Job::DeliverWebhook { delivery_id }).await?;
+    jobs::wake();
+    Ok(delivery)
+}
+
+// ============ Jobs ============
+
+pub async fn get_jobs(pool: &SqlitePool, query: JobsQuery) -> Result<Vec<JobRecord>, AppError> {
+    if let Some(ref status) = query.status
+        && !JOB_STATUSES.contains(&status.as_str())
+    {
+        return Err(AppError::BadRequest(format!(
+            "Status must be one of: {}",
+            JOB_STATUSES.join(", ")
+        )));
+    }
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    repository::get_jobs(pool, query.status.as_deref(), query.kind.as_deref(), limit).await
+}
+
+pub async fn get_job(pool: &SqlitePool, id: i64) -> Result<JobRecord, AppError> {
+    repository::get_job(pool, id).await
+}
+
+/// Перезапускает задачу, у которой кончились попытки
+pub async fn retry_job(pool: &SqlitePool, id: i64) -> Result<JobRecord, AppError> {
+    let job = repository::get_job(pool, id).await?;
+    if job.status != JOB_DEAD {
+        return Err(AppError::BadRequest(format!(
+            "Only dead jobs can be retried, this one is {}",
+            job.status
+        )));
+    }
+    let job = repository::retry_dead_job(pool, id)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Job is no longer dead".to_string()))?;
+    jobs::wake();
+    Ok(job)
+}
+
+// ============ Email ============
+
+pub async fn get_email_preferences(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<EmailPreferences, AppError> {
+    repository::get_email_preferences(pool, user_id).await
+}
+
+pub async fn update_email_preferences(
+    pool: &SqlitePool,
+    user_id: i64,
+    req: EmailPreferences,
+) -> Result<EmailPreferences, AppError> {
+    if !EMAIL_MODES.contains(&req.mode.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid email mode. Allowed: {}",
+            EMAIL_MODES.join(", ")
+        )));
+    }
+
+    repository::set_email_mode(pool, user_id, &req.mode).await?;
+    Ok(req)
+}
+
+// ============ Notifications ============
+
+pub async fn get_notifications(
+    pool: &SqlitePool,
+    user_id: i64,
+    unread_only: bool,
+) -> Result<Vec<Notification>, AppError> {
+    repository::get_notifications(pool, user_id, unread_only).await
+}
+
+pub async fn count_unread_notifications(pool: &SqlitePool, user_id: i64) -> Result<i64, AppError> {
+    repository::count_unread_notifications(pool, user_id).await
+}
+
+pub async fn mark_notification_read(
+    pool: &SqlitePool,
+    user_id: i64,
+    id: i64,
+) -> Result<Notification, AppError> {
+    repository::mark_notification_read(pool, user_id, id).await
+}
+
+pub async fn mark_all_notifications_read(pool: &SqlitePool, user_id: i64) -> Result<u64, AppError> {
+    repository::mark_all_notifications_read(pool, user_id).await
+}
+
+// ============ Health ============
+
+/// Дольше проверка компонента не ждёт: зависшая база для нас тоже недоступна
+const HEALTH_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Готовность принимать запросы. Без ML-сервиса задачи создаются без прогноза,
+/// поэтому его недоступность - это degraded, а не down
+pub async fn check_readiness(pool: &SqlitePool, ml_client: &MlClient) -> ReadinessReport {
+    let (database, migrations, ml_service) = tokio::join!(
+        check_component(true, async {
+            repository::ping(pool).await.map_err(|e| {
+                tracing::warn!("Readiness: database check failed: {}", e);
+                "Database unavailable".to_string()
+            })
+        }),
+        check_component(true, check_migrations(pool)),
+        check_component(false, async {
+            ml_client.check_health().await.map_err(|e| {
+                tracing::warn!("Readiness: ML service check failed: {}", e);
+                "ML service unavailable".to_string()
+            })
+        }),
+    );
+    let components = BTreeMap::from([
+        ("database", database),
+        ("migrations", migrations),
+        ("ml_service", ml_service),
+    ]);
+
+    let mut status = HealthStatus::Up;
+    for component in components.values().filter(|c| c.status == HealthStatus::Down) {
+        if component.required {
+            status = HealthStatus::Down;
+            break;
+        }
+        status = HealthStatus::Degraded;
+    }
+    ReadinessReport { status, components }
+}
+
+/// Все ли миграции из бинарника применены к базе
+async fn check_migrations(pool: &SqlitePool) -> Result<(), String> {
+    let applied = repository::get_applied_migrations(pool).await.map_err(|e| {
+        tracing::warn!("Readiness: cannot read applied migrations: {}", e);
+        "Cannot read applied migrations".to_string()
+    })?;
+    let pending: Vec<String> = repository::MIGRATOR
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| migration.version.to_string())
+        .collect();
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        let error = format!("Pending migrations: {}", pending.join(", "));
+        tracing::warn!("Readiness: {}", error);
+        Err(error)
+    }
+}
+
+async fn check_component(
+    required: bool,
+    check: impl Future<Output = Result<(), String>>,
+) -> ComponentHealth {
+    let started = Instant::now();
+    let result = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("No answer in {}s", HEALTH_CHECK_TIMEOUT.as_secs())));
+
+    ComponentHealth {
+        status: if result.is_ok() { HealthStatus::Up } else { HealthStatus::Down },
+        required,
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: result.err(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ViewFilters;
+
+    fn record(fields: &[(&str, &str)]) -> Record {
+        fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn users() -> HashMap<String, i64> {
+        HashMap::from([("ann@example.test".to_string(), 7)])
+    }
+
+    #[test]
+    fn import_row_uses_mapping_and_defaults() {
+        let mapping = HashMap::from([("title".to_string(), "Summary".to_string())]);
+        let row = record(&[
+            ("Summary", " Write docs "),
+            ("assignee_email", "Ann@Example.test"),
+            ("predicted_hours", "1.5"),
+            ("due_date", "2026-03-01"),
+            ("description", "  "),
+        ]);
+
+        let task = validate_import_row(&row, &mapping, &users()).expect("valid row");
+        assert_eq!(task.title, "Write docs");
+        assert_eq!(task.status, "todo");
+        assert_eq!(task.assignee_id, Some(7));
+        assert_eq!(task.predicted_hours, Some(1.5));
+        assert_eq!(task.due_date, NaiveDate::from_ymd_opt(2026, 3, 1));
+        assert_eq!(task.description, None);
+    }
+
+    #[test]
+    fn import_row_reports_every_bad_field() {
+        let row = record(&[
+            ("status", "blocked"),
+            ("assignee_email", "nobody@example.test"),
+            ("predicted_hours", "-1"),
+            ("actual_hours", "NaN"),
+            ("planned_date", "01/02/2026"),
+        ]);
+
+        let errors = validate_import_row(&row, &HashMap::new(), &users()).expect_err("invalid");
+        let fields: Vec<&str> = errors.iter().filter_map(|(f, _)| f.as_deref()).collect();
+        assert_eq!(
+            fields,
+            ["title", "status", "assignee_email", "predicted_hours", "actual_hours", "planned_date"]
+        );
+    }
+
+    fn view_request(value: serde_json::Value) -> CreateViewRequest {
+        serde_json::from_value(value).expect("view request")
+    }
+
+    #[test]
+    fn view_defaults_are_valid() {
+        let req = view_request(serde_json::json!({"name": "Мои"}));
+        assert_eq!((req.sort_by.as_str(), req.sort_desc), ("created_at", true));
+        assert!(validate_view(&req).is_ok());
+    }
+
+    #[test]
+    fn view_rejects_bad_fields() {
+        for value in [
+            serde_json::json!({"name": "  "}),
+            serde_json::json!({"name": "v", "filters": {"status": "blocked"}}),
+            serde_json::json!({"name": "v", "filters": {"unassigned": true, "assignee_id": 3}}),
+            serde_json::json!({"name": "v", "filters": {"unassigned": true, "assigned_to_me": true}}),
+            serde_json::json!({"name": "v", "sort_by": "password_hash"}),
+            serde_json::json!({"name": "v", "display": "gantt"}),
+        ] {
+            let req = view_request(value.clone());
+            assert!(matches!(validate_view(&req), Err(AppError::BadRequest(_))), "{}", value);
+        }
+    }
+
+    #[test]
+    fn view_filters_resolve_for_the_viewer() {
+        let view = SavedView {
+            id: 1,
+            owner_id: 2,
+            name: "v".to_string(),
+            shared: true,
+            filters: sqlx::types::Json(ViewFilters {
+                assigned_to_me: true,
+                assignee_id: Some(9),
+                created_by_me: true,
+                watching: true,
+                search: Some("  ".to_string()),
+                ..ViewFilters::default()
+            }),
+            sort_by: "due_date".to_string(),
+            sort_desc: false,
+            display: "list".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        // Общее представление показывает задачи того, кто его открыл, а не владельца
+        let query = view_task_query(&view, 5);
+        assert_eq!(query.assignee_id, Some(5));
+        assert_eq!(query.created_by, Some(5));
+        assert_eq!(query.watcher_id, Some(5));
+        assert_eq!(query.search, None);
+        assert_eq!((query.sort_by.as_str(), query.sort_desc), ("due_date", false));
+    }
+
+    async fn user_with_two_factor(pool: &SqlitePool, recovery_code: &str) -> User {
+        let hash = auth::hash_password("password123").expect("hash");
+        let user =
+            repository::create_user(pool, "ann@example.test", &hash, "Ann", policy::ROLE_MEMBER)
+                .await
+                .expect("user");
+        let secret = totp::generate_secret();
+        repository::save_totp_secret(pool, user.id, &secret).await.expect("totp");
+        let codes = hash_recovery_codes(&[recovery_code.to_string()]);
+        repository::enable_totp(pool, user.id, 0, &codes).await.expect("enable");
+        user
+    }
+
+    fn disable_request(password: &str, code: &str) -> DisableTwoFactorRequest {
+        DisableTwoFactorRequest {
+            password: password.to_string(),
+            code: code.to_string(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn disabling_two_factor_needs_password_and_second_factor() {
+        let pool = repository::test_pool().await;
+        let user = user_with_two_factor(&pool, "abcde-12345").await;
+        let client = ClientInfo::default();
+
+        for req in [
+            disable_request("password123", ""),
+            disable_request("password123", "000000"),
+            disable_request("wrong", "abcde-12345"),
+        ] {
+            let result = disable_two_factor(&pool, user.id, &req, &client).await;
+            assert!(matches!(result, Err(AppError::BadRequest(_))));
+            assert!(two_factor_enabled(&pool, user.id).await.unwrap());
+        }
+
+        // Ошибки считаются как при входе: после бесплатных попыток - пауза
+        let req = disable_request("password123", "ABCDE12345");
+        let throttled = disable_two_factor(&pool, user.id, &req, &client).await;
+        assert!(matches!(throttled, Err(AppError::TooManyRequests(_))));
+        login_guard::record_success(&pool, user.id, &user.email, None).await.unwrap();
+
+        // Резервный код годится вместо кода из приложения, регистр и дефис не важны
+        disable_two_factor(&pool, user.id, &req, &client).await.expect("disabled");
+        assert!(!two_factor_enabled(&pool, user.id).await.unwrap());
+    }
+
+    /// ML-сервис, который отвечает только на проверку здоровья
+    fn start_healthy_ml() -> MlClient {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind ml");
+        let url = format!("http://{}", listener.local_addr().expect("ml address"));
+        let server = actix_web::HttpServer::new(|| {
+            actix_web::App::new()
+                .route("/health", actix_web::web::get().to(actix_web::HttpResponse::Ok))
+        })
+        .workers(1)
+        .listen(listener)
+        .expect("listen ml")
+        .run();
+        actix_web::rt::spawn(server);
+        MlClient::new(url)
+    }
+
+    #[actix_web::test]
+    async fn readiness_needs_database_and_migrations_but_not_ml() {
+        let pool = repository::test_pool().await;
+
+        let ready = check_readiness(&pool, &start_healthy_ml()).await;
+        assert_eq!(ready.status, HealthStatus::Up);
+        assert!(ready.components.values().all(|c| c.status == HealthStatus::Up));
+        assert!(ready.components.values().all(|c| c.error.is_none()));
+
+        // Без ML-сервиса приложение работает, только без прогнозов
+        let unreachable = MlClient::new("http://127.0.0.1:9".to_string());
+        let degraded = check_readiness(&pool, &unreachable).await;
+        assert_eq!(degraded.status, HealthStatus::Degraded);
+        let ml = &degraded.components["ml_service"];
+        assert_eq!(ml.status, HealthStatus::Down);
+        assert!(!ml.required);
+        assert_eq!(ml.error.as_deref(), Some("ML service unavailable"));
+
+        let latest = repository::MIGRATOR.iter().map(|m| m.version).max().expect("migrations");
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = ?")
+            .bind(latest)
+            .execute(&pool)
+            .await
+            .expect("forget migration");
+        let down = check_readiness(&pool, &unreachable).await;
+        assert_eq!(down.status, HealthStatus::Down);
+        assert_eq!(down.components["database"].status, HealthStatus::Up);
+        let migrations = &down.components["migrations"];
+        assert_eq!(migrations.status, HealthStatus::Down);
+        let pending = format!("Pending migrations: {}", latest);
+        assert_eq!(migrations.error.as_deref(), Some(pending.as_str()));
+    }
+}
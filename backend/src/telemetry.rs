@@ -0,0 +1,167 @@
+//! Логи запросов: у каждого запроса свой X-Request-Id и span с пользователем,
+//! маршрутом и временем ответа, так что все строки одного запроса связаны.
+//! Формат - текст или JSON, уровни задаются фильтром в синтаксисе RUST_LOG.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use std::time::Instant;
+use tracing::Instrument;
+use tracing::field::Empty;
+use tracing_subscriber::EnvFilter;
+use crate::config::Config;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Чужой идентификатор принимаем, только если он короткий и без мусора
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Подписчик tracing по настройкам. Фильтр уже проверен при загрузке настроек
+pub fn init(config: &Config) {
+    let builder = tracing_subscriber::fmt().with_env_filter(EnvFilter::new(&config.log_filter));
+    if config.log_format == "json" {
+        builder.json().with_current_span(true).with_span_list(false).init();
+    } else {
+        builder.init();
+    }
+}
+
+/// Идентификатор запроса, который сейчас обрабатывается, - для исходящих вызовов
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Выполняет фоновую работу от имени запроса, который её поставил
+pub async fn with_request_id<F: Future>(request_id: Option<String>, fut: F) -> F::Output {
+    match request_id {
+        Some(request_id) => REQUEST_ID.scope(request_id, fut).await,
+        None => fut.await,
+    }
+}
+
+/// Пользователь запроса, как только он известен
+pub fn record_user(user_id: i64) {
+    tracing::Span::current().record("user_id", user_id);
+}
+
+/// Middleware: берёт X-Request-Id клиента или выдаёт новый, возвращает его в
+/// ответе и пишет строку о завершении запроса
+pub async fn request_context(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(String::from)
+        .unwrap_or_else(new_request_id);
+
+    // Путь не пишем: в нём бывают секреты (ссылка на календарь), маршрут - шаблон
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        route = Empty,
+        user_id = Empty,
+        status = Empty,
+        latency_ms = Empty,
+    );
+    let started = Instant::now();
+    let result = REQUEST_ID
+        .scope(request_id.clone(), next.call(req).instrument(span.clone()))
+        .await;
+    span.record("latency_ms", started.elapsed().as_millis() as u64);
+
+    let _entered = span.enter();
+    match result {
+        Ok(mut res) => {
+            span.record("route", res.request().match_pattern().as_deref().unwrap_or("unmatched"));
+            span.record("status", res.status().as_u16());
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            if res.status().is_server_error() {
+                tracing::warn!(target: "http_log", "request failed");
+            } else {
+                tracing::info!(target: "http_log", "request finished");
+            }
+            Ok(res)
+        }
+        Err(e) => {
+            tracing::warn!(target: "http_log", "request failed: {}", e);
+            Err(e)
+        }
+    }
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+fn new_request_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body};
+    use actix_web::{App, HttpResponse, web};
+
+    #[test]
+    fn request_id_from_client_must_be_short_and_plain() {
+        for id in ["trace-42", "a1b2.c3:d4_e5", &"x".repeat(MAX_REQUEST_ID_LENGTH)] {
+            assert!(is_valid_request_id(id), "{}", id);
+        }
+        for id in ["", "not a valid id", "id\nnext", "идентификатор", &"x".repeat(129)] {
+            assert!(!is_valid_request_id(id), "{}", id);
+        }
+        assert!(is_valid_request_id(&new_request_id()));
+    }
+
+    #[actix_web::test]
+    async fn request_id_is_kept_or_replaced_and_visible_to_the_handler() {
+        let app = init_service(
+            App::new().wrap(from_fn(request_context)).route(
+                "/",
+                web::get().to(|| async {
+                    HttpResponse::Ok().body(current_request_id().unwrap_or_default())
+                }),
+            ),
+        )
+        .await;
+        let call = |id: &'static str| {
+            let app = &app;
+            async move {
+                let req = TestRequest::get().insert_header((REQUEST_ID_HEADER, id));
+                let res = call_service(app, req.to_request()).await;
+                let header = res.headers().get(REQUEST_ID_HEADER).unwrap();
+                let header = header.to_str().unwrap().to_string();
+                let body = String::from_utf8(read_body(res).await.to_vec()).unwrap();
+                (header, body)
+            }
+        };
+
+        assert_eq!(call("trace-42").await, ("trace-42".to_string(), "trace-42".to_string()));
+        let (generated, seen) = call("not a valid id").await;
+        assert_eq!(generated.len(), 32);
+        assert_eq!(generated, seen);
+        assert_eq!(current_request_id(), None);
+    }
+
+    #[actix_web::test]
+    async fn background_work_runs_with_the_request_id() {
+        let id = with_request_id(Some("trace-42".to_string()), async { current_request_id() });
+        assert_eq!(id.await.as_deref(), Some("trace-42"));
+        assert_eq!(with_request_id(None, async { current_request_id() }).await, None);
+    }
+}
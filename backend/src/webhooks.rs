@@ -0,0 +1,183 @@
+use crate::errors::AppError;
+use crate::jobs::{self, Attempt, Job};
+use crate::models::{Task, WebhookPayload, WebhookSubscription};
+use crate::repository;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+pub const EVENT_CREATED: &str = "task.created";
+pub const EVENT_UPDATED: &str = "task.updated";
+pub const EVENT_COMPLETED: &str = "task.completed";
+pub const EVENT_DELETED: &str = "task.deleted";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Кладёт событие в outbox для всех активных подписок на него и ставит доставки в очередь.
+/// Ошибки только логируются: сбой вебхуков не должен ломать изменение задачи
+pub async fn enqueue(pool: &SqlitePool, event: &str, before: Option<&Task>, after: Option<&Task>) {
+    let subscriptions = match repository::get_webhooks_for_event(pool, event).await {
+        Ok(subscriptions) => subscriptions,
+        Err(e) => {
+            tracing::error!("Failed to load webhook subscriptions: {}", e);
+            return;
+        }
+    };
+    if subscriptions.is_empty() {
+        return;
+    }
+
+    let payload = WebhookPayload {
+        event,
+        occurred_at: Utc::now(),
+        before,
+        after,
+    };
+    let payload = match serde_json::to_value(&payload) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::error!("Failed to encode webhook payload: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = insert_deliveries(pool, &subscriptions, event, &payload).await {
+        tracing::error!("Failed to enqueue webhooks for {}: {}", event, e);
+    }
+}
+
+/// Доставка и её задача в очереди появляются вместе
+async fn insert_deliveries(
+    pool: &SqlitePool,
+    subscriptions: &[WebhookSubscription],
+    event: &str,
+    payload: &serde_json::Value,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    for subscription in subscriptions {
+        let delivery_id =
+            repository::enqueue_webhook_delivery(&mut *tx, subscription.id, event, payload).await?;
+        jobs::enqueue(&mut *tx, &Job::DeliverWebhook { delivery_id }).await?;
+    }
+    tx.commit().await?;
+    jobs::wake();
+    Ok(())
+}
+
+/// Подпись тела запроса: `sha256=<hex HMAC-SHA256(secret, body)>`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub fn client() -> Client {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("Failed to build webhook HTTP client")
+}
+
+/// Одна попытка доставки из очереди. Ошибка - повод для повтора
+pub async fn deliver(
+    pool: &SqlitePool,
+    client: &Client,
+    delivery_id: i64,
+    attempt: &Attempt,
+) -> Result<(), String> {
+    let delivery = repository::get_undelivered_webhook_delivery(pool, delivery_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    // Уже доставлена или удалена вместе с подпиской
+    let Some(delivery) = delivery else {
+        return Ok(());
+    };
+
+    let subscription = repository::get_webhook_by_id(pool, delivery.subscription_id)
+        .await
+        .ok()
+        .filter(|s| s.active);
+    let Some(subscription) = subscription else {
+        let _ = repository::mark_webhook_attempt_failed(
+            pool,
+            delivery.id,
+            None,
+            "Subscription is disabled",
+            None,
+        )
+        .await;
+        return Ok(());
+    };
+
+    let body = delivery.payload.0.to_string();
+    let result = client
+        .post(&subscription.url)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Event", &delivery.event)
+        .header("X-Webhook-Delivery", delivery.id.to_string())
+        .header("X-Webhook-Signature-256", sign(&subscription.secret, body.as_bytes()))
+        .body(body)
+        .send()
+        .await;
+
+    let (status_code, error) = match result {
+        Ok(response) if response.status().is_success() => {
+            let code = i64::from(response.status().as_u16());
+            return repository::mark_webhook_delivered(pool, delivery.id, code)
+                .await
+                .map_err(|e| e.to_string());
+        }
+        Ok(response) => (
+            Some(i64::from(response.status().as_u16())),
+            format!("Endpoint returned {}", response.status()),
+        ),
+        Err(e) => (None, e.to_string()),
+    };
+
+    tracing::warn!(
+        "Webhook delivery {} failed (attempt {}): {}",
+        delivery.id,
+        attempt.number,
+        error
+    );
+    if let Err(e) = repository::mark_webhook_attempt_failed(
+        pool,
+        delivery.id,
+        status_code,
+        &error,
+        attempt.retry_in,
+    )
+    .await
+    {
+        tracing::error!("Failed to record webhook delivery {}: {}", delivery.id, e);
+    }
+    Err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_prefixed_hex_hmac_sha256() {
+        // Тестовый вектор RFC 4231, случай 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn signature_depends_on_secret_and_body() {
+        let body = br#"{"event":"task.created"}"#;
+        assert_eq!(sign("secret", body), sign("secret", body));
+        assert_ne!(sign("secret", body), sign("other", body));
+        assert_ne!(sign("secret", body), sign("secret", b"{}"));
+        // Пустой секрет допустим для HMAC и не роняет доставку
+        assert!(sign("", body).starts_with("sha256="));
+    }
+}
@@ -1,26 +1,85 @@
 use crate::models::*;
-use gloo_storage::{LocalStorage, Storage};
+use gloo_storage::{LocalStorage, SessionStorage, Storage};
 
 const API_URL: &str = "http://localhost:8080/api";
 const TOKEN_KEY: &str = "auth_token";
+const REFRESH_TOKEN_KEY: &str = "refresh_token";
+/// Момент (мс с эпохи), когда истекает access-токен
+const TOKEN_EXPIRES_KEY: &str = "auth_token_expires";
+/// Access-токен обновляется заранее, чтобы не истёк по дороге к серверу
+const REFRESH_MARGIN_MS: f64 = 30_000.0;
+/// state начатого входа через SSO: ответ провайдера принимается только в этой вкладке
+const OIDC_STATE_KEY: &str = "oidc_state";
 
 pub fn get_token() -> Option<String> {
     LocalStorage::get(TOKEN_KEY).ok()
 }
 
-pub fn set_token(token: &str) {
-    let _ = LocalStorage::set(TOKEN_KEY, token);
+fn store_tokens(auth: &AuthResponse) {
+    let expires_at = web_sys::js_sys::Date::now() + auth.expires_in as f64 * 1000.0;
+    let _ = LocalStorage::set(TOKEN_KEY, &auth.token);
+    let _ = LocalStorage::set(REFRESH_TOKEN_KEY, &auth.refresh_token);
+    let _ = LocalStorage::set(TOKEN_EXPIRES_KEY, expires_at);
 }
 
 pub fn clear_token() {
     LocalStorage::delete(TOKEN_KEY);
+    LocalStorage::delete(REFRESH_TOKEN_KEY);
+    LocalStorage::delete(TOKEN_EXPIRES_KEY);
+}
+
+/// Действующий access-токен. Истекающий обменивается на новую пару по refresh-токену
+async fn access_token() -> Result<String, String> {
+    let token = get_token().ok_or("Not authenticated")?;
+    let expires_at: f64 = LocalStorage::get(TOKEN_EXPIRES_KEY).unwrap_or(0.0);
+    if web_sys::js_sys::Date::now() + REFRESH_MARGIN_MS < expires_at {
+        return Ok(token);
+    }
+    refresh_tokens().await.map(|auth| auth.token)
+}
+
+async fn refresh_tokens() -> Result<AuthResponse, String> {
+    let refresh_token: String =
+        LocalStorage::get(REFRESH_TOKEN_KEY).map_err(|_| "Not authenticated")?;
+    let response = client()
+        .post(format!("{}/refresh", API_URL))
+        .json(&RefreshRequest { refresh_token })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        let auth: AuthResponse = response.json().await.map_err(|e| e.to_string())?;
+        store_tokens(&auth);
+        Ok(auth)
+    } else {
+        // Сессию отозвали или refresh-токен истёк - нужен новый вход
+        clear_token();
+        Err("Session expired".to_string())
+    }
+}
+
+/// Адрес SSE-канала. Токен идёт в query, так как EventSource не передаёт заголовки
+pub fn events_url() -> Option<String> {
+    get_token().map(|token| format!("{}/events?token={}", API_URL, token))
 }
 
 fn client() -> reqwest::Client {
     reqwest::Client::new()
 }
 
-pub async fn login(email: String, password: String) -> Result<AuthResponse, String> {
+/// Ошибку входа сервер описывает сам: пауза после неудач, требуется сброс пароля
+async fn login_error(response: reqwest::Response) -> String {
+    match response.status() {
+        reqwest::StatusCode::UNAUTHORIZED => "Invalid credentials".to_string(),
+        _ => {
+            let body: serde_json::Value = response.json().await.unwrap_or_default();
+            body["error"].as_str().unwrap_or("Login failed").to_string()
+        }
+    }
+}
+
+pub async fn login(email: String, password: String) -> Result<LoginResponse, String> {
     let response = client()
         .post(format!("{}/login", API_URL))
         .json(&LoginRequest { email, password })
@@ -28,17 +87,352 @@ pub async fn login(email: String, password: String) -> Result<AuthResponse, Stri
         .await
         .map_err(|e| e.to_string())?;
 
+    if response.status().is_success() {
+        let login: LoginResponse = response.json().await.map_err(|e| e.to_string())?;
+        if let LoginResponse::Authenticated(auth) = &login {
+            store_tokens(auth);
+        }
+        Ok(login)
+    } else {
+        Err(login_error(response).await)
+    }
+}
+
+/// Второй шаг входа: код из приложения или резервный код
+pub async fn login_two_factor(challenge: String, code: String) -> Result<AuthResponse, String> {
+    let response = client()
+        .post(format!("{}/login/2fa", API_URL))
+        .json(&TwoFactorLoginRequest { challenge, code })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
     if response.status().is_success() {
         let auth: AuthResponse = response.json().await.map_err(|e| e.to_string())?;
-        set_token(&auth.token);
+        store_tokens(&auth);
         Ok(auth)
+    } else if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        Err("Invalid code".to_string())
+    } else {
+        Err(login_error(response).await)
+    }
+}
+
+/// Настроен ли вход через SSO. При ошибке кнопку входа просто не показываем
+pub async fn oidc_enabled() -> bool {
+    let response = client().get(format!("{}/oidc", API_URL)).send().await;
+    match response {
+        Ok(response) if response.status().is_success() => response
+            .json::<OidcStatus>()
+            .await
+            .is_ok_and(|status| status.enabled),
+        _ => false,
+    }
+}
+
+/// Начинает вход через SSO и возвращает адрес страницы провайдера
+pub async fn start_oidc_login() -> Result<String, String> {
+    let response = client()
+        .post(format!("{}/oidc/start", API_URL))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        let start: OidcStart = response.json().await.map_err(|e| e.to_string())?;
+        SessionStorage::set(OIDC_STATE_KEY, &start.state).map_err(|e| e.to_string())?;
+        Ok(start.authorization_url)
+    } else {
+        Err(login_error(response).await)
+    }
+}
+
+/// Возврат от провайдера с кодом. Чужой state означает, что вход начат не здесь
+pub async fn finish_oidc_login(code: String, state: String) -> Result<LoginResponse, String> {
+    let expected: Option<String> = SessionStorage::get(OIDC_STATE_KEY).ok();
+    SessionStorage::delete(OIDC_STATE_KEY);
+    if expected.as_deref() != Some(state.as_str()) {
+        return Err("Single sign-on was not started from this page, please try again".to_string());
+    }
+
+    let response = client()
+        .post(format!("{}/oidc/callback", API_URL))
+        .json(&OidcCallbackRequest { code, state })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        let login: LoginResponse = response.json().await.map_err(|e| e.to_string())?;
+        if let LoginResponse::Authenticated(auth) = &login {
+            store_tokens(auth);
+        }
+        Ok(login)
+    } else if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        Err("Single sign-on failed or expired, please try again".to_string())
+    } else {
+        Err(login_error(response).await)
+    }
+}
+
+/// Отзывает текущую сессию на сервере. Локальные токены удаляются в любом случае
+pub async fn logout() {
+    if let Ok(token) = access_token().await {
+        let _ = client()
+            .post(format!("{}/logout", API_URL))
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+            .await;
+    }
+    clear_token();
+}
+
+pub async fn logout_everywhere() -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/logout-all", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        clear_token();
+        Ok(())
+    } else {
+        Err("Failed to log out".to_string())
+    }
+}
+
+pub async fn get_sessions() -> Result<Vec<Session>, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .get(format!("{}/me/sessions", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to fetch sessions".to_string())
+    }
+}
+
+pub async fn revoke_session(id: i64) -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .delete(format!("{}/sessions/{}", API_URL, id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err("Failed to revoke session".to_string())
+    }
+}
+
+pub async fn get_two_factor() -> Result<TwoFactorStatus, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .get(format!("{}/me/2fa", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to fetch two-factor status".to_string())
+    }
+}
+
+pub async fn setup_two_factor() -> Result<TwoFactorSetup, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/me/2fa/setup", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to start two-factor setup".to_string())
+    }
+}
+
+/// После включения токены обновляются сразу: выданные до настройки
+/// обязательной 2FA не пускают дальше экрана настройки
+pub async fn confirm_two_factor(code: String) -> Result<RecoveryCodes, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/me/2fa/confirm", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&TwoFactorCodeRequest { code })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        let codes = response.json().await.map_err(|e| e.to_string())?;
+        refresh_tokens().await?;
+        Ok(codes)
+    } else {
+        Err("Invalid code".to_string())
+    }
+}
+
+pub async fn regenerate_recovery_codes(code: String) -> Result<RecoveryCodes, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/me/2fa/recovery-codes", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&TwoFactorCodeRequest { code })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Invalid code".to_string())
+    }
+}
+
+/// Нужны пароль и код из приложения или резервный код
+pub async fn disable_two_factor(password: String, code: String) -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .delete(format!("{}/me/2fa", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&DisableTwoFactorRequest { password, code })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        Err(body["error"]
+            .as_str()
+            .unwrap_or("Failed to disable two-factor authentication")
+            .to_string())
+    }
+}
+
+pub async fn get_access_tokens() -> Result<Vec<AccessToken>, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .get(format!("{}/me/tokens", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to fetch access tokens".to_string())
+    }
+}
+
+pub async fn create_access_token(
+    req: CreateAccessTokenRequest,
+) -> Result<CreatedAccessToken, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/me/tokens", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&req)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
     } else {
-        Err("Invalid credentials".to_string())
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        Err(body["error"]
+            .as_str()
+            .unwrap_or("Failed to create access token")
+            .to_string())
+    }
+}
+
+pub async fn revoke_access_token(id: i64) -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .delete(format!("{}/tokens/{}", API_URL, id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err("Failed to revoke access token".to_string())
+    }
+}
+
+/// Сервер отвечает одинаково для любого адреса; отказ - только при частых запросах
+pub async fn forgot_password(email: String) -> Result<(), String> {
+    let response = client()
+        .post(format!("{}/password/forgot", API_URL))
+        .json(&ForgotPasswordRequest { email })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        let error = body["error"].as_str().unwrap_or("Failed to request password reset");
+        Err(error.to_string())
+    }
+}
+
+pub async fn reset_password(token: String, new_password: String) -> Result<(), String> {
+    let response = client()
+        .post(format!("{}/password/reset", API_URL))
+        .json(&ResetPasswordRequest { token, new_password })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        Err(body["error"]
+            .as_str()
+            .unwrap_or("Failed to reset password")
+            .to_string())
     }
 }
 
 pub async fn get_tasks() -> Result<Vec<Task>, String> {
-    let token = get_token().ok_or("Not authenticated")?;
+    let token = access_token().await?;
 
     let response = client()
         .get(format!("{}/tasks", API_URL))
@@ -54,12 +448,104 @@ pub async fn get_tasks() -> Result<Vec<Task>, String> {
     }
 }
 
+pub async fn get_watched_tasks() -> Result<Vec<Task>, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .get(format!("{}/tasks?watching=true", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to fetch watched tasks".to_string())
+    }
+}
+
+/// Поиск на языке запросов. Сервер сообщает, где в запросе ошибка, - эту строку и возвращаем
+pub async fn query_tasks(q: &str) -> Result<Vec<Task>, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .get(format!("{}/tasks", API_URL))
+        .query(&[("q", q)])
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        Err(body["error"]
+            .as_str()
+            .unwrap_or("Failed to run query")
+            .to_string())
+    }
+}
+
+pub async fn watch_task(id: i64) -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/tasks/{}/watch", API_URL, id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err("Failed to watch task".to_string())
+    }
+}
+
+pub async fn unwatch_task(id: i64) -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .delete(format!("{}/tasks/{}/watch", API_URL, id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err("Failed to unwatch task".to_string())
+    }
+}
+
+pub async fn get_task_watchers(id: i64) -> Result<Vec<User>, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .get(format!("{}/tasks/{}/watchers", API_URL, id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to fetch watchers".to_string())
+    }
+}
+
 pub async fn create_task(
     title: String,
     description: Option<String>,
     assignee_id: Option<i64>,
+    due_date: Option<String>,
 ) -> Result<Task, String> {
-    let token = get_token().ok_or("Not authenticated")?;
+    let token = access_token().await?;
 
     let response = client()
         .post(format!("{}/tasks", API_URL))
@@ -68,6 +554,7 @@ pub async fn create_task(
             title,
             description,
             assignee_id,
+            due_date,
         })
         .send()
         .await
@@ -80,19 +567,30 @@ pub async fn create_task(
     }
 }
 
-pub async fn update_task_status(id: i64, status: String) -> Result<Task, String> {
-    let token = get_token().ok_or("Not authenticated")?;
+pub async fn delete_task(id: i64) -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .delete(format!("{}/tasks/{}", API_URL, id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err("Failed to delete task".to_string())
+    }
+}
+
+pub async fn update_task(id: i64, req: UpdateTaskRequest) -> Result<Task, String> {
+    let token = access_token().await?;
 
     let response = client()
         .put(format!("{}/tasks/{}", API_URL, id))
         .header("Authorization", format!("Bearer {}", token))
-        .json(&UpdateTaskRequest {
-            title: None,
-            description: None,
-            status: Some(status),
-            assignee_id: None,
-            actual_hours: None,
-        })
+        .json(&req)
         .send()
         .await
         .map_err(|e| e.to_string())?;
@@ -104,11 +602,62 @@ pub async fn update_task_status(id: i64, status: String) -> Result<Task, String>
     }
 }
 
-pub async fn delete_task(id: i64) -> Result<(), String> {
-    let token = get_token().ok_or("Not authenticated")?;
+pub async fn get_users() -> Result<Vec<User>, String> {
+    let token = access_token().await?;
 
     let response = client()
-        .delete(format!("{}/tasks/{}", API_URL, id))
+        .get(format!("{}/users", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to fetch users".to_string())
+    }
+}
+
+pub async fn get_calendar_feed() -> Result<CalendarFeedStatus, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .get(format!("{}/me/calendar", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to fetch calendar feed".to_string())
+    }
+}
+
+pub async fn create_calendar_feed() -> Result<CalendarFeedResponse, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/me/calendar", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to create calendar feed".to_string())
+    }
+}
+
+pub async fn revoke_calendar_feed() -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .delete(format!("{}/me/calendar", API_URL))
         .header("Authorization", format!("Bearer {}", token))
         .send()
         .await
@@ -117,17 +666,16 @@ pub async fn delete_task(id: i64) -> Result<(), String> {
     if response.status().is_success() {
         Ok(())
     } else {
-        Err("Failed to delete task".to_string())
+        Err("Failed to revoke calendar feed".to_string())
     }
 }
 
-pub async fn update_task(id: i64, req: UpdateTaskRequest) -> Result<Task, String> {
-    let token = get_token().ok_or("Not authenticated")?;
+pub async fn get_notifications() -> Result<Vec<Notification>, String> {
+    let token = access_token().await?;
 
     let response = client()
-        .put(format!("{}/tasks/{}", API_URL, id))
+        .get(format!("{}/notifications", API_URL))
         .header("Authorization", format!("Bearer {}", token))
-        .json(&req)
         .send()
         .await
         .map_err(|e| e.to_string())?;
@@ -135,15 +683,67 @@ pub async fn update_task(id: i64, req: UpdateTaskRequest) -> Result<Task, String
     if response.status().is_success() {
         response.json().await.map_err(|e| e.to_string())
     } else {
-        Err("Failed to update task".to_string())
+        Err("Failed to fetch notifications".to_string())
     }
 }
 
-pub async fn get_users() -> Result<Vec<User>, String> {
-    let token = get_token().ok_or("Not authenticated")?;
+pub async fn get_unread_count() -> Result<i64, String> {
+    let token = access_token().await?;
 
     let response = client()
-        .get(format!("{}/users", API_URL))
+        .get(format!("{}/notifications/unread-count", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        let count: UnreadCount = response.json().await.map_err(|e| e.to_string())?;
+        Ok(count.unread)
+    } else {
+        Err("Failed to fetch unread count".to_string())
+    }
+}
+
+pub async fn mark_notification_read(id: i64) -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/notifications/{}/read", API_URL, id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err("Failed to mark notification as read".to_string())
+    }
+}
+
+pub async fn mark_all_notifications_read() -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/notifications/read-all", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err("Failed to mark notifications as read".to_string())
+    }
+}
+
+pub async fn get_email_preferences() -> Result<EmailPreferences, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .get(format!("{}/me/email-preferences", API_URL))
         .header("Authorization", format!("Bearer {}", token))
         .send()
         .await
@@ -152,15 +752,33 @@ pub async fn get_users() -> Result<Vec<User>, String> {
     if response.status().is_success() {
         response.json().await.map_err(|e| e.to_string())
     } else {
-        Err("Failed to fetch users".to_string())
+        Err("Failed to fetch email preferences".to_string())
     }
 }
 
-pub async fn get_me() -> Result<User, String> {
-    let token = get_token().ok_or("Not authenticated")?;
+pub async fn update_email_preferences(mode: String) -> Result<EmailPreferences, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .put(format!("{}/me/email-preferences", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&EmailPreferences { mode })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to update email preferences".to_string())
+    }
+}
+
+pub async fn get_task_comments(task_id: i64) -> Result<Vec<Comment>, String> {
+    let token = access_token().await?;
 
     let response = client()
-        .get(format!("{}/me", API_URL))
+        .get(format!("{}/tasks/{}/comments", API_URL, task_id))
         .header("Authorization", format!("Bearer {}", token))
         .send()
         .await
@@ -169,6 +787,94 @@ pub async fn get_me() -> Result<User, String> {
     if response.status().is_success() {
         response.json().await.map_err(|e| e.to_string())
     } else {
-        Err("Failed to fetch current user".to_string())
+        Err("Failed to fetch comments".to_string())
     }
-}
\ No newline at end of file
+}
+
+pub async fn create_comment(task_id: i64, body: String) -> Result<Comment, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/tasks/{}/comments", API_URL, task_id))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&CreateCommentRequest { body })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to add comment".to_string())
+    }
+}
+
+pub async fn get_views() -> Result<Vec<SavedView>, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .get(format!("{}/views", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to fetch views".to_string())
+    }
+}
+
+pub async fn create_view(req: CreateViewRequest) -> Result<SavedView, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .post(format!("{}/views", API_URL))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&req)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to save view".to_string())
+    }
+}
+
+pub async fn update_view(id: i64, req: CreateViewRequest) -> Result<SavedView, String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .put(format!("{}/views/{}", API_URL, id))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&req)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        response.json().await.map_err(|e| e.to_string())
+    } else {
+        Err("Failed to update view".to_string())
+    }
+}
+
+pub async fn delete_view(id: i64) -> Result<(), String> {
+    let token = access_token().await?;
+
+    let response = client()
+        .delete(format!("{}/views/{}", API_URL, id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err("Failed to delete view".to_string())
+    }
+}
@@ -3,7 +3,7 @@ mod models;
 mod pages;
 
 use leptos::*;
-use pages::{login::LoginPage, tasks::TasksPage};
+use pages::{login::LoginPage, tasks::TasksPage, two_factor::TwoFactorGate};
 use crate::models::User;
 
 fn main() {
@@ -14,12 +14,18 @@ fn main() {
 #[component]
 fn App() -> impl IntoView {
     let (user, set_user) = create_signal(Option::<User>::None);
+    let (setup_required, set_setup_required) = create_signal(false);
 
     view! {
         {move || {
-            match user.get() {
-                None => view! { <LoginPage on_login=set_user /> }.into_view(),
-                Some(u) => view! { <TasksPage user=u on_logout=set_user /> }.into_view(),
+            match (user.get(), setup_required.get()) {
+                (None, _) => view! {
+                    <LoginPage on_login=set_user on_setup_required=set_setup_required />
+                }.into_view(),
+                (Some(_), true) => view! {
+                    <TwoFactorGate setup_required=set_setup_required on_logout=set_user />
+                }.into_view(),
+                (Some(u), false) => view! { <TasksPage user=u on_logout=set_user /> }.into_view(),
             }
         }}
     }
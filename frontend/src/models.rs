@@ -3,12 +3,14 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: i64,
+    /// Чужой email сервер отдаёт только администраторам
+    #[serde(default)]
     pub email: String,
     pub name: String,
     pub role: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub id: i64,
     pub title: String,
@@ -18,6 +20,10 @@ pub struct Task {
     pub actual_hours: Option<f64>,
     pub assignee_id: Option<i64>,
     pub created_by: i64,
+    pub due_date: Option<String>,
+    pub planned_date: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -29,7 +35,127 @@ pub struct LoginRequest {
 #[derive(Debug, Clone, Deserialize)]
 pub struct AuthResponse {
     pub token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
     pub user: User,
+    /// Роль требует 2FA: до её настройки доступен только экран настройки
+    #[serde(default)]
+    pub two_factor_setup_required: bool,
+}
+
+/// Сначала пробуется пара токенов, без неё ответ - вызов второго шага
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwoFactorChallenge {
+    pub challenge: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge: String,
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcStatus {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcStart {
+    pub authorization_url: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OidcCallbackRequest {
+    pub code: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_left: i64,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DisableTwoFactorRequest {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Session {
+    pub id: i64,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub current: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccessToken {
+    pub id: i64,
+    pub name: String,
+    pub scope: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateAccessTokenRequest {
+    pub name: String,
+    pub scope: String,
+    pub expires_in_days: Option<i64>,
+}
+
+/// Сам токен приходит только в ответе на создание
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreatedAccessToken {
+    #[serde(flatten)]
+    pub access_token: AccessToken,
+    pub token: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -37,6 +163,7 @@ pub struct CreateTaskRequest {
     pub title: String,
     pub description: Option<String>,
     pub assignee_id: Option<i64>,
+    pub due_date: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -46,4 +173,89 @@ pub struct UpdateTaskRequest {
     pub status: Option<String>,
     pub assignee_id: Option<Option<i64>>,
     pub actual_hours: Option<f64>,
-}
\ No newline at end of file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub planned_date: Option<Option<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TaskEvent {
+    pub event: String,
+    pub task_id: i64,
+    pub task: Option<Task>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CalendarFeedStatus {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CalendarFeedResponse {
+    pub url: String,
+}
+#[derive(Debug, Clone, Deserialize)]
+pub struct Notification {
+    pub id: i64,
+    pub message: String,
+    pub read_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UnreadCount {
+    pub unread: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailPreferences {
+    pub mode: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Comment {
+    pub id: i64,
+    pub author_id: i64,
+    pub body: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateCommentRequest {
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewFilters {
+    pub status: Option<String>,
+    pub assignee_id: Option<i64>,
+    pub assigned_to_me: bool,
+    pub unassigned: bool,
+    pub created_by_me: bool,
+    pub watching: bool,
+    pub search: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SavedView {
+    pub id: i64,
+    pub owner_id: i64,
+    pub name: String,
+    pub shared: bool,
+    pub filters: ViewFilters,
+    pub sort_by: String,
+    pub sort_desc: bool,
+    pub display: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateViewRequest {
+    pub name: String,
+    pub shared: bool,
+    pub filters: ViewFilters,
+    pub sort_by: String,
+    pub sort_desc: bool,
+    pub display: String,
+}
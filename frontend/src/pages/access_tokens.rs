@@ -0,0 +1,131 @@
+use crate::api;
+use crate::models::{AccessToken, CreateAccessTokenRequest};
+use leptos::*;
+
+/// Личные токены для скриптов: создание, список и отзыв
+#[component]
+pub fn AccessTokensSection() -> impl IntoView {
+    let (tokens, set_tokens) = create_signal(Vec::<AccessToken>::new());
+    let (name, set_name) = create_signal(String::new());
+    let (scope, set_scope) = create_signal(String::from("read"));
+    let (expires_in_days, set_expires_in_days) = create_signal(String::from("90"));
+    let (new_token, set_new_token) = create_signal(Option::<String>::None);
+    let (error, set_error) = create_signal(Option::<String>::None);
+
+    create_effect(move |_| {
+        spawn_local(async move {
+            if let Ok(list) = api::get_access_tokens().await {
+                set_tokens.set(list);
+            }
+        });
+    });
+
+    let create = move |_| {
+        let req = CreateAccessTokenRequest {
+            name: name.get(),
+            scope: scope.get(),
+            expires_in_days: expires_in_days.get().parse().ok(),
+        };
+        spawn_local(async move {
+            set_error.set(None);
+            match api::create_access_token(req).await {
+                Ok(created) => {
+                    set_name.set(String::new());
+                    set_new_token.set(Some(created.token));
+                    set_tokens.update(|list| list.insert(0, created.access_token));
+                }
+                Err(e) => set_error.set(Some(e)),
+            }
+        });
+    };
+
+    let revoke = move |id: i64| {
+        spawn_local(async move {
+            if api::revoke_access_token(id).await.is_ok() {
+                set_tokens.update(|list| list.retain(|t| t.id != id));
+            }
+        });
+    };
+
+    view! {
+        <section class="space-y-2">
+            <h3 class="font-medium">"Access tokens"</h3>
+            <p class="text-sm text-gray-600">
+                "Tokens let scripts call the API without your password."
+            </p>
+
+            {move || error.get().map(|e| view! {
+                <div class="bg-red-100 text-red-700 p-2 rounded text-sm">{e}</div>
+            })}
+            {move || new_token.get().map(|token| view! {
+                <div class="bg-yellow-50 border border-yellow-200 p-2 rounded text-xs break-all">
+                    <p class="mb-1">"Copy this token now, it will not be shown again:"</p>
+                    <code>{token}</code>
+                </div>
+            })}
+
+            <ul class="divide-y text-sm">
+                <For
+                    each=move || tokens.get()
+                    key=|token| token.id
+                    children=move |token| {
+                        let details = format!(
+                            "{} · expires {} · last used {}",
+                            token.scope,
+                            token.expires_at.clone().unwrap_or_else(|| "never".to_string()),
+                            token.last_used_at.clone().unwrap_or_else(|| "never".to_string()),
+                        );
+                        view! {
+                            <li class="py-2 flex justify-between items-center gap-2">
+                                <div class="min-w-0">
+                                    <p class="truncate">{token.name.clone()}</p>
+                                    <p class="text-xs text-gray-500">{details}</p>
+                                </div>
+                                <button
+                                    on:click=move |_| revoke(token.id)
+                                    class="text-red-600 hover:underline text-xs shrink-0"
+                                >
+                                    "Revoke"
+                                </button>
+                            </li>
+                        }
+                    }
+                />
+            </ul>
+
+            <div class="flex gap-2">
+                <input
+                    type="text"
+                    placeholder="Token name"
+                    class="flex-1 min-w-0 border rounded px-2 py-1 text-sm"
+                    prop:value=name
+                    on:input=move |ev| set_name.set(event_target_value(&ev))
+                />
+                <select
+                    class="border rounded px-2 py-1 text-sm"
+                    prop:value=scope
+                    on:change=move |ev| set_scope.set(event_target_value(&ev))
+                >
+                    <option value="read">"Read"</option>
+                    <option value="write">"Read & write"</option>
+                </select>
+                <select
+                    class="border rounded px-2 py-1 text-sm"
+                    prop:value=expires_in_days
+                    on:change=move |ev| set_expires_in_days.set(event_target_value(&ev))
+                >
+                    <option value="30">"30 days"</option>
+                    <option value="90">"90 days"</option>
+                    <option value="365">"1 year"</option>
+                    <option value="never">"No expiry"</option>
+                </select>
+            </div>
+            <button
+                on:click=create
+                class="px-3 py-1 bg-blue-600 text-white rounded hover:bg-blue-700 text-sm"
+            >
+                "Create token"
+            </button>
+        </section>
+    }
+}
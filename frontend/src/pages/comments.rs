@@ -0,0 +1,78 @@
+use crate::api;
+use crate::models::{Comment, User};
+use crate::pages::mentions::{MentionText, MentionTextarea};
+use leptos::*;
+
+#[component]
+pub fn TaskComments(task_id: i64, users: Vec<User>) -> impl IntoView {
+    let (comments, set_comments) = create_signal(Vec::<Comment>::new());
+    let (body, set_body) = create_signal(String::new());
+    let users = store_value(users);
+
+    create_effect(move |_| {
+        spawn_local(async move {
+            if let Ok(fetched) = api::get_task_comments(task_id).await {
+                set_comments.set(fetched);
+            }
+        });
+    });
+
+    let submit = move |_| {
+        let text = body.get();
+        if text.trim().is_empty() {
+            return;
+        }
+        spawn_local(async move {
+            if let Ok(comment) = api::create_comment(task_id, text).await {
+                set_comments.update(|c| c.push(comment));
+                set_body.set(String::new());
+            }
+        });
+    };
+
+    let author_name = move |id: i64| {
+        users.with_value(|all| {
+            all.iter()
+                .find(|u| u.id == id)
+                .map(|u| u.name.clone())
+                .unwrap_or_else(|| format!("User #{}", id))
+        })
+    };
+
+    view! {
+        <section class="mt-6 space-y-3">
+            <h3 class="font-medium">"Comments"</h3>
+            <For
+                each=move || comments.get()
+                key=|c| c.id
+                children=move |c| {
+                    view! {
+                        <div class="text-sm border-l-2 pl-2">
+                            <p class="text-xs text-gray-500">
+                                {author_name(c.author_id)} " · " {c.created_at.clone()}
+                            </p>
+                            <p class="whitespace-pre-wrap">
+                                <MentionText text=c.body.clone() users=users.get_value() />
+                            </p>
+                        </div>
+                    }
+                }
+            />
+            <MentionTextarea
+                value=body
+                set_value=set_body
+                users=users.get_value()
+                placeholder="Write a comment, @mention someone"
+                rows="2"
+            />
+            <div class="flex justify-end">
+                <button
+                    on:click=submit
+                    class="px-3 py-1 bg-blue-600 text-white rounded hover:bg-blue-700 text-sm"
+                >
+                    "Comment"
+                </button>
+            </div>
+        </section>
+    }
+}
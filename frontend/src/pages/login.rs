@@ -1,37 +1,148 @@
 use leptos::*;
 use crate::api;
-use crate::models::User;
+use crate::models::{LoginResponse, User};
+
+#[derive(Clone, PartialEq)]
+enum Mode {
+    Login,
+    /// Запрос ссылки для сброса пароля
+    Forgot,
+    /// Открыта ссылка из письма
+    Reset(String),
+    /// Пароль принят, ждём код второго фактора
+    TwoFactor(String),
+}
+
+/// Параметр адресной строки: токен сброса из ссылки вида `/?reset_token=...`
+/// или код и state, с которыми вернул провайдер SSO
+fn query_param(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get(name)
+        .filter(|value| !value.is_empty())
+}
+
+/// Убирает токен из адресной строки, чтобы он не остался в истории
+fn clear_url() {
+    if let Some(history) = web_sys::window().and_then(|w| w.history().ok()) {
+        let _ = history.replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some("/"));
+    }
+}
 
 #[component]
-pub fn LoginPage(on_login: WriteSignal<Option<User>>) -> impl IntoView {
+pub fn LoginPage(
+    on_login: WriteSignal<Option<User>>,
+    on_setup_required: WriteSignal<bool>,
+) -> impl IntoView {
+    let initial_mode = query_param("reset_token").map_or(Mode::Login, Mode::Reset);
+    let (mode, set_mode) = create_signal(initial_mode);
     let (email, set_email) = create_signal(String::new());
     let (password, set_password) = create_signal(String::new());
+    let (code, set_code) = create_signal(String::new());
     let (error, set_error) = create_signal(Option::<String>::None);
+    let (info, set_info) = create_signal(Option::<String>::None);
     let (loading, set_loading) = create_signal(false);
+    let (sso_enabled, set_sso_enabled) = create_signal(false);
+
+    let switch_mode = move |next: Mode| {
+        set_error.set(None);
+        set_info.set(None);
+        set_password.set(String::new());
+        set_code.set(String::new());
+        set_mode.set(next);
+    };
+
+    let handle_login = move |result: Result<LoginResponse, String>| match result {
+        Ok(LoginResponse::Authenticated(auth)) => {
+            on_setup_required.set(auth.two_factor_setup_required);
+            on_login.set(Some(auth.user));
+        }
+        Ok(LoginResponse::TwoFactorRequired(challenge)) => {
+            switch_mode(Mode::TwoFactor(challenge.challenge));
+        }
+        Err(e) => {
+            set_error.set(Some(e));
+        }
+    };
+
+    spawn_local(async move {
+        set_sso_enabled.set(api::oidc_enabled().await);
+    });
+
+    // Возврат от провайдера SSO: с кодом или с ошибкой, если вход отменён
+    if let Some(state) = query_param("state") {
+        clear_url();
+        match (query_param("code"), query_param("error")) {
+            (Some(code), _) => spawn_local(async move {
+                set_loading.set(true);
+                handle_login(api::finish_oidc_login(code, state).await);
+                set_loading.set(false);
+            }),
+            (None, error) => set_error.set(Some(format!(
+                "Single sign-on failed: {}",
+                error.unwrap_or_else(|| "no code from the provider".to_string())
+            ))),
+        }
+    }
+
+    let start_sso = move |_| {
+        spawn_local(async move {
+            set_error.set(None);
+            match api::start_oidc_login().await {
+                Ok(url) => {
+                    if let Some(window) = web_sys::window() {
+                        let _ = window.location().set_href(&url);
+                    }
+                }
+                Err(e) => set_error.set(Some(e)),
+            }
+        });
+    };
 
     let submit = move |ev: web_sys::SubmitEvent| {
         ev.prevent_default();
-        
+
         let email_val = email.get();
         let password_val = password.get();
+        let code_val = code.get();
+        let current_mode = mode.get();
 
         spawn_local(async move {
             set_loading.set(true);
             set_error.set(None);
+            set_info.set(None);
 
-            match api::login(email_val, password_val).await {
-                Ok(auth) => {
-                    on_login.set(Some(auth.user));
-                }
-                Err(e) => {
-                    set_error.set(Some(e));
-                }
+            match current_mode {
+                Mode::Login => handle_login(api::login(email_val, password_val).await),
+                Mode::TwoFactor(challenge) => match api::login_two_factor(challenge, code_val).await {
+                    Ok(auth) => on_login.set(Some(auth.user)),
+                    Err(e) => set_error.set(Some(e)),
+                },
+                Mode::Forgot => match api::forgot_password(email_val).await {
+                    Ok(()) => set_info.set(Some(
+                        "If this email is registered, a reset link is on its way.".to_string(),
+                    )),
+                    Err(e) => set_error.set(Some(e)),
+                },
+                Mode::Reset(token) => match api::reset_password(token, password_val).await {
+                    Ok(()) => {
+                        clear_url();
+                        switch_mode(Mode::Login);
+                        set_info.set(Some("Password changed. You can log in now.".to_string()));
+                    }
+                    Err(e) => set_error.set(Some(e)),
+                },
             }
 
             set_loading.set(false);
         });
     };
 
+    let is_login = move || mode.get() == Mode::Login;
+    let is_reset = move || matches!(mode.get(), Mode::Reset(_));
+    let is_two_factor = move || matches!(mode.get(), Mode::TwoFactor(_));
+
     view! {
         <div class="min-h-screen flex items-center justify-center bg-gray-100">
             <div class="bg-white p-8 rounded-lg shadow-md w-full max-w-md">
@@ -41,38 +152,95 @@ pub fn LoginPage(on_login: WriteSignal<Option<User>>) -> impl IntoView {
                     {move || error.get().map(|e| view! {
                         <div class="bg-red-100 text-red-700 p-3 rounded">{e}</div>
                     })}
+                    {move || info.get().map(|message| view! {
+                        <div class="bg-green-100 text-green-700 p-3 rounded">{message}</div>
+                    })}
 
-                    <div>
-                        <label class="block text-sm font-medium mb-1">"Email"</label>
-                        <input
-                            type="email"
-                            class="w-full border rounded px-3 py-2"
-                            prop:value=email
-                            on:input=move |ev| set_email.set(event_target_value(&ev))
-                            required
-                        />
-                    </div>
-
-                    <div>
-                        <label class="block text-sm font-medium mb-1">"Password"</label>
-                        <input
-                            type="password"
-                            class="w-full border rounded px-3 py-2"
-                            prop:value=password
-                            on:input=move |ev| set_password.set(event_target_value(&ev))
-                            required
-                        />
-                    </div>
+                    <Show when=is_two_factor>
+                        <div>
+                            <label class="block text-sm font-medium mb-1">
+                                "Code from your authenticator app or a recovery code"
+                            </label>
+                            <input
+                                type="text"
+                                autocomplete="one-time-code"
+                                class="w-full border rounded px-3 py-2"
+                                prop:value=code
+                                on:input=move |ev| set_code.set(event_target_value(&ev))
+                                required
+                            />
+                        </div>
+                    </Show>
+
+                    <Show when=move || !is_reset() && !is_two_factor()>
+                        <div>
+                            <label class="block text-sm font-medium mb-1">"Email"</label>
+                            <input
+                                type="email"
+                                class="w-full border rounded px-3 py-2"
+                                prop:value=email
+                                on:input=move |ev| set_email.set(event_target_value(&ev))
+                                required
+                            />
+                        </div>
+                    </Show>
+
+                    <Show when=move || mode.get() != Mode::Forgot && !is_two_factor()>
+                        <div>
+                            <label class="block text-sm font-medium mb-1">
+                                {move || if is_reset() { "New password" } else { "Password" }}
+                            </label>
+                            <input
+                                type="password"
+                                class="w-full border rounded px-3 py-2"
+                                prop:value=password
+                                on:input=move |ev| set_password.set(event_target_value(&ev))
+                                required
+                            />
+                        </div>
+                    </Show>
 
                     <button
                         type="submit"
                         class="w-full bg-blue-600 text-white py-2 rounded hover:bg-blue-700 disabled:opacity-50"
                         disabled=loading
                     >
-                        {move || if loading.get() { "Loading..." } else { "Login" }}
+                        {move || match (loading.get(), mode.get()) {
+                            (true, _) => "Loading...",
+                            (false, Mode::Login) => "Login",
+                            (false, Mode::Forgot) => "Send reset link",
+                            (false, Mode::Reset(_)) => "Set new password",
+                            (false, Mode::TwoFactor(_)) => "Verify",
+                        }}
+                    </button>
+
+                    <Show when=move || is_login() && sso_enabled.get()>
+                        <button
+                            type="button"
+                            class="w-full border border-blue-600 text-blue-600 py-2 rounded hover:bg-blue-50 disabled:opacity-50"
+                            disabled=loading
+                            on:click=start_sso
+                        >
+                            "Sign in with SSO"
+                        </button>
+                    </Show>
+
+                    <button
+                        type="button"
+                        class="w-full text-sm text-blue-600 hover:underline"
+                        on:click=move |_| {
+                            if is_login() {
+                                switch_mode(Mode::Forgot);
+                            } else {
+                                clear_url();
+                                switch_mode(Mode::Login);
+                            }
+                        }
+                    >
+                        {move || if is_login() { "Forgot password?" } else { "Back to login" }}
                     </button>
                 </form>
             </div>
         </div>
     }
-}
\ No newline at end of file
+}
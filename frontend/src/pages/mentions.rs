@@ -0,0 +1,232 @@
+use crate::models::User;
+use leptos::*;
+use wasm_bindgen::JsCast;
+
+const MAX_SUGGESTIONS: usize = 5;
+
+fn is_mention_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '+')
+}
+
+/// "Alice Smith" -> "alicesmith", как на сервере
+fn compact_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Ищет пользователя по упоминанию по тем же правилам, что и сервер:
+/// email, имя без пробелов или локальная часть email
+fn resolve<'a>(mention: &str, users: &'a [User]) -> Option<&'a User> {
+    let mention = mention.to_lowercase();
+    let matches: Vec<&User> = if mention.contains('@') {
+        users.iter().filter(|u| u.email.to_lowercase() == mention).collect()
+    } else {
+        let by_name: Vec<&User> = users.iter().filter(|u| compact_name(&u.name) == mention).collect();
+        if by_name.is_empty() {
+            users
+                .iter()
+                .filter(|u| u.email.split('@').next().is_some_and(|l| l.to_lowercase() == mention))
+                .collect()
+        } else {
+            by_name
+        }
+    };
+
+    match matches.as_slice() {
+        [user] => Some(user),
+        _ => None,
+    }
+}
+
+/// Что вставить после `@`: имя, если оно однозначно, иначе email (если он нам виден)
+fn handle(user: &User, users: &[User]) -> String {
+    let name = compact_name(&user.name);
+    let unique = users.iter().filter(|u| compact_name(&u.name) == name).count() == 1;
+    if (unique || user.email.is_empty()) && !name.is_empty() { name } else { user.email.clone() }
+}
+
+enum Segment {
+    Text(String),
+    Mention(String, User),
+}
+
+fn segments(text: &str, users: &[User]) -> Vec<Segment> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = Vec::new();
+    let mut plain = String::new();
+
+    let mut i = 0;
+    while i < chars.len() {
+        let at_word_start = i == 0 || !is_mention_char(chars[i - 1]);
+        if chars[i] != '@' || !at_word_start {
+            plain.push(chars[i]);
+            i += 1;
+            continue;
+        }
+
+        let start = i + 1;
+        let mut end = start;
+        let mut seen_at = false;
+        while end < chars.len() {
+            if is_mention_char(chars[end]) {
+                end += 1;
+            } else if chars[end] == '@' && !seen_at && end > start {
+                seen_at = true;
+                end += 1;
+            } else {
+                break;
+            }
+        }
+        while end > start && matches!(chars[end - 1], '.' | '@') {
+            end -= 1;
+        }
+
+        let token: String = chars[start..end].iter().collect();
+        match resolve(&token, users) {
+            Some(user) if !token.is_empty() => {
+                if !plain.is_empty() {
+                    result.push(Segment::Text(std::mem::take(&mut plain)));
+                }
+                result.push(Segment::Mention(format!("@{}", token), user.clone()));
+            }
+            _ => plain.extend(&chars[i..end.max(start)]),
+        }
+        i = end.max(start);
+    }
+
+    if !plain.is_empty() {
+        result.push(Segment::Text(plain));
+    }
+    result
+}
+
+/// Текст, в котором упоминания известных пользователей подсвечены ссылками
+#[component]
+pub fn MentionText(text: String, users: Vec<User>) -> impl IntoView {
+    segments(&text, &users)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.into_view(),
+            Segment::Mention(raw, user) => view! {
+                <a
+                    href=(!user.email.is_empty()).then(|| format!("mailto:{}", user.email))
+                    title=user.name.clone()
+                    class="text-blue-600 bg-blue-50 rounded px-0.5 hover:underline"
+                >
+                    {raw}
+                </a>
+            }
+            .into_view(),
+        })
+        .collect_view()
+}
+
+/// Набираемое упоминание: текст до `@`, уже введённая часть и текст после курсора
+#[derive(Clone)]
+struct PendingMention {
+    before: String,
+    typed: String,
+    after: String,
+}
+
+fn pending_mention(text: &str, caret_utf16: usize) -> Option<PendingMention> {
+    let head: Vec<u16> = text.encode_utf16().take(caret_utf16).collect();
+    let head = String::from_utf16_lossy(&head);
+    let after = text[head.len().min(text.len())..].to_string();
+
+    let at = head.rfind('@')?;
+    let typed = &head[at + 1..];
+    let at_word_start = head[..at].chars().last().is_none_or(|c| !is_mention_char(c));
+    if !at_word_start || !typed.chars().all(|c| is_mention_char(c) || c == '@') {
+        return None;
+    }
+
+    Some(PendingMention {
+        before: head[..at].to_string(),
+        typed: typed.to_lowercase(),
+        after,
+    })
+}
+
+/// Поле ввода с подсказками пользователей после `@`
+#[component]
+pub fn MentionTextarea(
+    value: ReadSignal<String>,
+    set_value: WriteSignal<String>,
+    users: Vec<User>,
+    #[prop(optional)] placeholder: &'static str,
+    #[prop(default = "3")] rows: &'static str,
+) -> impl IntoView {
+    let (pending, set_pending) = create_signal(Option::<PendingMention>::None);
+    let users = store_value(users);
+
+    let on_input = move |ev: web_sys::Event| {
+        let Some(area) = ev
+            .target()
+            .and_then(|t| t.dyn_into::<web_sys::HtmlTextAreaElement>().ok())
+        else {
+            return;
+        };
+        let text = area.value();
+        let caret = area.selection_start().ok().flatten().unwrap_or(u32::MAX) as usize;
+        set_pending.set(pending_mention(&text, caret));
+        set_value.set(text);
+    };
+
+    let insert = move |user: User| {
+        if let Some(p) = pending.get_untracked() {
+            let handle = users.with_value(|all| handle(&user, all));
+            set_value.set(format!("{}@{} {}", p.before, handle, p.after.trim_start()));
+        }
+        set_pending.set(None);
+    };
+
+    view! {
+        <div class="relative">
+            <textarea
+                class="w-full border rounded px-3 py-2"
+                rows=rows
+                placeholder=placeholder
+                prop:value=value
+                on:input=on_input
+                on:blur=move |_| set_pending.set(None)
+            />
+            {move || {
+                let p = pending.get()?;
+                let matches: Vec<User> = users.with_value(|all| {
+                    all.iter()
+                        .filter(|u| {
+                            compact_name(&u.name).starts_with(&p.typed)
+                                || u.email.to_lowercase().starts_with(&p.typed)
+                        })
+                        .take(MAX_SUGGESTIONS)
+                        .cloned()
+                        .collect()
+                });
+                (!matches.is_empty()).then(|| view! {
+                    <ul class="absolute z-50 left-0 right-0 bg-white border rounded shadow">
+                        {matches.into_iter().map(|u| {
+                            let name = u.name.clone();
+                            let email = u.email.clone();
+                            view! {
+                                // mousedown, чтобы выбор сработал раньше blur у textarea
+                                <li
+                                    on:mousedown=move |ev| {
+                                        ev.prevent_default();
+                                        insert(u.clone());
+                                    }
+                                    class="px-3 py-1 cursor-pointer hover:bg-blue-50"
+                                >
+                                    <span>{name}</span>
+                                    <span class="text-xs text-gray-500 ml-2">{email}</span>
+                                </li>
+                            }
+                        }).collect_view()}
+                    </ul>
+                })
+            }}
+        </div>
+    }
+}
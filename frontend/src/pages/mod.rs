@@ -1,2 +1,9 @@
+pub mod access_tokens;
+pub mod comments;
 pub mod login;
-pub mod tasks;
\ No newline at end of file
+pub mod mentions;
+pub mod notifications;
+pub mod profile;
+pub mod tasks;
+pub mod two_factor;
+pub mod views;
@@ -0,0 +1,106 @@
+use crate::api;
+use crate::models::Notification;
+use leptos::*;
+
+/// Колокольчик со счётчиком непрочитанных. `refresh` меняется при каждом
+/// событии доски - тогда счётчик перезапрашивается
+#[component]
+pub fn NotificationBell(refresh: ReadSignal<u32>) -> impl IntoView {
+    let (unread, set_unread) = create_signal(0i64);
+    let (open, set_open) = create_signal(false);
+    let (items, set_items) = create_signal(Vec::<Notification>::new());
+
+    create_effect(move |_| {
+        refresh.track();
+        spawn_local(async move {
+            if let Ok(count) = api::get_unread_count().await {
+                set_unread.set(count);
+            }
+        });
+    });
+
+    let toggle = move |_| {
+        let opening = !open.get();
+        set_open.set(opening);
+        if opening {
+            spawn_local(async move {
+                if let Ok(fetched) = api::get_notifications().await {
+                    set_items.set(fetched);
+                }
+            });
+        }
+    };
+
+    let mark_read = move |id: i64| {
+        spawn_local(async move {
+            if api::mark_notification_read(id).await.is_ok() {
+                set_items.update(|items| {
+                    if let Some(n) = items.iter_mut().find(|n| n.id == id && n.read_at.is_none()) {
+                        n.read_at = Some(String::new());
+                        set_unread.update(|c| *c = (*c - 1).max(0));
+                    }
+                });
+            }
+        });
+    };
+
+    let mark_all = move |_| {
+        spawn_local(async move {
+            if api::mark_all_notifications_read().await.is_ok() {
+                set_items.update(|items| {
+                    for n in items.iter_mut().filter(|n| n.read_at.is_none()) {
+                        n.read_at = Some(String::new());
+                    }
+                });
+                set_unread.set(0);
+            }
+        });
+    };
+
+    view! {
+        <div class="relative">
+            <button on:click=toggle class="relative text-gray-600 hover:text-gray-800 text-xl" title="Notifications">
+                "🔔"
+                <Show when=move || { unread.get() > 0 }>
+                    <span class="absolute -top-1 -right-2 bg-red-600 text-white text-xs rounded-full px-1">
+                        {move || unread.get()}
+                    </span>
+                </Show>
+            </button>
+
+            <Show when=move || open.get()>
+                <div class="absolute right-0 mt-2 w-80 bg-white rounded-lg shadow-lg z-40">
+                    <div class="flex justify-between items-center px-3 py-2 border-b">
+                        <span class="font-medium">"Notifications"</span>
+                        <button on:click=mark_all class="text-sm text-blue-600 hover:underline">
+                            "Mark all as read"
+                        </button>
+                    </div>
+                    <ul class="max-h-96 overflow-y-auto">
+                        {move || {
+                            let list = items.get();
+                            if list.is_empty() {
+                                view! { <li class="px-3 py-2 text-sm text-gray-500">"No notifications"</li> }.into_view()
+                            } else {
+                                list.into_iter().map(|n| {
+                                    let id = n.id;
+                                    let unread = n.read_at.is_none();
+                                    view! {
+                                        <li
+                                            on:click=move |_| mark_read(id)
+                                            class="px-3 py-2 text-sm border-b cursor-pointer hover:bg-gray-50"
+                                            class:font-semibold=unread
+                                        >
+                                            <p>{n.message}</p>
+                                            <p class="text-xs text-gray-400">{n.created_at}</p>
+                                        </li>
+                                    }
+                                }).collect_view()
+                            }
+                        }}
+                    </ul>
+                </div>
+            </Show>
+        </div>
+    }
+}
@@ -0,0 +1,191 @@
+use crate::api;
+use crate::models::{Session, User};
+use crate::pages::access_tokens::AccessTokensSection;
+use crate::pages::two_factor::TwoFactorSection;
+use leptos::*;
+
+#[component]
+pub fn ProfileModal<C>(on_close: C, on_logout: WriteSignal<Option<User>>) -> impl IntoView
+where
+    C: Fn() + Copy + 'static,
+{
+    let (feed_enabled, set_feed_enabled) = create_signal(false);
+    let (feed_url, set_feed_url) = create_signal(Option::<String>::None);
+    let (email_mode, set_email_mode) = create_signal(String::from("immediate"));
+    let (sessions, set_sessions) = create_signal(Vec::<Session>::new());
+
+    create_effect(move |_| {
+        spawn_local(async move {
+            if let Ok(status) = api::get_calendar_feed().await {
+                set_feed_enabled.set(status.enabled);
+            }
+            if let Ok(prefs) = api::get_email_preferences().await {
+                set_email_mode.set(prefs.mode);
+            }
+            if let Ok(list) = api::get_sessions().await {
+                set_sessions.set(list);
+            }
+        });
+    });
+
+    let change_email_mode = move |ev| {
+        let mode = event_target_value(&ev);
+        spawn_local(async move {
+            if let Ok(prefs) = api::update_email_preferences(mode).await {
+                set_email_mode.set(prefs.mode);
+            }
+        });
+    };
+
+    let create_feed = move |_| {
+        spawn_local(async move {
+            if let Ok(feed) = api::create_calendar_feed().await {
+                set_feed_enabled.set(true);
+                set_feed_url.set(Some(feed.url));
+            }
+        });
+    };
+
+    let revoke_feed = move |_| {
+        spawn_local(async move {
+            if api::revoke_calendar_feed().await.is_ok() {
+                set_feed_enabled.set(false);
+                set_feed_url.set(None);
+            }
+        });
+    };
+
+    let revoke_session = move |session: Session| {
+        spawn_local(async move {
+            if api::revoke_session(session.id).await.is_ok() {
+                if session.current {
+                    api::clear_token();
+                    on_logout.set(None);
+                } else {
+                    set_sessions.update(|list| list.retain(|s| s.id != session.id));
+                }
+            }
+        });
+    };
+
+    let logout_everywhere = move |_| {
+        spawn_local(async move {
+            if api::logout_everywhere().await.is_ok() {
+                on_logout.set(None);
+            }
+        });
+    };
+
+    view! {
+        <div class="fixed inset-0 bg-black bg-opacity-50 flex items-center justify-center z-50">
+            <div class="bg-white rounded-lg p-6 w-full max-w-md">
+                <div class="flex justify-between items-center mb-4">
+                    <h2 class="text-lg font-semibold">"Profile"</h2>
+                    <button
+                        on:click=move |_| on_close()
+                        class="text-gray-500 hover:text-gray-700 text-xl"
+                    >
+                        "×"
+                    </button>
+                </div>
+
+                <section class="space-y-2 mb-6">
+                    <h3 class="font-medium">"Email notifications"</h3>
+                    <select
+                        class="w-full border rounded px-3 py-2"
+                        prop:value=email_mode
+                        on:change=change_email_mode
+                    >
+                        <option value="immediate">"Immediately"</option>
+                        <option value="digest">"Daily digest"</option>
+                        <option value="off">"Off"</option>
+                    </select>
+                </section>
+
+                <section class="space-y-2">
+                    <h3 class="font-medium">"Calendar feed"</h3>
+                    <p class="text-sm text-gray-600">
+                        "Subscribe to your assigned tasks with due or planned dates in any calendar app."
+                    </p>
+
+                    {move || feed_url.get().map(|url| view! {
+                        <div class="bg-yellow-50 border border-yellow-200 p-2 rounded text-xs break-all">
+                            <p class="mb-1">"Copy this link now, it will not be shown again:"</p>
+                            <code>{url}</code>
+                        </div>
+                    })}
+
+                    <div class="flex gap-2">
+                        <button
+                            on:click=create_feed
+                            class="px-3 py-1 bg-blue-600 text-white rounded hover:bg-blue-700 text-sm"
+                        >
+                            {move || if feed_enabled.get() { "Regenerate link" } else { "Create link" }}
+                        </button>
+                        <Show when=move || feed_enabled.get()>
+                            <button
+                                on:click=revoke_feed
+                                class="px-3 py-1 border border-red-600 text-red-600 rounded hover:bg-red-50 text-sm"
+                            >
+                                "Revoke"
+                            </button>
+                        </Show>
+                    </div>
+                </section>
+
+                <div class="mt-6">
+                    <TwoFactorSection on_enabled=|| () />
+                </div>
+
+                <div class="mt-6">
+                    <AccessTokensSection />
+                </div>
+
+                <section class="space-y-2 mt-6">
+                    <h3 class="font-medium">"Sessions"</h3>
+                    <ul class="divide-y text-sm">
+                        <For
+                            each=move || sessions.get()
+                            key=|session| session.id
+                            children=move |session| {
+                                let device = session.user_agent.clone().unwrap_or_else(|| "Unknown device".to_string());
+                                let details = format!(
+                                    "{} · last active {}",
+                                    session.ip.clone().unwrap_or_else(|| "unknown IP".to_string()),
+                                    session.last_used_at.clone().unwrap_or_else(|| session.created_at.clone()),
+                                );
+                                let current = session.current;
+                                view! {
+                                    <li class="py-2 flex justify-between items-center gap-2">
+                                        <div class="min-w-0">
+                                            <p class="truncate" title=device.clone()>{device.clone()}</p>
+                                            <p class="text-xs text-gray-500">
+                                                {details}
+                                                {current.then_some(" · this device")}
+                                            </p>
+                                        </div>
+                                        <button
+                                            on:click=move |_| revoke_session(session.clone())
+                                            class="text-red-600 hover:underline text-xs shrink-0"
+                                        >
+                                            "Revoke"
+                                        </button>
+                                    </li>
+                                }
+                            }
+                        />
+                    </ul>
+                    <p class="text-sm text-gray-600">
+                        "Sign out on every device where you are logged in, including this one."
+                    </p>
+                    <button
+                        on:click=logout_everywhere
+                        class="px-3 py-1 border border-red-600 text-red-600 rounded hover:bg-red-50 text-sm"
+                    >
+                        "Log out everywhere"
+                    </button>
+                </section>
+            </div>
+        </div>
+    }
+}
@@ -1,6 +1,30 @@
 use crate::api;
-use crate::models::{Task, UpdateTaskRequest, User};
+use crate::models::{Task, TaskEvent, UpdateTaskRequest, User};
+use crate::pages::comments::TaskComments;
+use crate::pages::mentions::{MentionText, MentionTextarea};
+use crate::pages::notifications::NotificationBell;
+use crate::pages::profile::ProfileModal;
+use crate::pages::views::{QueryBar, ViewBar, ViewCriteria};
 use leptos::*;
+use std::collections::HashSet;
+use wasm_bindgen::{JsCast, closure::Closure};
+
+/// Заменяет задачу с тем же id или добавляет новую: событие от сервера
+/// может прийти раньше ответа на собственный запрос
+fn upsert_task(tasks: &mut Vec<Task>, task: Task) {
+    match tasks.iter_mut().find(|t| t.id == task.id) {
+        Some(existing) => *existing = task,
+        None => tasks.push(task),
+    }
+}
+
+fn apply_event(tasks: &mut Vec<Task>, event: TaskEvent) {
+    match (event.event.as_str(), event.task) {
+        ("task.deleted", _) => tasks.retain(|t| t.id != event.task_id),
+        (_, Some(task)) => upsert_task(tasks, task),
+        _ => {}
+    }
+}
 
 #[component]
 pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoView {
@@ -9,18 +33,49 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
     let (new_title, set_new_title) = create_signal(String::new());
     let (new_desc, set_new_desc) = create_signal(String::new());
     let (new_assignee, set_new_assignee) = create_signal(Option::<i64>::None);
+    let (new_due, set_new_due) = create_signal(String::new());
     let (loading, set_loading) = create_signal(true);
     let (editing_task, set_editing_task) = create_signal(Option::<Task>::None);
+    let (profile_open, set_profile_open) = create_signal(false);
+    let (events_seen, set_events_seen) = create_signal(0u32);
+    let (watched, set_watched) = create_signal(HashSet::<i64>::new());
+    let criteria = create_rw_signal(ViewCriteria::default());
+    let query_result = create_rw_signal(Option::<Vec<i64>>::None);
+    let me = user.id;
+
+    // Задачи после фильтров и сортировки текущего представления.
+    // Если задан запрос, остаются только найденные задачи в порядке сервера
+    let visible = create_memo(move |_| {
+        let criteria = criteria.get();
+        let watched = watched.get();
+        let mut visible: Vec<Task> = tasks
+            .get()
+            .into_iter()
+            .filter(|t| criteria.matches(t, me, &watched))
+            .collect();
+        match query_result.get() {
+            Some(ids) => {
+                visible.retain(|t| ids.contains(&t.id));
+                visible.sort_by_key(|t| ids.iter().position(|id| *id == t.id));
+            }
+            None => criteria.sort(&mut visible),
+        }
+        visible
+    });
 
     // Загрузка данных при монтировании
     create_effect(move |_| {
         spawn_local(async move {
             let tasks_result = api::get_tasks().await;
             let users_result = api::get_users().await;
+            let watched_result = api::get_watched_tasks().await;
 
             if let Ok(fetched) = tasks_result {
                 set_tasks.set(fetched);
             }
+            if let Ok(fetched) = watched_result {
+                set_watched.set(fetched.into_iter().map(|t| t.id).collect());
+            }
             if let Ok(fetched) = users_result {
                 set_users.set(fetched);
             }
@@ -28,12 +83,34 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
         });
     });
 
+    // Живые обновления доски. EventSource сам переподключается и присылает Last-Event-ID
+    if let Some(source) = api::events_url().and_then(|url| web_sys::EventSource::new(&url).ok()) {
+        for kind in ["task.created", "task.updated", "task.deleted"] {
+            let handler = Closure::<dyn FnMut(web_sys::MessageEvent)>::new(
+                move |ev: web_sys::MessageEvent| {
+                    let event = ev
+                        .data()
+                        .as_string()
+                        .and_then(|data| serde_json::from_str::<TaskEvent>(&data).ok());
+                    if let Some(event) = event {
+                        set_tasks.update(|tasks| apply_event(tasks, event));
+                        set_events_seen.update(|n| *n = n.wrapping_add(1));
+                    }
+                },
+            );
+            let _ = source.add_event_listener_with_callback(kind, handler.as_ref().unchecked_ref());
+            handler.forget();
+        }
+        on_cleanup(move || source.close());
+    }
+
     let create_task = move |ev: web_sys::SubmitEvent| {
         ev.prevent_default();
 
         let title = new_title.get();
         let desc = new_desc.get();
         let assignee = new_assignee.get();
+        let due = new_due.get();
 
         if title.is_empty() {
             return;
@@ -41,12 +118,17 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
 
         spawn_local(async move {
             let description = if desc.is_empty() { None } else { Some(desc) };
+            let due_date = if due.is_empty() { None } else { Some(due) };
 
-            if let Ok(task) = api::create_task(title, description, assignee).await {
-                set_tasks.update(|t| t.push(task));
+            if let Ok(task) = api::create_task(title, description, assignee, due_date).await {
+                set_watched.update(|w| {
+                    w.insert(task.id);
+                });
+                set_tasks.update(|t| upsert_task(t, task));
                 set_new_title.set(String::new());
                 set_new_desc.set(String::new());
                 set_new_assignee.set(None);
+                set_new_due.set(String::new());
             }
         });
     };
@@ -59,6 +141,8 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
                 status: Some(status),
                 assignee_id: None,
                 actual_hours: None,
+                due_date: None,
+                planned_date: None,
             };
             if let Ok(updated) = api::update_task(id, req).await {
                 set_tasks.update(|tasks| {
@@ -70,38 +154,39 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
         });
     };
 
-    let save_task = move |updated: Task| {
+    let delete = move |id: i64| {
         spawn_local(async move {
-            println!("Saving task");
-            let req = UpdateTaskRequest {
-                title: Some(updated.title.clone()),
-                description: Some(updated.description.clone()),
-                status: Some(updated.status.clone()),
-                assignee_id: Some(updated.assignee_id),
-                actual_hours: updated.actual_hours,
-            };
-            if let Ok(saved) = api::update_task(updated.id, req).await {
-                set_tasks.update(|tasks| {
-                    if let Some(task) = tasks.iter_mut().find(|t| t.id == saved.id) {
-                        *task = saved;
-                    }
-                });
-                set_editing_task.set(None);
+            if api::delete_task(id).await.is_ok() {
+                set_tasks.update(|tasks| tasks.retain(|t| t.id != id));
             }
         });
     };
 
-    let delete = move |id: i64| {
+    let toggle_watch = move |id: i64| {
+        let watching = watched.get_untracked().contains(&id);
         spawn_local(async move {
-            if api::delete_task(id).await.is_ok() {
-                set_tasks.update(|tasks| tasks.retain(|t| t.id != id));
+            let result = if watching {
+                api::unwatch_task(id).await
+            } else {
+                api::watch_task(id).await
+            };
+            if result.is_ok() {
+                set_watched.update(|w| {
+                    if watching {
+                        w.remove(&id);
+                    } else {
+                        w.insert(id);
+                    }
+                });
             }
         });
     };
 
     let logout = move |_| {
-        api::clear_token();
-        on_logout.set(None);
+        spawn_local(async move {
+            api::logout().await;
+            on_logout.set(None);
+        });
     };
 
     let get_user_name = move |user_id: Option<i64>| -> String {
@@ -129,7 +214,13 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
                 <div class="max-w-7xl mx-auto px-4 py-4 flex justify-between items-center">
                     <h1 class="text-xl font-bold">"Task Tracker"</h1>
                     <div class="flex items-center gap-4">
-                        <span class="text-gray-600">{user.name.clone()}</span>
+                        <NotificationBell refresh=events_seen />
+                        <button
+                            on:click=move |_| set_profile_open.set(true)
+                            class="text-gray-600 hover:underline"
+                        >
+                            {user.name.clone()}
+                        </button>
                         <button
                             on:click=logout
                             class="text-red-600 hover:underline"
@@ -175,6 +266,13 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
                                 }
                             />
                         </select>
+                        <input
+                            type="date"
+                            title="Due date"
+                            class="border rounded px-3 py-2"
+                            prop:value=new_due
+                            on:input=move |ev| set_new_due.set(event_target_value(&ev))
+                        />
                         <button
                             type="submit"
                             class="bg-blue-600 text-white px-4 py-2 rounded hover:bg-blue-700"
@@ -184,10 +282,21 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
                     </div>
                 </form>
 
-                // Kanban доска
+                <ViewBar criteria=criteria users=users me=me />
+                <QueryBar result=query_result />
+
+                // Kanban доска или список
                 {move || {
                     if loading.get() {
                         view! { <p>"Loading..."</p> }.into_view()
+                    } else if criteria.with(|c| c.display == "list") {
+                        view! {
+                            <TaskList
+                                tasks=visible
+                                get_user_name=get_user_name
+                                on_edit=move |task| set_editing_task.set(Some(task))
+                            />
+                        }.into_view()
                     } else {
                         view! {
                             <div class="grid grid-cols-1 md:grid-cols-3 gap-4">
@@ -202,9 +311,9 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
                                                 <For
                                                     each=move || {
                                                         let s = status.clone();
-                                                        tasks.get().into_iter().filter(move |t| t.status == s).collect::<Vec<_>>()
+                                                        visible.get().into_iter().filter(move |t| t.status == s).collect::<Vec<_>>()
                                                     }
-                                                    key=|task| (task.id, task.title.clone(), task.description.clone(), task.assignee_id, task.status.clone(), task.actual_hours.map(|h| h.to_bits()))
+                                                    key=|task| (task.id, task.title.clone(), task.description.clone(), task.assignee_id, task.status.clone(), task.actual_hours.map(|h| h.to_bits()), task.due_date.clone(), task.planned_date.clone())
                                                     children=move |task| {
                                                         let task_for_edit = task.clone();
                                                         let task_id = task.id;
@@ -213,7 +322,10 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
                                                         view! {
                                                             <TaskCard
                                                                 task=task
+                                                                users=users.get()
                                                                 assignee_name=assignee_name
+                                                                watching=Signal::derive(move || watched.get().contains(&task_id))
+                                                                on_toggle_watch=move || toggle_watch(task_id)
                                                                 on_status_change=move |s| update_status(task_id, s)
                                                                 on_edit=move || set_editing_task.set(Some(task_for_edit.clone()))
                                                                 on_delete=move || delete(task_id)
@@ -244,19 +356,68 @@ pub fn TasksPage(user: User, on_logout: WriteSignal<Option<User>>) -> impl IntoV
                     }
                 })
             }}
+
+            {move || {
+                profile_open.get().then(|| view! {
+                    <ProfileModal on_close=move || set_profile_open.set(false) on_logout=on_logout />
+                })
+            }}
         </div>
     }
 }
 
 #[component]
-fn TaskCard<S, E, D>(
+fn TaskList<N, E>(tasks: Memo<Vec<Task>>, get_user_name: N, on_edit: E) -> impl IntoView
+where
+    N: Fn(Option<i64>) -> String + Copy + 'static,
+    E: Fn(Task) + Copy + 'static,
+{
+    view! {
+        <table class="w-full bg-white rounded-lg shadow text-sm">
+            <thead class="text-left text-gray-500 border-b">
+                <tr>
+                    <th class="px-3 py-2">"Title"</th>
+                    <th class="px-3 py-2">"Status"</th>
+                    <th class="px-3 py-2">"Assignee"</th>
+                    <th class="px-3 py-2">"Due"</th>
+                    <th class="px-3 py-2">"Predicted"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {move || tasks.get().into_iter().map(|task| {
+                    let assignee = get_user_name(task.assignee_id);
+                    let predicted = task.predicted_hours.map(|h| format!("{:.1}h", h)).unwrap_or_default();
+                    let title = task.title.clone();
+                    let status = task.status.clone();
+                    let due = task.due_date.clone().unwrap_or_default();
+                    view! {
+                        <tr class="border-b hover:bg-gray-50 cursor-pointer" on:click=move |_| on_edit(task.clone())>
+                            <td class="px-3 py-2">{title}</td>
+                            <td class="px-3 py-2">{status}</td>
+                            <td class="px-3 py-2">{assignee}</td>
+                            <td class="px-3 py-2">{due}</td>
+                            <td class="px-3 py-2">{predicted}</td>
+                        </tr>
+                    }
+                }).collect_view()}
+            </tbody>
+        </table>
+    }
+}
+
+#[component]
+fn TaskCard<W, S, E, D>(
     task: Task,
+    users: Vec<User>,
     assignee_name: String,
+    watching: Signal<bool>,
+    on_toggle_watch: W,
     on_status_change: S,
     on_edit: E,
     on_delete: D,
 ) -> impl IntoView
 where
+    W: Fn() + 'static,
     S: Fn(String) + 'static,
     E: Fn() + 'static,
     D: Fn() + 'static,
@@ -268,6 +429,15 @@ where
             <div class="flex justify-between items-start mb-2">
                 <h4 class="font-medium">{task.title.clone()}</h4>
                 <div class="flex gap-1">
+                    <button
+                        on:click=move |_| on_toggle_watch()
+                        class="text-sm"
+                        class:text-blue-600=move || watching.get()
+                        class:text-gray-400=move || !watching.get()
+                        title=move || if watching.get() { "Stop watching" } else { "Watch" }
+                    >
+                        "👁"
+                    </button>
                     <button
                         on:click=move |_| on_edit()
                         class="text-blue-500 hover:text-blue-700 text-sm"
@@ -286,7 +456,9 @@ where
             </div>
 
             {task.description.clone().map(|d| view! {
-                <p class="text-sm text-gray-600 mb-2">{d}</p>
+                <p class="text-sm text-gray-600 mb-2">
+                    <MentionText text=d users=users />
+                </p>
             })}
 
             <p class="text-xs text-gray-500 mb-1">"Assignee: " {assignee_name}</p>
@@ -299,6 +471,14 @@ where
                 <p class="text-xs text-gray-500 mb-1">"Actual: " {format!("{:.1}h", h)}</p>
             })}
 
+            {task.due_date.clone().map(|d| view! {
+                <p class="text-xs text-gray-500 mb-1">"Due: " {d}</p>
+            })}
+
+            {task.planned_date.clone().map(|d| view! {
+                <p class="text-xs text-gray-500 mb-1">"Planned: " {d}</p>
+            })}
+
             <select
                 class="w-full text-sm border rounded px-2 py-1 mt-2"
                 on:change=move |ev| on_status_change(event_target_value(&ev))
@@ -326,10 +506,21 @@ fn EditTaskModal(
     let (actual_hours, set_actual_hours) = create_signal(
         task.actual_hours.map(|h| h.to_string()).unwrap_or_default()
     );
+    let (due_date, set_due_date) = create_signal(task.due_date.clone().unwrap_or_default());
+    let (planned_date, set_planned_date) = create_signal(task.planned_date.clone().unwrap_or_default());
     let (saving, set_saving) = create_signal(false);
+    let (watchers, set_watchers) = create_signal(Vec::<User>::new());
 
     let task_id = task.id;
 
+    create_effect(move |_| {
+        spawn_local(async move {
+            if let Ok(fetched) = api::get_task_watchers(task_id).await {
+                set_watchers.set(fetched);
+            }
+        });
+    });
+
     let close = move || set_editing_task.set(None);
 
     let submit = move |ev: web_sys::SubmitEvent| {
@@ -342,6 +533,8 @@ fn EditTaskModal(
             status: Some(status.get()),
             assignee_id: Some(assignee_id.get()),
             actual_hours: actual_hours.get().parse().ok(),
+            due_date: Some(if due_date.get().is_empty() { None } else { Some(due_date.get()) }),
+            planned_date: Some(if planned_date.get().is_empty() { None } else { Some(planned_date.get()) }),
         };
 
         spawn_local(async move {
@@ -369,7 +562,7 @@ fn EditTaskModal(
 
     view! {
         <div class="fixed inset-0 bg-black bg-opacity-50 flex items-center justify-center z-50">
-            <div class="bg-white rounded-lg p-6 w-full max-w-md">
+            <div class="bg-white rounded-lg p-6 w-full max-w-md max-h-screen overflow-y-auto">
                 <div class="flex justify-between items-center mb-4">
                     <h2 class="text-lg font-semibold">"Edit Task"</h2>
                     <button
@@ -394,11 +587,10 @@ fn EditTaskModal(
 
                     <div>
                         <label class="block text-sm font-medium mb-1">"Description"</label>
-                        <textarea
-                            class="w-full border rounded px-3 py-2"
-                            rows="3"
-                            prop:value=description
-                            on:input=move |ev| set_description.set(event_target_value(&ev))
+                        <MentionTextarea
+                            value=description
+                            set_value=set_description
+                            users=users.clone()
                         />
                     </div>
 
@@ -445,6 +637,27 @@ fn EditTaskModal(
                         />
                     </div>
 
+                    <div class="flex gap-2">
+                        <div class="flex-1">
+                            <label class="block text-sm font-medium mb-1">"Due Date"</label>
+                            <input
+                                type="date"
+                                class="w-full border rounded px-3 py-2"
+                                prop:value=due_date
+                                on:input=move |ev| set_due_date.set(event_target_value(&ev))
+                            />
+                        </div>
+                        <div class="flex-1">
+                            <label class="block text-sm font-medium mb-1">"Planned Date"</label>
+                            <input
+                                type="date"
+                                class="w-full border rounded px-3 py-2"
+                                prop:value=planned_date
+                                on:input=move |ev| set_planned_date.set(event_target_value(&ev))
+                            />
+                        </div>
+                    </div>
+
                     <div class="flex gap-2 justify-end">
                         <button
                             type="button"
@@ -462,6 +675,16 @@ fn EditTaskModal(
                         </button>
                     </div>
                 </form>
+
+                <p class="mt-4 text-sm text-gray-600">
+                    "Watchers: "
+                    {move || {
+                        let names: Vec<String> = watchers.get().into_iter().map(|u| u.name).collect();
+                        if names.is_empty() { "nobody".to_string() } else { names.join(", ") }
+                    }}
+                </p>
+
+                <TaskComments task_id=task_id users=users />
             </div>
         </div>
     }
@@ -0,0 +1,235 @@
+use crate::api;
+use crate::models::{TwoFactorSetup, TwoFactorStatus, User};
+use leptos::*;
+
+/// Включение, отключение и резервные коды второго фактора. `on_enabled`
+/// вызывается после подтверждения - экран обязательной настройки закрывается
+#[component]
+pub fn TwoFactorSection<F>(on_enabled: F) -> impl IntoView
+where
+    F: Fn() + Copy + 'static,
+{
+    let (status, set_status) = create_signal(Option::<TwoFactorStatus>::None);
+    let (setup, set_setup) = create_signal(Option::<TwoFactorSetup>::None);
+    let (recovery_codes, set_recovery_codes) = create_signal(Vec::<String>::new());
+    let (code, set_code) = create_signal(String::new());
+    let (password, set_password) = create_signal(String::new());
+    let (error, set_error) = create_signal(Option::<String>::None);
+
+    let load_status = move || {
+        spawn_local(async move {
+            if let Ok(current) = api::get_two_factor().await {
+                set_status.set(Some(current));
+            }
+        });
+    };
+    create_effect(move |_| load_status());
+
+    let start_setup = move |_| {
+        spawn_local(async move {
+            set_error.set(None);
+            match api::setup_two_factor().await {
+                Ok(new_setup) => set_setup.set(Some(new_setup)),
+                Err(e) => set_error.set(Some(e)),
+            }
+        });
+    };
+
+    let confirm = move |_| {
+        let code_val = code.get();
+        spawn_local(async move {
+            set_error.set(None);
+            match api::confirm_two_factor(code_val).await {
+                Ok(codes) => {
+                    set_setup.set(None);
+                    set_code.set(String::new());
+                    set_recovery_codes.set(codes.recovery_codes);
+                    load_status();
+                    on_enabled();
+                }
+                Err(e) => set_error.set(Some(e)),
+            }
+        });
+    };
+
+    let regenerate = move |_| {
+        let code_val = code.get();
+        spawn_local(async move {
+            set_error.set(None);
+            match api::regenerate_recovery_codes(code_val).await {
+                Ok(codes) => {
+                    set_code.set(String::new());
+                    set_recovery_codes.set(codes.recovery_codes);
+                    load_status();
+                }
+                Err(e) => set_error.set(Some(e)),
+            }
+        });
+    };
+
+    let disable = move |_| {
+        let password_val = password.get();
+        let code_val = code.get();
+        spawn_local(async move {
+            set_error.set(None);
+            match api::disable_two_factor(password_val, code_val).await {
+                Ok(()) => {
+                    set_password.set(String::new());
+                    set_code.set(String::new());
+                    set_recovery_codes.set(Vec::new());
+                    load_status();
+                }
+                Err(e) => set_error.set(Some(e)),
+            }
+        });
+    };
+
+    let enabled = move || status.get().is_some_and(|s| s.enabled);
+    let code_input = move || view! {
+        <input
+            type="text"
+            inputmode="numeric"
+            placeholder="Code from the app"
+            class="w-full border rounded px-3 py-2 text-sm"
+            prop:value=code
+            on:input=move |ev| set_code.set(event_target_value(&ev))
+        />
+    };
+
+    view! {
+        <section class="space-y-2">
+            <h3 class="font-medium">"Two-factor authentication"</h3>
+
+            {move || error.get().map(|e| view! {
+                <div class="bg-red-100 text-red-700 p-2 rounded text-sm">{e}</div>
+            })}
+
+            <Show when=move || !recovery_codes.get().is_empty()>
+                <div class="bg-yellow-50 border border-yellow-200 p-2 rounded text-xs">
+                    <p class="mb-1">
+                        "Save these recovery codes now, they will not be shown again. Each works once:"
+                    </p>
+                    <ul class="font-mono grid grid-cols-2 gap-1">
+                        {move || recovery_codes.get().into_iter().map(|c| view! { <li>{c}</li> }).collect_view()}
+                    </ul>
+                </div>
+            </Show>
+
+            <Show
+                when=enabled
+                fallback=move || match setup.get() {
+                    None => view! {
+                        <p class="text-sm text-gray-600">
+                            "Protect your account with a code from an authenticator app."
+                        </p>
+                        <button
+                            on:click=start_setup
+                            class="px-3 py-1 bg-blue-600 text-white rounded hover:bg-blue-700 text-sm"
+                        >
+                            "Set up"
+                        </button>
+                    }.into_view(),
+                    Some(pending) => view! {
+                        <p class="text-sm text-gray-600">
+                            "Add this account to your authenticator app, then enter the code it shows."
+                        </p>
+                        <div class="bg-gray-50 border p-2 rounded text-xs break-all space-y-1">
+                            <a href=pending.otpauth_uri.clone() class="text-blue-600 hover:underline">
+                                "Open in authenticator app"
+                            </a>
+                            <p>"Or enter the key manually: " <code>{pending.secret}</code></p>
+                        </div>
+                        {code_input}
+                        <button
+                            on:click=confirm
+                            class="px-3 py-1 bg-blue-600 text-white rounded hover:bg-blue-700 text-sm"
+                        >
+                            "Confirm"
+                        </button>
+                    }.into_view(),
+                }
+            >
+                <p class="text-sm text-gray-600">
+                    {move || format!(
+                        "Enabled. Recovery codes left: {}",
+                        status.get().map_or(0, |s| s.recovery_codes_left)
+                    )}
+                </p>
+                {code_input}
+                <button
+                    on:click=regenerate
+                    class="px-3 py-1 border border-blue-600 text-blue-600 rounded hover:bg-blue-50 text-sm"
+                >
+                    "New recovery codes"
+                </button>
+                <Show
+                    when=move || status.get().is_some_and(|s| !s.required)
+                    fallback=|| view! {
+                        <p class="text-xs text-gray-500">"Required for your role."</p>
+                    }
+                >
+                    <input
+                        type="password"
+                        placeholder="Password"
+                        class="w-full border rounded px-3 py-2 text-sm"
+                        prop:value=password
+                        on:input=move |ev| set_password.set(event_target_value(&ev))
+                    />
+                    <button
+                        on:click=disable
+                        class="px-3 py-1 border border-red-600 text-red-600 rounded hover:bg-red-50 text-sm"
+                    >
+                        "Disable"
+                    </button>
+                </Show>
+            </Show>
+        </section>
+    }
+}
+
+/// Роль требует 2FA, а она не настроена: дальше этого экрана не пускаем
+#[component]
+pub fn TwoFactorGate(
+    setup_required: WriteSignal<bool>,
+    on_logout: WriteSignal<Option<User>>,
+) -> impl IntoView {
+    let (enabled, set_enabled) = create_signal(false);
+
+    let logout = move |_| {
+        spawn_local(async move {
+            api::logout().await;
+            setup_required.set(false);
+            on_logout.set(None);
+        });
+    };
+
+    view! {
+        <div class="min-h-screen flex items-center justify-center bg-gray-100">
+            <div class="bg-white p-8 rounded-lg shadow-md w-full max-w-md space-y-4">
+                <h1 class="text-2xl font-bold text-center">"Task Tracker"</h1>
+                <p class="text-sm text-gray-700">
+                    "Your role requires two-factor authentication. Set it up to continue."
+                </p>
+                <TwoFactorSection on_enabled=move || set_enabled.set(true) />
+                <Show
+                    when=move || enabled.get()
+                    fallback=move || view! {
+                        <button
+                            on:click=logout
+                            class="w-full text-sm text-blue-600 hover:underline"
+                        >
+                            "Log out"
+                        </button>
+                    }
+                >
+                    <button
+                        on:click=move |_| setup_required.set(false)
+                        class="w-full bg-blue-600 text-white py-2 rounded hover:bg-blue-700"
+                    >
+                        "Continue"
+                    </button>
+                </Show>
+            </div>
+        </div>
+    }
+}
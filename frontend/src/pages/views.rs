@@ -0,0 +1,370 @@
+use crate::api;
+use crate::models::{CreateViewRequest, SavedView, Task, User, ViewFilters};
+use leptos::*;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+const SORT_FIELDS: [(&str, &str); 6] = [
+    ("created_at", "Created"),
+    ("updated_at", "Updated"),
+    ("due_date", "Due date"),
+    ("title", "Title"),
+    ("status", "Status"),
+    ("predicted_hours", "Estimate"),
+];
+
+/// Текущие условия доски: их можно сохранить как представление
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewCriteria {
+    pub filters: ViewFilters,
+    pub sort_by: String,
+    pub sort_desc: bool,
+    pub display: String,
+}
+
+impl Default for ViewCriteria {
+    fn default() -> Self {
+        Self {
+            filters: ViewFilters::default(),
+            sort_by: "created_at".to_string(),
+            sort_desc: true,
+            display: "kanban".to_string(),
+        }
+    }
+}
+
+impl ViewCriteria {
+    fn from_view(view: &SavedView) -> Self {
+        Self {
+            filters: view.filters.clone(),
+            sort_by: view.sort_by.clone(),
+            sort_desc: view.sort_desc,
+            display: view.display.clone(),
+        }
+    }
+
+    /// Те же правила отбора, что и на сервере: доска обновляется по SSE без перезапроса
+    pub fn matches(&self, task: &Task, me: i64, watched: &HashSet<i64>) -> bool {
+        let f = &self.filters;
+        f.status.as_ref().is_none_or(|s| *s == task.status)
+            && f.assignee_id.is_none_or(|id| task.assignee_id == Some(id))
+            && (!f.assigned_to_me || task.assignee_id == Some(me))
+            && (!f.unassigned || task.assignee_id.is_none())
+            && (!f.created_by_me || task.created_by == me)
+            && (!f.watching || watched.contains(&task.id))
+            && f.search.as_ref().is_none_or(|q| {
+                task.title.to_lowercase().contains(&q.to_lowercase())
+            })
+    }
+
+    pub fn sort(&self, tasks: &mut [Task]) {
+        tasks.sort_by(|a, b| {
+            let order = match self.sort_by.as_str() {
+                "updated_at" => a.updated_at.cmp(&b.updated_at),
+                "due_date" => a.due_date.cmp(&b.due_date),
+                "title" => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+                "status" => a.status.cmp(&b.status),
+                "predicted_hours" => a
+                    .predicted_hours
+                    .partial_cmp(&b.predicted_hours)
+                    .unwrap_or(Ordering::Equal),
+                _ => a.created_at.cmp(&b.created_at),
+            }
+            .then(a.id.cmp(&b.id));
+            if self.sort_desc { order.reverse() } else { order }
+        });
+    }
+
+    fn into_request(self, name: String, shared: bool) -> CreateViewRequest {
+        CreateViewRequest {
+            name,
+            shared,
+            filters: self.filters,
+            sort_by: self.sort_by,
+            sort_desc: self.sort_desc,
+            display: self.display,
+        }
+    }
+}
+
+/// Значение селекта исполнителя: "" - любой, "me", "none" или id пользователя
+fn assignee_value(filters: &ViewFilters) -> String {
+    if filters.assigned_to_me {
+        "me".to_string()
+    } else if filters.unassigned {
+        "none".to_string()
+    } else {
+        filters.assignee_id.map(|id| id.to_string()).unwrap_or_default()
+    }
+}
+
+fn set_assignee(filters: &mut ViewFilters, value: &str) {
+    filters.assigned_to_me = value == "me";
+    filters.unassigned = value == "none";
+    filters.assignee_id = value.parse().ok();
+}
+
+#[component]
+pub fn ViewBar(criteria: RwSignal<ViewCriteria>, users: ReadSignal<Vec<User>>, me: i64) -> impl IntoView {
+    let (views, set_views) = create_signal(Vec::<SavedView>::new());
+    let (selected, set_selected) = create_signal(Option::<i64>::None);
+    let (share, set_share) = create_signal(false);
+
+    create_effect(move |_| {
+        spawn_local(async move {
+            if let Ok(fetched) = api::get_views().await {
+                set_views.set(fetched);
+            }
+        });
+    });
+
+    let select_view = move |ev| {
+        let id: Option<i64> = event_target_value(&ev).parse().ok();
+        set_selected.set(id);
+        let view = id.and_then(|id| views.get_untracked().into_iter().find(|v| v.id == id));
+        match view {
+            Some(view) => {
+                set_share.set(view.shared);
+                criteria.set(ViewCriteria::from_view(&view));
+            }
+            None => criteria.set(ViewCriteria::default()),
+        }
+    };
+
+    let update_filters = move |f: &dyn Fn(&mut ViewFilters)| criteria.update(|c| f(&mut c.filters));
+
+    let selected_view = move || {
+        let id = selected.get()?;
+        views.get().into_iter().find(|v| v.id == id)
+    };
+    let is_own = move || selected_view().is_some_and(|v| v.owner_id == me);
+
+    let save_as_new = move |_| {
+        let Some(name) = web_sys::window()
+            .and_then(|w| w.prompt_with_message("View name").ok().flatten())
+            .filter(|n| !n.trim().is_empty())
+        else {
+            return;
+        };
+        let req = criteria.get_untracked().into_request(name, share.get_untracked());
+        spawn_local(async move {
+            if let Ok(view) = api::create_view(req).await {
+                set_selected.set(Some(view.id));
+                set_views.update(|v| v.push(view));
+            }
+        });
+    };
+
+    let save_current = move |_| {
+        let Some(view) = selected_view() else {
+            return;
+        };
+        let req = criteria.get_untracked().into_request(view.name, share.get_untracked());
+        spawn_local(async move {
+            if let Ok(updated) = api::update_view(view.id, req).await {
+                set_views.update(|views| {
+                    if let Some(v) = views.iter_mut().find(|v| v.id == updated.id) {
+                        *v = updated;
+                    }
+                });
+            }
+        });
+    };
+
+    let delete_current = move |_| {
+        let Some(id) = selected.get_untracked() else {
+            return;
+        };
+        spawn_local(async move {
+            if api::delete_view(id).await.is_ok() {
+                set_views.update(|v| v.retain(|v| v.id != id));
+                set_selected.set(None);
+                criteria.set(ViewCriteria::default());
+            }
+        });
+    };
+
+    view! {
+        <div class="bg-white p-3 rounded-lg shadow mb-4 flex flex-wrap gap-2 items-center text-sm">
+            <select
+                class="border rounded px-2 py-1 font-medium"
+                prop:value=move || selected.get().map(|id| id.to_string()).unwrap_or_default()
+                on:change=select_view
+            >
+                <option value="">"All tasks"</option>
+                {move || views.get().into_iter().map(|v| {
+                    let label = if v.shared { format!("{} (shared)", v.name) } else { v.name.clone() };
+                    view! { <option value=v.id.to_string()>{label}</option> }
+                }).collect_view()}
+            </select>
+
+            <select
+                class="border rounded px-2 py-1"
+                prop:value=move || criteria.get().filters.status.unwrap_or_default()
+                on:change=move |ev| {
+                    let value = event_target_value(&ev);
+                    update_filters(&|f| f.status = Some(value.clone()).filter(|s| !s.is_empty()));
+                }
+            >
+                <option value="">"Any status"</option>
+                <option value="todo">"To Do"</option>
+                <option value="in_progress">"In Progress"</option>
+                <option value="done">"Done"</option>
+            </select>
+
+            <select
+                class="border rounded px-2 py-1"
+                prop:value=move || assignee_value(&criteria.get().filters)
+                on:change=move |ev| {
+                    let value = event_target_value(&ev);
+                    update_filters(&|f| set_assignee(f, &value));
+                }
+            >
+                <option value="">"Anyone"</option>
+                <option value="me">"Assigned to me"</option>
+                <option value="none">"Unassigned"</option>
+                {move || users.get().into_iter().map(|u| {
+                    view! { <option value=u.id.to_string()>{u.name}</option> }
+                }).collect_view()}
+            </select>
+
+            <input
+                type="text"
+                placeholder="Search title"
+                class="border rounded px-2 py-1"
+                prop:value=move || criteria.get().filters.search.unwrap_or_default()
+                on:input=move |ev| {
+                    let value = event_target_value(&ev);
+                    update_filters(&|f| f.search = Some(value.clone()).filter(|s| !s.is_empty()));
+                }
+            />
+
+            <label class="flex items-center gap-1">
+                <input
+                    type="checkbox"
+                    prop:checked=move || criteria.get().filters.watching
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        update_filters(&|f| f.watching = checked);
+                    }
+                />
+                "Watching"
+            </label>
+
+            <label class="flex items-center gap-1">
+                <input
+                    type="checkbox"
+                    prop:checked=move || criteria.get().filters.created_by_me
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        update_filters(&|f| f.created_by_me = checked);
+                    }
+                />
+                "Created by me"
+            </label>
+
+            <select
+                class="border rounded px-2 py-1"
+                prop:value=move || criteria.get().sort_by
+                on:change=move |ev| {
+                    let value = event_target_value(&ev);
+                    criteria.update(|c| c.sort_by = value);
+                }
+            >
+                {SORT_FIELDS.iter().map(|(value, label)| {
+                    view! { <option value=*value>{*label}</option> }
+                }).collect_view()}
+            </select>
+            <button
+                class="border rounded px-2 py-1"
+                title="Toggle sort direction"
+                on:click=move |_| criteria.update(|c| c.sort_desc = !c.sort_desc)
+            >
+                {move || if criteria.get().sort_desc { "↓" } else { "↑" }}
+            </button>
+
+            <div class="flex border rounded overflow-hidden">
+                {["kanban", "list"].into_iter().map(|mode| view! {
+                    <button
+                        class="px-2 py-1"
+                        class:bg-blue-600=move || criteria.get().display == mode
+                        class:text-white=move || criteria.get().display == mode
+                        on:click=move |_| criteria.update(|c| c.display = mode.to_string())
+                    >
+                        {if mode == "kanban" { "Board" } else { "List" }}
+                    </button>
+                }).collect_view()}
+            </div>
+
+            <div class="flex items-center gap-2 ml-auto">
+                <label class="flex items-center gap-1">
+                    <input
+                        type="checkbox"
+                        prop:checked=share
+                        on:change=move |ev| set_share.set(event_target_checked(&ev))
+                    />
+                    "Shared"
+                </label>
+                <Show when=is_own>
+                    <button on:click=save_current class="text-blue-600 hover:underline">"Save"</button>
+                    <button on:click=delete_current class="text-red-600 hover:underline">"Delete"</button>
+                </Show>
+                <button on:click=save_as_new class="text-blue-600 hover:underline">"Save as view…"</button>
+            </div>
+        </div>
+    }
+}
+
+/// Строка запроса: `status = todo AND assignee = me ORDER BY due_date`.
+/// Результат - id задач в порядке сервера, None - запрос не задан
+#[component]
+pub fn QueryBar(result: RwSignal<Option<Vec<i64>>>) -> impl IntoView {
+    let (query, set_query) = create_signal(String::new());
+    let (error, set_error) = create_signal(Option::<String>::None);
+
+    let run = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        let q = query.get_untracked().trim().to_string();
+        if q.is_empty() {
+            set_error.set(None);
+            result.set(None);
+            return;
+        }
+        spawn_local(async move {
+            match api::query_tasks(&q).await {
+                Ok(tasks) => {
+                    set_error.set(None);
+                    result.set(Some(tasks.into_iter().map(|t| t.id).collect()));
+                }
+                Err(e) => set_error.set(Some(e)),
+            }
+        });
+    };
+
+    let clear = move |_| {
+        set_query.set(String::new());
+        set_error.set(None);
+        result.set(None);
+    };
+
+    view! {
+        <form on:submit=run class="bg-white p-3 rounded-lg shadow mb-4 text-sm">
+            <div class="flex gap-2">
+                <input
+                    type="text"
+                    placeholder="status = in_progress AND assignee = me AND predicted_hours > 4 ORDER BY updated_at DESC"
+                    class="border rounded px-2 py-1 flex-1 font-mono"
+                    prop:value=query
+                    on:input=move |ev| set_query.set(event_target_value(&ev))
+                />
+                <button type="submit" class="bg-blue-600 text-white px-3 py-1 rounded hover:bg-blue-700">
+                    "Query"
+                </button>
+                <Show when=move || result.with(|r| r.is_some())>
+                    <button type="button" on:click=clear class="text-gray-600 hover:underline">"Clear"</button>
+                </Show>
+            </div>
+            {move || error.get().map(|e| view! { <p class="text-red-600 mt-1">{e}</p> })}
+        </form>
+    }
+}